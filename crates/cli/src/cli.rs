@@ -1,23 +1,38 @@
 use crate::CleanupArgs;
 use crate::DepsArgs;
+use crate::DiffArgs;
 use crate::DocsArgs;
+use crate::FileArgs;
+use crate::HookArgs;
 use crate::MonitorArgs;
 use crate::ProfileArgs;
 use crate::ProjectArgs;
 use crate::RenameArgs;
+use crate::RunArgs;
+use crate::SecretsArgs;
+use crate::ShellExportArgs;
 use crate::SnapshotArgs;
 use crate::WatchArgs;
 use crate::handle_cleanup;
 use crate::handle_deps;
+use crate::handle_diff;
 use crate::handle_docs;
+use crate::handle_file;
 use crate::handle_find_replace;
+use crate::handle_history;
+use crate::handle_hook;
 use crate::handle_list_command;
 use crate::handle_path_command;
 use crate::handle_profile;
 use crate::handle_project;
+use crate::handle_redo;
 use crate::handle_rename;
 use crate::handle_replace;
+use crate::handle_run;
+use crate::handle_secrets;
+use crate::handle_shell_export;
 use crate::handle_snapshot;
+use crate::handle_undo;
 use crate::handle_watch;
 use crate::monitor::handle_monitor;
 use crate::replace::FindReplaceArgs;
@@ -27,9 +42,13 @@ use crate::wizard::run_wizard;
 use clap::{Parser, Subcommand};
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
-use envx_core::{Analyzer, EnvVarManager, ExportFormat, Exporter, ImportFormat, Importer};
+use envx_core::{
+    Analyzer, EnvVar, EnvVarManager, Expander, ExportFormat, Exporter, ImportFormat, Importer, SecretClassifier,
+    SnapshotManager, Unresolved,
+};
 use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 #[derive(Parser)]
 #[command(name = "envx")]
 #[command(about = "System Environment Variable Manager")]
@@ -37,6 +56,23 @@ use std::path::Path;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Take a snapshot before commands that delete or overwrite variables
+    #[arg(long, global = true, env = "ENVX_AUTO_SNAPSHOT", value_parser = clap::builder::FalseyValueParser::new())]
+    pub auto_snapshot: bool,
+}
+
+impl Cli {
+    /// The status to exit with when the command fails. `diff` follows diff(1)
+    /// and keeps 1 for "the sides differ", so its errors exit with 2.
+    #[must_use]
+    pub const fn error_exit_code(&self) -> i32 {
+        if matches!(self.command, Commands::Diff(_)) {
+            2
+        } else {
+            1
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -84,6 +120,10 @@ pub enum Commands {
         /// Show statistics summary
         #[arg(long)]
         stats: bool,
+
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
     },
 
     /// Get a specific environment variable
@@ -101,6 +141,14 @@ pub enum Commands {
         /// Output format (simple, detailed, json)
         #[arg(short, long, default_value = "simple")]
         format: String,
+
+        /// Expand references like ${VAR}, ${VAR:-default} and %VAR% in the values
+        #[arg(short, long)]
+        expand: bool,
+
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
     },
 
     /// Set an environment variable
@@ -135,7 +183,11 @@ pub enum Commands {
 
     /// Launch the TUI
     #[command(visible_alias = "ui")]
-    Tui,
+    Tui {
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
+    },
 
     /// Manage PATH variable
     Path {
@@ -164,7 +216,7 @@ pub enum Commands {
         #[arg(short = 'v', long)]
         vars: Vec<String>,
 
-        /// Export format (auto-detect from extension, or: env, json, yaml, txt)
+        /// Export format (auto-detect from extension, or: env, json, yaml, toml, txt, docker, compose)
         #[arg(short, long)]
         format: Option<String>,
 
@@ -179,6 +231,10 @@ pub enum Commands {
         /// Overwrite existing file without confirmation
         #[arg(long)]
         force: bool,
+
+        /// Write secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
     },
 
     /// Import environment variables from a file
@@ -190,7 +246,7 @@ pub enum Commands {
         #[arg(short = 'v', long)]
         vars: Vec<String>,
 
-        /// Import format (auto-detect from extension, or: env, json, yaml, txt)
+        /// Import format (auto-detect from file name, or: env, json, yaml, toml, txt, compose, dockerfile)
         #[arg(short, long)]
         format: Option<String>,
 
@@ -202,6 +258,14 @@ pub enum Commands {
         #[arg(long)]
         prefix: Option<String>,
 
+        /// Separator joining nested YAML keys and TOML tables, e.g. `DB__HOST`
+        #[arg(long, default_value = envx_core::importer::DEFAULT_SEPARATOR)]
+        separator: String,
+
+        /// Service of a Compose file to import, required when it has several
+        #[arg(long)]
+        service: Option<String>,
+
         /// Overwrite existing variables without confirmation
         #[arg(long)]
         overwrite: bool,
@@ -211,6 +275,31 @@ pub enum Commands {
         dry_run: bool,
     },
 
+    /// Show recent changes made by envx
+    History {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Clear the history
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Undo the last change(s), including changes from earlier sessions
+    Undo {
+        /// Number of changes to undo
+        #[arg(default_value = "1")]
+        count: usize,
+    },
+
+    /// Redo the last undone change(s)
+    Redo {
+        /// Number of changes to redo
+        #[arg(default_value = "1")]
+        count: usize,
+    },
+
     /// Manage environment snapshots
     Snapshot(SnapshotArgs),
 
@@ -243,6 +332,46 @@ pub enum Commands {
 
     /// Remove unused environment variables
     Cleanup(CleanupArgs),
+
+    /// Run a command with a composed environment (profile, env files, project)
+    #[command(visible_alias = "exec")]
+    Run(RunArgs),
+
+    /// Load project environments automatically when changing directories
+    Hook(HookArgs),
+
+    /// Print shell statements that apply a profile or project to the current shell
+    ShellExport(ShellExportArgs),
+
+    /// Manage the encryption of secrets in profiles and snapshots
+    Secrets(SecretsArgs),
+
+    /// Compare two sets of variables: the live environment, snapshots, profiles or files
+    Diff(DiffArgs),
+
+    /// Edit variables in a .env file without touching its comments and formatting
+    File(FileArgs),
+}
+
+/// Whether `--auto-snapshot` is on for this run
+static AUTO_SNAPSHOT: AtomicBool = AtomicBool::new(false);
+
+/// Takes the `--auto-snapshot` snapshot of `manager`'s variables before a
+/// command deletes or overwrites some of them.
+///
+/// Commands call this after the user confirmed and once they know there is
+/// something to change, so a cancelled command doesn't leave a snapshot.
+///
+/// # Errors
+///
+/// This function will return an error if the snapshot cannot be saved.
+pub(crate) fn snapshot_before(action: &str, manager: &EnvVarManager) -> Result<()> {
+    if !AUTO_SNAPSHOT.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let snapshot = SnapshotManager::new()?.create_auto(action, manager.list().into_iter().cloned().collect())?;
+    println!("💾 Saved snapshot {} before {action}", snapshot.name);
+    Ok(())
 }
 
 #[derive(Subcommand)]
@@ -333,6 +462,8 @@ pub enum PathAction {
 /// - TUI mode is requested (should be handled by main binary)
 #[allow(clippy::too_many_lines)]
 pub fn execute(cli: Cli) -> Result<()> {
+    AUTO_SNAPSHOT.store(cli.auto_snapshot, Ordering::Relaxed);
+
     match cli.command {
         Commands::List {
             source,
@@ -342,6 +473,7 @@ pub fn execute(cli: Cli) -> Result<()> {
             names_only,
             limit,
             stats,
+            reveal,
         } => {
             handle_list_command(
                 source.as_deref(),
@@ -351,11 +483,17 @@ pub fn execute(cli: Cli) -> Result<()> {
                 names_only,
                 limit,
                 stats,
+                reveal,
             )?;
         }
 
-        Commands::Get { pattern, format } => {
-            handle_get_command(&pattern, &format)?;
+        Commands::Get {
+            pattern,
+            format,
+            expand,
+            reveal,
+        } => {
+            handle_get_command(&pattern, &format, expand, reveal)?;
         }
 
         Commands::Set { name, value, temporary } => {
@@ -370,9 +508,9 @@ pub fn execute(cli: Cli) -> Result<()> {
             handle_analyze_command(&analysis_type)?;
         }
 
-        Commands::Tui => {
+        Commands::Tui { reveal } => {
             // Launch the TUI
-            envx_tui::run()?;
+            envx_tui::run(reveal)?;
         }
 
         Commands::Path {
@@ -391,8 +529,9 @@ pub fn execute(cli: Cli) -> Result<()> {
             source,
             metadata,
             force,
+            reveal,
         } => {
-            handle_export(&file, &vars, format, source, metadata, force)?;
+            handle_export(&file, &vars, format, source, metadata, force, reveal)?;
         }
 
         Commands::Import {
@@ -401,10 +540,34 @@ pub fn execute(cli: Cli) -> Result<()> {
             format,
             permanent,
             prefix,
+            separator,
+            service,
             overwrite,
             dry_run,
         } => {
-            handle_import(&file, &vars, format, permanent, prefix.as_ref(), overwrite, dry_run)?;
+            handle_import(
+                &file,
+                &vars,
+                format,
+                permanent,
+                prefix.as_ref(),
+                &separator,
+                service,
+                overwrite,
+                dry_run,
+            )?;
+        }
+
+        Commands::History { limit, clear } => {
+            handle_history(limit, clear)?;
+        }
+
+        Commands::Undo { count } => {
+            handle_undo(count)?;
+        }
+
+        Commands::Redo { count } => {
+            handle_redo(count)?;
         }
 
         Commands::Snapshot(args) => {
@@ -450,6 +613,32 @@ pub fn execute(cli: Cli) -> Result<()> {
             handle_cleanup(&args)?;
         }
 
+        Commands::Run(args) => {
+            handle_run(&args)?;
+        }
+
+        Commands::Hook(args) => {
+            handle_hook(args)?;
+        }
+
+        Commands::ShellExport(args) => {
+            handle_shell_export(&args)?;
+        }
+
+        Commands::Secrets(args) => {
+            handle_secrets(args)?;
+        }
+
+        Commands::Diff(args) => {
+            if handle_diff(&args)? {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::File(args) => {
+            handle_file(args)?;
+        }
+
         Commands::Init {
             template,
             wizard,
@@ -488,17 +677,35 @@ pub fn execute(cli: Cli) -> Result<()> {
     Ok(())
 }
 
-fn handle_get_command(pattern: &str, format: &str) -> Result<()> {
+fn handle_get_command(pattern: &str, format: &str, expand: bool, reveal: bool) -> Result<()> {
     let mut manager = EnvVarManager::new();
     manager.load_all()?;
 
-    let vars = manager.get_pattern(pattern);
+    let mut vars: Vec<EnvVar> = manager.get_pattern(pattern).into_iter().cloned().collect();
 
     if vars.is_empty() {
         eprintln!("No variables found matching pattern: {pattern}");
         return Ok(());
     }
 
+    if expand {
+        let mut expander = Expander::new(|_| None);
+        for var in manager.list() {
+            expander.define(&var.name, &var.value);
+        }
+        for var in &mut vars {
+            var.value = expander.get(&var.name)?.unwrap_or_default();
+        }
+        warn_unresolved(expander.unresolved());
+    }
+
+    if !reveal {
+        let classifier = SecretClassifier::for_current_project();
+        for var in &mut vars {
+            classifier.mask_var(var);
+        }
+    }
+
     match format {
         "json" => {
             println!("{}", serde_json::to_string_pretty(&vars)?);
@@ -509,6 +716,9 @@ fn handle_get_command(pattern: &str, format: &str) -> Result<()> {
                 println!("Value: {}", var.value);
                 println!("Source: {:?}", var.source);
                 println!("Modified: {}", var.modified.format("%Y-%m-%d %H:%M:%S"));
+                if let Some(origin) = &var.origin {
+                    println!("Origin: {origin}");
+                }
                 if let Some(orig) = &var.original_value {
                     println!("Original: {orig}");
                 }
@@ -524,25 +734,35 @@ fn handle_get_command(pattern: &str, format: &str) -> Result<()> {
     Ok(())
 }
 
+/// Prints references that could not be expanded to stderr.
+pub(crate) fn warn_unresolved(unresolved: &[Unresolved]) {
+    for reference in unresolved {
+        eprintln!("⚠️  Unresolved reference: {reference}");
+    }
+}
+
 fn handle_set_command(name: &str, value: &str, temporary: bool) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     let permanent = !temporary;
 
     manager.set(name, value, permanent)?;
+    let shown = SecretClassifier::for_current_project().mask(name, value);
     if permanent {
-        println!("✅ Set {name} = \"{value}\"");
+        println!("✅ Set {name} = \"{shown}\"");
         #[cfg(windows)]
         println!("📝 Note: You may need to restart your terminal for changes to take effect");
+        #[cfg(unix)]
+        println!("📝 Note: Open a new shell or source your shell config (e.g. ~/.bashrc) to pick up the change");
     } else {
-        println!("⚡ Set {name} = \"{value}\" (temporary - current session only)");
+        println!("⚡ Set {name} = \"{shown}\" (temporary - current session only)");
     }
     Ok(())
 }
 
 fn handle_delete_command(pattern: &str, force: bool) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     // Collect the names to delete first (owned data, not references)
@@ -574,12 +794,16 @@ fn handle_delete_command(pattern: &str, force: bool) -> Result<()> {
         }
     }
 
+    snapshot_before(&format!("delete {pattern}"), &manager)?;
+
     // Now we can safely delete since we're not holding any references to manager
-    for name in vars_to_delete {
-        manager.delete(&name)?;
-        println!("Deleted: {name}");
-    }
-    Ok(())
+    manager.batch(&format!("delete {pattern}"), |manager| {
+        for name in vars_to_delete {
+            manager.delete(&name)?;
+            println!("Deleted: {name}");
+        }
+        Ok(())
+    })
 }
 
 fn handle_analyze_command(analysis_type: &str) -> Result<()> {
@@ -621,6 +845,7 @@ fn handle_export(
     source: Option<String>,
     metadata: bool,
     force: bool,
+    reveal: bool,
 ) -> Result<()> {
     // Check if file exists
     if Path::new(&file).exists() && !force {
@@ -676,9 +901,12 @@ fn handle_export(
             "env" => ExportFormat::DotEnv,
             "json" => ExportFormat::Json,
             "yaml" | "yml" => ExportFormat::Yaml,
+            "toml" => ExportFormat::Toml,
             "txt" | "text" => ExportFormat::Text,
             "ps1" | "powershell" => ExportFormat::PowerShell,
             "sh" | "bash" => ExportFormat::Shell,
+            "docker" | "docker-env" => ExportFormat::DockerEnv,
+            "compose" | "docker-compose" => ExportFormat::Compose,
             _ => return Err(eyre!("Unsupported format: {}", fmt)),
         }
     } else {
@@ -686,21 +914,42 @@ fn handle_export(
         ExportFormat::from_extension(file)?
     };
 
+    // Mask secrets unless asked to write them
+    let mut masked = 0;
+    if !reveal {
+        let classifier = SecretClassifier::for_current_project();
+        for var in &mut vars_to_export {
+            if classifier.is_secret(&var.name, &var.value) {
+                classifier.mask_var(var);
+                masked += 1;
+            }
+        }
+    }
+
     // Export
     let exporter = Exporter::new(vars_to_export, metadata);
     exporter.export_to_file(file, export_format)?;
 
     println!("Exported {} variables to '{}'", exporter.count(), file);
+    if masked > 0 {
+        println!(
+            "🔒 Masked {masked} secret value{}. Use --reveal to export the real values.",
+            if masked == 1 { "" } else { "s" }
+        );
+    }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
 fn handle_import(
     file: &str,
     vars: &[String],
     format: Option<String>,
     permanent: bool,
     prefix: Option<&String>,
+    separator: &str,
+    service: Option<String>,
     overwrite: bool,
     dry_run: bool,
 ) -> Result<()> {
@@ -715,6 +964,9 @@ fn handle_import(
             "env" => ImportFormat::DotEnv,
             "json" => ImportFormat::Json,
             "yaml" | "yml" => ImportFormat::Yaml,
+            "toml" => ImportFormat::Toml,
+            "compose" | "docker-compose" => ImportFormat::Compose,
+            "dockerfile" => ImportFormat::Dockerfile,
             "txt" | "text" => ImportFormat::Text,
             _ => return Err(eyre!("Unsupported format: {}", fmt)),
         }
@@ -725,7 +977,15 @@ fn handle_import(
 
     // Import variables
     let mut importer = Importer::new();
+    importer.set_separator(separator);
+    if let Some(service) = service {
+        importer.set_service(service);
+    }
     importer.import_from_file(file, import_format)?;
+    for error in importer.parse_errors() {
+        eprintln!("⚠️  Skipped invalid line in {file}: {error}");
+    }
+    warn_unresolved(importer.unresolved());
 
     // Filter variables if patterns specified
     if !vars.is_empty() {
@@ -746,7 +1006,7 @@ fn handle_import(
     }
 
     // Check for conflicts
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     let mut conflicts = Vec::new();
@@ -796,19 +1056,31 @@ fn handle_import(
         }
         println!("\n(Dry run - no changes made)");
     } else {
+        if !conflicts.is_empty() {
+            snapshot_before(&format!("import {file}"), &manager)?;
+        }
+
         // Apply imports
-        let mut imported = 0;
-        let mut failed = 0;
-
-        for (name, value) in import_vars {
-            match manager.set(&name, &value, permanent) {
-                Ok(()) => imported += 1,
-                Err(e) => {
-                    eprintln!("Failed to import {name}: {e}");
-                    failed += 1;
+        let (imported, failed) = manager.batch(&format!("import {file}"), |manager| {
+            let mut imported = 0;
+            let mut failed = 0;
+
+            for (name, value) in import_vars {
+                let result = match importer.origin(&name) {
+                    Some(origin) => manager.set_with_origin(&name, &value, permanent, origin),
+                    None => manager.set(&name, &value, permanent),
+                };
+                match result {
+                    Ok(()) => imported += 1,
+                    Err(e) => {
+                        eprintln!("Failed to import {name}: {e}");
+                        failed += 1;
+                    }
                 }
             }
-        }
+
+            Ok((imported, failed))
+        })?;
 
         println!("Imported {imported} variables");
         if failed > 0 {
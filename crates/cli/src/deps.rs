@@ -126,7 +126,7 @@ fn handle_deps_show(variable: Option<&str>, show_unused: bool, args: &DepsArgs)
     tracker.scan()?;
 
     // Load current environment variables
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
     let all_vars: HashSet<String> = manager.list().iter().map(|v| v.name.clone()).collect();
 
@@ -436,9 +436,10 @@ pub fn handle_cleanup(args: &CleanupArgs) -> Result<()> {
     // Filter out variables that should be kept
     if !args.keep.is_empty() {
         unused.retain(|var| {
-            !args.keep.iter().any(|pattern| {
-                var.contains(pattern) || glob::Pattern::new(pattern).map(|p| p.matches(var)).unwrap_or(false)
-            })
+            !args
+                .keep
+                .iter()
+                .any(|pattern| var.contains(pattern) || glob::Pattern::new(pattern).is_ok_and(|p| p.matches(var)))
         });
     }
 
@@ -485,22 +486,28 @@ pub fn handle_cleanup(args: &CleanupArgs) -> Result<()> {
         }
     }
 
+    crate::cli::snapshot_before("cleanup", &manager)?;
+
     // Remove unused variables
     let mut removed = 0;
     let mut failed = 0;
 
-    for var in sorted_unused {
-        match manager.delete(&var) {
-            Ok(()) => {
-                removed += 1;
-                println!("✅ Removed: {var}");
-            }
-            Err(e) => {
-                failed += 1;
-                eprintln!("❌ Failed to remove {var}: {e}");
+    // One batch, so `envx undo` restores everything the cleanup removed
+    manager.batch("cleanup", |manager| {
+        for var in sorted_unused {
+            match manager.delete(&var) {
+                Ok(()) => {
+                    removed += 1;
+                    println!("✅ Removed: {var}");
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("❌ Failed to remove {var}: {e}");
+                }
             }
         }
-    }
+        Ok(())
+    })?;
 
     println!("\n📊 Cleanup complete:");
     println!("   - Removed: {removed} variables");
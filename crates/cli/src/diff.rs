@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::Utc;
+use clap::{Args, ValueEnum};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use comfy_table::Table;
+use envx_core::{
+    EnvVar, EnvVarManager, EnvVarSource, ImportFormat, Importer, ProfileManager, SecretClassifier, SnapshotDiff,
+    SnapshotManager, VarOrigin,
+};
+use serde_json::json;
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Old side: `current`, a snapshot, a profile or a .env/json/yaml/toml file.
+    /// Prefix with `snapshot:`, `profile:` or `file:` if the name is ambiguous.
+    pub a: String,
+    /// New side, same forms as the old side
+    pub b: String,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = DiffFormat::Unified)]
+    pub format: DiffFormat,
+    /// Show secret values instead of masking them
+    #[arg(long)]
+    pub reveal: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DiffFormat {
+    /// `-NAME=old` / `+NAME=new` lines
+    Unified,
+    /// A table with one row per changed variable
+    Table,
+    /// A JSON object with added, removed and modified variables
+    Json,
+}
+
+/// Handle the diff command.
+///
+/// Returns whether the two sides differ. Like diff(1), the binary exits with
+/// status 1 when they do and 2 when the diff fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A side cannot be found or is ambiguous
+/// - A snapshot, profile or file cannot be read or decrypted
+/// - The JSON output cannot be serialized
+pub fn handle_diff(args: &DiffArgs) -> Result<bool> {
+    let old = load_side(&args.a)?;
+    let new = load_side(&args.b)?;
+    let diff = SnapshotDiff::between(old, new);
+
+    let secrets = (!args.reveal).then(SecretClassifier::for_current_project);
+    let display = |var: &EnvVar| {
+        secrets
+            .as_ref()
+            .map_or_else(|| var.value.clone(), |secrets| secrets.mask(&var.name, &var.value))
+    };
+
+    println!("{}", render(&diff, args.format, &args.a, &args.b, display)?);
+    Ok(!diff.is_empty())
+}
+
+/// Renders a diff between the sides labelled `a` and `b` in `format`.
+fn render(
+    diff: &SnapshotDiff,
+    format: DiffFormat,
+    a: &str,
+    b: &str,
+    display: impl Fn(&EnvVar) -> String,
+) -> Result<String> {
+    let added: BTreeMap<_, _> = diff.added.values().map(|var| (&var.name, display(var))).collect();
+    let removed: BTreeMap<_, _> = diff.removed.values().map(|var| (&var.name, display(var))).collect();
+    let modified: BTreeMap<_, _> = diff
+        .modified
+        .values()
+        .map(|(old, new)| (&new.name, (display(old), display(new))))
+        .collect();
+
+    let output = match format {
+        DiffFormat::Json => {
+            let modified: BTreeMap<_, _> = modified
+                .iter()
+                .map(|(name, (old, new))| (name, json!({ "old": old, "new": new })))
+                .collect();
+            let output = json!({ "added": added, "removed": removed, "modified": modified });
+            serde_json::to_string_pretty(&output)?
+        }
+        _ if diff.is_empty() => format!("No differences found between {a} and {b}."),
+        DiffFormat::Unified => {
+            let mut lines = vec![format!("--- {a}"), format!("+++ {b}")];
+            // Interleave the changes in name order like a diff of sorted files
+            let mut names: Vec<&&String> = added.keys().chain(removed.keys()).chain(modified.keys()).collect();
+            names.sort();
+            for name in names {
+                if let Some(value) = removed.get(*name) {
+                    lines.push(format!("-{name}={value}"));
+                } else if let Some(value) = added.get(*name) {
+                    lines.push(format!("+{name}={value}"));
+                } else if let Some((old, new)) = modified.get(*name) {
+                    lines.push(format!("-{name}={old}"));
+                    lines.push(format!("+{name}={new}"));
+                }
+            }
+            lines.join("\n")
+        }
+        DiffFormat::Table => {
+            let mut table = Table::new();
+            table.set_header(vec!["Variable", "Change", a, b]);
+            for (name, value) in &removed {
+                table.add_row(vec![name.as_str(), "removed", value, ""]);
+            }
+            for (name, value) in &added {
+                table.add_row(vec![name.as_str(), "added", "", value]);
+            }
+            for (name, (old, new)) in &modified {
+                table.add_row(vec![name.as_str(), "modified", old, new]);
+            }
+            table.to_string()
+        }
+    };
+    Ok(output)
+}
+
+/// Where the variables of one side of a diff come from.
+#[derive(Debug, PartialEq, Eq)]
+enum Side<'a> {
+    Current,
+    Snapshot(&'a str),
+    Profile(&'a str),
+    File(&'a str),
+}
+
+/// Works out what `spec` names. An explicit prefix wins, then an existing
+/// file, then a snapshot or profile of that name if only one of them exists.
+fn classify(
+    spec: &str,
+    is_snapshot: impl FnOnce(&str) -> Result<bool>,
+    is_profile: impl FnOnce(&str) -> Result<bool>,
+) -> Result<Side<'_>> {
+    if spec == "current" {
+        return Ok(Side::Current);
+    }
+    if let Some(name) = spec.strip_prefix("snapshot:") {
+        return Ok(Side::Snapshot(name));
+    }
+    if let Some(name) = spec.strip_prefix("profile:") {
+        return Ok(Side::Profile(name));
+    }
+    if let Some(path) = spec.strip_prefix("file:") {
+        return Ok(Side::File(path));
+    }
+
+    if Path::new(spec).is_file() {
+        return Ok(Side::File(spec));
+    }
+    match (is_snapshot(spec)?, is_profile(spec)?) {
+        (true, true) => Err(eyre!(
+            "'{}' is both a snapshot and a profile, use snapshot:{} or profile:{}",
+            spec,
+            spec,
+            spec
+        )),
+        (true, false) => Ok(Side::Snapshot(spec)),
+        (false, true) => Ok(Side::Profile(spec)),
+        (false, false) => Err(eyre!("'{}' is not 'current', a file, a snapshot or a profile", spec)),
+    }
+}
+
+/// Loads the variables of one side of a diff.
+fn load_side(spec: &str) -> Result<Vec<EnvVar>> {
+    let side = classify(
+        spec,
+        |name| Ok(SnapshotManager::new()?.get(name).is_ok()),
+        |name| Ok(ProfileManager::new()?.get(name).is_some()),
+    )?;
+    match side {
+        Side::Current => {
+            let mut manager = EnvVarManager::new();
+            manager.load_all()?;
+            Ok(manager.list().into_iter().cloned().collect())
+        }
+        Side::Snapshot(name) => load_snapshot(name),
+        Side::Profile(name) => load_profile(name),
+        Side::File(path) => load_file(path),
+    }
+}
+
+fn load_snapshot(name: &str) -> Result<Vec<EnvVar>> {
+    let snapshots = SnapshotManager::new()?;
+    let mut snapshot = snapshots.get(name)?;
+    snapshots.decrypt(&mut snapshot)?;
+    Ok(snapshot.variables.into_values().collect())
+}
+
+fn load_profile(name: &str) -> Result<Vec<EnvVar>> {
+    let vars = ProfileManager::new()?.resolve(name)?;
+    Ok(vars
+        .into_iter()
+        .map(|(var_name, var)| side_var(var_name, var.value, Some(VarOrigin::Profile(var.profile))))
+        .collect())
+}
+
+fn load_file(path: &str) -> Result<Vec<EnvVar>> {
+    let mut importer = Importer::new();
+    importer.import_from_file(path, ImportFormat::from_extension(path)?)?;
+    Ok(importer
+        .get_variables()
+        .into_iter()
+        .map(|(name, value)| {
+            let origin = importer.origin(&name);
+            side_var(name, value, origin)
+        })
+        .collect())
+}
+
+fn side_var(name: String, value: String, origin: Option<VarOrigin>) -> EnvVar {
+    EnvVar {
+        name,
+        value,
+        source: EnvVarSource::Application("envx".to_string()),
+        modified: Utc::now(),
+        original_value: None,
+        origin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn var(name: &str, value: &str) -> EnvVar {
+        side_var(name.to_string(), value.to_string(), None)
+    }
+
+    fn sample_diff() -> SnapshotDiff {
+        SnapshotDiff::between(
+            vec![var("KEEP", "1"), var("GONE", "old"), var("PORT", "80")],
+            vec![var("KEEP", "1"), var("NEW", "x"), var("PORT", "8080")],
+        )
+    }
+
+    fn lookup(found: bool) -> impl FnOnce(&str) -> Result<bool> {
+        move |_| Ok(found)
+    }
+
+    #[test]
+    fn test_classify_prefixes_win() {
+        let snapshot = classify("snapshot:dev", lookup(false), lookup(true)).unwrap();
+        assert_eq!(snapshot, Side::Snapshot("dev"));
+        let profile = classify("profile:dev", lookup(true), lookup(false)).unwrap();
+        assert_eq!(profile, Side::Profile("dev"));
+        assert_eq!(
+            classify("file:dev", lookup(true), lookup(true)).unwrap(),
+            Side::File("dev")
+        );
+        assert_eq!(classify("current", lookup(true), lookup(true)).unwrap(), Side::Current);
+    }
+
+    #[test]
+    fn test_classify_prefers_existing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("dev.env");
+        std::fs::write(&path, "A=1\n").unwrap();
+        let spec = path.to_str().unwrap();
+
+        assert_eq!(classify(spec, lookup(true), lookup(true)).unwrap(), Side::File(spec));
+    }
+
+    #[test]
+    fn test_classify_bare_names() {
+        assert_eq!(
+            classify("dev", lookup(true), lookup(false)).unwrap(),
+            Side::Snapshot("dev")
+        );
+        assert_eq!(
+            classify("dev", lookup(false), lookup(true)).unwrap(),
+            Side::Profile("dev")
+        );
+
+        let ambiguous = classify("dev", lookup(true), lookup(true)).unwrap_err().to_string();
+        assert!(ambiguous.contains("snapshot:dev or profile:dev"));
+
+        let unknown = classify("dev", lookup(false), lookup(false)).unwrap_err().to_string();
+        assert!(unknown.contains("is not 'current'"));
+    }
+
+    #[test]
+    fn test_render_unified() {
+        let output = render(&sample_diff(), DiffFormat::Unified, "a", "b", |var| var.value.clone()).unwrap();
+        assert_eq!(output, "--- a\n+++ b\n-GONE=old\n+NEW=x\n-PORT=80\n+PORT=8080");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let output = render(&sample_diff(), DiffFormat::Table, "a", "b", |var| var.value.clone()).unwrap();
+        let rows: Vec<&str> = output.lines().collect();
+        assert!(output.contains("Variable"));
+        assert!(rows.iter().any(|row| row.contains("GONE") && row.contains("removed")));
+        assert!(rows.iter().any(|row| row.contains("NEW") && row.contains("added")));
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("PORT") && row.contains("modified") && row.contains("8080"))
+        );
+        assert!(!output.contains("KEEP"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let output = render(&sample_diff(), DiffFormat::Json, "a", "b", |var| var.value.clone()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "added": { "NEW": "x" },
+                "removed": { "GONE": "old" },
+                "modified": { "PORT": { "old": "80", "new": "8080" } },
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_masks_and_reports_no_differences() {
+        let output = render(&sample_diff(), DiffFormat::Unified, "a", "b", |_| "****".to_string()).unwrap();
+        assert!(output.contains("+NEW=****"));
+        assert!(!output.contains("8080"));
+
+        let same = SnapshotDiff::between(vec![var("A", "1")], vec![var("A", "1")]);
+        let output = render(&same, DiffFormat::Table, "a", "b", |var| var.value.clone()).unwrap();
+        assert_eq!(output, "No differences found between a and b.");
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use envx_core::{DotenvDocument, EnvVarManager};
+
+#[derive(Args)]
+pub struct FileArgs {
+    #[command(subcommand)]
+    pub command: FileCommands,
+}
+
+#[derive(Subcommand)]
+pub enum FileCommands {
+    /// Set a variable in a .env file, keeping its comments and formatting
+    Set {
+        /// The .env file (created if it doesn't exist)
+        file: PathBuf,
+        /// Variable name
+        key: String,
+        /// Value (defaults to the variable's value in the current environment)
+        value: Option<String>,
+    },
+    /// Remove a variable from a .env file, keeping its comments and formatting
+    Unset {
+        /// The .env file
+        file: PathBuf,
+        /// Variable name
+        key: String,
+    },
+}
+
+/// Handle commands that edit .env files.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The file cannot be read or written, or has invalid lines
+/// - The variable name cannot be used in a .env file
+/// - No value is given and the variable isn't set in the environment
+/// - The variable to remove isn't in the file
+pub fn handle_file(args: FileArgs) -> Result<()> {
+    match args.command {
+        FileCommands::Set { file, key, value } => {
+            let value = if let Some(value) = value {
+                value
+            } else {
+                let mut manager = EnvVarManager::new();
+                manager.load_all()?;
+                manager
+                    .get(&key)
+                    .map(|var| var.value.clone())
+                    .ok_or_else(|| eyre!("No value given and '{}' is not set in the environment", key))?
+            };
+
+            let mut document = DotenvDocument::open(&file)?;
+            let existed = document.get(&key).is_some();
+            document.set(&key, &value)?;
+            document.save(&file)?;
+
+            let action = if existed { "Updated" } else { "Added" };
+            println!("✅ {action} {key} in {}", file.display());
+        }
+        FileCommands::Unset { file, key } => {
+            if !file.exists() {
+                return Err(eyre!("File not found: {}", file.display()));
+            }
+
+            let mut document = DotenvDocument::open(&file)?;
+            if !document.unset(&key) {
+                return Err(eyre!("Variable '{}' not found in {}", key, file.display()));
+            }
+            document.save(&file)?;
+            println!("✅ Removed {key} from {}", file.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use color_eyre::Result;
+use envx_core::{EnvVarManager, HistoryEntry};
+
+/// Handle the history command: show recent changes, newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the journal cannot be read or cleared.
+pub fn handle_history(limit: usize, clear: bool) -> Result<()> {
+    let mut manager = EnvVarManager::with_journal()?;
+
+    if clear {
+        manager.history.clear();
+        manager.history.save()?;
+        println!("✅ Cleared history");
+        return Ok(());
+    }
+
+    let undone = manager.history.undone();
+    let recent = manager.history.recent(limit);
+    if recent.is_empty() && undone.is_empty() {
+        println!("No changes recorded yet.");
+        return Ok(());
+    }
+
+    if !undone.is_empty() {
+        println!("Undone (redo with `envx redo`):");
+        for entry in undone.iter().take(limit) {
+            print_entry("↷", entry);
+        }
+        println!();
+    }
+
+    if !recent.is_empty() {
+        println!("History (undo with `envx undo`):");
+        for (i, entry) in recent.iter().enumerate() {
+            print_entry(&format!("{:>3}", i + 1), entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle the undo command: revert the last `count` changes.
+///
+/// # Errors
+///
+/// This function will return an error if a change cannot be reverted or the
+/// journal cannot be read or written.
+pub fn handle_undo(count: usize) -> Result<()> {
+    let mut manager = EnvVarManager::with_journal()?;
+    manager.load_all()?;
+
+    for _ in 0..count {
+        let Some(entry) = manager.undo()? else {
+            println!("Nothing left to undo.");
+            break;
+        };
+        println!("↶ Undid: {}", entry.action.summary());
+    }
+
+    Ok(())
+}
+
+/// Handle the redo command: reapply the last `count` undone changes.
+///
+/// # Errors
+///
+/// This function will return an error if a change cannot be reapplied or the
+/// journal cannot be read or written.
+pub fn handle_redo(count: usize) -> Result<()> {
+    let mut manager = EnvVarManager::with_journal()?;
+    manager.load_all()?;
+
+    for _ in 0..count {
+        let Some(entry) = manager.redo()? else {
+            println!("Nothing left to redo.");
+            break;
+        };
+        println!("↷ Redid: {}", entry.action.summary());
+    }
+
+    Ok(())
+}
+
+fn print_entry(marker: &str, entry: &HistoryEntry) {
+    println!(
+        "{marker}  {}  {}",
+        entry
+            .timestamp
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        entry.action.summary()
+    );
+}
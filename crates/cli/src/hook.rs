@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand, ValueEnum};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use envx_core::{AllowList, EnvChange, EnvComposer, HookState, ProfileManager, ProjectManager, ShellDialect};
+use indexmap::IndexMap;
+
+#[derive(Args)]
+pub struct HookArgs {
+    #[command(subcommand)]
+    pub command: HookCommands,
+}
+
+#[derive(Subcommand)]
+pub enum HookCommands {
+    /// Print the hook for bash (add `eval "$(envx hook bash)"` to ~/.bashrc)
+    Bash,
+    /// Print the hook for zsh (add `eval "$(envx hook zsh)"` to ~/.zshrc)
+    Zsh,
+    /// Print the hook for fish (add `envx hook fish | source` to config.fish)
+    Fish,
+    /// Trust a project so the hook may load its environment (again after its
+    /// configuration or auto-load files change)
+    Allow {
+        /// Directory inside the project (defaults to the current directory)
+        path: Option<PathBuf>,
+    },
+    /// Stop trusting a project
+    Deny {
+        /// Directory inside the project (defaults to the current directory)
+        path: Option<PathBuf>,
+    },
+    /// Print the statements that update the shell for the current directory
+    #[command(hide = true)]
+    Export {
+        /// Shell to print statements for
+        shell: HookShell,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<HookShell> for ShellDialect {
+    fn from(shell: HookShell) -> Self {
+        match shell {
+            HookShell::Bash => Self::Bash,
+            HookShell::Zsh => Self::Zsh,
+            HookShell::Fish => Self::Fish,
+        }
+    }
+}
+
+/// Handle shell hook commands.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The path of the envx executable cannot be determined
+/// - No project is found for `allow`/`deny`
+/// - The allow-list cannot be read or written
+/// - The project environment cannot be resolved
+pub fn handle_hook(args: HookArgs) -> Result<()> {
+    match args.command {
+        HookCommands::Bash => print_hook(HookShell::Bash)?,
+        HookCommands::Zsh => print_hook(HookShell::Zsh)?,
+        HookCommands::Fish => print_hook(HookShell::Fish)?,
+        HookCommands::Allow { path } => {
+            let (root, config_path) = find_project(path)?;
+            AllowList::new()?.allow(&root, &config_path)?;
+            println!("✅ Allowed {}", root.display());
+        }
+        HookCommands::Deny { path } => {
+            let (root, _) = find_project(path)?;
+            if AllowList::new()?.deny(&root)? {
+                println!("✅ Denied {}", root.display());
+            } else {
+                println!("{} was not allowed", root.display());
+            }
+        }
+        HookCommands::Export { shell } => {
+            for change in hook_changes()? {
+                // Progress goes to stderr so it doesn't end up in the shell's `eval`
+                match change.render(shell.into()) {
+                    Ok(statement) => println!("{statement}"),
+                    Err(e) => eprintln!("envx: skipped {}: {e}", change.name()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_hook(shell: HookShell) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.to_str().ok_or_else(|| eyre!("Invalid envx executable path"))?;
+    let exe = format!("'{}'", exe.replace('\'', r"'\''"));
+
+    let script = match shell {
+        HookShell::Bash => format!(
+            r#"_envx_hook() {{
+  local previous_exit_status=$?
+  if [[ "$PWD" != "${{_ENVX_LAST_PWD:-}}" ]]; then
+    _ENVX_LAST_PWD="$PWD"
+    eval "$({exe} hook export bash)"
+  fi
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_envx_hook;"* ]]; then
+  PROMPT_COMMAND="_envx_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi"#
+        ),
+        HookShell::Zsh => format!(
+            r#"_envx_hook() {{
+  eval "$({exe} hook export zsh)"
+}}
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_envx_hook]}} )); then
+  chpwd_functions=(_envx_hook $chpwd_functions)
+fi
+_envx_hook"#
+        ),
+        HookShell::Fish => format!(
+            r"function __envx_hook --on-variable PWD
+    {exe} hook export fish | source
+end
+__envx_hook"
+        ),
+    };
+
+    println!("{script}");
+    Ok(())
+}
+
+fn find_project(path: Option<PathBuf>) -> Result<(PathBuf, PathBuf)> {
+    let dir = match path {
+        Some(path) => path.canonicalize()?,
+        None => std::env::current_dir()?,
+    };
+
+    let mut project = ProjectManager::for_dir(dir.clone());
+    let root = project
+        .find_and_load()?
+        .ok_or_else(|| eyre!("No project configuration found in {} or its parents", dir.display()))?;
+    let config_path = root.join(".envx").join("config.yaml");
+    Ok((root, config_path))
+}
+
+/// Computes the changes for the current directory, reporting progress on stderr
+/// so it doesn't end up in the shell's `eval`.
+fn hook_changes() -> Result<Vec<EnvChange>> {
+    let state = HookState::from_env();
+    let current: IndexMap<String, String> = std::env::vars().collect();
+
+    let mut project = ProjectManager::new()?;
+    let root = match project.find_and_load() {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("envx: could not load project configuration: {e}");
+            None
+        }
+    };
+
+    let target = match root {
+        Some(root) if AllowList::new()?.is_allowed(&root, &root.join(".envx").join("config.yaml")) => {
+            let base = state.base_env(&current);
+            let mut env = EnvComposer::new();
+            for (name, value) in &base {
+                env.set(name, value, None);
+            }
+            project.compose(&mut env, &ProfileManager::new()?)?;
+
+            let vars: IndexMap<String, String> = env
+                .vars()
+                .iter()
+                .filter(|(name, var)| base.get(*name) != Some(&var.value))
+                .map(|(name, var)| (name.clone(), var.value.clone()))
+                .collect();
+            Some((root, vars))
+        }
+        Some(root) => {
+            eprintln!(
+                "envx: {}, a file it auto-loads or a project profile is not allowed. Run `envx hook allow` to load its environment.",
+                root.join(".envx").join("config.yaml").display()
+            );
+            None
+        }
+        None => None,
+    };
+
+    if state.root != target.as_ref().map(|(root, _)| root.clone()) {
+        if let Some(old_root) = &state.root {
+            eprintln!("envx: unloading {}", old_root.display());
+        }
+        if let Some((root, vars)) = &target {
+            eprintln!("envx: loading {} ({} variables)", root.display(), vars.len());
+        }
+    }
+
+    state.transition(&current, target)
+}
@@ -1,6 +1,10 @@
 pub mod cli;
 mod deps;
+mod diff;
 mod docs;
+mod file;
+mod history;
+mod hook;
 mod list;
 pub mod monitor;
 mod path;
@@ -8,14 +12,22 @@ mod profile;
 mod project;
 mod rename;
 mod replace;
+mod run;
+mod secrets;
+mod shell_export;
 mod snapshot;
+mod source;
 mod watch;
 mod wizard;
 
 #[allow(clippy::wildcard_imports)]
 pub use cli::*;
 pub use deps::{CleanupArgs, DepsArgs, handle_cleanup, handle_deps};
+pub use diff::{DiffArgs, handle_diff};
 pub use docs::{DocsArgs, handle_docs};
+pub use file::{FileArgs, handle_file};
+pub use history::{handle_history, handle_redo, handle_undo};
+pub use hook::{HookArgs, handle_hook};
 pub use list::handle_list_command;
 pub use monitor::MonitorArgs;
 pub use path::handle_path_command;
@@ -23,6 +35,10 @@ pub use profile::{ProfileArgs, handle_profile};
 pub use project::{ProjectArgs, handle_project};
 pub use rename::{RenameArgs, handle_rename};
 pub use replace::{handle_find_replace, handle_replace};
+pub use run::{RunArgs, handle_run};
+pub use secrets::{SecretsArgs, handle_secrets};
+pub use shell_export::{ShellExportArgs, handle_shell_export};
 pub use snapshot::{SnapshotArgs, handle_snapshot};
+pub use source::SourceFilter;
 pub use watch::{WatchArgs, handle_watch};
 pub use wizard::{list_templates, run_wizard};
@@ -2,7 +2,7 @@ use color_eyre::Result;
 use color_eyre::eyre::eyre;
 use comfy_table::{Attribute, Cell, Color, ContentArrangement, Table};
 use console::{Term, style};
-use envx_core::EnvVarManager;
+use envx_core::{EnvVar, EnvVarManager, SecretClassifier};
 
 /// Handles the list command to display environment variables with various formatting options.
 ///
@@ -14,12 +14,14 @@ use envx_core::EnvVarManager;
 /// * `names_only` - Whether to display only variable names
 /// * `limit` - Optional limit on number of variables to display
 /// * `stats` - Whether to display statistics
+/// * `reveal` - Whether to show secret values instead of masking them
 ///
 /// # Errors
 /// Returns an error if:
 /// - Environment variables cannot be loaded
 /// - Invalid source type is provided
 /// - JSON serialization fails
+#[allow(clippy::too_many_arguments)]
 pub fn handle_list_command(
     source: Option<&str>,
     query: Option<&str>,
@@ -28,6 +30,7 @@ pub fn handle_list_command(
     names_only: bool,
     limit: Option<usize>,
     stats: bool,
+    reveal: bool,
 ) -> Result<()> {
     let mut manager = EnvVarManager::new();
     manager.load_all()?;
@@ -67,6 +70,23 @@ pub fn handle_list_command(
         print_statistics(&manager, &vars, total_count, query, source);
     }
 
+    // Mask secrets unless asked to reveal them
+    let masked: Vec<EnvVar>;
+    let vars = if reveal {
+        vars
+    } else {
+        let classifier = SecretClassifier::for_current_project();
+        masked = vars
+            .into_iter()
+            .map(|var| {
+                let mut var = var.clone();
+                classifier.mask_var(&mut var);
+                var
+            })
+            .collect();
+        masked.iter().collect()
+    };
+
     // Handle names_only flag
     if names_only {
         for var in vars {
@@ -178,7 +198,7 @@ fn print_statistics(
 }
 
 fn print_source_bar(label: &str, count: usize, max: usize, width: usize, color: &str) {
-    let filled = if max > 0 { (count * width / max).max(1) } else { 0 };
+    let filled = (count * width).checked_div(max).map_or(0, |f| f.max(1));
 
     let bar = "█".repeat(filled);
     let empty = "░".repeat(width - filled);
@@ -7,10 +7,14 @@ use comfy_table::Table;
 use comfy_table::presets::UTF8_FULL;
 use envx_core::EnvVarManager;
 use envx_core::EnvVarSource;
+use envx_core::SecretClassifier;
+use envx_core::secret;
 use serde::Serialize;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::SourceFilter;
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum OutputFormat {
     /// Live terminal output
@@ -21,29 +25,6 @@ pub enum OutputFormat {
     JsonLines,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
-pub enum SourceFilter {
-    #[value(name = "system")]
-    System,
-    #[value(name = "user")]
-    User,
-    #[value(name = "process")]
-    Process,
-    #[value(name = "shell")]
-    Shell,
-}
-
-impl From<SourceFilter> for EnvVarSource {
-    fn from(filter: SourceFilter) -> Self {
-        match filter {
-            SourceFilter::System => EnvVarSource::System,
-            SourceFilter::User => EnvVarSource::User,
-            SourceFilter::Process => EnvVarSource::Process,
-            SourceFilter::Shell => EnvVarSource::Shell,
-        }
-    }
-}
-
 #[derive(Args)]
 pub struct MonitorArgs {
     /// Variables to monitor (monitor all if not specified)
@@ -77,6 +58,10 @@ pub struct MonitorArgs {
     /// Export report on exit
     #[arg(long)]
     pub export_report: Option<PathBuf>,
+
+    /// Show secret values instead of masking them
+    #[arg(long)]
+    pub reveal: bool,
 }
 
 struct MonitorState {
@@ -115,10 +100,12 @@ pub fn handle_monitor(args: MonitorArgs) -> Result<()> {
         start_time: Local::now(),
     };
 
+    let classifier = (!args.reveal).then(SecretClassifier::for_current_project);
+
     print_monitor_header(&args);
 
     if args.show_initial {
-        print_initial_state(&state.initial);
+        print_initial_state(&state.initial, classifier.as_ref());
     }
 
     // Set up Ctrl+C handler
@@ -138,7 +125,12 @@ pub fn handle_monitor(args: MonitorArgs) -> Result<()> {
 
         state.current = collect_variables(&current_manager, &args);
 
-        let changes = detect_changes(&state);
+        let mut changes = detect_changes(&state);
+        if let Some(classifier) = &classifier {
+            for change in &mut changes {
+                mask_change(classifier, change);
+            }
+        }
 
         if !changes.is_empty() || !args.changes_only {
             display_changes(&changes, &args);
@@ -229,6 +221,20 @@ fn detect_changes(state: &MonitorState) -> Vec<ChangeRecord> {
     changes
 }
 
+/// Masks the old and new value of a change to a variable holding a secret.
+fn mask_change(classifier: &SecretClassifier, change: &mut ChangeRecord) {
+    let is_secret = [&change.old_value, &change.new_value]
+        .into_iter()
+        .flatten()
+        .any(|value| classifier.is_secret(&change.variable, value));
+
+    if is_secret {
+        for value in [&mut change.old_value, &mut change.new_value].into_iter().flatten() {
+            *value = secret::mask(value);
+        }
+    }
+}
+
 fn display_changes(changes: &[ChangeRecord], args: &MonitorArgs) {
     match args.format {
         OutputFormat::Live => {
@@ -313,7 +319,7 @@ fn print_monitor_header(args: &MonitorArgs) {
     println!("Press Ctrl+C to stop\n");
 }
 
-fn print_initial_state(vars: &HashMap<String, String>) {
+fn print_initial_state(vars: &HashMap<String, String>, classifier: Option<&SecretClassifier>) {
     if vars.is_empty() {
         println!("No variables match the criteria\n");
         return;
@@ -324,10 +330,11 @@ fn print_initial_state(vars: &HashMap<String, String>) {
     table.set_header(vec!["Variable", "Initial Value"]);
 
     for (name, value) in vars {
+        let value = classifier.map_or_else(|| value.clone(), |c| c.mask(name, value));
         let display_value = if value.len() > 50 {
             format!("{}...", &value[..47])
         } else {
-            value.clone()
+            value
         };
         table.add_row(vec![name.clone(), display_value]);
     }
@@ -423,6 +430,7 @@ mod tests {
             source,
             modified: chrono::Utc::now(),
             original_value: None,
+            origin: None,
         }
     }
 
@@ -479,6 +487,7 @@ mod tests {
             interval: 2,
             show_initial: false,
             export_report: None,
+            reveal: false,
         };
 
         let result = collect_variables(&manager, &args);
@@ -509,6 +518,7 @@ mod tests {
             interval: 2,
             show_initial: false,
             export_report: None,
+            reveal: false,
         };
 
         let result = collect_variables(&manager, &args);
@@ -535,6 +545,7 @@ mod tests {
             interval: 2,
             show_initial: false,
             export_report: None,
+            reveal: false,
         };
 
         let result = collect_variables(&manager, &args);
@@ -563,6 +574,7 @@ mod tests {
             interval: 2,
             show_initial: false,
             export_report: None,
+            reveal: false,
         };
 
         let result = collect_variables(&manager, &args);
@@ -584,6 +596,7 @@ mod tests {
             interval: 2,
             show_initial: false,
             export_report: None,
+            reveal: false,
         };
 
         let result = collect_variables(&manager, &args);
@@ -26,7 +26,7 @@ use envx_core::{EnvVarManager, PathManager};
 /// calling `expect()`.
 #[allow(clippy::too_many_lines)]
 pub fn handle_path_command(action: Option<PathAction>, check: bool, var: &str, permanent: bool) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     // Get the PATH variable
@@ -132,6 +132,9 @@ pub fn handle_path_command(action: Option<PathAction>, check: bool, var: &str, p
             if dry_run {
                 println!("\n(Dry run - no changes made)");
             } else {
+                if permanent {
+                    crate::cli::snapshot_before(&format!("path clean --permanent {var}"), &manager)?;
+                }
                 let removed_invalid = path_mgr.remove_invalid();
                 let removed_dupes = if dedupe {
                     path_mgr.deduplicate(false) // Keep last by default
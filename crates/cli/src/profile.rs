@@ -1,7 +1,10 @@
-use clap::{Args, Subcommand};
+use crate::cli::warn_unresolved;
+use clap::{Args, Subcommand, ValueEnum};
 use color_eyre::Result;
 use comfy_table::Table;
-use envx_core::{EnvVarManager, ProfileManager};
+use dialoguer::Select;
+use dialoguer::theme::ColorfulTheme;
+use envx_core::{EnvVarManager, LayerConflict, MergeSide, ProfileManager, ProfileScope, ProfileVar, SecretClassifier};
 
 #[derive(Args)]
 pub struct ProfileArgs {
@@ -18,6 +21,12 @@ pub enum ProfileCommands {
         /// Description
         #[arg(short, long)]
         description: Option<String>,
+        /// Inherit variables from this profile
+        #[arg(long, value_name = "PARENT")]
+        from: Option<String>,
+        /// Store the profile in the current project's .envx/profiles instead of globally
+        #[arg(long)]
+        project: bool,
     },
     /// List all profiles
     List,
@@ -25,6 +34,23 @@ pub enum ProfileCommands {
     Show {
         /// Profile name (shows active if not specified)
         name: Option<String>,
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
+        /// Show the merged variables including inherited ones
+        #[arg(long)]
+        resolved: bool,
+    },
+    /// Set or remove the profile a profile inherits from
+    SetParent {
+        /// Profile name
+        name: String,
+        /// Parent profile name
+        #[arg(required_unless_present = "clear")]
+        parent: Option<String>,
+        /// Stop inheriting from any profile
+        #[arg(long, conflicts_with = "parent")]
+        clear: bool,
     },
     /// Switch to a profile
     Switch {
@@ -53,6 +79,20 @@ pub enum ProfileCommands {
         /// Variable name
         name: String,
     },
+    /// Enable variables of a profile again
+    Enable {
+        /// Profile name
+        profile: String,
+        /// Variable name or wildcard pattern (e.g. `API_*`)
+        pattern: String,
+    },
+    /// Disable variables of a profile without removing their values
+    Disable {
+        /// Profile name
+        profile: String,
+        /// Variable name or wildcard pattern (e.g. `API_*`)
+        pattern: String,
+    },
     /// Delete a profile
     Delete {
         /// Profile name
@@ -80,13 +120,42 @@ pub enum ProfileCommands {
         #[arg(short, long)]
         overwrite: bool,
     },
-    /// Apply a profile to current environment
+    /// Compare the variables of two profiles
+    Diff {
+        /// First profile
+        profile1: String,
+        /// Second profile
+        profile2: String,
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// Merge the variables of one profile into another
+    Merge {
+        /// Profile to merge from
+        src: String,
+        /// Profile to merge into
+        dst: String,
+        /// Resolve conflicts without asking: keep the target's value (ours) or take the source's (theirs)
+        #[arg(short, long)]
+        strategy: Option<MergeStrategy>,
+    },
+    /// Apply one or more profiles to current environment
     Apply {
-        /// Profile name
-        name: String,
+        /// Profile names, later profiles override earlier ones
+        #[arg(required = true)]
+        names: Vec<String>,
     },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum MergeStrategy {
+    /// Keep the value of the profile merged into
+    Ours,
+    /// Take the value of the profile merged from
+    Theirs,
+}
+
 /// Handle profile-related commands.
 ///
 /// # Errors
@@ -101,18 +170,31 @@ pub enum ProfileCommands {
 /// - Profile data cannot be serialized/deserialized
 pub fn handle_profile(args: ProfileArgs) -> Result<()> {
     let mut profile_manager = ProfileManager::new()?;
-    let mut env_manager = EnvVarManager::new();
+    let mut env_manager = EnvVarManager::with_journal()?;
     env_manager.load_all()?;
 
     match args.command {
-        ProfileCommands::Create { name, description } => {
-            handle_profile_create(&mut profile_manager, &name, description)?;
+        ProfileCommands::Create {
+            name,
+            description,
+            from,
+            project,
+        } => {
+            let scope = if project {
+                ProfileScope::Project
+            } else {
+                ProfileScope::Global
+            };
+            handle_profile_create(&mut profile_manager, &name, description, from.as_deref(), scope)?;
         }
         ProfileCommands::List => {
             handle_profile_list(&profile_manager);
         }
-        ProfileCommands::Show { name } => {
-            handle_profile_show(&profile_manager, name)?;
+        ProfileCommands::Show { name, reveal, resolved } => {
+            handle_profile_show(&profile_manager, name, reveal, resolved)?;
+        }
+        ProfileCommands::SetParent { name, parent, clear: _ } => {
+            handle_profile_set_parent(&mut profile_manager, &name, parent.as_deref())?;
         }
         ProfileCommands::Switch { name, apply } => {
             handle_profile_switch(&mut profile_manager, &mut env_manager, &name, apply)?;
@@ -128,6 +210,12 @@ pub fn handle_profile(args: ProfileArgs) -> Result<()> {
         ProfileCommands::Remove { profile, name } => {
             handle_profile_remove(&mut profile_manager, &profile, &name)?;
         }
+        ProfileCommands::Enable { profile, pattern } => {
+            handle_profile_set_enabled(&mut profile_manager, &profile, &pattern, true)?;
+        }
+        ProfileCommands::Disable { profile, pattern } => {
+            handle_profile_set_enabled(&mut profile_manager, &profile, &pattern, false)?;
+        }
         ProfileCommands::Delete { name, force } => {
             handle_profile_delete(&mut profile_manager, &name, force)?;
         }
@@ -137,31 +225,78 @@ pub fn handle_profile(args: ProfileArgs) -> Result<()> {
         ProfileCommands::Import { file, name, overwrite } => {
             handle_profile_import(&mut profile_manager, &file, name, overwrite)?;
         }
-        ProfileCommands::Apply { name } => {
-            handle_profile_apply(&mut profile_manager, &mut env_manager, &name)?;
+        ProfileCommands::Diff {
+            profile1,
+            profile2,
+            reveal,
+        } => {
+            handle_profile_diff(&profile_manager, &profile1, &profile2, reveal)?;
+        }
+        ProfileCommands::Merge { src, dst, strategy } => {
+            handle_profile_merge(&mut profile_manager, &src, &dst, strategy)?;
+        }
+        ProfileCommands::Apply { names } => {
+            handle_profile_apply(&mut profile_manager, &mut env_manager, &names)?;
         }
     }
 
     Ok(())
 }
 
-fn handle_profile_create(profile_manager: &mut ProfileManager, name: &str, description: Option<String>) -> Result<()> {
-    profile_manager.create(name.to_string(), description)?;
-    println!("✅ Created profile: {name}");
+fn handle_profile_create(
+    profile_manager: &mut ProfileManager,
+    name: &str,
+    description: Option<String>,
+    from: Option<&str>,
+    scope: ProfileScope,
+) -> Result<()> {
+    profile_manager.create_in(name.to_string(), description, from, scope)?;
+    match from {
+        Some(parent) => println!("✅ Created {scope} profile: {name} (inherits from {parent})"),
+        None => println!("✅ Created {scope} profile: {name}"),
+    }
+    if profile_manager.shadows_global(name) {
+        println!("   It hides the global profile '{name}' inside this project.");
+    }
+    Ok(())
+}
+
+fn handle_profile_set_parent(profile_manager: &mut ProfileManager, name: &str, parent: Option<&str>) -> Result<()> {
+    profile_manager.set_parent(name, parent)?;
+    match parent {
+        Some(parent) => println!("✅ Profile {name} now inherits from {parent}"),
+        None => println!("✅ Profile {name} no longer inherits from another profile"),
+    }
     Ok(())
 }
 
 fn handle_profile_list(profile_manager: &ProfileManager) {
-    let profiles = profile_manager.list();
+    let mut profiles = profile_manager.list();
     if profiles.is_empty() {
         println!("No profiles found.");
     }
 
     let active = profile_manager.active().map(|p| &p.name);
     let mut table = Table::new();
-    table.set_header(vec!["Name", "Variables", "Created", "Description", "Status"]);
+    table.set_header(vec![
+        "Name",
+        "Variables",
+        "Inherits",
+        "Scope",
+        "Created",
+        "Description",
+        "Status",
+    ]);
 
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
     for profile in profiles {
+        let scope = match profile_manager.scope(&profile.name) {
+            Some(ProfileScope::Project) if profile_manager.shadows_global(&profile.name) => {
+                "project (hides global)".to_string()
+            }
+            Some(scope) => scope.to_string(),
+            None => String::new(),
+        };
         let status = if active == Some(&profile.name) {
             "● Active"
         } else {
@@ -171,6 +306,8 @@ fn handle_profile_list(profile_manager: &ProfileManager) {
         table.add_row(vec![
             profile.name.clone(),
             profile.variables.len().to_string(),
+            profile.parent.clone().unwrap_or_default(),
+            scope,
             profile.created_at.format("%Y-%m-%d").to_string(),
             profile.description.clone().unwrap_or_default(),
             status.to_string(),
@@ -180,7 +317,12 @@ fn handle_profile_list(profile_manager: &ProfileManager) {
     println!("{table}");
 }
 
-fn handle_profile_show(profile_manager: &ProfileManager, name: Option<String>) -> Result<()> {
+fn handle_profile_show(
+    profile_manager: &ProfileManager,
+    name: Option<String>,
+    reveal: bool,
+    resolved: bool,
+) -> Result<()> {
     let profile = if let Some(name) = name {
         profile_manager
             .get(&name)
@@ -192,18 +334,50 @@ fn handle_profile_show(profile_manager: &ProfileManager, name: Option<String>) -
     };
 
     println!("Profile: {}", profile.name);
+    match (profile_manager.scope(&profile.name), profile_manager.project_dir()) {
+        (Some(ProfileScope::Project), Some(dir)) => println!("Scope: project ({})", dir.display()),
+        (Some(scope), _) => println!("Scope: {scope}"),
+        (None, _) => {}
+    }
     println!("Description: {}", profile.description.as_deref().unwrap_or(""));
     println!("Created: {}", profile.created_at.format("%Y-%m-%d %H:%M:%S"));
     println!("Updated: {}", profile.updated_at.format("%Y-%m-%d %H:%M:%S"));
-    if let Some(parent) = &profile.parent {
-        println!("Inherits from: {parent}");
+    if profile.parent.is_some() {
+        let chain: Vec<&str> = profile_manager
+            .chain(&profile.name)?
+            .iter()
+            .skip(1)
+            .map(|p| p.name.as_str())
+            .collect();
+        println!("Inherits from: {}", chain.join(" → "));
+    }
+
+    let secrets = SecretClassifier::for_current_project();
+    if resolved {
+        println!("\nResolved variables:");
+        let vars = profile_manager.resolve(&profile.name)?;
+        let width = vars.keys().map(String::len).max().unwrap_or(0);
+        for (name, var) in &vars {
+            let value = if reveal {
+                var.value.clone()
+            } else {
+                secrets.mask(name, &var.value)
+            };
+            println!("  {name:<width$} = {value}  (from {})", var.profile);
+        }
+        return Ok(());
     }
-    println!("\nVariables:");
 
+    println!("\nVariables:");
     for (name, var) in &profile.variables {
         let status = if var.enabled { "✓" } else { "✗" };
         let override_flag = if var.override_system { " [override]" } else { "" };
-        println!("  {} {} = {}{}", status, name, var.value, override_flag);
+        let value = if reveal {
+            profile_manager.decrypt(&var.value)?
+        } else {
+            secrets.mask(name, &var.value)
+        };
+        println!("  {status} {name} = {value}{override_flag}");
     }
     Ok(())
 }
@@ -218,7 +392,8 @@ fn handle_profile_switch(
     println!("✅ Switched to profile: {name}");
 
     if apply {
-        profile_manager.apply(name, env_manager)?;
+        let unresolved = profile_manager.apply(name, env_manager)?;
+        warn_unresolved(&unresolved);
         println!("✅ Applied profile variables");
     }
     Ok(())
@@ -255,6 +430,26 @@ fn handle_profile_remove(profile_manager: &mut ProfileManager, profile: &str, na
     Ok(())
 }
 
+fn handle_profile_set_enabled(
+    profile_manager: &mut ProfileManager,
+    profile: &str,
+    pattern: &str,
+    enabled: bool,
+) -> Result<()> {
+    let names = profile_manager.set_enabled(profile, pattern, enabled)?;
+    let (icon, state) = if enabled {
+        ("✓", "Enabled")
+    } else {
+        ("✗", "Disabled")
+    };
+
+    println!("✅ {state} {} variable(s) in profile {profile}", names.len());
+    for name in names {
+        println!("  {icon} {name}");
+    }
+    Ok(())
+}
+
 fn handle_profile_delete(profile_manager: &mut ProfileManager, name: &str, force: bool) -> Result<()> {
     if !force {
         print!("⚠️  Delete profile '{name}'? [y/N] ");
@@ -299,12 +494,161 @@ fn handle_profile_import(
     Ok(())
 }
 
+fn handle_profile_diff(profile_manager: &ProfileManager, profile1: &str, profile2: &str, reveal: bool) -> Result<()> {
+    let diff = profile_manager.diff(profile1, profile2)?;
+    if diff.is_empty() {
+        println!("No differences found between profiles.");
+        return Ok(());
+    }
+
+    let secrets = (!reveal).then(SecretClassifier::for_current_project);
+    let display = |name: &str, var: &ProfileVar| {
+        let value = secrets
+            .as_ref()
+            .map_or_else(|| var.value.clone(), |secrets| secrets.mask(name, &var.value));
+        let disabled = if var.enabled { "" } else { " [disabled]" };
+        let override_flag = if var.override_system { " [override]" } else { "" };
+        format!("{value}{disabled}{override_flag}")
+    };
+
+    if !diff.added.is_empty() {
+        println!("➕ Added in {profile2}:");
+        for (name, var) in &diff.added {
+            println!("   {name} = {}", display(name, var));
+        }
+    }
+
+    if !diff.removed.is_empty() {
+        println!("\n➖ Removed in {profile2}:");
+        for (name, var) in &diff.removed {
+            println!("   {name} = {}", display(name, var));
+        }
+    }
+
+    if !diff.changed.is_empty() {
+        println!("\n🔄 Changed:");
+        for (name, (old, new)) in &diff.changed {
+            println!("   {name}:");
+            println!("     Old: {}", display(name, old));
+            println!("     New: {}", display(name, new));
+        }
+    }
+
+    if !diff.toggled.is_empty() {
+        println!("\n⏸️  Enabled state:");
+        for (name, (_, new)) in &diff.toggled {
+            let state = if new.enabled { "enabled" } else { "disabled" };
+            println!("   {name} is {state} in {profile2}");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_profile_merge(
+    profile_manager: &mut ProfileManager,
+    src: &str,
+    dst: &str,
+    strategy: Option<MergeStrategy>,
+) -> Result<()> {
+    let secrets = SecretClassifier::for_current_project();
+    let theme = ColorfulTheme::default();
+
+    let summary = profile_manager.merge(src, dst, |name, ours, theirs| match strategy {
+        Some(MergeStrategy::Ours) => Ok(MergeSide::Ours),
+        Some(MergeStrategy::Theirs) => Ok(MergeSide::Theirs),
+        None => {
+            let describe = |profile: &str, var: &ProfileVar| {
+                let disabled = if var.enabled { "" } else { " [disabled]" };
+                format!("{profile}: {}{disabled}", secrets.mask(name, &var.value))
+            };
+            let choice = Select::with_theme(&theme)
+                .with_prompt(format!("Conflict in {name}"))
+                .items(&[describe(dst, ours), describe(src, theirs)])
+                .default(0)
+                .interact()?;
+            Ok(if choice == 0 {
+                MergeSide::Ours
+            } else {
+                MergeSide::Theirs
+            })
+        }
+    })?;
+
+    println!(
+        "✅ Merged {src} into {dst}: {} added, {} taken from {src}, {} kept",
+        summary.added.len(),
+        summary.taken.len(),
+        summary.kept.len()
+    );
+    Ok(())
+}
+
 fn handle_profile_apply(
     profile_manager: &mut ProfileManager,
     env_manager: &mut EnvVarManager,
-    name: &str,
+    names: &[String],
 ) -> Result<()> {
-    profile_manager.apply(name, env_manager)?;
-    println!("✅ Applied profile: {name}");
+    let report = profile_manager.apply_layers(names, env_manager)?;
+    warn_unresolved(&report.unresolved);
+    print_layer_conflicts(&report.conflicts);
+
+    if let [name] = names {
+        println!("✅ Applied profile: {name}");
+        println!("💡 To load it into your shell: eval \"$(envx shell-export --profile {name})\"");
+    } else {
+        println!("✅ Applied profiles: {}", names.join(" + "));
+    }
     Ok(())
 }
+
+/// Prints which layer won each variable the applied profiles disagree on.
+pub(crate) fn print_layer_conflicts(conflicts: &[LayerConflict]) {
+    if let Some(table) = layer_conflicts_table(conflicts) {
+        println!("⚖️  Conflicts between layers:");
+        println!("{table}");
+    }
+}
+
+/// Like [`print_layer_conflicts`], but on stderr, for commands whose output
+/// is read by the shell or another program.
+pub(crate) fn warn_layer_conflicts(conflicts: &[LayerConflict]) {
+    if let Some(table) = layer_conflicts_table(conflicts) {
+        eprintln!("⚖️  Conflicts between layers:");
+        eprintln!("{table}");
+    }
+}
+
+fn layer_conflicts_table(conflicts: &[LayerConflict]) -> Option<Table> {
+    if conflicts.is_empty() {
+        return None;
+    }
+
+    let secrets = SecretClassifier::for_current_project();
+    let mut table = Table::new();
+    table.set_header(vec!["Variable", "Defined by", "Winner"]);
+
+    for conflict in conflicts {
+        let mut definitions: Vec<String> = conflict
+            .system
+            .iter()
+            .map(|value| format!("system = {}", secrets.mask(&conflict.name, value)))
+            .collect();
+        definitions.extend(conflict.layers.iter().map(|layer| {
+            let flag = if layer.override_system { " [override]" } else { "" };
+            format!(
+                "{} = {}{flag}",
+                layer.profile,
+                secrets.mask(&conflict.name, &layer.value)
+            )
+        }));
+
+        table.add_row(vec![
+            conflict.name.clone(),
+            definitions.join("\n"),
+            conflict.winner.clone().unwrap_or_else(|| "system".to_string()),
+        ]);
+    }
+
+    Some(table)
+}
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-use clap::command;
+use crate::cli::warn_unresolved;
+use crate::profile::print_layer_conflicts;
 use clap::{Args, Subcommand};
 use color_eyre::Result;
 use comfy_table::Table;
@@ -106,7 +107,7 @@ pub fn handle_project(args: ProjectArgs) -> Result<()> {
 
         ProjectCommands::Apply { force, file } => {
             let mut project = ProjectManager::new()?;
-            let mut env_manager = EnvVarManager::new();
+            let mut env_manager = EnvVarManager::with_journal()?;
             let mut profile_manager = ProfileManager::new()?;
 
             let loaded = if let Some(custom_file) = file {
@@ -130,8 +131,11 @@ pub fn handle_project(args: ProjectArgs) -> Result<()> {
                 }
 
                 // Apply configuration
-                project.apply(&mut env_manager, &mut profile_manager)?;
+                let applied = project.apply(&mut env_manager, &mut profile_manager)?;
+                warn_unresolved(&applied.unresolved);
+                print_layer_conflicts(&applied.conflicts);
                 println!("✅ Applied project configuration");
+                println!("💡 To load it into your shell: eval \"$(envx shell-export --project)\"");
 
                 if !report.warnings.is_empty() {
                     println!("\n⚠️  Warnings:");
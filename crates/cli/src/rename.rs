@@ -1,4 +1,4 @@
-use clap::{Args, arg};
+use clap::Args;
 use color_eyre::Result;
 use comfy_table::{Table, presets::UTF8_FULL};
 use envx_core::{EnvVarManager, env::split_wildcard_pattern};
@@ -27,7 +27,7 @@ pub struct RenameArgs {
 /// - File I/O operations fail when persisting changes
 /// - User input cannot be read from stdin during confirmation
 pub fn handle_rename(args: &RenameArgs) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     if args.dry_run {
@@ -51,6 +51,9 @@ pub fn handle_rename(args: &RenameArgs) -> Result<()> {
             println!("\nUse without --dry-run to apply changes");
         }
     } else {
+        if !preview_rename(&manager, &args.pattern, &args.replacement)?.is_empty() {
+            crate::cli::snapshot_before(&format!("rename {} {}", args.pattern, args.replacement), &manager)?;
+        }
         let renamed = manager.rename(&args.pattern, &args.replacement)?;
 
         if renamed.is_empty() {
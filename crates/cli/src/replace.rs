@@ -43,7 +43,7 @@ pub struct FindReplaceArgs {
 /// - File I/O operations fail when persisting changes
 /// - Wildcard pattern parsing fails
 pub fn handle_replace(args: &ReplaceArgs) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     if args.dry_run {
@@ -67,6 +67,9 @@ pub fn handle_replace(args: &ReplaceArgs) -> Result<()> {
             println!("\nUse without --dry-run to apply changes");
         }
     } else {
+        if !preview_replace(&manager, &args.pattern)?.is_empty() {
+            crate::cli::snapshot_before(&format!("replace {}", args.pattern), &manager)?;
+        }
         let replaced = manager.replace(&args.pattern, &args.value)?;
 
         if replaced.is_empty() {
@@ -135,7 +138,7 @@ fn preview_replace(manager: &EnvVarManager, pattern: &str) -> Result<Vec<(String
 /// - File I/O operations fail when persisting changes
 /// - Wildcard pattern parsing fails
 pub fn handle_find_replace(args: &FindReplaceArgs) -> Result<()> {
-    let mut manager = EnvVarManager::new();
+    let mut manager = EnvVarManager::with_journal()?;
     manager.load_all()?;
 
     if args.dry_run {
@@ -159,6 +162,9 @@ pub fn handle_find_replace(args: &FindReplaceArgs) -> Result<()> {
             println!("\nUse without --dry-run to apply changes");
         }
     } else {
+        if !preview_find_replace(&manager, &args.search, &args.replacement, args.pattern.as_deref())?.is_empty() {
+            crate::cli::snapshot_before(&format!("find-replace {}", args.search), &manager)?;
+        }
         let replaced = manager.find_replace(&args.search, &args.replacement, args.pattern.as_deref())?;
 
         if replaced.is_empty() {
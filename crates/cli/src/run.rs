@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::cli::warn_unresolved;
+use crate::profile::warn_layer_conflicts;
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use envx_core::{EnvComposer, ProfileManager, ProjectManager};
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Profile to apply to the command's environment (can be repeated, later profiles win)
+    #[arg(short, long)]
+    pub profile: Vec<String>,
+
+    /// Load variables from a .env file (can be repeated, later files win)
+    #[arg(short = 'e', long = "env-file", value_name = "FILE")]
+    pub env_files: Vec<PathBuf>,
+
+    /// Apply the project configuration (.envx/config.yaml)
+    #[arg(long)]
+    pub project: bool,
+
+    /// Start from an empty environment instead of the current one
+    #[arg(long)]
+    pub clear_env: bool,
+
+    /// Command to run, followed by its arguments
+    #[arg(
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "COMMAND"
+    )]
+    pub command: Vec<String>,
+}
+
+/// Handle the run command: execute a command in a composed environment.
+///
+/// The environment is built in layers: the current environment (unless
+/// `--clear-env`), the project configuration, the profiles and finally the
+/// env files. Nothing is persisted and the envx process itself is left
+/// untouched. The process exits with the command's exit code.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The profile or project configuration cannot be loaded or resolved
+/// - An env file cannot be read
+/// - The command cannot be started
+pub fn handle_run(args: &RunArgs) -> Result<()> {
+    let env = compose_env(args)?;
+
+    let (program, program_args) = args.command.split_first().ok_or_else(|| eyre!("No command given"))?;
+
+    let status = env
+        .command(program, program_args)
+        .status()
+        .map_err(|e| eyre!("Failed to run '{}': {}", program, e))?;
+
+    std::process::exit(exit_code(status));
+}
+
+fn compose_env(args: &RunArgs) -> Result<EnvComposer> {
+    let mut env = if args.clear_env {
+        EnvComposer::new()
+    } else {
+        EnvComposer::from_process()
+    };
+
+    if args.project || !args.profile.is_empty() {
+        let profile_manager = ProfileManager::new()?;
+
+        if args.project {
+            let mut project = ProjectManager::new()?;
+            if project.find_and_load()?.is_none() {
+                return Err(eyre!("No project configuration found. Run 'envx project init' first."));
+            }
+            warn_layer_conflicts(&project.compose(&mut env, &profile_manager)?);
+        }
+
+        warn_layer_conflicts(&env.add_profiles(&profile_manager, &args.profile)?);
+    }
+
+    for file in &args.env_files {
+        env.add_env_file(file)?;
+    }
+    warn_unresolved(env.unresolved());
+
+    Ok(env)
+}
+
+#[cfg(unix)]
+fn exit_code(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    // Follow the shell convention for commands killed by a signal
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
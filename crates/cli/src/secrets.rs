@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use dialoguer::Password;
+use envx_core::{KeySource, KeyStore, ProfileManager, SnapshotManager};
+
+/// Environment variable holding the new passphrase for `secrets rekey --passphrase`
+const NEW_PASSPHRASE_VAR: &str = "ENVX_NEW_PASSPHRASE";
+
+#[derive(Args)]
+pub struct SecretsArgs {
+    #[command(subcommand)]
+    pub command: SecretsCommands,
+}
+
+#[derive(Subcommand)]
+pub enum SecretsCommands {
+    /// Show how secrets in profiles and snapshots are encrypted
+    Status,
+    /// Re-encrypt all stored secrets with a new key
+    Rekey {
+        /// Derive the new key from a passphrase (read from `ENVX_NEW_PASSPHRASE` or prompted)
+        #[arg(long, conflicts_with = "keyfile")]
+        passphrase: bool,
+        /// Derive the new key from a key file
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+    },
+}
+
+/// Handle secrets-related commands.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The current key cannot be unlocked
+/// - The new passphrase cannot be read or the new key file doesn't exist
+/// - Profiles or snapshots cannot be read, re-encrypted or written
+/// - The new key cannot be stored
+pub fn handle_secrets(args: SecretsArgs) -> Result<()> {
+    let keys = KeyStore::new()?;
+
+    match args.command {
+        SecretsCommands::Status => match keys.describe()? {
+            Some(key) => println!("🔐 Secrets are encrypted with a {key}"),
+            None => println!("No key yet. One is created when the first secret is saved."),
+        },
+        SecretsCommands::Rekey { passphrase, keyfile } => {
+            handle_rekey(&keys, passphrase, keyfile)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_rekey(keys: &KeyStore, passphrase: bool, keyfile: Option<PathBuf>) -> Result<()> {
+    let old = keys.open()?;
+    let current = keys.describe()?;
+
+    // Without a flag keep the current kind of key
+    let passphrase = passphrase || (keyfile.is_none() && current.as_deref() == Some("passphrase"));
+    let source = if passphrase {
+        KeySource::Passphrase(read_new_passphrase()?)
+    } else {
+        KeySource::Keyfile(keyfile)
+    };
+
+    let new = keys.generate(&source)?;
+
+    // Re-encrypt everything in memory first, so a value that can't be
+    // decrypted stops the rekey before anything is written
+    let mut profiles = ProfileManager::new()?;
+    let profile_count = profiles.reencrypt(old.as_ref(), new.vault())?;
+    let snapshots = SnapshotManager::new()?;
+    let (reencrypted, snapshot_count) = snapshots.reencrypt(old.as_ref(), new.vault())?;
+
+    // Store the new key before any value encrypted with it is written
+    let pending = keys.stage(&new)?;
+    profiles
+        .save()
+        .and_then(|()| snapshots.save_all(&reencrypted))
+        .map_err(|e| {
+            eyre!(
+                "{e}\nSome values may already be encrypted with the new key, which is kept in {}",
+                pending.display()
+            )
+        })?;
+    keys.commit(new)?;
+
+    println!(
+        "✅ Re-encrypted {profile_count} profile value(s) and {snapshot_count} snapshot value(s) with a {}",
+        keys.describe()?.unwrap_or_default()
+    );
+    if passphrase {
+        println!("   Set ENVX_PASSPHRASE to the new passphrase to unlock them.");
+    }
+
+    Ok(())
+}
+
+fn read_new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = std::env::var(NEW_PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+
+    Password::new()
+        .with_prompt("New passphrase")
+        .with_confirmation("Confirm passphrase", "Passphrases don't match")
+        .interact()
+        .map_err(|e| eyre!("Could not read passphrase: {e}"))
+}
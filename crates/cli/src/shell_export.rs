@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use crate::cli::warn_unresolved;
+use crate::profile::warn_layer_conflicts;
+use clap::{Args, ValueEnum};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use envx_core::{EnvComposer, ProfileManager, ProjectManager, ShellDialect};
+use indexmap::IndexMap;
+
+#[derive(Args)]
+pub struct ShellExportArgs {
+    /// Profile to export (can be repeated, later profiles win)
+    #[arg(short, long)]
+    pub profile: Vec<String>,
+
+    /// Export the project configuration (.envx/config.yaml)
+    #[arg(long)]
+    pub project: bool,
+
+    /// Export variables from a .env file (can be repeated, later files win)
+    #[arg(short = 'e', long = "env-file", value_name = "FILE")]
+    pub env_files: Vec<PathBuf>,
+
+    /// Unset a variable (can be repeated)
+    #[arg(short, long, value_name = "NAME")]
+    pub unset: Vec<String>,
+
+    /// Shell to print statements for (defaults to the current shell)
+    #[arg(short, long)]
+    pub shell: Option<ExportShell>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportShell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(alias = "pwsh")]
+    Powershell,
+    Cmd,
+}
+
+impl From<ExportShell> for ShellDialect {
+    fn from(shell: ExportShell) -> Self {
+        match shell {
+            ExportShell::Bash => Self::Bash,
+            ExportShell::Zsh => Self::Zsh,
+            ExportShell::Fish => Self::Fish,
+            ExportShell::Powershell => Self::PowerShell,
+            ExportShell::Cmd => Self::Cmd,
+        }
+    }
+}
+
+/// Handle the shell-export command: print statements that apply a profile or
+/// project to the calling shell.
+///
+/// The output is meant to be evaluated by the shell, e.g.
+/// `eval "$(envx shell-export --profile dev)"` or, in fish,
+/// `envx shell-export --profile dev | source`. Only variables whose value
+/// differs from the current environment are printed.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - Nothing to export was given
+/// - The profile or project configuration cannot be loaded or resolved
+/// - An env file cannot be read
+pub fn handle_shell_export(args: &ShellExportArgs) -> Result<()> {
+    if args.profile.is_empty() && !args.project && args.env_files.is_empty() && args.unset.is_empty() {
+        return Err(eyre!(
+            "Nothing to export. Use --profile, --project, --env-file or --unset."
+        ));
+    }
+
+    let current: IndexMap<String, String> = std::env::vars().collect();
+    let mut env = EnvComposer::from_process();
+
+    if args.project || !args.profile.is_empty() {
+        let profile_manager = ProfileManager::new()?;
+
+        if args.project {
+            let mut project = ProjectManager::new()?;
+            if project.find_and_load()?.is_none() {
+                return Err(eyre!("No project configuration found. Run 'envx project init' first."));
+            }
+            warn_layer_conflicts(&project.compose(&mut env, &profile_manager)?);
+        }
+
+        warn_layer_conflicts(&env.add_profiles(&profile_manager, &args.profile)?);
+    }
+
+    for file in &args.env_files {
+        env.add_env_file(file)?;
+    }
+
+    for name in &args.unset {
+        env.remove(name);
+    }
+
+    warn_unresolved(env.unresolved());
+
+    let dialect = args.shell.map_or_else(ShellDialect::detect, ShellDialect::from);
+    for change in env.changes(&current) {
+        // The output is evaluated, so warnings go to stderr
+        match change.render(dialect) {
+            Ok(statement) => println!("{statement}"),
+            Err(e) => eprintln!("⚠️  Skipped {}: {e}", change.name()),
+        }
+    }
+
+    Ok(())
+}
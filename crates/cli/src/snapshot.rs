@@ -1,7 +1,20 @@
-use clap::{Args, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand, ValueEnum};
 use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use comfy_table::Table;
-use envx_core::{EnvVarManager, SnapshotManager};
+use dialoguer::Password;
+use envx_core::{
+    ArchiveSecrets, EnvVarManager, EnvVarSource, PrunePolicy, RestoreOptions, SecretClassifier, Snapshot,
+    SnapshotArchive, SnapshotDiff, SnapshotManager,
+};
+
+use crate::SourceFilter;
+
+/// Environment variable holding the passphrase of snapshot archives
+const ARCHIVE_PASSPHRASE_VAR: &str = "ENVX_ARCHIVE_PASSPHRASE";
 
 #[derive(Args)]
 pub struct SnapshotArgs {
@@ -18,13 +31,26 @@ pub enum SnapshotCommands {
         /// Description
         #[arg(short, long)]
         description: Option<String>,
+        /// Tag the snapshot (repeatable)
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Label the snapshot with KEY=VALUE (repeatable)
+        #[arg(short, long = "label", value_name = "KEY=VALUE")]
+        labels: Vec<String>,
     },
     /// List all snapshots
-    List,
+    List {
+        /// Only list snapshots with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
     /// Show details of a snapshot
     Show {
         /// Snapshot name or ID
         snapshot: String,
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
     },
     /// Restore from a snapshot
     Restore {
@@ -33,6 +59,21 @@ pub enum SnapshotCommands {
         /// Force restore without confirmation
         #[arg(short, long)]
         force: bool,
+        /// Only restore variables matching this name or wildcard pattern (repeatable)
+        #[arg(short, long = "pattern", value_name = "PATTERN")]
+        patterns: Vec<String>,
+        /// Only restore variables from this source (repeatable)
+        #[arg(short, long = "source", value_name = "SOURCE", value_enum)]
+        sources: Vec<SourceFilter>,
+        /// Only add and update variables, don't remove any (the default)
+        #[arg(short, long, conflicts_with = "prune")]
+        merge: bool,
+        /// Also remove session variables matching --pattern or --source that aren't in the snapshot
+        #[arg(long)]
+        prune: bool,
+        /// Show what would change without restoring anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Delete a snapshot
     Delete {
@@ -42,15 +83,96 @@ pub enum SnapshotCommands {
         #[arg(short, long)]
         force: bool,
     },
-    /// Compare two snapshots
+    /// Add or remove tags of a snapshot
+    Tag {
+        /// Snapshot name or ID
+        snapshot: String,
+        /// Tags to add or remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Remove the tags instead of adding them
+        #[arg(short, long)]
+        remove: bool,
+    },
+    /// Set or remove labels of a snapshot
+    Label {
+        /// Snapshot name or ID
+        snapshot: String,
+        /// Labels to set as KEY=VALUE, or keys to remove with --remove
+        #[arg(required = true)]
+        labels: Vec<String>,
+        /// Remove the labels instead of setting them
+        #[arg(short, long)]
+        remove: bool,
+    },
+    /// Delete old snapshots
+    Prune {
+        /// Keep this many of the newest snapshots
+        #[arg(long, value_name = "N", required_unless_present = "older_than")]
+        keep_last: Option<usize>,
+        /// Only delete snapshots older than this age, e.g. 30d, 12h, 2w
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+        /// Only consider snapshots with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Show which snapshots would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete without confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Convert snapshots saved by earlier versions to the compressed format
+    Migrate,
+    /// Export a snapshot to a file that can be imported on another machine
+    Export {
+        /// Snapshot name or ID
+        snapshot: String,
+        /// Archive file to write, gzip-compressed if it ends with .gz
+        #[arg(short, long)]
+        output: PathBuf,
+        /// How to write secret values
+        #[arg(long, value_enum, default_value_t = SecretsMode::Mask)]
+        secrets: SecretsMode,
+        /// Overwrite the file if it exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Import a snapshot exported with `snapshot export`
+    Import {
+        /// Archive file to read
+        file: PathBuf,
+        /// Replace a snapshot with the same ID
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Compare two snapshots, or a snapshot with the current environment
     Diff {
         /// First snapshot
         snapshot1: String,
         /// Second snapshot
-        snapshot2: String,
+        #[arg(required_unless_present = "current")]
+        snapshot2: Option<String>,
+        /// Compare with the current environment instead of a second snapshot
+        #[arg(long, conflicts_with = "snapshot2")]
+        current: bool,
+        /// Show secret values instead of masking them
+        #[arg(long)]
+        reveal: bool,
     },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SecretsMode {
+    /// Leave secret values out of the archive
+    Mask,
+    /// Encrypt secret values with a passphrase (read from `ENVX_ARCHIVE_PASSPHRASE` or prompted)
+    Encrypt,
+    /// Write secret values in plain text
+    Plain,
+}
+
 /// Handle snapshot-related commands.
 ///
 /// # Errors
@@ -62,72 +184,105 @@ pub enum SnapshotCommands {
 /// - File I/O operations fail during snapshot operations
 /// - User input cannot be read from stdin
 /// - Invalid snapshot names or IDs are provided
+#[allow(clippy::too_many_lines)]
 pub fn handle_snapshot(args: SnapshotArgs) -> Result<()> {
     let snapshot_manager = SnapshotManager::new()?;
-    let mut env_manager = EnvVarManager::new();
+    let mut env_manager = EnvVarManager::with_journal()?;
     env_manager.load_all()?;
 
     match args.command {
-        SnapshotCommands::Create { name, description } => {
+        SnapshotCommands::Create {
+            name,
+            description,
+            tags,
+            labels,
+        } => {
             let vars = env_manager.list().into_iter().cloned().collect();
-            let snapshot = snapshot_manager.create(name, description, vars)?;
+            let mut snapshot = Snapshot::from_vars(name, description, vars);
+            for tag in &tags {
+                snapshot.add_tag(tag)?;
+            }
+            for label in &labels {
+                let (key, value) = parse_label(label)?;
+                snapshot.set_label(key, value)?;
+            }
+            snapshot_manager.save_snapshot(&snapshot)?;
             println!("✅ Created snapshot: {} (ID: {})", snapshot.name, snapshot.id);
         }
-        SnapshotCommands::List => {
-            let snapshots = snapshot_manager.list()?;
+        SnapshotCommands::List { tag } => {
+            let snapshots: Vec<_> = snapshot_manager
+                .summaries()?
+                .into_iter()
+                .filter(|snapshot| tag.as_deref().is_none_or(|tag| snapshot.has_tag(tag)))
+                .collect();
             if snapshots.is_empty() {
                 println!("No snapshots found.");
                 return Ok(());
             }
 
             let mut table = Table::new();
-            table.set_header(vec!["Name", "ID", "Created", "Variables", "Description"]);
+            table.set_header(vec!["Name", "ID", "Created", "Variables", "Tags", "Description"]);
 
             for snapshot in snapshots {
                 table.add_row(vec![
-                    snapshot.name,
+                    snapshot.name.clone(),
                     snapshot.id[..8].to_string(),
                     snapshot.created_at.format("%Y-%m-%d %H:%M").to_string(),
-                    snapshot.variables.len().to_string(),
-                    snapshot.description.unwrap_or_default(),
+                    snapshot.variable_count.to_string(),
+                    snapshot.tags().join(", "),
+                    snapshot.description.clone().unwrap_or_default(),
                 ]);
             }
 
             println!("{table}");
         }
-        SnapshotCommands::Show { snapshot } => {
-            let snap = snapshot_manager.get(&snapshot)?;
+        SnapshotCommands::Show { snapshot, reveal } => {
+            let mut snap = snapshot_manager.get(&snapshot)?;
+            if reveal {
+                snapshot_manager.decrypt(&mut snap)?;
+            }
+            let display = display_value(reveal);
             println!("Snapshot: {}", snap.name);
             println!("ID: {}", snap.id);
             println!("Created: {}", snap.created_at.format("%Y-%m-%d %H:%M:%S"));
-            println!("Description: {}", snap.description.unwrap_or_default());
+            println!("Description: {}", snap.description.clone().unwrap_or_default());
+            let tags = snap.tags();
+            if !tags.is_empty() {
+                println!("Tags: {}", tags.join(", "));
+            }
+            let labels = snap.labels();
+            if !labels.is_empty() {
+                let labels: Vec<String> = labels.iter().map(|(key, value)| format!("{key}={value}")).collect();
+                println!("Labels: {}", labels.join(", "));
+            }
             println!("Variables: {}", snap.variables.len());
 
             // Show first 10 variables
             println!("\nFirst 10 variables:");
             for (i, (name, var)) in snap.variables.iter().take(10).enumerate() {
-                println!("  {}. {} = {}", i + 1, name, var.value);
+                println!("  {}. {} = {}", i + 1, name, display(name, &var.value));
             }
 
             if snap.variables.len() > 10 {
                 println!("  ... and {} more", snap.variables.len() - 10);
             }
         }
-        SnapshotCommands::Restore { snapshot, force } => {
-            if !force {
-                print!("⚠️  This will replace all current environment variables. Continue? [y/N] ");
-                std::io::Write::flush(&mut std::io::stdout())?;
-
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
-                if !input.trim().eq_ignore_ascii_case("y") {
-                    println!("Cancelled.");
-                    return Ok(());
-                }
-            }
-
-            snapshot_manager.restore(&snapshot, &mut env_manager)?;
-            println!("✅ Restored from snapshot: {snapshot}");
+        SnapshotCommands::Restore {
+            snapshot,
+            force,
+            patterns,
+            sources,
+            // Merging is what a restore does unless --prune is given
+            merge: _,
+            prune,
+            dry_run,
+        } => {
+            let options = RestoreOptions {
+                patterns,
+                sources: sources.into_iter().map(EnvVarSource::from).collect(),
+                prune,
+            };
+            handle_snapshot_restore(&snapshot_manager, &mut env_manager, &snapshot, &options, force, dry_run)?;
         }
         SnapshotCommands::Delete { snapshot, force } => {
             if !force {
@@ -145,25 +300,130 @@ pub fn handle_snapshot(args: SnapshotArgs) -> Result<()> {
             snapshot_manager.delete(&snapshot)?;
             println!("✅ Deleted snapshot: {snapshot}");
         }
-        SnapshotCommands::Diff { snapshot1, snapshot2 } => {
-            let diff = snapshot_manager.diff(&snapshot1, &snapshot2)?;
+        SnapshotCommands::Tag { snapshot, tags, remove } => {
+            let snap = snapshot_manager.modify(&snapshot, |snap| {
+                for tag in &tags {
+                    if remove {
+                        snap.remove_tag(tag);
+                    } else {
+                        snap.add_tag(tag)?;
+                    }
+                }
+                Ok(())
+            })?;
+            println!("✅ Tags of {}: {}", snap.name, snap.tags().join(", "));
+        }
+        SnapshotCommands::Label {
+            snapshot,
+            labels,
+            remove,
+        } => {
+            let snap = snapshot_manager.modify(&snapshot, |snap| {
+                for label in &labels {
+                    if remove {
+                        snap.remove_label(label);
+                    } else {
+                        let (key, value) = parse_label(label)?;
+                        snap.set_label(key, value)?;
+                    }
+                }
+                Ok(())
+            })?;
+            let labels: Vec<String> = snap
+                .labels()
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            println!("✅ Labels of {}: {}", snap.name, labels.join(", "));
+        }
+        SnapshotCommands::Prune {
+            keep_last,
+            older_than,
+            tag,
+            dry_run,
+            force,
+        } => {
+            let policy = PrunePolicy {
+                keep_last,
+                older_than: older_than.as_deref().map(parse_age).transpose()?,
+                tag,
+            };
+            handle_snapshot_prune(&snapshot_manager, &policy, dry_run, force)?;
+        }
+        SnapshotCommands::Migrate => {
+            let report = snapshot_manager.migrate()?;
+            if report.migrated == 0 && report.skipped.is_empty() {
+                println!("All snapshots already use the compressed format.");
+            } else {
+                println!(
+                    "✅ Migrated {} snapshot(s): {} KiB -> {} KiB",
+                    report.migrated,
+                    report.bytes_before.div_ceil(1024),
+                    report.bytes_after.div_ceil(1024)
+                );
+            }
+            for path in &report.skipped {
+                println!("⚠️  Skipped {}: not a readable snapshot", path.display());
+            }
+        }
+        SnapshotCommands::Export {
+            snapshot,
+            output,
+            secrets,
+            force,
+        } => {
+            handle_snapshot_export(&snapshot_manager, &snapshot, &output, secrets, force)?;
+        }
+        SnapshotCommands::Import { file, force } => {
+            let archive = SnapshotArchive::from_bytes(&fs::read(&file)?)?;
+            let passphrase = if archive.is_encrypted() {
+                Some(read_archive_passphrase(false)?)
+            } else {
+                None
+            };
+            let masked = archive.masked.clone();
+            let snap = snapshot_manager.import(archive, passphrase.as_deref(), force)?;
+            println!("✅ Imported snapshot: {} (ID: {})", snap.name, snap.id);
+            if !masked.is_empty() {
+                println!(
+                    "⚠️  {} secret(s) were masked on export and are not included: {}",
+                    masked.len(),
+                    masked.join(", ")
+                );
+                println!("   Restoring keeps their current values.");
+            }
+        }
+        SnapshotCommands::Diff {
+            snapshot1,
+            snapshot2,
+            current: _,
+            reveal,
+        } => {
+            let (diff, snapshot2) = match snapshot2 {
+                Some(snapshot2) => (snapshot_manager.diff(&snapshot1, &snapshot2)?, snapshot2),
+                None => (
+                    snapshot_manager.diff_current(&snapshot1, &env_manager)?,
+                    "current environment".to_string(),
+                ),
+            };
+            let display = display_value(reveal);
 
-            if diff.added.is_empty() && diff.removed.is_empty() && diff.modified.is_empty() {
-                println!("No differences found between snapshots.");
+            if diff.is_empty() {
+                println!("No differences found between {snapshot1} and {snapshot2}.");
                 return Ok(());
             }
 
             if !diff.added.is_empty() {
                 println!("➕ Added in {snapshot2}:");
                 for (name, var) in &diff.added {
-                    println!("   {} = {}", name, var.value);
+                    println!("   {} = {}", name, display(name, &var.value));
                 }
             }
 
             if !diff.removed.is_empty() {
                 println!("\n➖ Removed in {snapshot2}:");
                 for (name, var) in &diff.removed {
-                    println!("   {} = {}", name, var.value);
+                    println!("   {} = {}", name, display(name, &var.value));
                 }
             }
 
@@ -171,8 +431,8 @@ pub fn handle_snapshot(args: SnapshotArgs) -> Result<()> {
                 println!("\n🔄 Modified:");
                 for (name, (old, new)) in &diff.modified {
                     println!("   {name}:");
-                    println!("     Old: {}", old.value);
-                    println!("     New: {}", new.value);
+                    println!("     Old: {}", display(name, &old.value));
+                    println!("     New: {}", display(name, &new.value));
                 }
             }
         }
@@ -180,3 +440,260 @@ pub fn handle_snapshot(args: SnapshotArgs) -> Result<()> {
 
     Ok(())
 }
+
+fn handle_snapshot_restore(
+    snapshot_manager: &SnapshotManager,
+    env_manager: &mut EnvVarManager,
+    snapshot: &str,
+    options: &RestoreOptions,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let plan = snapshot_manager.plan_restore(snapshot, env_manager, options)?;
+    let sets = plan.added.len() + plan.modified.len();
+    if sets == 0 && plan.removed.is_empty() {
+        println!("Nothing to restore, the environment already matches snapshot {snapshot}.");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("🔍 Restoring {snapshot} would:");
+        print_restore_plan(&plan, &display_value(false));
+        return Ok(());
+    }
+
+    if !force {
+        print!(
+            "⚠️  This will set {sets} and remove {} environment variable(s). Continue? [y/N] ",
+            plan.removed.len()
+        );
+        std::io::Write::flush(&mut std::io::stdout())?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    let report = snapshot_manager.restore_with(snapshot, env_manager, options)?;
+    println!(
+        "✅ Restored from snapshot: {snapshot} ({sets} set, {} removed)",
+        report.diff.removed.len()
+    );
+    println!(
+        "💾 Saved the previous environment. Undo with: envx snapshot restore {}",
+        report.backup.name
+    );
+    Ok(())
+}
+
+fn handle_snapshot_export(
+    snapshot_manager: &SnapshotManager,
+    snapshot: &str,
+    output: &Path,
+    secrets: SecretsMode,
+    force: bool,
+) -> Result<()> {
+    if output.exists() && !force {
+        return Err(eyre!(
+            "{} already exists, use --force to overwrite it",
+            output.display()
+        ));
+    }
+
+    let secrets = match secrets {
+        SecretsMode::Mask => ArchiveSecrets::Mask,
+        SecretsMode::Encrypt => ArchiveSecrets::Encrypt(read_archive_passphrase(true)?),
+        SecretsMode::Plain => ArchiveSecrets::Plain,
+    };
+    let archive = snapshot_manager.export(snapshot, &secrets)?;
+    let compressed = output.extension().is_some_and(|ext| ext == "gz");
+    fs::write(output, archive.to_bytes(compressed)?)?;
+
+    println!(
+        "✅ Exported snapshot {} ({} variables) to {}",
+        archive.snapshot.name,
+        archive.snapshot.variables.len(),
+        output.display()
+    );
+    if !archive.masked.is_empty() {
+        println!(
+            "🔒 Left out {} secret(s), use --secrets encrypt to include them",
+            archive.masked.len()
+        );
+    }
+    Ok(())
+}
+
+fn read_archive_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(ARCHIVE_PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+
+    let mut prompt = Password::new().with_prompt("Archive passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation("Confirm passphrase", "Passphrases don't match");
+    }
+    prompt.interact().map_err(|e| eyre!("Could not read passphrase: {e}"))
+}
+
+fn handle_snapshot_prune(
+    snapshot_manager: &SnapshotManager,
+    policy: &PrunePolicy,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let candidates = snapshot_manager.prune_candidates(policy)?;
+    if candidates.is_empty() {
+        println!("No snapshots to prune.");
+        return Ok(());
+    }
+
+    println!(
+        "🗑️  {} {} snapshot(s):",
+        if dry_run { "Would delete" } else { "Deleting" },
+        candidates.len()
+    );
+    for snapshot in &candidates {
+        println!(
+            "   {} ({})",
+            snapshot.name,
+            snapshot.created_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    if !force {
+        print!("⚠️  Continue? [y/N] ");
+        std::io::Write::flush(&mut std::io::stdout())?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    let pruned = snapshot_manager.prune(policy)?;
+    println!("✅ Deleted {} snapshot(s)", pruned.len());
+    Ok(())
+}
+
+/// Prints the changes a restore makes, sorted by variable name.
+fn print_restore_plan(plan: &SnapshotDiff, display: &impl Fn(&str, &str) -> String) {
+    let mut added: Vec<_> = plan.added.values().collect();
+    added.sort_by(|a, b| a.name.cmp(&b.name));
+    if !added.is_empty() {
+        println!("➕ Add {} variable(s):", added.len());
+        for var in added {
+            println!("   {} = {}", var.name, display(&var.name, &var.value));
+        }
+    }
+
+    let mut modified: Vec<_> = plan.modified.values().collect();
+    modified.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    if !modified.is_empty() {
+        println!("🔄 Change {} variable(s):", modified.len());
+        for (old, new) in modified {
+            println!("   {}:", new.name);
+            println!("     Old: {}", display(&old.name, &old.value));
+            println!("     New: {}", display(&new.name, &new.value));
+        }
+    }
+
+    let mut removed: Vec<_> = plan.removed.keys().collect();
+    removed.sort();
+    if !removed.is_empty() {
+        println!("➖ Remove {} variable(s):", removed.len());
+        for name in removed {
+            println!("   {name}");
+        }
+    }
+}
+
+fn parse_label(label: &str) -> Result<(&str, &str)> {
+    label
+        .split_once('=')
+        .ok_or_else(|| eyre!("Invalid label '{}', expected KEY=VALUE", label))
+}
+
+/// Parses an age like `30d`, `12h`, `2w` or `45m`.
+fn parse_age(age: &str) -> Result<chrono::Duration> {
+    let invalid = || {
+        eyre!(
+            "Invalid age '{}', expected a positive number followed by m, h, d or w (e.g. 30d)",
+            age
+        )
+    };
+    let unit = age.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = age[..age.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    if amount <= 0 {
+        return Err(invalid());
+    }
+    let duration = match unit {
+        'm' => chrono::Duration::try_minutes(amount),
+        'h' => chrono::Duration::try_hours(amount),
+        'd' => chrono::Duration::try_days(amount),
+        'w' => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+    // Prune compares snapshots against now minus the age, which must be a valid date
+    chrono::Utc::now().checked_sub_signed(duration).ok_or_else(invalid)?;
+    Ok(duration)
+}
+
+/// Returns a function that formats a value for display, masking secrets unless `reveal` is set.
+fn display_value(reveal: bool) -> impl Fn(&str, &str) -> String {
+    let secrets = (!reveal).then(SecretClassifier::for_current_project);
+    move |name, value| {
+        secrets
+            .as_ref()
+            .map_or_else(|| value.to_string(), |secrets| secrets.mask(name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30m").unwrap(), chrono::Duration::minutes(30));
+        assert_eq!(parse_age("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_age("2w").unwrap(), chrono::Duration::weeks(2));
+    }
+
+    #[test]
+    fn test_parse_age_rejects_invalid_ages() {
+        for age in [
+            "",
+            "d",
+            "30",
+            "30y",
+            "0d",
+            "-5d",
+            "x5d",
+            "9223372036854775807w",
+            "100000000w",
+        ] {
+            assert!(parse_age(age).is_err(), "{age} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_restore_merge_conflicts_with_prune() {
+        use clap::Parser;
+
+        let parse = |args: &[&str]| crate::Cli::try_parse_from(["envx", "snapshot", "restore", "x"].iter().chain(args));
+        assert!(parse(&["--merge"]).is_ok());
+        assert!(parse(&["--prune"]).is_ok());
+        assert!(parse(&["--merge", "--prune"]).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use clap::ValueEnum;
+use envx_core::EnvVarSource;
+
+/// A variable source that commands can filter on
+#[derive(Debug, Clone, ValueEnum)]
+pub enum SourceFilter {
+    #[value(name = "system")]
+    System,
+    #[value(name = "user")]
+    User,
+    #[value(name = "process")]
+    Process,
+    #[value(name = "shell")]
+    Shell,
+}
+
+impl From<SourceFilter> for EnvVarSource {
+    fn from(filter: SourceFilter) -> Self {
+        match filter {
+            SourceFilter::System => EnvVarSource::System,
+            SourceFilter::User => EnvVarSource::User,
+            SourceFilter::Process => EnvVarSource::Process,
+            SourceFilter::Shell => EnvVarSource::Shell,
+        }
+    }
+}
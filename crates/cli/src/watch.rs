@@ -2,7 +2,7 @@ use std::{path::PathBuf, time::Duration};
 
 use clap::{Args, ValueEnum};
 use color_eyre::Result;
-use envx_core::{ConflictStrategy, EnvVarManager, EnvWatcher, SyncMode, WatchConfig};
+use envx_core::{ConflictStrategy, EnvVarManager, EnvWatcher, SecretClassifier, SyncMode, WatchConfig};
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Direction {
@@ -47,6 +47,10 @@ pub struct WatchArgs {
     /// Quiet mode - less output
     #[arg(short, long)]
     pub quiet: bool,
+
+    /// Show secret values in logged changes instead of masking them
+    #[arg(long)]
+    pub reveal: bool,
 }
 
 /// Handle file watching and synchronization operations.
@@ -88,6 +92,7 @@ pub fn handle_watch(args: &WatchArgs) -> Result<()> {
         debounce_duration: Duration::from_millis(args.debounce),
         log_changes: !args.quiet,
         conflict_strategy: ConflictStrategy::UseLatest,
+        secrets: (!args.reveal).then(SecretClassifier::for_current_project),
         ..Default::default()
     };
 
@@ -251,6 +251,7 @@ mod tests {
             source: EnvVarSource::User,
             modified: Utc::now(),
             original_value: None,
+            origin: None,
         }
     }
 
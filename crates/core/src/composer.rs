@@ -0,0 +1,358 @@
+use crate::expand::Unresolved;
+use crate::importer::is_toml;
+use crate::profile_manager::expand_profile_vars;
+use crate::{EnvChange, EnvVarManager, EnvVarSource, ImportFormat, Importer, LayerConflict, ProfileManager, VarOrigin};
+use ahash::AHashSet as HashSet;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use indexmap::IndexMap;
+use std::path::Path;
+use std::process::Command;
+
+/// A variable value in a composed environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposedVar {
+    pub value: String,
+    pub origin: Option<VarOrigin>,
+}
+
+/// Builds an environment from layers without touching the process or
+/// persistent state.
+///
+/// Later layers override earlier ones. The result can be handed to a child
+/// process or rendered as shell statements.
+#[derive(Debug, Clone, Default)]
+pub struct EnvComposer {
+    vars: IndexMap<String, ComposedVar>,
+    unresolved: Vec<Unresolved>,
+    /// Variables defined system-wide, see [`EnvComposer::mark_system_vars`]
+    system: HashSet<String>,
+    /// Whether the system variables still have to be marked before the first
+    /// profile is added, see [`EnvComposer::from_process`]
+    system_pending: bool,
+}
+
+impl EnvComposer {
+    /// Creates an empty environment.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an environment seeded with the current process environment.
+    ///
+    /// The system variables among them are marked (see
+    /// [`EnvComposer::mark_system_vars`]) when the first profile is added, so
+    /// environments without profiles never load them.
+    #[must_use]
+    pub fn from_process() -> Self {
+        let mut composer = Self::new();
+        for (name, value) in std::env::vars() {
+            composer.set(&name, &value, None);
+        }
+        composer.system_pending = true;
+        composer
+    }
+
+    /// Marks the variables defined system-wide, e.g. in `/etc/environment`
+    /// or the machine part of the Windows registry.
+    ///
+    /// Profiles only replace their values if they set `override_system`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system variables cannot be loaded.
+    pub fn mark_system_vars(&mut self) -> Result<()> {
+        let mut manager = EnvVarManager::new();
+        manager.load_all()?;
+        self.system = manager
+            .vars
+            .into_values()
+            .filter(|var| var.source == EnvVarSource::System)
+            .map(|var| var.name)
+            .collect();
+        self.system_pending = false;
+        Ok(())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str, origin: Option<VarOrigin>) {
+        self.vars.insert(
+            name.to_string(),
+            ComposedVar {
+                value: value.to_string(),
+                origin,
+            },
+        );
+    }
+
+    /// Sets a variable only if it isn't defined yet.
+    ///
+    /// Returns `true` if the value was set.
+    pub fn set_default(&mut self, name: &str, value: &str, origin: Option<VarOrigin>) -> bool {
+        if self.vars.contains_key(name) {
+            return false;
+        }
+
+        self.set(name, value, origin);
+        true
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ComposedVar> {
+        self.vars.shift_remove(name)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&ComposedVar> {
+        self.vars.get(name)
+    }
+
+    #[must_use]
+    pub const fn vars(&self) -> &IndexMap<String, ComposedVar> {
+        &self.vars
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Adds the variables of a profile, including inherited ones. Returns the
+    /// system variables the profile also defines, see
+    /// [`EnvComposer::add_profiles`].
+    ///
+    /// References in the values are expanded against the profile and the
+    /// environment composed so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the profile cannot be resolved or a value cannot be expanded.
+    pub fn add_profile(&mut self, profiles: &ProfileManager, name: &str) -> Result<Vec<LayerConflict>> {
+        self.add_profiles(profiles, &[name])
+    }
+
+    /// Adds the variables of several profiles layered in order, see
+    /// [`ProfileManager::resolve_layers`]. Returns the variables the layers
+    /// disagree on.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A profile cannot be resolved or a value cannot be expanded
+    /// - The system variables cannot be loaded, see [`EnvComposer::mark_system_vars`]
+    pub fn add_profiles<S: AsRef<str>>(
+        &mut self,
+        profiles: &ProfileManager,
+        names: &[S],
+    ) -> Result<Vec<LayerConflict>> {
+        if self.system_pending && !names.is_empty() {
+            self.mark_system_vars()?;
+        }
+
+        let (mut vars, conflicts) = profiles.resolve_layers(names, |name| {
+            self.vars
+                .get(name)
+                .filter(|var| var.origin.is_none() && self.system.contains(name))
+                .map(|var| var.value.clone())
+        })?;
+        let unresolved = expand_profile_vars(&mut vars, |var| self.vars.get(var).map(|v| v.value.clone()))?;
+        self.add_unresolved(unresolved);
+
+        for (var_name, var) in vars {
+            self.set(&var_name, &var.value, Some(VarOrigin::Profile(var.profile)));
+        }
+        Ok(conflicts)
+    }
+
+    /// Adds the variables defined in a `.env` file, or in a TOML file if the
+    /// path ends in `.toml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn add_env_file(&mut self, path: &Path) -> Result<()> {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| eyre!("Invalid file path: {}", path.display()))?;
+
+        let mut importer = Importer::new();
+        let format = if is_toml(path) {
+            ImportFormat::Toml
+        } else {
+            ImportFormat::DotEnv
+        };
+        importer.import_from_file_with(path_str, format, |name| {
+            self.vars.get(name).map(|var| var.value.clone())
+        })?;
+
+        let mut vars = importer.get_variables();
+        vars.sort();
+        for (name, value) in vars {
+            let origin = importer.origin(&name);
+            self.set(&name, &value, origin);
+        }
+        self.add_unresolved(importer.unresolved().to_vec());
+        Ok(())
+    }
+
+    /// References to unset variables found while expanding the added values.
+    #[must_use]
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+
+    pub(crate) fn add_unresolved(&mut self, unresolved: Vec<Unresolved>) {
+        for reference in unresolved {
+            if !self.unresolved.contains(&reference) {
+                self.unresolved.push(reference);
+            }
+        }
+    }
+
+    /// Returns the changes that turn `current` into this environment.
+    ///
+    /// Variables are set in the order they were composed and removed
+    /// variables are unset after them.
+    #[must_use]
+    pub fn changes(&self, current: &IndexMap<String, String>) -> Vec<EnvChange> {
+        let sets = self
+            .vars
+            .iter()
+            .filter(|(name, var)| current.get(*name) != Some(&var.value))
+            .map(|(name, var)| EnvChange::Set {
+                name: name.clone(),
+                value: var.value.clone(),
+            });
+        let unsets = current
+            .keys()
+            .filter(|name| !self.vars.contains_key(*name))
+            .map(|name| EnvChange::Unset { name: name.clone() });
+
+        sets.chain(unsets).collect()
+    }
+
+    /// Creates a command that runs `program` with exactly this environment.
+    #[must_use]
+    pub fn command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env_clear()
+            .envs(self.vars.iter().map(|(name, var)| (name, &var.value)));
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layers_override() {
+        let mut env = EnvComposer::new();
+        env.set("A", "1", None);
+        env.set("B", "1", None);
+        env.set("A", "2", Some(VarOrigin::Profile("dev".to_string())));
+
+        assert_eq!(env.len(), 2);
+        assert_eq!(env.get("A").unwrap().value, "2");
+        assert_eq!(
+            env.get("A").unwrap().origin,
+            Some(VarOrigin::Profile("dev".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_set_default_keeps_existing() {
+        let mut env = EnvComposer::new();
+        env.set("PORT", "8080", None);
+
+        assert!(!env.set_default("PORT", "3000", None));
+        assert!(env.set_default("HOST", "localhost", None));
+        assert_eq!(env.get("PORT").unwrap().value, "8080");
+        assert_eq!(env.get("HOST").unwrap().value, "localhost");
+    }
+
+    #[test]
+    fn test_add_env_file() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join(".env");
+        fs::write(&path, "# comment\nDB_HOST=localhost\nDB_PORT=\"5432\"\n").unwrap();
+
+        let mut env = EnvComposer::new();
+        env.add_env_file(&path).unwrap();
+
+        assert_eq!(env.get("DB_PORT").unwrap().value, "5432");
+        assert_eq!(
+            env.get("DB_HOST").unwrap().origin,
+            Some(VarOrigin::file(path.clone(), Some(2)))
+        );
+        assert!(env.add_env_file(&temp.path().join("missing.env")).is_err());
+    }
+
+    #[test]
+    fn test_changes() {
+        let current: IndexMap<String, String> = [("KEEP", "1"), ("CHANGE", "old"), ("DROP", "x")]
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+
+        let mut env = EnvComposer::new();
+        env.set("KEEP", "1", None);
+        env.set("CHANGE", "new", None);
+        env.set("ADD", "2", None);
+
+        assert_eq!(
+            env.changes(&current),
+            vec![
+                EnvChange::Set {
+                    name: "CHANGE".to_string(),
+                    value: "new".to_string()
+                },
+                EnvChange::Set {
+                    name: "ADD".to_string(),
+                    value: "2".to_string()
+                },
+                EnvChange::Unset {
+                    name: "DROP".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_process_is_a_copy() {
+        let mut env = EnvComposer::from_process();
+        env.set("ENVX_COMPOSER_TEST_ONLY", "1", None);
+
+        assert!(std::env::var("ENVX_COMPOSER_TEST_ONLY").is_err());
+        // System variables are only loaded once a profile is added
+        assert!(env.system_pending);
+        assert!(env.system.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_uses_composed_env() {
+        let mut env = EnvComposer::new();
+        env.set("ENVX_CHILD_VAR", "from-envx", None);
+        env.set("PATH", &std::env::var("PATH").unwrap_or_default(), None);
+
+        let output = env
+            .command(
+                "sh",
+                &["-c".to_string(), "printf %s \"$ENVX_CHILD_VAR\"; exit 3".to_string()],
+            )
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "from-envx");
+        assert_eq!(output.status.code(), Some(3));
+    }
+}
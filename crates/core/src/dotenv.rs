@@ -0,0 +1,860 @@
+//! Parser for `.env` files shared by every command that reads them.
+//!
+//! The dialect is python-dotenv's, plus backtick quotes:
+//! - Blank lines and lines starting with `#` are ignored
+//! - `KEY=value`, optionally prefixed with `export `, with spaces allowed
+//!   around `=`
+//! - Unquoted values end at the end of the line; a `#` preceded by
+//!   whitespace starts a comment, and surrounding whitespace is trimmed
+//! - Single-quoted values are literal except for `\'` and `\\`, which stand
+//!   for `'` and `\`
+//! - Double-quoted values support `\n`, `\r`, `\t`, `\a`, `\b`, `\f`, `\v`,
+//!   `\"`, `\'` and `\\`; other escapes are kept as they are
+//! - Backtick-quoted values are literal and may contain both kinds of quotes
+//! - Quoted values may span several lines, keeping their line breaks, and may
+//!   be followed by a comment
+//! - A name without `=` declares a variable without a value and is skipped
+//!
+//! Docker Compose reads a few values differently: it keeps `\\` in single
+//! quotes and `\'` in double quotes as written, also decodes `\$` and octal
+//! escapes in double quotes and has no backtick quotes. The
+//! `compose_differences` case of the conformance corpus covers these.
+//!
+//! Invalid lines are skipped and reported with their line and column, so a
+//! single typo doesn't hide the rest of the file.
+//!
+//! [`DotenvDocument`] edits single assignments of a file, leaving its
+//! comments, blank lines and the order of the other variables untouched.
+
+use crate::shell_config::is_shell_identifier;
+use crate::snapshot_store::write_atomic;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::fmt::{self, Write};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+
+/// How a value was quoted in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    None,
+    Single,
+    Double,
+    Backtick,
+}
+
+impl Quote {
+    /// Returns true if `${VAR}` references in values quoted like this are
+    /// expanded. Single- and backtick-quoted values are literal.
+    #[must_use]
+    pub const fn expands(self) -> bool {
+        matches!(self, Self::None | Self::Double)
+    }
+
+    const fn from_char(c: char) -> Option<Self> {
+        match c {
+            '\'' => Some(Self::Single),
+            '"' => Some(Self::Double),
+            '`' => Some(Self::Backtick),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "unquoted"),
+            Self::Single => write!(f, "single-quoted"),
+            Self::Double => write!(f, "double-quoted"),
+            Self::Backtick => write!(f, "backtick-quoted"),
+        }
+    }
+}
+
+/// A variable assignment in a `.env` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    /// Line of the key, starting at 1
+    pub line: usize,
+    pub quote: Quote,
+    /// Bytes of the lines the assignment is on, including the line break
+    pub span: Range<usize>,
+    /// Bytes of the value as written, including its quotes
+    pub value_span: Range<usize>,
+}
+
+/// A syntax error in a `.env` file
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("line {line}, column {column}: {message}")]
+pub struct ParseError {
+    /// Line of the error, starting at 1
+    pub line: usize,
+    /// Column of the error in characters, starting at 1
+    pub column: usize,
+    pub message: String,
+}
+
+/// The assignments of a `.env` file and the errors in it
+#[derive(Debug, Clone, Default)]
+pub struct Dotenv {
+    pub entries: Vec<Entry>,
+    pub errors: Vec<ParseError>,
+}
+
+impl Dotenv {
+    /// Returns the entries, or the first error if the file has any.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first syntax error in the file.
+    pub fn into_result(self) -> std::result::Result<Vec<Entry>, ParseError> {
+        match self.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(self.entries),
+        }
+    }
+}
+
+/// Parses the contents of a `.env` file.
+#[must_use]
+pub fn parse(content: &str) -> Dotenv {
+    let mut parser = Parser {
+        chars: content.chars().collect(),
+        pos: 0,
+        byte: 0,
+        line: 1,
+        column: 1,
+    };
+    let mut dotenv = Dotenv::default();
+
+    while !parser.at_end() {
+        let start = (parser.pos, parser.byte, parser.line, parser.column);
+        match parser.statement() {
+            Ok(Some(entry)) => dotenv.entries.push(entry),
+            Ok(None) => {}
+            Err(error) => {
+                // Resume after the line the error is on, which comes before
+                // the end of the file for an unterminated quote
+                (parser.pos, parser.byte, parser.line, parser.column) = start;
+                parser.skip_to_line_after(error.line);
+                dotenv.errors.push(error);
+            }
+        }
+    }
+
+    dotenv
+}
+
+/// Reads a `.env` file, skipping invalid lines with a warning on stderr that
+/// gives their line and column, like `import` does.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn parse_file(path: &Path) -> Result<Vec<Entry>> {
+    let content = fs::read_to_string(path)?;
+    let parsed = parse(&content);
+    for error in &parsed.errors {
+        eprintln!("⚠️  Skipped invalid line in {}: {error}", path.display());
+    }
+    Ok(parsed.entries)
+}
+
+/// Returns true if `key` reads back as a variable name from a `.env` file.
+/// Names like `APP.PORT` or `my-var` are fine for docker compose and
+/// python-dotenv even though a shell can't source them.
+#[must_use]
+pub fn is_dotenv_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('#')
+        && !key.contains(|c: char| c.is_whitespace() || c == '=' || Quote::from_char(c).is_some())
+}
+
+/// Returns true if `key` can be written to a `.env` file, warning on stderr
+/// that it is skipped otherwise. Writers filter with this before creating or
+/// updating a file, so both behave the same.
+pub(crate) fn check_key(key: &str) -> bool {
+    let valid = is_dotenv_key(key);
+    if !valid {
+        eprintln!("⚠️  Skipped {key}: not a valid variable name for a .env file");
+    }
+    valid
+}
+
+fn file_error(path: &Path, error: &ParseError) -> color_eyre::Report {
+    eyre!(
+        "Invalid .env file {}:{}:{}: {}",
+        path.display(),
+        error.line,
+        error.column,
+        error.message
+    )
+}
+
+/// A `.env` file that is edited in place.
+///
+/// Setting or removing a variable only rewrites the bytes of its value or its
+/// lines, so comments, blank lines, quoting and the order of the other
+/// variables stay as they were.
+#[derive(Debug, Clone, Default)]
+pub struct DotenvDocument {
+    content: String,
+    entries: Vec<Entry>,
+}
+
+impl DotenvDocument {
+    /// Parses a document, rejecting it if any line is invalid so edits can't
+    /// land in the middle of a broken value.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first syntax error in the content.
+    pub fn new(content: String) -> std::result::Result<Self, ParseError> {
+        let entries = parse(&content).into_result()?;
+        Ok(Self { content, entries })
+    }
+
+    /// Reads a `.env` file, or returns an empty document if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or with the path, line and
+    /// column of the first syntax error in it.
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Self::new(content).map_err(|e| file_error(path, &e))
+    }
+
+    /// Writes the document to `path` through a temporary file next to it, so
+    /// a failed write never leaves a truncated file behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, self.content.as_bytes())
+    }
+
+    /// The assignments in the document, in file order
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the value of a variable, from its last assignment.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+
+    /// Sets a variable, rewriting the value of its last assignment or adding
+    /// one at the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `key` isn't a valid shell variable name (see
+    /// [`is_shell_identifier`]), so the file can also be sourced by a shell.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if !is_shell_identifier(key) {
+            return Err(eyre!("Invalid variable name '{}' for a .env file", key));
+        }
+        self.write_value(key, value);
+        Ok(())
+    }
+
+    fn write_value(&mut self, key: &str, value: &str) {
+        if let Some(entry) = self.entries.iter().rev().find(|entry| entry.key == key) {
+            if entry.value == value {
+                return;
+            }
+            let mut text = format_value(value, entry.quote);
+            // Keep a comment right after an empty value a comment
+            if entry.value_span.is_empty() && !text.is_empty() && self.content[entry.value_span.end..].starts_with('#')
+            {
+                text.push(' ');
+            }
+            self.content.replace_range(entry.value_span.clone(), &text);
+        } else {
+            let newline = if self.content.contains("\r\n") { "\r\n" } else { "\n" };
+            if !self.content.is_empty() && !self.content.ends_with('\n') {
+                self.content.push_str(newline);
+            }
+            let line = format!("{key}={}{newline}", format_value(value, Quote::None));
+            self.content.push_str(&line);
+        }
+
+        self.reparse();
+    }
+
+    /// Removes every assignment of a variable. Returns false if there was none.
+    pub fn unset(&mut self, key: &str) -> bool {
+        let spans: Vec<Range<usize>> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .map(|entry| entry.span.clone())
+            .collect();
+        for span in spans.iter().rev() {
+            self.content.replace_range(span.clone(), "");
+        }
+
+        self.reparse();
+        !spans.is_empty()
+    }
+
+    /// Makes the document define exactly `vars`: values are updated in place,
+    /// other variables are removed and new ones are added in the given order.
+    ///
+    /// Unlike [`DotenvDocument::set`], any name that reads back from the file
+    /// is accepted (see [`is_dotenv_key`]), like a newly written file would.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name cannot be read back from a `.env` file.
+    pub fn sync(&mut self, vars: &[(String, String)]) -> Result<()> {
+        if let Some((name, _)) = vars.iter().find(|(name, _)| !is_dotenv_key(name)) {
+            return Err(eyre!("Invalid variable name '{}' for a .env file", name));
+        }
+
+        let stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|entry| !vars.iter().any(|(name, _)| *name == entry.key))
+            .map(|entry| entry.key.clone())
+            .collect();
+        for key in stale {
+            self.unset(&key);
+        }
+        for (name, value) in vars {
+            self.write_value(name, value);
+        }
+        Ok(())
+    }
+
+    /// Puts the comment `# {text}` on the line above the last assignment of
+    /// `key`, replacing a comment there that starts with `# {prefix}`.
+    pub fn set_comment(&mut self, key: &str, prefix: &str, text: &str) {
+        let Some(start) = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.span.start)
+        else {
+            return;
+        };
+
+        let newline = if self.content.contains("\r\n") { "\r\n" } else { "\n" };
+        let comment = format!("# {text}{newline}");
+        let above = self.content[..start]
+            .strip_suffix('\n')
+            .map(|before| before.rfind('\n').map_or(0, |i| i + 1));
+        let range = match above {
+            // A line inside the value of another variable isn't a comment
+            Some(line)
+                if self.content[line..start].trim_end().starts_with(&format!("# {prefix}"))
+                    && !self.entries.iter().any(|entry| entry.span.contains(&line)) =>
+            {
+                line..start
+            }
+            _ => start..start,
+        };
+        self.content.replace_range(range, &comment);
+        self.reparse();
+    }
+
+    /// Puts the comment lines `# {line}` and a blank line at the top of the
+    /// document, replacing a header written before that has the same first line.
+    pub fn set_header(&mut self, lines: &[String]) {
+        let Some(first) = lines.first() else {
+            return;
+        };
+        let newline = if self.content.contains("\r\n") { "\r\n" } else { "\n" };
+
+        let first = format!("# {first}");
+        let mut end = 0;
+        if self.content.lines().next().is_some_and(|line| line.trim_end() == first) {
+            // The old header ends with the first line that isn't a comment
+            for line in self.content.split_inclusive('\n') {
+                let text = line.trim_end();
+                if !text.starts_with('#') {
+                    if text.is_empty() {
+                        end += line.len();
+                    }
+                    break;
+                }
+                end += line.len();
+            }
+        }
+
+        let mut header = String::new();
+        for line in lines {
+            let _ = write!(header, "# {line}{newline}");
+        }
+        header.push_str(newline);
+        self.content.replace_range(..end, &header);
+        self.reparse();
+    }
+
+    fn reparse(&mut self) {
+        let parsed = parse(&self.content);
+        debug_assert!(parsed.errors.is_empty(), "edits keep the document valid");
+        self.entries = parsed.entries;
+    }
+}
+
+impl fmt::Display for DotenvDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.content)
+    }
+}
+
+/// Writes a value so it reads back unchanged, in the quoting it had if
+/// possible. Values with `$` are single-quoted so they aren't expanded, and
+/// values with whitespace are quoted so the file can also be sourced by a shell.
+/// Values with `\r` are double-quoted, the only quoting that escapes it.
+fn format_value(value: &str, quote: Quote) -> String {
+    let plain =
+        !value.contains(char::is_whitespace) && !value.starts_with(|c| c == '#' || Quote::from_char(c).is_some());
+
+    let quote = match quote {
+        _ if value.contains('\r') => Quote::Double,
+        Quote::Backtick if !value.contains('`') => Quote::Backtick,
+        Quote::Single => Quote::Single,
+        _ if value.contains('$') => Quote::Single,
+        Quote::None if plain => Quote::None,
+        _ => Quote::Double,
+    };
+
+    match quote {
+        Quote::None => value.to_string(),
+        Quote::Single => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        Quote::Backtick => format!("`{value}`"),
+        Quote::Double => {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t");
+            format!("\"{escaped}\"")
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Byte offset of `pos` in the content
+    byte: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        self.byte += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_blanks(&mut self) -> bool {
+        let start = self.pos;
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+        self.pos != start
+    }
+
+    /// Skips the rest of the line, including the line break.
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn skip_to_line_after(&mut self, line: usize) {
+        while !self.at_end() && self.line <= line {
+            self.skip_line();
+        }
+    }
+
+    fn at_line_end(&self) -> bool {
+        match self.peek() {
+            None | Some('\n') => true,
+            Some('\r') => self.chars.get(self.pos + 1) == Some(&'\n'),
+            _ => false,
+        }
+    }
+
+    /// Parses one line, or several for a multi-line value.
+    fn statement(&mut self) -> std::result::Result<Option<Entry>, ParseError> {
+        let start = self.byte;
+        self.skip_blanks();
+        if self.at_line_end() || self.peek() == Some('#') {
+            self.skip_line();
+            return Ok(None);
+        }
+
+        self.skip_export();
+        let line = self.line;
+        let key = self.key()?;
+
+        self.skip_blanks();
+        if self.at_line_end() || self.peek() == Some('#') {
+            // A declaration without a value
+            self.skip_line();
+            return Ok(None);
+        }
+        if self.peek() != Some('=') {
+            return Err(self.error(format!("Expected '=' after variable name '{key}'")));
+        }
+        self.bump();
+
+        let blank = self.skip_blanks();
+        let value_start = self.byte;
+        let (value, quote, value_end) = if let Some(quote) = self.peek().and_then(Quote::from_char) {
+            let value = self.quoted_value(quote)?;
+            let end = self.byte;
+            self.skip_blanks();
+            if !self.at_line_end() && self.peek() != Some('#') {
+                return Err(self.error(format!("Unexpected character after {quote} value")));
+            }
+            (value, quote, end)
+        } else {
+            let (value, end) = self.unquoted_value(blank);
+            (value, Quote::None, end)
+        };
+        self.skip_line();
+
+        Ok(Some(Entry {
+            key,
+            value,
+            line,
+            quote,
+            span: start..self.byte,
+            value_span: value_start..value_end,
+        }))
+    }
+
+    fn skip_export(&mut self) {
+        const EXPORT: &str = "export";
+        let is_export = EXPORT
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+            && matches!(self.chars.get(self.pos + EXPORT.len()), Some(' ' | '\t'));
+        if is_export {
+            for _ in 0..EXPORT.len() {
+                self.bump();
+            }
+            self.skip_blanks();
+        }
+    }
+
+    fn key(&mut self) -> std::result::Result<String, ParseError> {
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            if Quote::from_char(c).is_some() {
+                return Err(self.error(format!("Unexpected {c} in variable name")));
+            }
+            key.push(c);
+            self.bump();
+        }
+
+        if key.is_empty() {
+            return Err(self.error("Missing variable name before '='"));
+        }
+        Ok(key)
+    }
+
+    /// Reads an unquoted value up to the end of the line or a comment and
+    /// returns it with the byte it ends at. `blank` tells if whitespace
+    /// precedes the value, which makes a leading `#` start a comment.
+    fn unquoted_value(&mut self, mut blank: bool) -> (String, usize) {
+        let mut value = String::new();
+        let mut end = self.byte;
+        while let Some(c) = self.peek() {
+            if c == '\n' || (c == '#' && blank) {
+                break;
+            }
+            blank = c.is_whitespace();
+            value.push(c);
+            self.bump();
+            if !blank {
+                end = self.byte;
+            }
+        }
+        (value.trim_end().to_string(), end)
+    }
+
+    fn quoted_value(&mut self, quote: Quote) -> std::result::Result<String, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let Some(delimiter) = self.bump() else {
+            unreachable!("quoted_value is called on a quote");
+        };
+
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.bump() else {
+                return Err(ParseError {
+                    line,
+                    column,
+                    message: format!("Unterminated {quote} value"),
+                });
+            };
+            match c {
+                c if c == delimiter => break,
+                '\r' if self.peek() == Some('\n') => {}
+                '\\' if quote != Quote::Backtick => {
+                    let Some(next) = self.peek() else { continue };
+                    match (quote, next) {
+                        (_, '\\') | (Quote::Single, '\'') | (Quote::Double, '"' | '\'') => value.push(next),
+                        (Quote::Double, 'n') => value.push('\n'),
+                        (Quote::Double, 'r') => value.push('\r'),
+                        (Quote::Double, 't') => value.push('\t'),
+                        (Quote::Double, 'a') => value.push('\u{7}'),
+                        (Quote::Double, 'b') => value.push('\u{8}'),
+                        (Quote::Double, 'f') => value.push('\u{c}'),
+                        (Quote::Double, 'v') => value.push('\u{b}'),
+                        _ => {
+                            value.push('\\');
+                            continue;
+                        }
+                    }
+                    self.bump();
+                }
+                c => value.push(c),
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::TempDir;
+
+    /// Expected outcome of a case in the conformance corpus
+    #[derive(Deserialize)]
+    struct Expected {
+        #[serde(default)]
+        entries: Vec<(String, String)>,
+        #[serde(default)]
+        errors: Vec<(usize, usize)>,
+    }
+
+    /// Runs every `<case>.env` in `tests/dotenv` against `<case>.json`.
+    #[test]
+    fn test_conformance_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("dotenv");
+        let mut cases = 0;
+
+        for entry in fs::read_dir(&corpus).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|s| s.to_str()) != Some("env") {
+                continue;
+            }
+
+            let content = fs::read_to_string(&path).unwrap();
+            let expected: Expected =
+                serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+            let parsed = parse(&content);
+
+            let entries: Vec<(String, String)> = parsed.entries.into_iter().map(|e| (e.key, e.value)).collect();
+            assert_eq!(entries, expected.entries, "entries of {}", path.display());
+            let errors: Vec<(usize, usize)> = parsed.errors.iter().map(|e| (e.line, e.column)).collect();
+            assert_eq!(errors, expected.errors, "errors of {}", path.display());
+
+            // Every value survives being written back in any quoting
+            for (_, value) in &entries {
+                for quote in [Quote::None, Quote::Single, Quote::Double, Quote::Backtick] {
+                    let written = format!("KEY={} # comment\n", format_value(value, quote));
+                    let reparsed = parse(&written).into_result().unwrap();
+                    assert_eq!(&reparsed[0].value, value, "{written}");
+                }
+            }
+            cases += 1;
+        }
+
+        assert!(cases > 0, "no cases in {}", corpus.display());
+    }
+
+    #[test]
+    fn test_entries_record_line_and_quote() {
+        let parsed = parse("# comment\nA=1\nB='x'\n\nexport C=\"multi\nline\"\nD=`lit`\n");
+        let entries: Vec<(&str, usize, Quote)> = parsed
+            .entries
+            .iter()
+            .map(|e| (e.key.as_str(), e.line, e.quote))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("A", 2, Quote::None),
+                ("B", 3, Quote::Single),
+                ("C", 5, Quote::Double),
+                ("D", 7, Quote::Backtick)
+            ]
+        );
+        assert!(Quote::Double.expands());
+        assert!(!Quote::Single.expands());
+    }
+
+    #[test]
+    fn test_errors_report_line_and_column() {
+        let parsed = parse("GOOD=1\nBAD KEY=2\nQ=\"open\nNEXT=3\n");
+        assert_eq!(
+            parsed.errors[0].to_string(),
+            "line 2, column 5: Expected '=' after variable name 'BAD'"
+        );
+        assert_eq!(
+            parsed.errors[1].to_string(),
+            "line 3, column 3: Unterminated double-quoted value"
+        );
+        assert!(parse("A=1\nB\n").into_result().is_ok());
+        assert_eq!(parse("A=1\n=2\n").into_result().unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_document_set_keeps_the_rest_of_the_file() {
+        let content = "# Database\nexport DB_HOST=localhost # local only\n\nDB_PASS='old'\nEMPTY= # unset\r\n# end";
+        let mut document = DotenvDocument::new(content.to_string()).unwrap();
+
+        document.set("DB_HOST", "db.internal").unwrap();
+        document.set("DB_PASS", "it's new").unwrap();
+        document.set("EMPTY", "filled").unwrap();
+        document.set("NEW", "two words").unwrap();
+        document.set("DB_HOST", "db.internal").unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            "# Database\nexport DB_HOST=db.internal # local only\n\nDB_PASS='it\\'s new'\nEMPTY= filled # unset\r\n# end\r\nNEW=\"two words\"\r\n"
+        );
+        assert_eq!(document.get("DB_PASS"), Some("it's new"));
+        assert_eq!(document.get("NEW"), Some("two words"));
+        for key in ["BAD KEY", "", "1ST", "APP.NAME", "APP-NAME", "#A"] {
+            assert!(document.set(key, "value").is_err(), "{key} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_document_set_double_quotes_carriage_returns() {
+        let mut document = DotenvDocument::new("A='x'\nB=`y`\n".to_string()).unwrap();
+        document.set("A", "one\r\ntwo").unwrap();
+        document.set("B", "three\r").unwrap();
+        document.set("C", "four\r\n").unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            "A=\"one\\r\\ntwo\"\nB=\"three\\r\"\nC=\"four\\r\\n\"\n"
+        );
+        let reparsed = DotenvDocument::new(document.to_string()).unwrap();
+        assert_eq!(reparsed.get("A"), Some("one\r\ntwo"));
+        assert_eq!(reparsed.get("B"), Some("three\r"));
+        assert_eq!(reparsed.get("C"), Some("four\r\n"));
+    }
+
+    #[test]
+    fn test_document_save_replaces_the_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(".env");
+        fs::write(&path, "A=1\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let mut document = DotenvDocument::open(&path).unwrap();
+        document.set("B", "2").unwrap();
+        document.save(&path).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A=1\nB=2\n");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_document_unset_and_sync() {
+        let content = "A=1\n# keep\nB=\"multi\nline\"\nC=3\nB=again\n";
+        let mut document = DotenvDocument::new(content.to_string()).unwrap();
+
+        assert!(document.unset("B"));
+        assert!(!document.unset("MISSING"));
+        assert_eq!(document.to_string(), "A=1\n# keep\nC=3\n");
+
+        document
+            .sync(&[
+                ("C".to_string(), "3".to_string()),
+                ("D".to_string(), "${HOME}".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(document.to_string(), "# keep\nC=3\nD='${HOME}'\n");
+        assert!(DotenvDocument::new("A=\"open\n".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_skips_invalid_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(".env");
+        fs::write(&path, "A=1\nBAD KEY=2\nC=3\n").unwrap();
+
+        let keys: Vec<String> = parse_file(&path).unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, ["A", "C"]);
+        fs::write(&path, "export A=1\r\nB=2\r\n").unwrap();
+        assert_eq!(parse_file(&path).unwrap().len(), 2);
+        assert!(parse_file(&temp_dir.path().join("missing.env")).is_err());
+    }
+}
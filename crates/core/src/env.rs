@@ -1,9 +1,16 @@
 use crate::EnvxError;
+use crate::history::{DEFAULT_MAX_ENTRIES, History, HistoryAction, HistoryEntry, REDACTED_VALUE};
+use crate::secret::SecretClassifier;
+use crate::shell_config::ShellConfig;
+use crate::vault::{KeyStore, Vault, is_encrypted};
 use chrono::{DateTime, Utc};
 use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use indexmap::IndexMap;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EnvVarSource {
@@ -21,18 +28,72 @@ pub struct EnvVar {
     pub source: EnvVarSource,
     pub modified: DateTime<Utc>,
     pub original_value: Option<String>,
+    /// Where the value came from. On Unix the origin of a permanent value is
+    /// kept next to it in the managed shell block, so later envx runs still
+    /// know it. It is serialized with the variable, e.g. into snapshots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<VarOrigin>,
+}
+
+/// Where a variable's value came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VarOrigin {
+    /// A file such as a `.env` file, an imported file or a shell startup file
+    File { path: PathBuf, line: Option<usize> },
+    /// A profile applied by envx
+    Profile(String),
+    /// The `defaults` section of a project configuration file
+    ProjectDefault(PathBuf),
+    /// A Windows registry key
+    Registry(String),
+}
+
+impl VarOrigin {
+    #[must_use]
+    pub const fn file(path: PathBuf, line: Option<usize>) -> Self {
+        Self::File { path, line }
+    }
+}
+
+impl fmt::Display for VarOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File { path, line: Some(line) } => write!(f, "{}:{line}", path.display()),
+            Self::File { path, line: None } => write!(f, "{}", path.display()),
+            Self::Profile(name) => write!(f, "profile '{name}'"),
+            Self::ProjectDefault(path) => write!(f, "project default ({})", path.display()),
+            Self::Registry(key) => write!(f, "registry {key}"),
+        }
+    }
 }
 
 pub struct EnvVarManager {
     pub vars: IndexMap<String, EnvVar>,
-    pub history: Vec<crate::history::HistoryEntry>,
+    pub history: History,
+    /// Changes of the batch in progress, see [`EnvVarManager::batch`]
+    batch: Option<Vec<HistoryAction>>,
+    /// Startup files permanent variables are written to on Unix, the current
+    /// user's if not set
+    shell_config: Option<ShellConfig>,
+    /// Key store secret values in the journal are encrypted with, the
+    /// user's if not set
+    keys: Option<KeyStore>,
+    /// The unlocked key, once a secret has been encrypted or decrypted
+    vault: Option<Vault>,
+    /// Decides which values are secrets, loaded on first use
+    secrets: Option<SecretClassifier>,
 }
 
 impl Default for EnvVarManager {
     fn default() -> Self {
         Self {
             vars: IndexMap::new(),
-            history: Vec::new(),
+            history: History::default(),
+            batch: None,
+            shell_config: None,
+            keys: None,
+            vault: None,
+            secrets: None,
         }
     }
 }
@@ -43,6 +104,135 @@ impl EnvVarManager {
         Self::default()
     }
 
+    /// Creates a manager that records its changes in the user's persistent
+    /// journal, so they can be undone by later envx runs.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the journal exists but cannot be read or parsed.
+    pub fn with_journal() -> Result<Self> {
+        Ok(Self {
+            history: History::load(History::default_path()?, DEFAULT_MAX_ENTRIES)?,
+            ..Self::default()
+        })
+    }
+
+    /// Writes permanent variables to the startup files in `config`'s home
+    /// directory instead of the current user's.
+    #[must_use]
+    pub fn with_shell_config(mut self, config: ShellConfig) -> Self {
+        self.shell_config = Some(config);
+        self
+    }
+
+    /// Encrypts secret values in the journal with the key in `keys` instead
+    /// of the user's.
+    #[must_use]
+    pub fn with_key_store(mut self, keys: KeyStore) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Runs `f` as a single batch: all changes it makes are recorded as one
+    /// history entry and are undone and redone together.
+    ///
+    /// Changes made before `f` fails are still recorded, so they can be
+    /// undone. Nested batches are merged into the outermost one.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `f`, or an error if the journal cannot be saved.
+    pub fn batch<T>(&mut self, description: &str, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.batch.is_some() {
+            return f(self);
+        }
+
+        self.batch = Some(Vec::new());
+        let result = f(self);
+        let actions = self.batch.take().unwrap_or_default();
+
+        if !actions.is_empty() {
+            self.history.add(HistoryEntry::new(HistoryAction::Batch {
+                description: description.to_string(),
+                actions,
+            }));
+            self.history.save()?;
+        }
+
+        result
+    }
+
+    fn record(&mut self, action: HistoryAction) -> Result<()> {
+        let action = self.protect(action)?;
+        if let Some(batch) = &mut self.batch {
+            batch.push(action);
+            return Ok(());
+        }
+
+        self.history.add(HistoryEntry::new(action));
+        self.history.save()
+    }
+
+    /// Encrypts the secret values of a change that goes into a journal file.
+    /// Values that can't be encrypted are replaced by [`REDACTED_VALUE`], so
+    /// no secret is ever written in plain text.
+    fn protect(&mut self, action: HistoryAction) -> Result<HistoryAction> {
+        if self.history.path().is_none() {
+            return Ok(action);
+        }
+
+        action.try_map_values(&mut |name, value| {
+            let secrets = self.secrets.get_or_insert_with(SecretClassifier::for_current_project);
+            if is_encrypted(&value) || !secrets.is_secret(name, &value) {
+                return Ok(value);
+            }
+
+            Ok(match self.vault(true).and_then(|vault| vault.encrypt(&value)) {
+                Ok(encrypted) => encrypted,
+                Err(e) => {
+                    tracing::warn!("Not recording the value of {name} in the history: {e}");
+                    REDACTED_VALUE.to_string()
+                }
+            })
+        })
+    }
+
+    /// Decrypts the secret values of a journal entry so it can be undone or
+    /// redone.
+    fn reveal(&mut self, action: HistoryAction) -> Result<HistoryAction> {
+        action.try_map_values(&mut |name, value| {
+            if value == REDACTED_VALUE {
+                Err(eyre!(
+                    "The value of {name} is a secret that was not recorded in the history, so this change can't be reverted"
+                ))
+            } else if is_encrypted(&value) {
+                self.vault(false)?.decrypt(&value)
+            } else {
+                Ok(value)
+            }
+        })
+    }
+
+    /// Returns the key for the secrets in the journal, unlocking it on first
+    /// use. A key is only set up if `create` is true.
+    fn vault(&mut self, create: bool) -> Result<&Vault> {
+        if self.vault.is_none() {
+            let keys = match &self.keys {
+                Some(keys) => keys.clone(),
+                None => KeyStore::new()?,
+            };
+            let vault = if create {
+                keys.unlock()?
+            } else {
+                keys.unlock_existing()?
+            };
+            self.vault = Some(vault);
+        }
+        self.vault
+            .as_ref()
+            .ok_or_else(|| eyre!("The encryption key could not be unlocked"))
+    }
+
     /// Loads environment variables from all available sources (process, system, and user).
     ///
     /// This method loads environment variables from the current process environment
@@ -65,6 +255,7 @@ impl EnvVarManager {
                     source: EnvVarSource::Process,
                     modified: Utc::now(),
                     original_value: None,
+                    origin: None,
                 },
             );
         }
@@ -96,6 +287,10 @@ impl EnvVarManager {
                         source: EnvVarSource::System,
                         modified: Utc::now(),
                         original_value: None,
+                        origin: Some(VarOrigin::Registry(
+                            "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Control\\Session Manager\\Environment"
+                                .to_string(),
+                        )),
                     },
                 );
             }
@@ -113,6 +308,7 @@ impl EnvVarManager {
                         source: EnvVarSource::User,
                         modified: Utc::now(),
                         original_value: None,
+                        origin: Some(VarOrigin::Registry("HKEY_CURRENT_USER\\Environment".to_string())),
                     },
                 );
             }
@@ -121,7 +317,6 @@ impl EnvVarManager {
 
     #[cfg(unix)]
     fn load_unix_vars(&mut self) {
-        // On Unix, we primarily work with process environment
         // Shell-specific vars can be detected by checking common patterns
         for (key, value) in std::env::vars() {
             let source = if key.starts_with("BASH_") || key.starts_with("ZSH_") {
@@ -138,9 +333,55 @@ impl EnvVarManager {
                     source,
                     modified: Utc::now(),
                     original_value: None,
+                    origin: None,
                 },
             );
         }
+
+        // User and system variables come from the startup files that define them
+        let vars = crate::startup_env::StartupEnvLoader::for_current_user().load();
+        self.merge_startup_vars(vars);
+    }
+
+    /// Marks variables defined in startup files with their real source.
+    ///
+    /// Values already present in the process environment are kept as they are
+    /// the effective ones, startup files often build on other variables
+    /// (`PATH="$HOME/bin:$PATH"`). Variables that only exist in a startup file
+    /// are added with the value written there.
+    #[cfg(unix)]
+    fn merge_startup_vars(&mut self, vars: Vec<crate::startup_env::StartupVar>) {
+        for startup_var in vars {
+            // A value envx persisted shows where envx got it from, unless it was changed since
+            let origin = match startup_var.origin {
+                Some(origin)
+                    if self
+                        .vars
+                        .get(&startup_var.name)
+                        .is_none_or(|var| var.value == startup_var.value) =>
+                {
+                    Some(origin)
+                }
+                _ => Some(VarOrigin::file(startup_var.path, Some(startup_var.line))),
+            };
+
+            if let Some(var) = self.vars.get_mut(&startup_var.name) {
+                var.source = startup_var.source;
+                var.origin = origin;
+            } else {
+                self.vars.insert(
+                    startup_var.name.clone(),
+                    EnvVar {
+                        name: startup_var.name,
+                        value: startup_var.value,
+                        source: startup_var.source,
+                        modified: Utc::now(),
+                        original_value: None,
+                        origin,
+                    },
+                );
+            }
+        }
     }
 
     #[must_use]
@@ -215,6 +456,22 @@ impl EnvVarManager {
     /// - File system operations fail when modifying shell configuration files on Unix
     /// - Other platform-specific environment variable persistence operations fail
     pub fn set(&mut self, name: &str, value: &str, permanent: bool) -> Result<()> {
+        self.set_var(name, value, permanent, None)
+    }
+
+    /// Sets an environment variable and records where its value came from.
+    ///
+    /// Behaves like [`EnvVarManager::set`], the origin is shown by
+    /// `envx get --format detailed` and the TUI.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`EnvVarManager::set`].
+    pub fn set_with_origin(&mut self, name: &str, value: &str, permanent: bool, origin: VarOrigin) -> Result<()> {
+        self.set_var(name, value, permanent, Some(origin))
+    }
+
+    fn set_var(&mut self, name: &str, value: &str, permanent: bool, origin: Option<VarOrigin>) -> Result<()> {
         if name.is_empty() {
             return Err(EnvxError::InvalidName("Variable name cannot be empty".to_string()).into());
         }
@@ -231,15 +488,31 @@ impl EnvVarManager {
             }
         }
 
+        let old_value = self.vars.get(name).map(|v| v.value.clone());
+        let was_permanent = permanent && old_value.is_some() && self.is_persisted(name);
+
+        self.apply_set(name, value, permanent, origin)?;
+
+        self.record(HistoryAction::Set {
+            name: name.to_string(),
+            old_value,
+            new_value: value.to_string(),
+            permanent,
+            was_permanent,
+        })
+    }
+
+    /// Sets a variable without recording it in the history.
+    fn apply_set(&mut self, name: &str, value: &str, permanent: bool, origin: Option<VarOrigin>) -> Result<()> {
         let old_var = self.vars.get(name).cloned();
 
-        // Record history
-        self.history
-            .push(crate::history::HistoryEntry::new(crate::history::HistoryAction::Set {
-                name: name.to_string(),
-                old_value: old_var.as_ref().map(|v| v.value.clone()),
-                new_value: value.to_string(),
-            }));
+        if permanent {
+            #[cfg(windows)]
+            Self::set_windows_var(name, value, false)?;
+
+            #[cfg(unix)]
+            self.set_unix_var(name, value, origin.as_ref())?;
+        }
 
         // Update in-memory
         let var = EnvVar {
@@ -252,20 +525,13 @@ impl EnvVarManager {
             },
             modified: Utc::now(),
             original_value: old_var.map(|v| v.value),
+            origin,
         };
         self.vars.insert(name.to_string(), var);
 
         // Apply to process
         unsafe { std::env::set_var(name, value) };
 
-        if permanent {
-            #[cfg(windows)]
-            Self::set_windows_var(name, value, false)?;
-
-            #[cfg(unix)]
-            Self::set_unix_var(name, value);
-        }
-
         Ok(())
     }
 
@@ -307,12 +573,55 @@ impl EnvVarManager {
     }
 
     #[cfg(unix)]
-    fn set_unix_var(name: &str, value: &str) {
-        // On Unix, we'd typically need to modify shell config files
-        // This is a simplified version - real implementation would handle
-        // .bashrc, .zshrc, etc.
-        println!("Note: To make this permanent on Unix, add to your shell config:");
-        println!("export {name}=\"{value}\"");
+    fn set_unix_var(&self, name: &str, value: &str, origin: Option<&VarOrigin>) -> Result<()> {
+        let config = match &self.shell_config {
+            Some(config) => config.clone(),
+            None => ShellConfig::for_current_user()?,
+        };
+
+        for path in config.persist_with_origin(name, value, origin)? {
+            tracing::debug!("Persisted {} in {}", name, path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Startup files of the user, `None` without a home directory.
+    #[cfg(unix)]
+    fn existing_shell_config(&self) -> Option<ShellConfig> {
+        self.shell_config
+            .clone()
+            .or_else(|| dirs::home_dir().map(ShellConfig::new))
+    }
+
+    /// Returns true if `name` is persisted beyond the envx process, in the
+    /// managed shell blocks on Unix and the registry on Windows.
+    fn is_persisted(&self, name: &str) -> bool {
+        #[cfg(unix)]
+        {
+            self.existing_shell_config()
+                .and_then(|config| config.managed_vars().ok())
+                .is_some_and(|vars| vars.contains_key(name))
+        }
+
+        #[cfg(not(unix))]
+        self.vars
+            .get(name)
+            .is_some_and(|var| matches!(var.source, EnvVarSource::System | EnvVarSource::User))
+    }
+
+    #[cfg(unix)]
+    fn delete_unix_var(&self, name: &str) -> Result<()> {
+        // Without a home directory there are no startup files to remove it from
+        let Some(config) = self.existing_shell_config() else {
+            return Ok(());
+        };
+
+        for path in config.remove(name)? {
+            tracing::debug!("Removed {} from {}", name, path.display());
+        }
+
+        Ok(())
     }
 
     /// Deletes an environment variable by name.
@@ -323,37 +632,63 @@ impl EnvVarManager {
     ///
     /// # Errors
     ///
-    /// Returns an error if the variable with the given name does not exist.
+    /// Returns an error if:
+    /// - The variable with the given name does not exist
+    /// - The shell configuration files cannot be updated on Unix
     pub fn delete(&mut self, name: &str) -> Result<()> {
         let old_var = self
             .vars
             .swap_remove(name)
             .ok_or_else(|| EnvxError::VarNotFound(name.to_string()))?;
 
-        self.history.push(crate::history::HistoryEntry::new(
-            crate::history::HistoryAction::Delete {
-                name: name.to_string(),
-                old_value: old_var.value.clone(),
-            },
-        ));
-
         // Remove from current process
         unsafe { std::env::remove_var(name) };
 
         // Remove from system if it was a permanent variable
+        #[cfg(windows)]
         match old_var.source {
             EnvVarSource::System | EnvVarSource::User => {
-                #[cfg(windows)]
                 delete_windows_var(name, matches!(old_var.source, EnvVarSource::System));
-
-                #[cfg(unix)]
-                delete_unix_var(name);
             }
             _ => {
                 // Process, Shell, or Application variables don't need system removal
             }
         }
 
+        // A variable persisted by an earlier envx run shows up as a plain process
+        // variable in this one, so always clear it from the managed shell blocks
+        #[cfg(unix)]
+        self.delete_unix_var(name)?;
+
+        self.record(HistoryAction::Delete {
+            name: name.to_string(),
+            old_value: old_var.value,
+            permanent: matches!(old_var.source, EnvVarSource::System | EnvVarSource::User),
+        })
+    }
+
+    /// Removes a variable without recording it in the history.
+    ///
+    /// Unlike [`EnvVarManager::delete`], a variable that doesn't exist is not an error.
+    fn apply_delete(&mut self, name: &str, permanent: bool) -> Result<()> {
+        self.vars.swap_remove(name);
+        unsafe { std::env::remove_var(name) };
+
+        if permanent {
+            self.unpersist(name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the persisted value of a variable, keeping it in the process.
+    fn unpersist(&self, name: &str) -> Result<()> {
+        #[cfg(windows)]
+        delete_windows_var(name, false);
+
+        #[cfg(unix)]
+        self.delete_unix_var(name)?;
+
         Ok(())
     }
 
@@ -378,54 +713,126 @@ impl EnvVarManager {
 
     /// Undoes the last environment variable operation.
     ///
-    /// This method reverses the most recent operation (set or delete) by restoring
-    /// the previous state from the history. For set operations, it either restores
-    /// the previous value or removes the variable if it didn't exist before. For
-    /// delete operations, it restores the deleted variable with its previous value.
+    /// Set operations restore the previous value, or remove the variable if it
+    /// didn't exist before, and delete operations restore the deleted variable.
+    /// Batches are undone as a whole. Permanent changes are reverted
+    /// permanently, and an old value is only persisted again if it was
+    /// persisted before. The undone entry can be redone with [`EnvVarManager::redo`].
+    ///
+    /// Returns the undone entry, or `None` if there is nothing to undo.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reverting a permanent change fails, a secret value
+    /// of the change cannot be decrypted or the journal cannot be saved.
+    pub fn undo(&mut self) -> Result<Option<HistoryEntry>> {
+        let Some(entry) = self.history.pop() else {
+            return Ok(None);
+        };
+
+        let action = match self.reveal(entry.action.clone()) {
+            Ok(action) => action,
+            Err(e) => {
+                self.history.push_redone(entry);
+                return Err(e);
+            }
+        };
+        self.revert(&action)?;
+        self.history.push_undone(entry.clone());
+        self.history.save()?;
+
+        Ok(Some(entry))
+    }
+
+    /// Redoes the last undone operation.
+    ///
+    /// Returns the redone entry, or `None` if there is nothing to redo.
     ///
     /// # Errors
     ///
-    /// Currently, this method always returns `Ok(())` and does not produce errors,
-    /// but it returns a `Result` for future extensibility and consistency with
-    /// other methods in the API.
-    pub fn undo(&mut self) -> Result<()> {
-        if let Some(entry) = self.history.pop() {
-            // Implement undo logic based on history entry
-            match entry.action {
-                crate::history::HistoryAction::Set { name, old_value, .. } => {
-                    if let Some(old) = old_value {
-                        // Variable existed before - restore old value without adding to history
-                        let var = EnvVar {
-                            name: name.clone(),
-                            value: old.clone(),
-                            source: EnvVarSource::Process,
-                            modified: Utc::now(),
-                            original_value: self.vars.get(&name).map(|v| v.value.clone()),
-                        };
-                        self.vars.insert(name.clone(), var);
-                        unsafe { std::env::set_var(&name, &old) };
-                    } else {
-                        // Variable didn't exist before - remove it without adding to history
-                        self.vars.swap_remove(&name);
-                        unsafe { std::env::remove_var(&name) };
+    /// Returns an error if reapplying a permanent change fails, a secret value
+    /// of the change cannot be decrypted or the journal cannot be saved.
+    pub fn redo(&mut self) -> Result<Option<HistoryEntry>> {
+        let Some(entry) = self.history.pop_undone() else {
+            return Ok(None);
+        };
+
+        let action = match self.reveal(entry.action.clone()) {
+            Ok(action) => action,
+            Err(e) => {
+                self.history.push_undone(entry);
+                return Err(e);
+            }
+        };
+        self.replay(&action)?;
+        self.history.push_redone(entry.clone());
+        self.history.save()?;
+
+        Ok(Some(entry))
+    }
+
+    fn revert(&mut self, action: &HistoryAction) -> Result<()> {
+        match action {
+            HistoryAction::Set {
+                name,
+                old_value,
+                permanent,
+                was_permanent,
+                ..
+            } => match old_value {
+                // An old value that only lived in the session isn't persisted by undo
+                Some(old) if *permanent && !*was_permanent => {
+                    self.apply_set(name, old, false, None)?;
+                    self.unpersist(name)
+                }
+                Some(old) => self.apply_set(name, old, *permanent, None),
+                None => self.apply_delete(name, *permanent),
+            },
+            HistoryAction::Delete {
+                name,
+                old_value,
+                permanent,
+            } => self.apply_set(name, old_value, *permanent, None),
+            HistoryAction::BatchUpdate { changes } => {
+                for (name, old_value, _) in changes.iter().rev() {
+                    match old_value {
+                        Some(old) => self.apply_set(name, old, false, None)?,
+                        None => self.apply_delete(name, false)?,
                     }
                 }
-                crate::history::HistoryAction::Delete { name, old_value } => {
-                    // Restore deleted variable without adding to history
-                    let var = EnvVar {
-                        name: name.clone(),
-                        value: old_value.clone(),
-                        source: EnvVarSource::Process,
-                        modified: Utc::now(),
-                        original_value: None,
-                    };
-                    self.vars.insert(name.clone(), var);
-                    unsafe { std::env::set_var(&name, &old_value) };
+                Ok(())
+            }
+            HistoryAction::Batch { actions, .. } => {
+                for action in actions.iter().rev() {
+                    self.revert(action)?;
                 }
-                crate::history::HistoryAction::BatchUpdate { .. } => {}
+                Ok(())
+            }
+        }
+    }
+
+    fn replay(&mut self, action: &HistoryAction) -> Result<()> {
+        match action {
+            HistoryAction::Set {
+                name,
+                new_value,
+                permanent,
+                ..
+            } => self.apply_set(name, new_value, *permanent, None),
+            HistoryAction::Delete { name, permanent, .. } => self.apply_delete(name, *permanent),
+            HistoryAction::BatchUpdate { changes } => {
+                for (name, _, new_value) in changes {
+                    self.apply_set(name, new_value, false, None)?;
+                }
+                Ok(())
+            }
+            HistoryAction::Batch { actions, .. } => {
+                for action in actions {
+                    self.replay(action)?;
+                }
+                Ok(())
             }
         }
-        Ok(())
     }
 
     pub fn clear(&mut self) {
@@ -443,6 +850,12 @@ impl EnvVarManager {
     /// - The source variable specified by the pattern doesn't exist (for exact matches)
     /// - System-level operations fail when updating environment variables
     pub fn rename(&mut self, pattern: &str, replacement: &str) -> Result<Vec<(String, String)>> {
+        self.batch(&format!("rename {pattern} to {replacement}"), |manager| {
+            manager.rename_vars(pattern, replacement)
+        })
+    }
+
+    fn rename_vars(&mut self, pattern: &str, replacement: &str) -> Result<Vec<(String, String)>> {
         let mut renamed = Vec::new();
 
         if pattern.contains('*') {
@@ -518,6 +931,12 @@ impl EnvVarManager {
     /// - The pattern contains multiple wildcards (not supported)
     /// - System-level operations fail when updating environment variables
     pub fn replace(&mut self, pattern: &str, new_value: &str) -> Result<Vec<(String, String, String)>> {
+        self.batch(&format!("replace {pattern}"), |manager| {
+            manager.replace_values(pattern, new_value)
+        })
+    }
+
+    fn replace_values(&mut self, pattern: &str, new_value: &str) -> Result<Vec<(String, String, String)>> {
         let mut replaced = Vec::new();
 
         if pattern.contains('*') {
@@ -569,6 +988,17 @@ impl EnvVarManager {
         search: &str,
         replacement: &str,
         pattern: Option<&str>,
+    ) -> Result<Vec<(String, String, String)>> {
+        self.batch(&format!("find and replace '{search}'"), |manager| {
+            manager.find_replace_values(search, replacement, pattern)
+        })
+    }
+
+    fn find_replace_values(
+        &mut self,
+        search: &str,
+        replacement: &str,
+        pattern: Option<&str>,
     ) -> Result<Vec<(String, String, String)>> {
         let mut replaced = Vec::new();
 
@@ -617,7 +1047,9 @@ impl EnvVarManager {
         Ok(replaced)
     }
 }
-fn wildcard_to_regex(pattern: &str) -> String {
+
+/// Converts a wildcard pattern (* and ?) into an anchored regex.
+pub(crate) fn wildcard_to_regex(pattern: &str) -> String {
     let mut regex = String::new();
     regex.push('^');
 
@@ -701,14 +1133,15 @@ fn delete_windows_var(name: &str, system: bool) {
     }
 }
 
-#[cfg(unix)]
-fn delete_unix_var(name: &str) {
-    // On Unix, we'd typically need to modify shell config files
-    // This is a simplified version - real implementation would handle
-    // .bashrc, .zshrc, etc.
-    println!("Note: To permanently remove this on Unix, remove from your shell config:");
-    println!("unset {name}");
-    println!("And remove any 'export {name}=...' lines from .bashrc/.zshrc/etc.");
+#[cfg(test)]
+impl EnvVarManager {
+    /// A manager whose permanent changes go to a throwaway home directory,
+    /// so tests never touch the real shell startup files.
+    pub(crate) fn for_tests() -> Self {
+        static HOME: std::sync::LazyLock<tempfile::TempDir> =
+            std::sync::LazyLock::new(|| tempfile::TempDir::new().expect("temp home"));
+        Self::new().with_shell_config(ShellConfig::new(HOME.path().to_path_buf()))
+    }
 }
 
 #[cfg(test)]
@@ -730,12 +1163,13 @@ mod tests {
             source,
             modified: Utc::now(),
             original_value: None,
+            origin: None,
         }
     }
 
     // Helper to create a manager with test data
     fn create_test_manager() -> EnvVarManager {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Add some test variables
         manager.vars.insert(
@@ -772,7 +1206,7 @@ mod tests {
 
     #[test]
     fn test_new() {
-        let manager = EnvVarManager::new();
+        let manager = EnvVarManager::for_tests();
         assert!(manager.vars.is_empty());
         assert!(manager.history.is_empty());
     }
@@ -890,7 +1324,7 @@ mod tests {
 
     #[test]
     fn test_set_temporary() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set a new variable temporarily
         manager.set("TEST_VAR", "test_value", false).unwrap();
@@ -973,7 +1407,7 @@ mod tests {
 
     #[test]
     fn test_history_tracking() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set a variable
         manager.set("VAR1", "value1", false).unwrap();
@@ -988,7 +1422,7 @@ mod tests {
         assert_eq!(manager.history.len(), 3);
 
         // Verify history entries
-        if let crate::history::HistoryAction::Delete { name, old_value } = &manager.history[2].action {
+        if let crate::history::HistoryAction::Delete { name, old_value, .. } = &manager.history[2].action {
             assert_eq!(name, "VAR1");
             assert_eq!(old_value, "value2");
         } else {
@@ -998,7 +1432,7 @@ mod tests {
 
     #[test]
     fn test_undo_set() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set a new variable
         manager.set("UNDO_TEST", "value1", false).unwrap();
@@ -1017,7 +1451,7 @@ mod tests {
 
     #[test]
     fn test_undo_delete() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set and then delete a variable
         manager.set("DELETE_TEST", "value", false).unwrap();
@@ -1029,6 +1463,145 @@ mod tests {
         assert_eq!(manager.get("DELETE_TEST").unwrap().value, "value");
     }
 
+    #[test]
+    fn test_redo() {
+        let mut manager = EnvVarManager::for_tests();
+        manager.set("REDO_TEST", "value1", false).unwrap();
+        manager.set("REDO_TEST", "value2", false).unwrap();
+
+        manager.undo().unwrap();
+        manager.undo().unwrap();
+        assert!(manager.get("REDO_TEST").is_none());
+
+        let entry = manager.redo().unwrap().unwrap();
+        assert_eq!(entry.action.summary(), "set REDO_TEST");
+        assert_eq!(manager.get("REDO_TEST").unwrap().value, "value1");
+
+        // A new change drops what is left to redo
+        manager.set("REDO_TEST", "value3", false).unwrap();
+        assert!(manager.redo().unwrap().is_none());
+        assert!(manager.undo().unwrap().is_some());
+        assert_eq!(manager.get("REDO_TEST").unwrap().value, "value1");
+    }
+
+    #[test]
+    fn test_undo_batch_is_atomic() {
+        let mut manager = EnvVarManager::for_tests();
+        manager.set("BATCH_OLD", "value", false).unwrap();
+
+        manager
+            .batch("rename and add", |manager| {
+                manager.set("BATCH_NEW", "value", false)?;
+                manager.delete("BATCH_OLD")?;
+                manager.set("BATCH_EXTRA", "x", false)
+            })
+            .unwrap();
+        assert_eq!(manager.history.len(), 2);
+
+        let entry = manager.undo().unwrap().unwrap();
+        assert_eq!(entry.action.summary(), "rename and add (3 changes)");
+        assert_eq!(manager.get("BATCH_OLD").unwrap().value, "value");
+        assert!(manager.get("BATCH_NEW").is_none());
+        assert!(manager.get("BATCH_EXTRA").is_none());
+
+        manager.redo().unwrap();
+        assert!(manager.get("BATCH_OLD").is_none());
+        assert_eq!(manager.get("BATCH_NEW").unwrap().value, "value");
+    }
+
+    #[test]
+    fn test_failed_batch_is_recorded() {
+        let mut manager = EnvVarManager::for_tests();
+
+        let result = manager.batch("partial", |manager| {
+            manager.set("PARTIAL_A", "1", false)?;
+            manager.delete("PARTIAL_MISSING")
+        });
+        assert!(result.is_err());
+
+        manager.undo().unwrap();
+        assert!(manager.get("PARTIAL_A").is_none());
+    }
+
+    #[test]
+    fn test_journal_survives_restart() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("history.json");
+
+        let mut manager = EnvVarManager {
+            history: History::load(path.clone(), DEFAULT_MAX_ENTRIES).unwrap(),
+            ..EnvVarManager::for_tests()
+        };
+        manager.set("JOURNAL_TEST", "value", false).unwrap();
+        manager.rename("JOURNAL_TEST", "JOURNAL_RENAMED").unwrap();
+
+        // A new process sees the journal and undoes the rename as a whole
+        let mut manager = EnvVarManager {
+            history: History::load(path, DEFAULT_MAX_ENTRIES).unwrap(),
+            ..EnvVarManager::for_tests()
+        };
+        manager.vars.insert(
+            "JOURNAL_RENAMED".to_string(),
+            create_test_var("JOURNAL_RENAMED", "value", EnvVarSource::User),
+        );
+
+        let entry = manager.undo().unwrap().unwrap();
+        assert_eq!(
+            entry.action.summary(),
+            "rename JOURNAL_TEST to JOURNAL_RENAMED (2 changes)"
+        );
+        assert_eq!(manager.get("JOURNAL_TEST").unwrap().value, "value");
+        assert!(manager.get("JOURNAL_RENAMED").is_none());
+    }
+
+    #[test]
+    fn test_journal_encrypts_secret_values() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("history.json");
+        let keys = KeyStore::at(temp.path().join("keys"));
+
+        let mut manager = EnvVarManager {
+            history: History::load(path.clone(), DEFAULT_MAX_ENTRIES).unwrap(),
+            ..EnvVarManager::for_tests().with_key_store(keys.clone())
+        };
+        manager.set("JOURNAL_API_TOKEN", "first-secret-value", false).unwrap();
+        manager.set("JOURNAL_API_TOKEN", "second-secret-value", false).unwrap();
+
+        let journal = std::fs::read_to_string(&path).unwrap();
+        assert!(!journal.contains("secret-value"));
+
+        let mut manager = EnvVarManager {
+            history: History::load(path, DEFAULT_MAX_ENTRIES).unwrap(),
+            ..EnvVarManager::for_tests().with_key_store(keys)
+        };
+        manager.undo().unwrap();
+        assert_eq!(manager.get("JOURNAL_API_TOKEN").unwrap().value, "first-secret-value");
+        manager.redo().unwrap();
+        assert_eq!(manager.get("JOURNAL_API_TOKEN").unwrap().value, "second-secret-value");
+    }
+
+    #[test]
+    fn test_journal_redacts_secrets_without_a_key() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("history.json");
+        let blocker = temp.path().join("blocker");
+        std::fs::write(&blocker, "").unwrap();
+
+        let mut manager = EnvVarManager {
+            history: History::load(path.clone(), DEFAULT_MAX_ENTRIES).unwrap(),
+            ..EnvVarManager::for_tests().with_key_store(KeyStore::at(blocker))
+        };
+        manager.set("JOURNAL_DB_PASSWORD", "plain-secret-value", false).unwrap();
+
+        let journal = std::fs::read_to_string(&path).unwrap();
+        assert!(!journal.contains("plain-secret-value"));
+        assert!(journal.contains(REDACTED_VALUE));
+
+        assert!(manager.undo().is_err());
+        assert_eq!(manager.history.len(), 1);
+        assert_eq!(manager.get("JOURNAL_DB_PASSWORD").unwrap().value, "plain-secret-value");
+    }
+
     #[test]
     fn test_wildcard_to_regex() {
         // Test asterisk wildcard
@@ -1075,7 +1648,7 @@ mod tests {
 
     #[test]
     fn test_load_all() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set some test environment variables
         unsafe { std::env::set_var("TEST_LOAD_VAR1", "value1") };
@@ -1099,7 +1672,7 @@ mod tests {
     #[test]
     #[cfg(unix)]
     fn test_unix_shell_detection() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set a mock shell variable
         unsafe { std::env::set_var("BASH_VERSION", "5.0.0") };
@@ -1114,9 +1687,64 @@ mod tests {
         unsafe { std::env::remove_var("BASH_VERSION") };
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_merge_startup_vars() {
+        use crate::startup_env::StartupVar;
+
+        let mut manager = EnvVarManager::for_tests();
+        manager.vars.insert(
+            "EDITOR".to_string(),
+            create_test_var("EDITOR", "nvim", EnvVarSource::Process),
+        );
+
+        manager.merge_startup_vars(vec![
+            StartupVar {
+                name: "EDITOR".to_string(),
+                value: "vim".to_string(),
+                source: EnvVarSource::User,
+                path: PathBuf::from("/home/user/.profile"),
+                line: 3,
+                origin: Some(VarOrigin::Profile("dev".to_string())),
+            },
+            StartupVar {
+                name: "LANG".to_string(),
+                value: "C.UTF-8".to_string(),
+                source: EnvVarSource::System,
+                path: PathBuf::from("/etc/environment"),
+                line: 1,
+                origin: None,
+            },
+            StartupVar {
+                name: "API_URL".to_string(),
+                value: "http://localhost".to_string(),
+                source: EnvVarSource::User,
+                path: PathBuf::from("/home/user/.profile"),
+                line: 5,
+                origin: Some(VarOrigin::Profile("dev".to_string())),
+            },
+        ]);
+
+        // The process value wins, but the source and origin come from the file.
+        // The origin envx recorded no longer applies to a value changed since
+        let editor = manager.get("EDITOR").unwrap();
+        assert_eq!(editor.value, "nvim");
+        assert_eq!(editor.source, EnvVarSource::User);
+        assert_eq!(editor.origin.as_ref().unwrap().to_string(), "/home/user/.profile:3");
+
+        let lang = manager.get("LANG").unwrap();
+        assert_eq!(lang.value, "C.UTF-8");
+        assert_eq!(lang.source, EnvVarSource::System);
+        assert_eq!(manager.filter_by_source(&EnvVarSource::User).len(), 2);
+
+        // A value persisted by envx keeps the origin it was set from
+        let api_url = manager.get("API_URL").unwrap();
+        assert_eq!(api_url.origin, Some(VarOrigin::Profile("dev".to_string())));
+    }
+
     #[test]
     fn test_special_characters_in_values() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Test with various special characters
         let special_values = vec![
@@ -1139,7 +1767,7 @@ mod tests {
 
     #[test]
     fn test_variable_ordering() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Add variables in specific order
         let vars = vec!["ZETA", "ALPHA", "GAMMA", "BETA"];
@@ -1172,7 +1800,7 @@ mod tests {
 
     #[test]
     fn test_empty_operations() {
-        let manager = EnvVarManager::new();
+        let manager = EnvVarManager::for_tests();
 
         // Test operations on empty manager
         assert_eq!(manager.list().len(), 0);
@@ -1185,7 +1813,7 @@ mod tests {
 
     #[test]
     fn test_case_sensitivity() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Variable names are case-sensitive
         manager.set("test_var", "lower", false).unwrap();
@@ -1202,7 +1830,7 @@ mod tests {
 
     #[test]
     fn test_original_value_tracking() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // First set - no original value
         manager.set("TRACK_VAR", "v1", false).unwrap();
@@ -1219,7 +1847,7 @@ mod tests {
 
     #[test]
     fn test_rename_exact_match() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("OLD_VAR", "value", false).unwrap();
 
         let renamed = manager.rename("OLD_VAR", "NEW_VAR").unwrap();
@@ -1233,7 +1861,7 @@ mod tests {
 
     #[test]
     fn test_rename_wildcard_prefix() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("APP_VAR1", "value1", false).unwrap();
         manager.set("APP_VAR2", "value2", false).unwrap();
         manager.set("OTHER_VAR", "other", false).unwrap();
@@ -1250,7 +1878,7 @@ mod tests {
 
     #[test]
     fn test_rename_target_exists_error() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("VAR1", "value1", false).unwrap();
         manager.set("VAR2", "value2", false).unwrap();
 
@@ -1261,7 +1889,7 @@ mod tests {
 
     #[test]
     fn test_rename_not_found_error() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         let result = manager.rename("NONEXISTENT", "NEW_VAR");
         assert!(result.is_err());
@@ -1270,7 +1898,7 @@ mod tests {
 
     #[test]
     fn test_replace_single_variable() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("MY_VAR", "old_value", false).unwrap();
 
         let replaced = manager.replace("MY_VAR", "new_value").unwrap();
@@ -1285,7 +1913,7 @@ mod tests {
 
     #[test]
     fn test_replace_wildcard_pattern() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("API_KEY", "old_key", false).unwrap();
         manager.set("API_SECRET", "old_secret", false).unwrap();
         manager.set("OTHER_VAR", "other", false).unwrap();
@@ -1300,7 +1928,7 @@ mod tests {
 
     #[test]
     fn test_replace_not_found() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         let result = manager.replace("NONEXISTENT", "value");
         assert!(result.is_err());
@@ -1308,7 +1936,7 @@ mod tests {
 
     #[test]
     fn test_find_replace_in_values() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("DATABASE_URL", "localhost:5432", false).unwrap();
         manager.set("API_URL", "localhost:8080", false).unwrap();
 
@@ -1344,9 +1972,50 @@ mod tests {
         assert!(split_wildcard_pattern("*_*").is_err());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_permanent_changes_go_to_injected_shell_config() {
+        let home = tempfile::TempDir::new().unwrap();
+        let config = ShellConfig::new(home.path().to_path_buf());
+        let mut manager = EnvVarManager::new().with_shell_config(config.clone());
+
+        manager.set("INJECTED_RC_TEST", "value", true).unwrap();
+        assert_eq!(config.managed_vars().unwrap()["INJECTED_RC_TEST"], "value");
+
+        manager.delete("INJECTED_RC_TEST").unwrap();
+        assert!(config.managed_vars().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_undo_permanent_set_restores_persisted_state() {
+        let home = tempfile::TempDir::new().unwrap();
+        let config = ShellConfig::new(home.path().to_path_buf());
+        let mut manager = EnvVarManager::new().with_shell_config(config.clone());
+
+        // A value that only lived in the session stays out of the startup files
+        manager.set("UNDO_PERM_SESSION", "session", false).unwrap();
+        manager.set("UNDO_PERM_SESSION", "saved", true).unwrap();
+        assert_eq!(config.managed_vars().unwrap()["UNDO_PERM_SESSION"], "saved");
+        manager.undo().unwrap();
+        assert_eq!(manager.get("UNDO_PERM_SESSION").unwrap().value, "session");
+        assert_eq!(std::env::var("UNDO_PERM_SESSION").unwrap(), "session");
+        assert!(!config.managed_vars().unwrap().contains_key("UNDO_PERM_SESSION"));
+
+        // A value that was persisted is persisted again
+        manager.set("UNDO_PERM_SAVED", "old", true).unwrap();
+        manager.set("UNDO_PERM_SAVED", "new", true).unwrap();
+        manager.undo().unwrap();
+        assert_eq!(config.managed_vars().unwrap()["UNDO_PERM_SAVED"], "old");
+
+        // Redo persists the new value again
+        manager.redo().unwrap();
+        assert_eq!(config.managed_vars().unwrap()["UNDO_PERM_SAVED"], "new");
+    }
+
     #[test]
     fn test_delete_permanent_variable() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Set a permanent variable (this would normally write to registry/shell config)
         manager.set("DELETE_PERM_TEST", "value", true).unwrap();
@@ -1370,7 +2039,7 @@ mod tests {
 
     #[test]
     fn test_delete_tracks_source() {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
 
         // Add variables with different sources
         manager.vars.insert(
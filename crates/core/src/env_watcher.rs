@@ -1,4 +1,6 @@
-use crate::EnvVarManager;
+use crate::dotenv::{self, DotenvDocument};
+use crate::secret::mask;
+use crate::{EnvVarManager, ImportFormat, Importer, SecretClassifier};
 use ahash::AHashMap as HashMap;
 use color_eyre::Result;
 use notify::{RecommendedWatcher, RecursiveMode};
@@ -38,6 +40,8 @@ pub struct WatchConfig {
     pub log_changes: bool,
     /// Conflict resolution strategy
     pub conflict_strategy: ConflictStrategy,
+    /// Masks secret values in logged changes, `None` logs them in clear
+    pub secrets: Option<SecretClassifier>,
 }
 
 #[derive(Debug, Clone)]
@@ -64,10 +68,10 @@ impl Default for WatchConfig {
                 ".env.*".to_string(),
                 "*.yaml".to_string(),
                 "*.yml".to_string(),
-                "*.toml".to_string(),
             ],
             log_changes: true,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: Some(SecretClassifier::new()),
         }
     }
 }
@@ -261,8 +265,8 @@ impl EnvWatcher {
                         }
                     }
 
-                    // Check if file matches patterns
-                    if !Self::matches_patterns(&path, &config.patterns) {
+                    // Check if file matches patterns, files named explicitly are always watched
+                    if !Self::matches_patterns(&path, &config.patterns) && !Self::is_named_file(&path, &config.paths) {
                         if config.log_changes {
                             println!("⏭️  File doesn't match patterns: {}", path.display());
                         }
@@ -360,8 +364,9 @@ impl EnvWatcher {
 
         let load_result = match extension {
             "env" => Self::load_env_file(path, &mut manager, variable_filter),
-            "yaml" | "yml" => Self::load_yaml_file(path, &mut manager, variable_filter),
-            "json" => Self::load_json_file(path, &mut manager, variable_filter),
+            "yaml" | "yml" => Self::load_imported_file(path, ImportFormat::Yaml, &mut manager, variable_filter),
+            "json" => Self::load_imported_file(path, ImportFormat::Json, &mut manager, variable_filter),
+            "toml" => Self::load_imported_file(path, ImportFormat::Toml, &mut manager, variable_filter),
             _ => {
                 // Try to load as .env format by default
                 Self::load_env_file(path, &mut manager, variable_filter)
@@ -387,28 +392,40 @@ impl EnvWatcher {
 
             if let Some(old_value) = before_vars.get(&var.name) {
                 if old_value != &var.value {
+                    let (old_value, new_value) = match &config.secrets {
+                        Some(secrets)
+                            if secrets.is_secret(&var.name, old_value) || secrets.is_secret(&var.name, &var.value) =>
+                        {
+                            (mask(old_value), mask(&var.value))
+                        }
+                        _ => (old_value.clone(), var.value.clone()),
+                    };
                     Self::log_change(
                         change_log,
                         path.to_path_buf(),
                         ChangeType::VariableModified(var.name.clone()),
-                        format!("Changed {} from '{}' to '{}'", var.name, old_value, var.value),
+                        format!("Changed {} from '{old_value}' to '{new_value}'", var.name),
                     );
 
                     if config.log_changes {
-                        println!("  🔄 {} changed from '{}' to '{}'", var.name, old_value, var.value);
+                        println!("  🔄 {} changed from '{old_value}' to '{new_value}'", var.name);
                     }
                     changes_made = true;
                 }
             } else {
+                let value = config
+                    .secrets
+                    .as_ref()
+                    .map_or_else(|| var.value.clone(), |secrets| secrets.mask(&var.name, &var.value));
                 Self::log_change(
                     change_log,
                     path.to_path_buf(),
                     ChangeType::VariableAdded(var.name.clone()),
-                    format!("Added {} = '{}'", var.name, var.value),
+                    format!("Added {} = '{value}'", var.name),
                 );
 
                 if config.log_changes {
-                    println!("  ➕ {} = '{}'", var.name, var.value);
+                    println!("  ➕ {} = '{value}'", var.name);
                 }
                 changes_made = true;
             }
@@ -439,71 +456,39 @@ impl EnvWatcher {
     }
 
     fn load_env_file(path: &Path, manager: &mut EnvVarManager, variable_filter: Option<&Vec<String>>) -> Result<()> {
-        let content = fs::read_to_string(path)?;
-
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value.trim().trim_matches('"').trim_matches('\'');
-
-                // Apply filter if specified
-                if let Some(filter) = variable_filter {
-                    if !filter.iter().any(|f| key.contains(f)) {
-                        continue;
-                    }
+        for entry in dotenv::parse_file(path)? {
+            // Apply filter if specified
+            if let Some(filter) = variable_filter {
+                if !filter.iter().any(|f| entry.key.contains(f)) {
+                    continue;
                 }
-
-                manager.set(key, value, true)?;
             }
-        }
-
-        Ok(())
-    }
-
-    fn load_yaml_file(path: &Path, manager: &mut EnvVarManager, variable_filter: Option<&Vec<String>>) -> Result<()> {
-        let content = fs::read_to_string(path)?;
-        let yaml: serde_yaml::Value = serde_yaml::from_str(&content)?;
 
-        if let serde_yaml::Value::Mapping(map) = yaml {
-            for (key, value) in map {
-                if let (Some(key_str), Some(value_str)) = (key.as_str(), value.as_str()) {
-                    // Apply filter if specified
-                    if let Some(filter) = variable_filter {
-                        if !filter.iter().any(|f| key_str.contains(f)) {
-                            continue;
-                        }
-                    }
-
-                    manager.set(key_str, value_str, true)?;
-                }
-            }
+            manager.set(&entry.key, &entry.value, true)?;
         }
 
         Ok(())
     }
 
-    fn load_json_file(path: &Path, manager: &mut EnvVarManager, variable_filter: Option<&Vec<String>>) -> Result<()> {
-        let content = fs::read_to_string(path)?;
-        let json: serde_json::Value = serde_json::from_str(&content)?;
-
-        if let serde_json::Value::Object(map) = json {
-            for (key, value) in map {
-                if let serde_json::Value::String(value_str) = value {
-                    // Apply filter if specified
-                    if let Some(filter) = variable_filter {
-                        if !filter.iter().any(|f| key.contains(f)) {
-                            continue;
-                        }
-                    }
+    /// Loads a YAML, JSON or TOML file the same way `import` reads it.
+    fn load_imported_file(
+        path: &Path,
+        format: ImportFormat,
+        manager: &mut EnvVarManager,
+        variable_filter: Option<&Vec<String>>,
+    ) -> Result<()> {
+        let mut importer = Importer::new();
+        importer.import_from_file(&path.to_string_lossy(), format)?;
 
-                    manager.set(&key, &value_str, true)?;
+        for (key, value) in importer.get_variables() {
+            // Apply filter if specified
+            if let Some(filter) = variable_filter {
+                if !filter.iter().any(|f| key.contains(f)) {
+                    continue;
                 }
             }
+
+            manager.set(&key, &value, true)?;
         }
 
         Ok(())
@@ -562,14 +547,19 @@ impl EnvWatcher {
                         }
 
                         if changed {
-                            // Write to output file
-                            let mut content = String::new();
-                            #[allow(clippy::format_push_string)]
-                            for (name, value) in &current_snapshot {
-                                content.push_str(&format!("{name}={value}\n"));
-                            }
-
-                            if let Err(e) = fs::write(output, &content) {
+                            // Update the output file in place, keeping its comments and order
+                            let mut vars: Vec<(String, String)> = current_snapshot
+                                .iter()
+                                .filter(|(name, _)| dotenv::check_key(name))
+                                .map(|(name, value)| (name.clone(), value.clone()))
+                                .collect();
+                            vars.sort();
+                            let result = DotenvDocument::open(output).and_then(|mut document| {
+                                document.sync(&vars)?;
+                                document.save(output)
+                            });
+
+                            if let Err(e) = result {
                                 eprintln!("Failed to write to output file: {e}");
                             } else if config.log_changes {
                                 println!("💾 Updated output file");
@@ -600,6 +590,26 @@ impl EnvWatcher {
         })
     }
 
+    /// Returns true if `path` is one of the files in `paths`, e.g. a TOML
+    /// file the user asked to watch, which is loaded whatever its name.
+    fn is_named_file(path: &Path, paths: &[PathBuf]) -> bool {
+        // The file may be gone after a delete, so compare through its directory
+        let resolve = |path: &Path| {
+            let dir = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+        };
+        let Some(path) = resolve(path) else {
+            return false;
+        };
+        paths
+            .iter()
+            .filter(|named| !named.is_dir())
+            .any(|named| resolve(named).as_ref() == Some(&path))
+    }
+
     fn log_change(change_log: &Arc<Mutex<Vec<ChangeEvent>>>, path: PathBuf, change_type: ChangeType, details: String) {
         let event = ChangeEvent {
             timestamp: chrono::Utc::now(),
@@ -661,7 +671,7 @@ mod tests {
     use tempfile::TempDir;
 
     fn create_test_manager() -> EnvVarManager {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("TEST_VAR", "initial_value", false).unwrap();
         manager.set("ANOTHER_VAR", "another_value", false).unwrap();
         manager
@@ -676,6 +686,7 @@ mod tests {
             patterns: vec!["*.env".to_string(), "*.json".to_string(), "*.yaml".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         }
     }
 
@@ -703,7 +714,8 @@ mod tests {
         assert!(matches!(config.mode, SyncMode::FileToSystem));
         assert!(config.auto_reload);
         assert_eq!(config.debounce_duration, Duration::from_millis(300));
-        assert_eq!(config.patterns.len(), 5);
+        assert_eq!(config.patterns.len(), 4);
+        assert!(!config.patterns.contains(&"*.toml".to_string()));
         assert!(config.log_changes);
         assert!(matches!(config.conflict_strategy, ConflictStrategy::UseLatest));
     }
@@ -804,6 +816,19 @@ mod tests {
         assert!(!EnvWatcher::matches_patterns(&PathBuf::from("README.md"), &patterns));
     }
 
+    #[test]
+    fn test_named_files_are_watched() {
+        let temp_dir = TempDir::new().unwrap();
+        let named = temp_dir.path().join("settings.toml");
+        fs::write(&named, "PORT = 8080\n").unwrap();
+        let paths = vec![named.clone(), temp_dir.path().to_path_buf()];
+
+        assert!(EnvWatcher::is_named_file(&named, &paths));
+        assert!(!EnvWatcher::is_named_file(&temp_dir.path().join("Cargo.toml"), &paths));
+        fs::remove_file(&named).unwrap();
+        assert!(EnvWatcher::is_named_file(&named, &paths));
+    }
+
     #[test]
     fn test_load_env_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -819,7 +844,7 @@ SINGLE_QUOTED='single quoted'
         "#;
         fs::write(&env_file, content).unwrap();
 
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         EnvWatcher::load_env_file(&env_file, &mut manager, None).unwrap();
 
         assert_eq!(manager.get("TEST_VAR").unwrap().value, "test_value");
@@ -841,7 +866,7 @@ API_SECRET=another_secret
         ";
         fs::write(&env_file, content).unwrap();
 
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         let filter = vec!["API".to_string()];
         EnvWatcher::load_env_file(&env_file, &mut manager, Some(&filter)).unwrap();
 
@@ -863,8 +888,8 @@ API_SECRET=another_secret
         }"#;
         fs::write(&json_file, content).unwrap();
 
-        let mut manager = EnvVarManager::new();
-        EnvWatcher::load_json_file(&json_file, &mut manager, None).unwrap();
+        let mut manager = EnvVarManager::for_tests();
+        EnvWatcher::load_imported_file(&json_file, ImportFormat::Json, &mut manager, None).unwrap();
 
         assert_eq!(manager.get("TEST_VAR").unwrap().value, "json_value");
         assert_eq!(manager.get("NUMBER_VAR").unwrap().value, "42");
@@ -880,15 +905,21 @@ API_SECRET=another_secret
 TEST_VAR: yaml_value
 NESTED_VAR: nested_value
 QUOTED: "quoted yaml"
+PORT: 8080
+DB:
+  HOST: localhost
         "#;
         fs::write(&yaml_file, content).unwrap();
 
-        let mut manager = EnvVarManager::new();
-        EnvWatcher::load_yaml_file(&yaml_file, &mut manager, None).unwrap();
+        let mut manager = EnvVarManager::for_tests();
+        EnvWatcher::load_imported_file(&yaml_file, ImportFormat::Yaml, &mut manager, None).unwrap();
 
+        // Read like `import` does, with numbers and nested keys
         assert_eq!(manager.get("TEST_VAR").unwrap().value, "yaml_value");
         assert_eq!(manager.get("NESTED_VAR").unwrap().value, "nested_value");
         assert_eq!(manager.get("QUOTED").unwrap().value, "quoted yaml");
+        assert_eq!(manager.get("PORT").unwrap().value, "8080");
+        assert_eq!(manager.get("DB__HOST").unwrap().value, "localhost");
     }
 
     #[test]
@@ -925,9 +956,10 @@ QUOTED: "quoted yaml"
             patterns: vec!["*.env".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         };
 
-        let manager = EnvVarManager::new();
+        let manager = EnvVarManager::for_tests();
         let mut watcher = EnvWatcher::new(config, manager);
 
         // Start watching
@@ -961,6 +993,7 @@ QUOTED: "quoted yaml"
             patterns: vec!["*.env".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         };
 
         let manager = create_test_manager();
@@ -984,6 +1017,7 @@ QUOTED: "quoted yaml"
             patterns: vec!["*.env".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         };
 
         let manager = create_test_manager();
@@ -1039,9 +1073,10 @@ QUOTED: "quoted yaml"
             patterns: vec!["*.env".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         };
 
-        let manager = EnvVarManager::new();
+        let manager = EnvVarManager::for_tests();
         let manager_arc = Arc::new(Mutex::new(manager));
         let change_log = Arc::new(Mutex::new(Vec::new()));
 
@@ -1072,6 +1107,7 @@ QUOTED: "quoted yaml"
             patterns: vec!["*.env".to_string()],
             log_changes: false,
             conflict_strategy: ConflictStrategy::UseLatest,
+            secrets: None,
         };
 
         let manager = create_test_manager();
@@ -0,0 +1,442 @@
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use indexmap::IndexMap;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ExpandError {
+    #[error("Circular reference: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("{name}: {message}")]
+    Required { name: String, message: String },
+
+    #[error("Unterminated '${{' in \"{0}\"")]
+    Unterminated(String),
+
+    #[error("Invalid reference '${{{0}}}'")]
+    InvalidReference(String),
+}
+
+/// A reference to a variable that has no value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unresolved {
+    /// Name of the missing variable
+    pub name: String,
+    /// Variable whose value contains the reference, if known
+    pub referenced_by: Option<String>,
+}
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.referenced_by {
+            Some(owner) => write!(f, "{} (referenced by {})", self.name, owner),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Expands variable references in values.
+///
+/// Supported syntax:
+/// - `$VAR` and `${VAR}`
+/// - `${VAR:-default}` / `${VAR-default}`: use `default` if `VAR` is unset or
+///   empty / unset
+/// - `${VAR:?message}` / `${VAR?message}`: fail if `VAR` is unset or empty / unset
+/// - `%VAR%` (Windows style); unknown names are left as they are
+/// - `$$` for a literal `$`
+///
+/// Variables added with [`Expander::define`] may reference each other in any
+/// order, references are expanded recursively and cycles are reported as
+/// errors. A variable that references itself (`PATH=$PATH:/opt/bin`) gets the
+/// value from the fallback lookup instead. Values from the fallback lookup are
+/// used as they are.
+///
+/// Unset variables without a default expand to an empty string and are
+/// collected in [`Expander::unresolved`].
+pub struct Expander<'a> {
+    vars: IndexMap<String, String>,
+    literals: HashSet<String>,
+    fallback: Box<dyn Fn(&str) -> Option<String> + 'a>,
+    cache: HashMap<String, String>,
+    stack: Vec<String>,
+    unresolved: Vec<Unresolved>,
+}
+
+impl<'a> Expander<'a> {
+    /// Creates an expander that looks up variables it doesn't define in `fallback`.
+    pub fn new(fallback: impl Fn(&str) -> Option<String> + 'a) -> Self {
+        Self {
+            vars: IndexMap::new(),
+            literals: HashSet::new(),
+            fallback: Box::new(fallback),
+            cache: HashMap::new(),
+            stack: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    /// Creates an expander that falls back to the current process environment.
+    #[must_use]
+    pub fn from_process() -> Expander<'static> {
+        Expander::new(|name| std::env::var(name).ok())
+    }
+
+    /// Defines a variable whose value may contain references.
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), value.to_string());
+        self.literals.remove(name);
+        self.cache.clear();
+    }
+
+    /// Defines a variable whose value is used as is, e.g. a single-quoted
+    /// value in a `.env` file.
+    pub fn define_literal(&mut self, name: &str, value: &str) {
+        self.define(name, value);
+        self.literals.insert(name.to_string());
+    }
+
+    /// Returns the expanded value of a variable, or `None` if it is unset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value has invalid syntax, a required variable is
+    /// missing or the references form a cycle.
+    pub fn get(&mut self, name: &str) -> Result<Option<String>, ExpandError> {
+        if let Some(value) = self.cache.get(name) {
+            return Ok(Some(value.clone()));
+        }
+
+        let Some(raw) = self.vars.get(name).cloned() else {
+            return Ok((self.fallback)(name));
+        };
+
+        if self.literals.contains(name) {
+            return Ok(Some(raw));
+        }
+
+        if let Some(pos) = self.stack.iter().position(|n| n == name) {
+            // A variable extending itself means the value it had before
+            if pos == self.stack.len() - 1 {
+                return Ok((self.fallback)(name));
+            }
+
+            let mut chain = self.stack[pos..].to_vec();
+            chain.push(name.to_string());
+            return Err(ExpandError::Cycle(chain));
+        }
+
+        self.stack.push(name.to_string());
+        let result = self.expand(&raw);
+        self.stack.pop();
+
+        let value = result?;
+        self.cache.insert(name.to_string(), value.clone());
+        Ok(Some(value))
+    }
+
+    /// Expands all references in `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value has invalid syntax, a required variable is
+    /// missing or the references form a cycle.
+    pub fn expand(&mut self, value: &str) -> Result<String, ExpandError> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+
+        while let Some(pos) = rest.find(['$', '%']) {
+            result.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+
+            let consumed = if tail.starts_with('%') {
+                self.expand_percent(tail, &mut result)?
+            } else if tail.starts_with("$$") {
+                result.push('$');
+                2
+            } else if tail.starts_with("${") {
+                self.expand_braced(tail, &mut result)?
+            } else {
+                self.expand_simple(tail, &mut result)?
+            };
+
+            rest = &tail[consumed..];
+        }
+
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Expands every defined variable, in definition order.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error found, see [`Expander::expand`].
+    pub fn expand_all(&mut self) -> Result<IndexMap<String, String>, ExpandError> {
+        let names: Vec<String> = self.vars.keys().cloned().collect();
+        let mut expanded = IndexMap::with_capacity(names.len());
+
+        for name in names {
+            let value = self.get(&name)?.unwrap_or_default();
+            expanded.insert(name, value);
+        }
+
+        Ok(expanded)
+    }
+
+    /// References to unset variables found so far.
+    #[must_use]
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+
+    /// `$NAME`, or a lone `$`. Returns the number of bytes consumed.
+    fn expand_simple(&mut self, tail: &str, result: &mut String) -> Result<usize, ExpandError> {
+        let name_len = name_length(&tail[1..]);
+        if name_len == 0 {
+            result.push('$');
+            return Ok(1);
+        }
+
+        let name = &tail[1..=name_len];
+        match self.get(name)? {
+            Some(value) => result.push_str(&value),
+            None => self.report_unresolved(name),
+        }
+
+        Ok(1 + name_len)
+    }
+
+    /// `${NAME}` with an optional modifier. Returns the number of bytes consumed.
+    fn expand_braced(&mut self, tail: &str, result: &mut String) -> Result<usize, ExpandError> {
+        let end = closing_brace(tail).ok_or_else(|| ExpandError::Unterminated(tail.to_string()))?;
+        let inner = &tail[2..end];
+
+        let name_len = name_length(inner);
+        if name_len == 0 {
+            return Err(ExpandError::InvalidReference(inner.to_string()));
+        }
+        let (name, modifier) = inner.split_at(name_len);
+
+        let value = self.get(name)?;
+        let is_empty = value.as_deref().is_none_or(str::is_empty);
+
+        let expanded = match modifier {
+            "" => {
+                if value.is_none() {
+                    self.report_unresolved(name);
+                }
+                value.unwrap_or_default()
+            }
+            _ if modifier.starts_with(":-") => {
+                if is_empty {
+                    self.expand(&modifier[2..])?
+                } else {
+                    value.unwrap_or_default()
+                }
+            }
+            _ if modifier.starts_with('-') => match value {
+                Some(value) => value,
+                None => self.expand(&modifier[1..])?,
+            },
+            _ if modifier.starts_with(":?") || modifier.starts_with('?') => {
+                let unset = if modifier.starts_with(':') {
+                    is_empty
+                } else {
+                    value.is_none()
+                };
+                if unset {
+                    let message = modifier.trim_start_matches(':')[1..].to_string();
+                    return Err(ExpandError::Required {
+                        name: name.to_string(),
+                        message: if message.is_empty() {
+                            "required variable is not set".to_string()
+                        } else {
+                            self.expand(&message)?
+                        },
+                    });
+                }
+                value.unwrap_or_default()
+            }
+            _ => return Err(ExpandError::InvalidReference(inner.to_string())),
+        };
+
+        result.push_str(&expanded);
+        Ok(end + 1)
+    }
+
+    /// `%NAME%`, or a lone `%`. Returns the number of bytes consumed.
+    fn expand_percent(&mut self, tail: &str, result: &mut String) -> Result<usize, ExpandError> {
+        let candidate = tail[1..].find('%').map(|end| &tail[1..=end]);
+        let Some(name) = candidate.filter(|name| is_windows_name(name)) else {
+            result.push('%');
+            return Ok(1);
+        };
+
+        if let Some(value) = self.get(name)? {
+            result.push_str(&value);
+        } else {
+            // cmd leaves unknown references untouched
+            self.report_unresolved(name);
+            result.push('%');
+            result.push_str(name);
+            result.push('%');
+        }
+
+        Ok(name.len() + 2)
+    }
+
+    fn report_unresolved(&mut self, name: &str) {
+        let unresolved = Unresolved {
+            name: name.to_string(),
+            referenced_by: self.stack.last().cloned(),
+        };
+        if !self.unresolved.contains(&unresolved) {
+            self.unresolved.push(unresolved);
+        }
+    }
+}
+
+/// Length of the variable name at the start of `s`, 0 if there is none.
+fn name_length(s: &str) -> usize {
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len())
+}
+
+fn is_windows_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | ')'))
+}
+
+/// Byte index of the `}` closing the `${` at the start of `s`, allowing nested `${...}`.
+fn closing_brace(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'$' && bytes.get(i + 1) == Some(&b'{') {
+            depth += 1;
+            i += 2;
+            continue;
+        }
+        if bytes[i] == b'}' {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+        i += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "EMPTY" => Some(String::new()),
+            "PATH" => Some("/usr/bin".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_simple_references() {
+        let mut expander = Expander::new(lookup);
+        assert_eq!(expander.expand("$HOME/bin").unwrap(), "/home/me/bin");
+        assert_eq!(expander.expand("${HOME}_x").unwrap(), "/home/me_x");
+        assert_eq!(expander.expand("%HOME%\\bin").unwrap(), "/home/me\\bin");
+        assert_eq!(expander.expand("cost: $$5, 100% $").unwrap(), "cost: $5, 100% $");
+        assert!(expander.unresolved().is_empty());
+    }
+
+    #[test]
+    fn test_defaults_and_required() {
+        let mut expander = Expander::new(lookup);
+        assert_eq!(expander.expand("${MISSING:-fallback}").unwrap(), "fallback");
+        assert_eq!(expander.expand("${EMPTY:-fallback}").unwrap(), "fallback");
+        assert_eq!(expander.expand("${EMPTY-fallback}").unwrap(), "");
+        assert_eq!(expander.expand("${MISSING:-${HOME}/x}").unwrap(), "/home/me/x");
+        assert_eq!(expander.expand("${HOME:?set HOME}").unwrap(), "/home/me");
+
+        assert_eq!(
+            expander.expand("${MISSING:?set MISSING first}").unwrap_err(),
+            ExpandError::Required {
+                name: "MISSING".to_string(),
+                message: "set MISSING first".to_string()
+            }
+        );
+        assert!(expander.expand("${EMPTY?}").is_ok());
+        assert!(expander.expand("${EMPTY:?}").is_err());
+    }
+
+    #[test]
+    fn test_nested_definitions() {
+        let mut expander = Expander::new(lookup);
+        expander.define("URL", "http://${HOST}:${PORT}/api");
+        expander.define("HOST", "localhost");
+        expander.define("PORT", "${PORT_OVERRIDE:-8080}");
+        expander.define_literal("RAW", "$HOST");
+        expander.define("PATH", "$HOME/bin:$PATH");
+
+        let vars = expander.expand_all().unwrap();
+        assert_eq!(vars["URL"], "http://localhost:8080/api");
+        assert_eq!(vars["RAW"], "$HOST");
+        assert_eq!(vars["PATH"], "/home/me/bin:/usr/bin");
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let mut expander = Expander::new(lookup);
+        expander.define("A", "${B}");
+        expander.define("B", "x${C}");
+        expander.define("C", "$A");
+
+        assert_eq!(
+            expander.get("A").unwrap_err(),
+            ExpandError::Cycle(vec!["A".to_string(), "B".to_string(), "C".to_string(), "A".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_unresolved_references() {
+        let mut expander = Expander::new(lookup);
+        expander.define("GREETING", "hello $NAME from %COMPUTERNAME%");
+
+        assert_eq!(expander.get("GREETING").unwrap().unwrap(), "hello  from %COMPUTERNAME%");
+        assert_eq!(
+            expander.unresolved(),
+            &[
+                Unresolved {
+                    name: "NAME".to_string(),
+                    referenced_by: Some("GREETING".to_string())
+                },
+                Unresolved {
+                    name: "COMPUTERNAME".to_string(),
+                    referenced_by: Some("GREETING".to_string())
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let mut expander = Expander::new(lookup);
+        assert!(matches!(expander.expand("${HOME"), Err(ExpandError::Unterminated(_))));
+        assert!(matches!(expander.expand("${}"), Err(ExpandError::InvalidReference(_))));
+        assert!(matches!(
+            expander.expand("${HOME/x}"),
+            Err(ExpandError::InvalidReference(_))
+        ));
+    }
+}
@@ -1,21 +1,35 @@
 use crate::EnvVar;
+use crate::dotenv::{self, DotenvDocument};
 use color_eyre::Result;
 use std::fs;
 use std::path::Path;
 
+/// First line of the header of a `.env` file exported with metadata
+const DOTENV_TITLE: &str = "Environment variables exported by envx";
+/// Starts the comment above each variable of a `.env` file exported with metadata
+const DOTENV_SOURCE: &str = "Source: ";
+
 #[derive(Debug, Clone, Copy)]
 pub enum ExportFormat {
     DotEnv,
     Json,
     Yaml,
+    Toml,
     Text,
     PowerShell,
     Shell,
+    /// A file for `docker run --env-file`, which takes values literally
+    DockerEnv,
+    /// An `environment:` section to paste into a Compose service
+    Compose,
 }
 
 impl ExportFormat {
     /// Determines the export format from a file path's extension.
     ///
+    /// The Docker formats share extensions with `.env` and YAML files, so
+    /// they are never picked here.
+    ///
     /// # Errors
     ///
     /// Currently this function never returns an error and always succeeds,
@@ -27,6 +41,7 @@ impl ExportFormat {
             "env" => Ok(Self::DotEnv),
             "json" => Ok(Self::Json),
             "yaml" | "yml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
             "txt" | "text" => Ok(Self::Text),
             "ps1" => Ok(Self::PowerShell),
             "sh" | "bash" => Ok(Self::Shell),
@@ -65,46 +80,76 @@ impl Exporter {
 
     /// Exports environment variables to a file in the specified format.
     ///
+    /// An existing `.env` file is updated in place: its comments and the order
+    /// of its variables are kept, variables that aren't exported are removed
+    /// and new ones are added at the end. With metadata, its header and the
+    /// comments above the variables are refreshed. Either way, variables whose
+    /// names can't be used in a `.env` file are skipped with a warning.
+    ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - The file cannot be created or written to due to filesystem permissions or disk space issues
     /// - JSON serialization fails when using JSON format
-    /// - YAML formatting fails when using YAML format
+    /// - YAML formatting fails when using YAML or Compose format
+    /// - An existing `.env` file has invalid lines
     pub fn export_to_file(&self, path: &str, format: ExportFormat) -> Result<()> {
+        if matches!(format, ExportFormat::DotEnv) && fs::metadata(path).is_ok_and(|m| m.len() > 0) {
+            return self.update_dotenv(Path::new(path));
+        }
+
         let content = match format {
             ExportFormat::DotEnv => self.to_dotenv(),
             ExportFormat::Json => self.to_json()?,
             ExportFormat::Yaml => self.to_yaml(),
+            ExportFormat::Toml => self.to_toml(),
             ExportFormat::Text => self.to_text(),
             ExportFormat::PowerShell => self.to_powershell(),
             ExportFormat::Shell => self.to_shell(),
+            ExportFormat::DockerEnv => self.to_docker_env(),
+            ExportFormat::Compose => self.to_compose()?,
         };
 
         fs::write(path, content)?;
         Ok(())
     }
 
+    fn update_dotenv(&self, path: &Path) -> Result<()> {
+        let mut document = DotenvDocument::open(path)?;
+        let vars = self.dotenv_variables();
+        let values: Vec<(String, String)> = vars.iter().map(|var| (var.name.clone(), var.value.clone())).collect();
+        document.sync(&values)?;
+
+        if self.include_metadata {
+            document.set_header(&dotenv_header(vars.len()));
+            for var in &vars {
+                document.set_comment(&var.name, DOTENV_SOURCE, &source_comment(var));
+            }
+        }
+
+        document.save(path)
+    }
+
+    /// The variables that can be written to a `.env` file
+    fn dotenv_variables(&self) -> Vec<&EnvVar> {
+        self.variables
+            .iter()
+            .filter(|var| dotenv::check_key(&var.name))
+            .collect()
+    }
+
     fn to_dotenv(&self) -> String {
         let mut lines = Vec::new();
+        let vars = self.dotenv_variables();
 
         if self.include_metadata {
-            lines.push("# Environment variables exported by envx".to_string());
-            lines.push(format!(
-                "# Date: {}",
-                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-            ));
-            lines.push(format!("# Count: {}", self.variables.len()));
+            lines.extend(dotenv_header(vars.len()).iter().map(|line| format!("# {line}")));
             lines.push(String::new());
         }
 
-        for var in &self.variables {
+        for var in vars {
             if self.include_metadata {
-                lines.push(format!(
-                    "# Source: {:?}, Modified: {}",
-                    var.source,
-                    var.modified.format("%Y-%m-%d %H:%M:%S")
-                ));
+                lines.push(format!("# {}", source_comment(var)));
             }
 
             // For .env format, we need to handle escaping more carefully
@@ -215,6 +260,40 @@ impl Exporter {
         lines.join("\n")
     }
 
+    fn to_toml(&self) -> String {
+        let mut lines = Vec::new();
+
+        if self.include_metadata {
+            lines.push("# Environment variables exported by envx".to_string());
+            lines.push(format!(
+                "# Date: {}",
+                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+            lines.push(String::new());
+        }
+
+        for var in &self.variables {
+            if self.include_metadata {
+                lines.push(format!("# Source: {:?}", var.source));
+            }
+
+            // Names that aren't bare keys, like `ProgramFiles(x86)`, are quoted
+            let bare = !var.name.is_empty()
+                && var
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            let key = if bare {
+                var.name.clone()
+            } else {
+                toml::Value::String(var.name.clone()).to_string()
+            };
+            lines.push(format!("{key} = {}", toml::Value::String(var.value.clone())));
+        }
+
+        lines.join("\n")
+    }
+
     fn to_text(&self) -> String {
         let mut lines = Vec::new();
 
@@ -253,9 +332,7 @@ impl Exporter {
                 lines.push(format!("# {} ({:?})", var.name, var.source));
             }
 
-            // Escape PowerShell special characters
-            let escaped_value = var.value.replace('`', "``").replace('"', "`\"");
-            lines.push(format!("$env:{} = \"{}\"", var.name, escaped_value));
+            lines.push(format!("$env:{} = \"{}\"", var.name, escape_powershell(&var.value)));
         }
 
         lines.join("\n")
@@ -274,22 +351,90 @@ impl Exporter {
                 lines.push(format!("# {} ({:?})", var.name, var.source));
             }
 
-            // Escape shell special characters
-            let escaped_value = var
-                .value
-                .replace('\\', "\\\\")
-                .replace('"', "\\\"")
-                .replace('$', "\\$")
-                .replace('`', "\\`");
+            lines.push(format!("export {}=\"{}\"", var.name, escape_shell(&var.value)));
+        }
 
-            lines.push(format!("export {}=\"{}\"", var.name, escaped_value));
+        lines.join("\n")
+    }
+
+    fn to_docker_env(&self) -> String {
+        let mut lines = Vec::new();
+
+        if self.include_metadata {
+            lines.push("# Environment variables exported by envx for docker --env-file".to_string());
+            lines.push(format!(
+                "# Date: {}",
+                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+            lines.push(String::new());
+        }
+
+        for var in &self.variables {
+            // Docker reads everything after `=` as the value, without unquoting
+            // or unescaping it, so a value can't span lines
+            if var.value.contains(['\n', '\r']) {
+                lines.push(format!("# {} skipped: multi-line values aren't supported", var.name));
+            } else {
+                lines.push(format!("{}={}", var.name, var.value));
+            }
         }
 
         lines.join("\n")
     }
+
+    fn to_compose(&self) -> Result<String> {
+        // Compose interpolates `$` in values, `$$` is a literal dollar sign
+        let environment: serde_yaml::Mapping = self
+            .variables
+            .iter()
+            .map(|var| (var.name.clone().into(), var.value.replace('$', "$$").into()))
+            .collect();
+        let mut fragment = serde_yaml::Mapping::new();
+        fragment.insert("environment".into(), environment.into());
+        let yaml = serde_yaml::to_string(&fragment)?;
+
+        if self.include_metadata {
+            Ok(format!(
+                "# Environment variables exported by envx\n# Date: {}\n{yaml}",
+                chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+            ))
+        } else {
+            Ok(yaml)
+        }
+    }
 }
 
-// ...existing code...
+/// Escapes a value for use inside double quotes in POSIX shells.
+pub(crate) fn escape_shell(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+}
+
+/// Escapes a value for use inside double quotes in PowerShell.
+pub(crate) fn escape_powershell(value: &str) -> String {
+    value.replace('`', "``").replace('"', "`\"")
+}
+
+/// Lines of the header of a `.env` file exported with metadata, without `#`
+fn dotenv_header(count: usize) -> Vec<String> {
+    vec![
+        DOTENV_TITLE.to_string(),
+        format!("Date: {}", chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")),
+        format!("Count: {count}"),
+    ]
+}
+
+/// The comment above a variable of a `.env` file exported with metadata, without `#`
+fn source_comment(var: &EnvVar) -> String {
+    format!(
+        "{DOTENV_SOURCE}{:?}, Modified: {}",
+        var.source,
+        var.modified.format("%Y-%m-%d %H:%M:%S")
+    )
+}
 
 #[cfg(test)]
 mod tests {
@@ -297,6 +442,7 @@ mod tests {
     use super::*;
     use crate::EnvVar;
     use crate::EnvVarSource as VarSource;
+    use crate::{ImportFormat, Importer};
     use chrono::{DateTime, Utc};
     use std::fs;
     use tempfile::NamedTempFile;
@@ -310,6 +456,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "PATH_VAR".to_string(),
@@ -317,6 +464,7 @@ mod tests {
                 source: VarSource::System,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "QUOTED_VAR".to_string(),
@@ -324,6 +472,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "SPECIAL_CHARS".to_string(),
@@ -331,6 +480,7 @@ mod tests {
                 source: VarSource::Process,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "EMPTY_VAR".to_string(),
@@ -338,6 +488,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "UNICODE_VAR".to_string(),
@@ -345,6 +496,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ]
     }
@@ -359,6 +511,10 @@ mod tests {
             ExportFormat::from_extension("file.ENV").unwrap(),
             ExportFormat::DotEnv
         ));
+        assert!(matches!(
+            ExportFormat::from_extension("config.toml").unwrap(),
+            ExportFormat::Toml
+        ));
         assert!(matches!(
             ExportFormat::from_extension("file.json").unwrap(),
             ExportFormat::Json
@@ -479,6 +635,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "EQUALS_VALUE".to_string(),
@@ -486,6 +643,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "SPACES_AROUND".to_string(),
@@ -493,6 +651,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ];
 
@@ -592,6 +751,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "COMMENT".to_string(),
@@ -599,6 +759,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "LEADING_SPACE".to_string(),
@@ -606,6 +767,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "TRAILING_SPACE".to_string(),
@@ -613,6 +775,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ];
 
@@ -626,6 +789,58 @@ mod tests {
         assert!(output.contains("TRAILING_SPACE: \"value  \""));
     }
 
+    #[test]
+    fn test_to_docker_env() {
+        let mut vars = create_test_vars();
+        vars[0].value = "has \"quotes\" and $DOLLAR".to_string();
+        vars[1].value = "line1\nline2".to_string();
+        let exporter = Exporter::new(vars.clone(), false);
+
+        let output = exporter.to_docker_env();
+        let lines: Vec<&str> = output.lines().collect();
+        // Docker takes values literally, so nothing is quoted or escaped
+        assert_eq!(lines[0], format!("{}=has \"quotes\" and $DOLLAR", vars[0].name));
+        assert!(lines[1].starts_with(&format!("# {} skipped", vars[1].name)));
+        assert_eq!(lines.len(), vars.len());
+    }
+
+    #[test]
+    fn test_to_compose_escapes_interpolation() {
+        let mut vars = create_test_vars();
+        vars[0].value = "cost $5".to_string();
+        vars[1].value = "123".to_string();
+        let exporter = Exporter::new(vars.clone(), true);
+
+        let output = exporter.to_compose().unwrap();
+        assert!(output.starts_with("# Environment variables exported by envx"));
+
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let environment = &parsed["environment"];
+        assert_eq!(environment[vars[0].name.as_str()], "cost $$5");
+        // Numbers stay strings
+        assert_eq!(environment[vars[1].name.as_str()], "123");
+    }
+
+    #[test]
+    fn test_to_toml_roundtrip() {
+        let temp_file = NamedTempFile::with_suffix(".toml").unwrap();
+        let path = temp_file.path().to_str().unwrap();
+        let mut vars = create_test_vars();
+        vars[0].name = "ProgramFiles(x86)".to_string();
+
+        Exporter::new(vars.clone(), true)
+            .export_to_file(path, ExportFormat::Toml)
+            .unwrap();
+
+        let mut importer = Importer::new();
+        importer.import_from_file(path, ImportFormat::Toml).unwrap();
+        let imported: std::collections::HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(imported.len(), vars.len());
+        for var in &vars {
+            assert_eq!(imported[&var.name], var.value);
+        }
+    }
+
     #[test]
     fn test_to_text() {
         let vars = create_test_vars();
@@ -679,6 +894,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "DOLLAR".to_string(),
@@ -686,6 +902,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ];
 
@@ -728,6 +945,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "BACKTICK".to_string(),
@@ -735,6 +953,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "BACKSLASH".to_string(),
@@ -742,6 +961,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ];
 
@@ -764,6 +984,7 @@ mod tests {
             (ExportFormat::DotEnv, ".env"),
             (ExportFormat::Json, ".json"),
             (ExportFormat::Yaml, ".yaml"),
+            (ExportFormat::Toml, ".toml"),
             (ExportFormat::Text, ".txt"),
             (ExportFormat::PowerShell, ".ps1"),
             (ExportFormat::Shell, ".sh"),
@@ -782,6 +1003,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_export_dotenv_updates_existing_file() {
+        let temp_file = NamedTempFile::with_suffix(".env").unwrap();
+        let path = temp_file.path().to_str().unwrap();
+        fs::write(path, "# Keep me\nSIMPLE_VAR=old # note\nREMOVED=1\n").unwrap();
+
+        Exporter::new(create_test_vars()[..1].to_vec(), false)
+            .export_to_file(path, ExportFormat::DotEnv)
+            .unwrap();
+
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(content, "# Keep me\nSIMPLE_VAR=simple_value # note\n");
+    }
+
+    #[test]
+    fn test_export_dotenv_same_rules_for_new_and_existing_files() {
+        let mut vars = create_test_vars()[..2].to_vec();
+        let mut dashed = vars[0].clone();
+        dashed.name = "MY-VAR".to_string();
+        vars.push(dashed);
+        let mut invalid = vars[0].clone();
+        invalid.name = "MY VAR".to_string();
+        vars.push(invalid);
+        let exporter = Exporter::new(vars, true);
+
+        let new_file = NamedTempFile::with_suffix(".env").unwrap();
+        let new_path = new_file.path().to_str().unwrap();
+        exporter.export_to_file(new_path, ExportFormat::DotEnv).unwrap();
+
+        let existing_file = NamedTempFile::with_suffix(".env").unwrap();
+        let existing_path = existing_file.path().to_str().unwrap();
+        fs::write(existing_path, "# Keep me\nSIMPLE_VAR=old\n").unwrap();
+        exporter.export_to_file(existing_path, ExportFormat::DotEnv).unwrap();
+
+        for path in [new_path, existing_path] {
+            let content = fs::read_to_string(path).unwrap();
+            assert!(content.starts_with(&format!("# {DOTENV_TITLE}\n")), "{content}");
+            assert!(content.contains("# Count: 3\n"), "{content}");
+            assert_eq!(content.matches("# Source: ").count(), 3, "{content}");
+            assert!(content.contains("\nMY-VAR=simple_value"), "{content}");
+            assert!(!content.contains("MY VAR"), "{content}");
+        }
+
+        // Exporting again refreshes the metadata instead of adding more
+        exporter.export_to_file(existing_path, ExportFormat::DotEnv).unwrap();
+        let content = fs::read_to_string(existing_path).unwrap();
+        assert_eq!(content.matches(DOTENV_TITLE).count(), 1, "{content}");
+        assert_eq!(content.matches("# Source: ").count(), 3, "{content}");
+        assert!(content.contains("# Keep me\n"), "{content}");
+        assert!(content.contains("SIMPLE_VAR=simple_value\n"), "{content}");
+    }
+
     #[test]
     fn test_empty_export() {
         let exporter = Exporter::new(vec![], true);
@@ -818,6 +1091,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "NAME.WITH.DOTS".to_string(),
@@ -825,6 +1099,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "_UNDERSCORE_START".to_string(),
@@ -832,6 +1107,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
             EnvVar {
                 name: "123_NUMBER_START".to_string(),
@@ -839,6 +1115,7 @@ mod tests {
                 source: VarSource::User,
                 modified: Utc::now(),
                 original_value: None,
+                origin: None,
             },
         ];
 
@@ -871,6 +1148,7 @@ mod tests {
             source: VarSource::User,
             modified: Utc::now(),
             original_value: None,
+            origin: None,
         }];
 
         let exporter = Exporter::new(vars, false);
@@ -896,6 +1174,7 @@ mod tests {
             source: VarSource::System,
             modified: fixed_time,
             original_value: None,
+            origin: None,
         }];
 
         let exporter = Exporter::new(vars, true);
@@ -1,5 +1,16 @@
 use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of entries the journal keeps by default
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// Stored in the journal instead of a secret value that could not be
+/// encrypted. Changes containing it can't be undone or redone.
+pub const REDACTED_VALUE: &str = "envx:redacted";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HistoryAction {
@@ -7,14 +18,106 @@ pub enum HistoryAction {
         name: String,
         old_value: Option<String>,
         new_value: String,
+        /// Whether the change was persisted beyond the envx process
+        #[serde(default)]
+        permanent: bool,
+        /// Whether the old value was persisted before a permanent change, so
+        /// undo can restore exactly that. Entries recorded before this was
+        /// tracked assume it was.
+        #[serde(default = "persisted_before")]
+        was_permanent: bool,
     },
     Delete {
         name: String,
         old_value: String,
+        /// Whether the variable was persisted beyond the envx process
+        #[serde(default)]
+        permanent: bool,
     },
     BatchUpdate {
         changes: Vec<(String, Option<String>, String)>,
     },
+    /// Several changes made by one command, undone and redone together
+    Batch {
+        description: String,
+        actions: Vec<HistoryAction>,
+    },
+}
+
+const fn persisted_before() -> bool {
+    true
+}
+
+impl HistoryAction {
+    /// Returns a one-line description of the change.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Set { name, old_value, .. } => {
+                if old_value.is_some() {
+                    format!("update {name}")
+                } else {
+                    format!("set {name}")
+                }
+            }
+            Self::Delete { name, .. } => format!("delete {name}"),
+            Self::BatchUpdate { changes } => format!("batch update ({} changes)", changes.len()),
+            Self::Batch { description, actions } => {
+                let count = actions.len();
+                format!("{description} ({count} change{})", if count == 1 { "" } else { "s" })
+            }
+        }
+    }
+
+    /// Replaces every old and new value of the change with the result of
+    /// `f`, which is called with the variable name and the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error `f` returns.
+    pub fn try_map_values(self, f: &mut impl FnMut(&str, String) -> Result<String>) -> Result<Self> {
+        Ok(match self {
+            Self::Set {
+                name,
+                old_value,
+                new_value,
+                permanent,
+                was_permanent,
+            } => Self::Set {
+                old_value: old_value.map(|value| f(&name, value)).transpose()?,
+                new_value: f(&name, new_value)?,
+                name,
+                permanent,
+                was_permanent,
+            },
+            Self::Delete {
+                name,
+                old_value,
+                permanent,
+            } => Self::Delete {
+                old_value: f(&name, old_value)?,
+                name,
+                permanent,
+            },
+            Self::BatchUpdate { changes } => Self::BatchUpdate {
+                changes: changes
+                    .into_iter()
+                    .map(|(name, old_value, new_value)| {
+                        let old_value = old_value.map(|value| f(&name, value)).transpose()?;
+                        let new_value = f(&name, new_value)?;
+                        Ok((name, old_value, new_value))
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            Self::Batch { description, actions } => Self::Batch {
+                description,
+                actions: actions
+                    .into_iter()
+                    .map(|action| action.try_map_values(f))
+                    .collect::<Result<_>>()?,
+            },
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,34 +138,260 @@ impl HistoryEntry {
     }
 }
 
-#[derive(Debug, Default)]
-pub struct History {
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
     entries: Vec<HistoryEntry>,
+    #[serde(default)]
+    undone: Vec<HistoryEntry>,
+}
+
+/// Undo/redo journal of environment changes.
+///
+/// A history loaded with [`History::load`] is written back to its file by
+/// [`History::save`], so undo and redo work across envx runs.
+#[derive(Debug)]
+pub struct History {
+    journal: Journal,
     max_entries: usize,
+    path: Option<PathBuf>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ENTRIES)
+    }
 }
 
 impl History {
+    /// Creates an in-memory history.
     #[must_use]
     pub const fn new(max_entries: usize) -> Self {
         Self {
-            entries: Vec::new(),
+            journal: Journal {
+                entries: Vec::new(),
+                undone: Vec::new(),
+            },
             max_entries,
+            path: None,
         }
     }
 
+    /// Returns the location of the current user's journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data/config directory cannot be found.
+    pub fn default_path() -> Result<PathBuf> {
+        let config_dir = if cfg!(windows) {
+            dirs::data_dir()
+                .ok_or_else(|| eyre!("Could not find data directory"))?
+                .join("envx")
+        } else {
+            dirs::config_dir()
+                .ok_or_else(|| eyre!("Could not find config directory"))?
+                .join("envx")
+        };
+
+        Ok(config_dir.join("history.json"))
+    }
+
+    /// Loads the journal stored at `path`, starting empty if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file exists but cannot be read or parsed.
+    pub fn load(path: PathBuf, max_entries: usize) -> Result<Self> {
+        let journal: Journal = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Journal::default()
+        };
+
+        let mut history = Self {
+            journal,
+            max_entries,
+            path: Some(path),
+        };
+        history.trim();
+        Ok(history)
+    }
+
+    /// Returns the file the journal is saved to, if any.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Records a new change. Anything that was undone can no longer be redone.
     pub fn add(&mut self, entry: HistoryEntry) {
-        self.entries.push(entry);
-        if self.entries.len() > self.max_entries {
-            self.entries.remove(0);
-        }
+        self.journal.entries.push(entry);
+        self.journal.undone.clear();
+        self.trim();
+    }
+
+    /// Removes the most recent change so it can be undone.
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        self.journal.entries.pop()
+    }
+
+    /// Remembers an undone change so it can be redone.
+    pub fn push_undone(&mut self, entry: HistoryEntry) {
+        self.journal.undone.push(entry);
+        self.trim();
+    }
+
+    /// Removes the most recently undone change so it can be redone.
+    pub fn pop_undone(&mut self) -> Option<HistoryEntry> {
+        self.journal.undone.pop()
+    }
+
+    /// Records a redone change without forgetting the other undone changes.
+    pub fn push_redone(&mut self, entry: HistoryEntry) {
+        self.journal.entries.push(entry);
+        self.trim();
     }
 
     #[must_use]
     pub fn recent(&self, count: usize) -> Vec<&HistoryEntry> {
-        self.entries.iter().rev().take(count).collect()
+        self.journal.entries.iter().rev().take(count).collect()
+    }
+
+    /// Returns the changes that can be redone, most recently undone first.
+    #[must_use]
+    pub fn undone(&self) -> Vec<&HistoryEntry> {
+        self.journal.undone.iter().rev().collect()
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.journal.entries
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.journal.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.journal.entries.is_empty()
     }
 
     pub fn clear(&mut self) {
-        self.entries.clear();
+        self.journal.entries.clear();
+        self.journal.undone.clear();
+    }
+
+    /// Writes the journal to its file. Does nothing for an in-memory history.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.journal)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn trim(&mut self) {
+        if self.journal.entries.len() > self.max_entries {
+            let excess = self.journal.entries.len() - self.max_entries;
+            self.journal.entries.drain(..excess);
+        }
+        // The changes undone first are redone last
+        if self.journal.undone.len() > self.max_entries {
+            let excess = self.journal.undone.len() - self.max_entries;
+            self.journal.undone.drain(..excess);
+        }
+    }
+}
+
+impl std::ops::Index<usize> for History {
+    type Output = HistoryEntry;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.journal.entries[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn set(name: &str) -> HistoryEntry {
+        HistoryEntry::new(HistoryAction::Set {
+            name: name.to_string(),
+            old_value: None,
+            new_value: "1".to_string(),
+            permanent: false,
+            was_permanent: false,
+        })
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut history = History::new(2);
+        history.add(set("A"));
+        history.add(set("B"));
+        history.add(set("C"));
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.recent(1)[0].action.summary(), "set C");
+        assert_eq!(history[0].action.summary(), "set B");
+    }
+
+    #[test]
+    fn test_max_undone_entries() {
+        let mut history = History::new(2);
+        for name in ["A", "B", "C"] {
+            history.push_undone(set(name));
+        }
+
+        // The change undone first would be redone last, so it goes first
+        let undone: Vec<_> = history.undone().iter().map(|entry| entry.action.summary()).collect();
+        assert_eq!(undone, ["set C", "set B"]);
+    }
+
+    #[test]
+    fn test_new_change_clears_redo() {
+        let mut history = History::default();
+        history.add(set("A"));
+        let entry = history.pop().unwrap();
+        history.push_undone(entry);
+        assert_eq!(history.undone().len(), 1);
+
+        history.add(set("B"));
+        assert!(history.undone().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("envx").join("history.json");
+
+        let mut history = History::load(path.clone(), DEFAULT_MAX_ENTRIES).unwrap();
+        assert!(history.is_empty());
+        history.add(set("A"));
+        history.add(HistoryEntry::new(HistoryAction::Batch {
+            description: "rename OLD to NEW".to_string(),
+            actions: vec![set("NEW").action],
+        }));
+        let undone = history.pop().unwrap();
+        history.push_undone(undone);
+        history.save().unwrap();
+
+        let loaded = History::load(path, DEFAULT_MAX_ENTRIES).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.undone()[0].action.summary(), "rename OLD to NEW (1 change)");
     }
 }
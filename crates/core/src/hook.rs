@@ -0,0 +1,427 @@
+use crate::ProjectConfig;
+use crate::profile_manager::PROJECT_PROFILES_DIR;
+use crate::shell_export::EnvChange;
+use ahash::AHashMap as HashMap;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the shell variable the hook keeps its state in
+pub const HOOK_STATE_VAR: &str = "ENVX_HOOK_STATE";
+
+/// What the shell hook loaded into the shell the last time it ran.
+///
+/// The state lives in the shell itself (see [`HOOK_STATE_VAR`]), so every
+/// shell session tracks its own project independently.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookState {
+    /// Root of the project whose environment is loaded
+    pub root: Option<PathBuf>,
+    /// Values the variables had before the project was loaded, `None` if unset
+    pub saved: IndexMap<String, Option<String>>,
+}
+
+impl HookState {
+    /// Reads the state from the current process environment.
+    ///
+    /// A missing or unreadable state is treated as "nothing loaded".
+    #[must_use]
+    pub fn from_env() -> Self {
+        std::env::var(HOOK_STATE_VAR)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns `current` with every variable the hook changed put back the way
+    /// it was before the project was loaded.
+    #[must_use]
+    pub fn base_env(&self, current: &IndexMap<String, String>) -> IndexMap<String, String> {
+        let mut base = current.clone();
+        base.shift_remove(HOOK_STATE_VAR);
+
+        for (name, saved) in &self.saved {
+            match saved {
+                Some(value) => {
+                    base.insert(name.clone(), value.clone());
+                }
+                None => {
+                    base.shift_remove(name);
+                }
+            }
+        }
+
+        base
+    }
+
+    /// Computes the changes that take the shell from `current` to the
+    /// environment of `project`, or back to the base environment when there
+    /// is no project.
+    ///
+    /// `project` holds the project root and the variables the project sets on
+    /// top of the base environment. Returns the changes, including the update
+    /// of [`HOOK_STATE_VAR`] itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new state cannot be serialized.
+    pub fn transition(
+        &self,
+        current: &IndexMap<String, String>,
+        project: Option<(PathBuf, IndexMap<String, String>)>,
+    ) -> Result<Vec<EnvChange>> {
+        let base = self.base_env(current);
+        let mut desired = base.clone();
+        let mut next = Self::default();
+
+        if let Some((root, vars)) = project {
+            for (name, value) in vars {
+                next.saved.insert(name.clone(), base.get(&name).cloned());
+                desired.insert(name, value);
+            }
+            next.root = Some(root);
+        }
+
+        let mut changes = Vec::new();
+        let names: IndexSet<&String> = self.saved.keys().chain(next.saved.keys()).collect();
+        for name in names {
+            match (desired.get(name), current.get(name)) {
+                (Some(value), current_value) if current_value != Some(value) => changes.push(EnvChange::Set {
+                    name: name.clone(),
+                    value: value.clone(),
+                }),
+                (None, Some(_)) => changes.push(EnvChange::Unset { name: name.clone() }),
+                _ => {}
+            }
+        }
+
+        let state_json = if next.root.is_some() {
+            Some(serde_json::to_string(&next)?)
+        } else {
+            None
+        };
+        match (state_json, current.get(HOOK_STATE_VAR)) {
+            (Some(json), old) if old != Some(&json) => changes.push(EnvChange::Set {
+                name: HOOK_STATE_VAR.to_string(),
+                value: json,
+            }),
+            (None, Some(_)) => changes.push(EnvChange::Unset {
+                name: HOOK_STATE_VAR.to_string(),
+            }),
+            _ => {}
+        }
+
+        Ok(changes)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AllowListFile {
+    /// Project root → SHA-256 of the configuration that was allowed
+    dirs: HashMap<PathBuf, String>,
+}
+
+/// Project directories the user trusts to change their shell environment.
+///
+/// An entry records the checksum of the project configuration, the
+/// auto-load files it lists and the project profiles at the time it was
+/// allowed, so editing any of them means the project has to be allowed again.
+pub struct AllowList {
+    path: PathBuf,
+    file: AllowListFile,
+}
+
+impl AllowList {
+    /// Loads the allow-list of the current user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The data/config directory cannot be found
+    /// - The existing allow-list file cannot be read or parsed
+    pub fn new() -> Result<Self> {
+        let config_dir = if cfg!(windows) {
+            dirs::data_dir()
+                .ok_or_else(|| eyre!("Could not find data directory"))?
+                .join("envx")
+        } else {
+            dirs::config_dir()
+                .ok_or_else(|| eyre!("Could not find config directory"))?
+                .join("envx")
+        };
+
+        Self::load(config_dir.join("allowed.json"))
+    }
+
+    /// Loads an allow-list from a specific file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file exists but cannot be read or parsed.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let file = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            AllowListFile::default()
+        };
+
+        Ok(Self { path, file })
+    }
+
+    /// Returns true if `root` is allowed with its current configuration.
+    #[must_use]
+    pub fn is_allowed(&self, root: &Path, config_path: &Path) -> bool {
+        match (self.file.dirs.get(root), checksum(root, config_path)) {
+            (Some(allowed), Ok(current)) => *allowed == current,
+            _ => false,
+        }
+    }
+
+    /// Returns true if `root` was allowed before, even if its configuration changed since.
+    #[must_use]
+    pub fn contains(&self, root: &Path) -> bool {
+        self.file.dirs.contains_key(root)
+    }
+
+    /// Trusts `root` with the current contents of `config_path`, its
+    /// auto-load files and the project profiles.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the configuration cannot be read
+    /// or the allow-list cannot be saved.
+    pub fn allow(&mut self, root: &Path, config_path: &Path) -> Result<()> {
+        let sum = checksum(root, config_path)?;
+        self.file.dirs.insert(root.to_path_buf(), sum);
+        self.save()
+    }
+
+    /// Removes `root` from the allow-list.
+    ///
+    /// Returns true if it was allowed before.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the allow-list cannot be saved.
+    pub fn deny(&mut self, root: &Path) -> Result<bool> {
+        let removed = self.file.dirs.remove(root).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.file)?)?;
+        Ok(())
+    }
+}
+
+/// Hashes the project configuration together with the auto-load files it
+/// lists and every file in the project profiles directory next to it. Each
+/// file is hashed with its name and length, a missing one as missing, so
+/// moving content between files changes the checksum too.
+fn checksum(root: &Path, config_path: &Path) -> Result<String> {
+    let content = fs::read(config_path)?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+
+    // A configuration that can't be parsed loads nothing, so it's enough to hash it
+    let auto_load = ProjectConfig::load(config_path)
+        .map(|config| config.auto_load)
+        .unwrap_or_default();
+    for file in auto_load {
+        hash_file(&mut hasher, &file, &root.join(&file));
+    }
+
+    // The profiles the configuration names can come from here, and a project
+    // profile shadows a global one of the same name
+    let profiles_dir = config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(PROJECT_PROFILES_DIR);
+    let mut profiles: Vec<_> = fs::read_dir(&profiles_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    hasher.update(b"profiles");
+    for path in profiles {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        hash_file(&mut hasher, &name, &path);
+    }
+
+    let digest = hasher.finalize();
+    Ok(digest.iter().fold(String::with_capacity(64), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    }))
+}
+
+fn hash_file(hasher: &mut Sha256, name: &str, path: &Path) {
+    hasher.update(name.as_bytes());
+    hasher.update([0]);
+    match fs::read(path) {
+        Ok(content) => {
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(&content);
+        }
+        Err(_) => hasher.update(b"missing"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn env(pairs: &[(&str, &str)]) -> IndexMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    fn apply(current: &mut IndexMap<String, String>, changes: &[EnvChange]) {
+        for change in changes {
+            match change {
+                EnvChange::Set { name, value } => {
+                    current.insert(name.clone(), value.clone());
+                }
+                EnvChange::Unset { name } => {
+                    current.shift_remove(name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_enter_and_leave_project() {
+        let mut current = env(&[("HOME", "/home/me"), ("PORT", "80")]);
+        let project = env(&[("PORT", "3000"), ("API_URL", "http://localhost")]);
+
+        // Entering the project exports its variables
+        let changes = HookState::default()
+            .transition(&current, Some((PathBuf::from("/work/app"), project.clone())))
+            .unwrap();
+        apply(&mut current, &changes);
+        assert_eq!(current["PORT"], "3000");
+        assert_eq!(current["API_URL"], "http://localhost");
+
+        let state: HookState = serde_json::from_str(&current[HOOK_STATE_VAR]).unwrap();
+        assert_eq!(state.root, Some(PathBuf::from("/work/app")));
+
+        // Staying in the project is a no-op
+        let changes = state
+            .transition(&current, Some((PathBuf::from("/work/app"), project)))
+            .unwrap();
+        assert!(changes.is_empty());
+
+        // Leaving restores the previous values
+        let changes = state.transition(&current, None).unwrap();
+        apply(&mut current, &changes);
+        assert_eq!(current, env(&[("HOME", "/home/me"), ("PORT", "80")]));
+    }
+
+    #[test]
+    fn test_switch_between_projects() {
+        let mut current = env(&[("SHARED", "orig")]);
+
+        let changes = HookState::default()
+            .transition(
+                &current,
+                Some((PathBuf::from("/a"), env(&[("SHARED", "a"), ("ONLY_A", "1")]))),
+            )
+            .unwrap();
+        apply(&mut current, &changes);
+
+        let state: HookState = serde_json::from_str(&current[HOOK_STATE_VAR]).unwrap();
+        let changes = state
+            .transition(&current, Some((PathBuf::from("/b"), env(&[("SHARED", "b")]))))
+            .unwrap();
+        apply(&mut current, &changes);
+
+        assert_eq!(current["SHARED"], "b");
+        assert!(!current.contains_key("ONLY_A"));
+
+        // The value from before any project is still what gets restored
+        let state: HookState = serde_json::from_str(&current[HOOK_STATE_VAR]).unwrap();
+        assert_eq!(state.saved["SHARED"], Some("orig".to_string()));
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("project");
+        let config = root.join(".envx").join("config.yaml");
+        fs::create_dir_all(config.parent().unwrap()).unwrap();
+        fs::write(&config, "name: test\n").unwrap();
+
+        let list_path = temp.path().join("allowed.json");
+        let mut list = AllowList::load(list_path.clone()).unwrap();
+        assert!(!list.is_allowed(&root, &config));
+
+        list.allow(&root, &config).unwrap();
+        assert!(AllowList::load(list_path.clone()).unwrap().is_allowed(&root, &config));
+
+        // Editing the configuration revokes trust until it is allowed again
+        fs::write(&config, "name: test\ndefaults:\n  EVIL: 1\n").unwrap();
+        assert!(!list.is_allowed(&root, &config));
+        assert!(list.contains(&root));
+
+        // So does editing a file the configuration auto-loads
+        let mut project = ProjectConfig::new(Some("test".to_string()));
+        project.auto_load = vec![".env".to_string()];
+        project.save(&config).unwrap();
+        list.allow(&root, &config).unwrap();
+        assert!(list.is_allowed(&root, &config));
+        fs::write(root.join(".env"), "EVIL=1\n").unwrap();
+        assert!(!list.is_allowed(&root, &config));
+        list.allow(&root, &config).unwrap();
+        fs::write(root.join(".env"), "EVIL=2\n").unwrap();
+        assert!(!list.is_allowed(&root, &config));
+
+        assert!(list.deny(&root).unwrap());
+        assert!(!AllowList::load(list_path).unwrap().contains(&root));
+    }
+
+    #[test]
+    fn test_allow_list_covers_project_profiles() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("project");
+        let config = root.join(".envx").join("config.yaml");
+        let profiles = root.join(".envx").join(PROJECT_PROFILES_DIR);
+        fs::create_dir_all(&profiles).unwrap();
+        fs::write(&config, "name: test\nprofile: dev\n").unwrap();
+        fs::write(profiles.join("dev.yaml"), "name: dev\n").unwrap();
+
+        let mut list = AllowList::load(temp.path().join("allowed.json")).unwrap();
+        list.allow(&root, &config).unwrap();
+        assert!(list.is_allowed(&root, &config));
+
+        // Editing a project profile revokes trust
+        fs::write(profiles.join("dev.yaml"), "name: dev\nvariables:\n  EVIL: 1\n").unwrap();
+        assert!(!list.is_allowed(&root, &config));
+
+        // So does adding one, which could shadow a global profile
+        list.allow(&root, &config).unwrap();
+        fs::write(profiles.join("prod.yaml"), "name: prod\n").unwrap();
+        assert!(!list.is_allowed(&root, &config));
+
+        // And removing one
+        list.allow(&root, &config).unwrap();
+        fs::remove_file(profiles.join("prod.yaml")).unwrap();
+        assert!(!list.is_allowed(&root, &config));
+    }
+}
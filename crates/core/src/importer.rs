@@ -1,30 +1,55 @@
-use ahash::AHashMap as HashMap;
+use crate::VarOrigin;
+use crate::dotenv::{self, ParseError};
+use crate::expand::{Expander, Unresolved};
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy)]
 pub enum ImportFormat {
     DotEnv,
     Json,
     Yaml,
+    Toml,
+    /// The `environment:` and `env_file:` sections of a Docker Compose file
+    Compose,
+    /// The `ENV` and `ARG` instructions of a Dockerfile
+    Dockerfile,
     Text,
 }
 
 impl ImportFormat {
     /// Determines the import format based on file extension.
     ///
+    /// Dockerfiles and Compose files (`docker-compose.yml`, `compose.yaml`)
+    /// are recognized by their file name.
+    ///
     /// # Errors
     ///
     /// This function currently never returns an error, but uses `Result` for future extensibility.
     pub fn from_extension(path: &str) -> Result<Self> {
         let ext = Path::new(path).extension().and_then(|s| s.to_str()).unwrap_or("");
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        if name == "dockerfile" || name.starts_with("dockerfile.") || name.ends_with(".dockerfile") {
+            return Ok(Self::Dockerfile);
+        }
 
         match ext.to_lowercase().as_str() {
             "env" => Ok(Self::DotEnv),
             "json" => Ok(Self::Json),
+            "yaml" | "yml" if name.starts_with("docker-compose") || name.starts_with("compose.") => Ok(Self::Compose),
             "yaml" | "yml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
             "txt" | "text" => Ok(Self::Text),
             _ => {
                 // Check if filename is .env or similar
@@ -40,9 +65,22 @@ impl ImportFormat {
     }
 }
 
+/// Separator nested YAML keys are joined with by default
+pub const DEFAULT_SEPARATOR: &str = "__";
+
 #[derive(Debug, Clone, Default)]
 pub struct Importer {
     variables: HashMap<String, String>,
+    path: Option<PathBuf>,
+    lines: HashMap<String, usize>,
+    /// Single- and backtick-quoted `.env` values, which are not expanded
+    literals: HashSet<String>,
+    unresolved: Vec<Unresolved>,
+    errors: Vec<ParseError>,
+    /// Joins nested YAML keys and TOML tables, `__` if not set
+    separator: Option<String>,
+    /// The only Compose service to import, all services if not set
+    service: Option<String>,
 }
 
 impl Importer {
@@ -51,20 +89,58 @@ impl Importer {
         Self::default()
     }
 
+    /// Sets the separator nested YAML keys and TOML tables are joined with,
+    /// so `db: {host: x}` is imported as `db__host` by default.
+    pub fn set_separator(&mut self, separator: impl Into<String>) {
+        self.separator = Some(separator.into());
+    }
+
+    /// Imports only the environment of `service` from Compose files instead
+    /// of merging the environments of all services.
+    pub fn set_service(&mut self, service: impl Into<String>) {
+        self.service = Some(service.into());
+    }
+
     /// Imports environment variables from a file in the specified format.
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - The file cannot be read (file not found, permission denied, etc.)
-    /// - The file content cannot be parsed in the specified format (e.g., invalid JSON syntax)
+    /// - The file content cannot be parsed in the specified format (e.g., invalid JSON or YAML syntax)
+    /// - The Compose service set with [`Importer::set_service`] doesn't exist, or an
+    ///   `env_file` of a Compose file can't be read
+    /// - A `.env` value has an invalid or circular variable reference
     pub fn import_from_file(&mut self, path: &str, format: ImportFormat) -> Result<()> {
+        self.import_from_file_with(path, format, |name| std::env::var(name).ok())
+    }
+
+    /// Imports environment variables like [`Importer::import_from_file`], but
+    /// looks up references to variables that aren't defined in the file with
+    /// `lookup` instead of the process environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`Importer::import_from_file`].
+    pub fn import_from_file_with(
+        &mut self,
+        path: &str,
+        format: ImportFormat,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
         let content = fs::read_to_string(path)?;
+        self.path = Some(PathBuf::from(path));
 
         match format {
-            ImportFormat::DotEnv => self.parse_dotenv(&content),
+            ImportFormat::DotEnv => {
+                self.parse_dotenv(&content);
+                self.expand_references(lookup)?;
+            }
             ImportFormat::Json => self.parse_json(&content)?,
-            ImportFormat::Yaml => self.parse_yaml(&content),
+            ImportFormat::Yaml => self.parse_yaml(&content)?,
+            ImportFormat::Toml => self.parse_toml(&content)?,
+            ImportFormat::Compose => self.parse_compose(&content, &lookup)?,
+            ImportFormat::Dockerfile => self.parse_dockerfile(&content, &lookup)?,
             ImportFormat::Text => self.parse_text(&content),
         }
 
@@ -76,6 +152,53 @@ impl Importer {
         self.variables.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
     }
 
+    /// Returns the file and line an imported variable was read from.
+    ///
+    /// The line is known for line based formats like `.env` and text files,
+    /// and for YAML keys.
+    #[must_use]
+    pub fn origin(&self, name: &str) -> Option<VarOrigin> {
+        if !self.variables.contains_key(name) {
+            return None;
+        }
+
+        let path = self.path.clone()?;
+        Some(VarOrigin::file(path, self.lines.get(name).copied()))
+    }
+
+    /// References to unset variables found while expanding `.env` values.
+    #[must_use]
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+
+    /// Lines of a `.env` or text file that couldn't be parsed and were skipped.
+    #[must_use]
+    pub fn parse_errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Expands `${VAR}` style references in the imported values.
+    ///
+    /// Values may reference each other and anything `lookup` knows about.
+    /// Single- and backtick-quoted `.env` values are left as they are, and
+    /// so are single-quoted Dockerfile values.
+    fn expand_references(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let mut expander = Expander::new(lookup);
+        for (name, value) in &self.variables {
+            if self.literals.contains(name) {
+                expander.define_literal(name, value);
+            } else {
+                expander.define(name, value);
+            }
+        }
+
+        let expanded = expander.expand_all()?;
+        self.variables = expanded.into_iter().collect();
+        self.unresolved = expander.unresolved().to_vec();
+        Ok(())
+    }
+
     pub fn filter_by_patterns(&mut self, patterns: &[String]) {
         let mut matched = HashMap::new();
 
@@ -96,6 +219,7 @@ impl Importer {
         }
 
         self.variables = matched;
+        self.lines.retain(|key, _| self.variables.contains_key(key));
     }
 
     pub fn add_prefix(&mut self, prefix: &str) {
@@ -106,164 +230,396 @@ impl Importer {
         }
 
         self.variables = prefixed;
+        self.lines = self
+            .lines
+            .drain()
+            .map(|(key, line)| (format!("{prefix}{key}"), line))
+            .collect();
     }
 
     fn parse_dotenv(&mut self, content: &str) {
-        for line in content.lines() {
-            let line = line.trim();
+        let parsed = dotenv::parse(content);
+        for entry in parsed.entries {
+            self.define(entry.key.clone(), entry.value, entry.quote.expands());
+            self.lines.insert(entry.key, entry.line);
+        }
+        self.errors.extend(parsed.errors);
+    }
 
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
+    /// Sets a variable, replacing an earlier definition and its line.
+    fn define(&mut self, name: String, value: String, expands: bool) {
+        if expands {
+            self.literals.remove(&name);
+        } else {
+            self.literals.insert(name.clone());
+        }
+        self.lines.remove(&name);
+        self.variables.insert(name, value);
+    }
 
-            // Parse KEY=VALUE or KEY="VALUE"
-            if let Some(eq_pos) = line.find('=') {
-                let key = line[..eq_pos].trim();
-                let value = line[eq_pos + 1..].trim();
+    /// Imports a JSON object of variable names to values, or the structured
+    /// format `export` writes. Numbers, booleans and null are converted to
+    /// strings like in YAML files, arrays and objects are skipped.
+    fn parse_json(&mut self, content: &str) -> Result<()> {
+        // JSON is a subset of YAML, so both share the scalar conversion
+        let parsed: YamlValue = serde_json::from_str(content)?;
 
-                // Validate key
-                if key.is_empty() || key.contains(' ') {
-                    continue;
+        // Handle both simple object and structured format
+        if let Some(obj) = parsed.as_mapping() {
+            // Check if it's a structured export with metadata
+            if let Some(vars) = obj.get("variables") {
+                if let Some(vars) = vars.as_sequence() {
+                    for var in vars {
+                        if let (Some(name), Some(value)) = (
+                            var.get("name").and_then(YamlValue::as_str),
+                            var.get("value").and_then(scalar_string),
+                        ) {
+                            self.variables.insert(name.to_string(), value);
+                        }
+                    }
                 }
-
-                // Process value
-                let processed_value = if (value.starts_with('"') && value.ends_with('"'))
-                    || (value.starts_with('\'') && value.ends_with('\''))
-                {
-                    // Remove quotes and unescape
-                    let unquoted = &value[1..value.len() - 1];
-
-                    // Process escape sequences properly
-                    Self::unescape_string(unquoted)
-                } else {
-                    // Remove inline comments (but not # in values)
-                    if let Some(comment_pos) = value.find(" #") {
-                        value[..comment_pos].trim().to_string()
-                    } else {
-                        value.to_string()
+            } else {
+                // Simple key-value format
+                for (key, value) in obj {
+                    if let (Some(key), Some(value)) = (key.as_str(), scalar_string(value)) {
+                        self.variables.insert(key.to_string(), value);
                     }
-                };
+                }
+            }
+        }
 
-                self.variables.insert(key.to_string(), processed_value);
+        Ok(())
+    }
+
+    /// Imports the first document of a YAML file. Nested keys and list
+    /// items are joined with the separator, and other scalars are converted
+    /// to strings.
+    fn parse_yaml(&mut self, content: &str) -> Result<()> {
+        let Some(document) = serde_yaml::Deserializer::from_str(content).next() else {
+            return Ok(());
+        };
+        let mut value = YamlValue::deserialize(document)?;
+        value.apply_merge()?;
+
+        match value {
+            YamlValue::Null => Ok(()),
+            YamlValue::Mapping(_) => {
+                self.flatten_yaml(String::new(), value);
+                self.find_yaml_lines(content);
+                Ok(())
             }
+            _ => Err(eyre!("YAML import needs a mapping of variable names to values")),
         }
     }
 
-    fn unescape_string(input: &str) -> String {
-        let mut result = String::new();
-        let mut chars = input.chars().peekable();
+    /// Records the line of each variable set by a block mapping key, so
+    /// origins point into YAML files as they do for `.env` files. Variables
+    /// from list items, flow mappings and merged anchors get no line.
+    fn find_yaml_lines(&mut self, content: &str) {
+        let mut parents: Vec<(usize, String)> = Vec::new();
+        let mut seen_key = false;
+        for (index, line) in content.lines().enumerate() {
+            // Only the first document is imported
+            if seen_key && (line.starts_with("---") || line.starts_with("...")) {
+                break;
+            }
+            let trimmed = line.trim_start();
+            let Some(key) = yaml_key(trimmed) else {
+                continue;
+            };
+            seen_key = true;
+
+            let indent = line.len() - trimmed.len();
+            while parents
+                .last()
+                .is_some_and(|(parent_indent, _)| *parent_indent >= indent)
+            {
+                parents.pop();
+            }
+            let name = parents
+                .last()
+                .map_or_else(|| key.to_string(), |(_, parent)| self.nested_name(parent, key));
+            if self.variables.contains_key(&name) {
+                self.lines.insert(name.clone(), index + 1);
+            }
+            parents.push((indent, name));
+        }
+    }
 
-        while let Some(ch) = chars.next() {
-            if ch == '\\' {
-                match chars.peek() {
-                    Some('\\') => {
-                        result.push('\\');
-                        chars.next(); // consume the second backslash
-                    }
-                    Some('n') => {
-                        result.push('\n');
-                        chars.next(); // consume the 'n'
-                    }
-                    Some('r') => {
-                        result.push('\r');
-                        chars.next(); // consume the 'r'
-                    }
-                    Some('t') => {
-                        result.push('\t');
-                        chars.next(); // consume the 't'
-                    }
-                    Some('"') => {
-                        result.push('"');
-                        chars.next(); // consume the quote
-                    }
-                    Some('\'') => {
-                        result.push('\'');
-                        chars.next(); // consume the single quote
-                    }
-                    _ => {
-                        // Unknown escape sequence, keep the backslash
-                        result.push('\\');
+    fn flatten_yaml(&mut self, name: String, value: YamlValue) {
+        match value {
+            YamlValue::Mapping(map) => {
+                for (key, value) in map {
+                    // Keys that are lists or maps can't name a variable
+                    if let Some(key) = scalar_string(&key) {
+                        let child = self.nested_name(&name, &key);
+                        self.flatten_yaml(child, value);
                     }
                 }
-            } else {
-                result.push(ch);
+            }
+            YamlValue::Sequence(items) => {
+                for (index, value) in items.into_iter().enumerate() {
+                    let child = self.nested_name(&name, &index.to_string());
+                    self.flatten_yaml(child, value);
+                }
+            }
+            YamlValue::Tagged(tagged) => self.flatten_yaml(name, tagged.value),
+            scalar => {
+                if let Some(value) = scalar_string(&scalar) {
+                    self.variables.insert(name, value);
+                }
             }
         }
+    }
 
-        result
+    /// Imports a TOML file. Tables and array items are joined with the
+    /// separator like nested YAML keys.
+    fn parse_toml(&mut self, content: &str) -> Result<()> {
+        let table: toml::Table = content.parse()?;
+        for (key, value) in table {
+            self.flatten_toml(key, value);
+        }
+        Ok(())
     }
 
-    fn parse_json(&mut self, content: &str) -> Result<()> {
-        let parsed: serde_json::Value = serde_json::from_str(content)?;
+    fn flatten_toml(&mut self, name: String, value: toml::Value) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    let child = self.nested_name(&name, &key);
+                    self.flatten_toml(child, value);
+                }
+            }
+            toml::Value::Array(items) => {
+                for (index, value) in items.into_iter().enumerate() {
+                    let child = self.nested_name(&name, &index.to_string());
+                    self.flatten_toml(child, value);
+                }
+            }
+            toml::Value::String(value) => {
+                self.variables.insert(name, value);
+            }
+            toml::Value::Integer(n) => {
+                self.variables.insert(name, n.to_string());
+            }
+            toml::Value::Float(n) => {
+                self.variables.insert(name, n.to_string());
+            }
+            toml::Value::Boolean(b) => {
+                self.variables.insert(name, b.to_string());
+            }
+            toml::Value::Datetime(datetime) => {
+                self.variables.insert(name, datetime.to_string());
+            }
+        }
+    }
 
-        // Handle both simple object and structured format
-        if let Some(obj) = parsed.as_object() {
-            // Check if it's a structured export with metadata
-            if obj.contains_key("variables") {
-                if let Some(vars) = obj["variables"].as_array() {
-                    for var in vars {
-                        if let (Some(name), Some(value)) = (
-                            var.get("name").and_then(|v| v.as_str()),
-                            var.get("value").and_then(|v| v.as_str()),
-                        ) {
-                            self.variables.insert(name.to_string(), value.to_string());
-                        }
+    /// Name of a nested key or item of the variable `name`.
+    fn nested_name(&self, name: &str, key: &str) -> String {
+        if name.is_empty() {
+            key.to_string()
+        } else {
+            let separator = self.separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+            format!("{name}{separator}{key}")
+        }
+    }
+
+    /// Imports the environment of the service set with
+    /// [`Importer::set_service`], which may be left out if the Compose file
+    /// has a single service. As in Compose, the files listed in `env_file:`
+    /// are read first and `environment:` overrides them, and references in
+    /// `environment:` are only looked up in `lookup` and the `.env` file next
+    /// to the Compose file, never in other variables of the file.
+    fn parse_compose(&mut self, content: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+        let mut compose: YamlValue = serde_yaml::from_str(content)?;
+        // `environment: { <<: *common, ... }` is the usual way to share variables
+        compose.apply_merge()?;
+        let services = compose
+            .get("services")
+            .and_then(YamlValue::as_mapping)
+            .ok_or_else(|| eyre!("The Compose file has no services"))?;
+
+        let name = match &self.service {
+            Some(name) => name.clone(),
+            None if services.len() == 1 => services
+                .keys()
+                .next()
+                .and_then(YamlValue::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            None => {
+                let names: Vec<&str> = services.keys().filter_map(YamlValue::as_str).collect();
+                return Err(eyre!(
+                    "The Compose file has several services ({}), pick one with --service",
+                    names.join(", ")
+                ));
+            }
+        };
+        let service = services
+            .get(name.as_str())
+            .ok_or_else(|| eyre!("Service '{}' not found in the Compose file", name))?;
+
+        // env_file paths and the project .env are relative to the Compose file
+        let base = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        // The host environment takes precedence over the project .env, as in Compose
+        let project_env = self.read_env_file(&base.join(".env"), lookup, false)?;
+        let lookup = |var: &str| lookup(var).or_else(|| project_env.get(var).cloned());
+
+        let env_files = match service.get("env_file") {
+            None => Vec::new(),
+            Some(YamlValue::Sequence(files)) => files.iter().collect(),
+            Some(file) => vec![file],
+        };
+        for file in env_files {
+            let (path, required) = if let Some(path) = file.as_str() {
+                (path, true)
+            } else {
+                let path = file
+                    .get("path")
+                    .and_then(YamlValue::as_str)
+                    .ok_or_else(|| eyre!("Invalid env_file entry in service '{}'", name))?;
+                let required = file.get("required").and_then(YamlValue::as_bool).unwrap_or(true);
+                (path, required)
+            };
+
+            let vars = self.read_env_file(&base.join(path), &lookup, required)?;
+            self.variables.extend(vars);
+        }
+
+        match service.get("environment") {
+            Some(YamlValue::Mapping(vars)) => {
+                for (key, value) in vars {
+                    // A variable without a value is passed through from the host
+                    if value.is_null() {
+                        continue;
+                    }
+                    if let (Some(key), Some(value)) = (key.as_str(), scalar_string(value)) {
+                        let value = self.expand_alone(key, &value, &lookup)?;
+                        self.variables.insert(key.to_string(), value);
                     }
                 }
-            } else {
-                // Simple key-value format
-                for (key, value) in obj {
-                    if let Some(val_str) = value.as_str() {
-                        self.variables.insert(key.clone(), val_str.to_string());
+            }
+            Some(YamlValue::Sequence(vars)) => {
+                for var in vars {
+                    if let Some((key, value)) = var.as_str().and_then(|var| var.split_once('=')) {
+                        let value = self.expand_alone(key, value, &lookup)?;
+                        self.variables.insert(key.to_string(), value);
                     }
                 }
             }
+            _ => {}
         }
 
         Ok(())
     }
 
-    fn parse_yaml(&mut self, content: &str) {
-        // Simple YAML parser for key: value pairs
-        let mut skip_remaining = false;
+    /// Expands the value of `name` with `lookup` only, so it doesn't see the
+    /// other variables being imported.
+    fn expand_alone(&mut self, name: &str, value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+        let mut expander = Expander::new(lookup);
+        expander.define(name, value);
+        let value = expander.get(name)?.unwrap_or_default();
+        self.unresolved.extend_from_slice(expander.unresolved());
+        Ok(value)
+    }
 
-        for line in content.lines() {
-            let line = line.trim();
+    /// Reads and expands a `.env` file used by a Compose file. Its values may
+    /// reference each other and otherwise `lookup`. A missing file is empty
+    /// unless it is `required`.
+    fn read_env_file(
+        &mut self,
+        path: &Path,
+        lookup: &dyn Fn(&str) -> Option<String>,
+        required: bool,
+    ) -> Result<HashMap<String, String>> {
+        if !required && !path.exists() {
+            return Ok(HashMap::new());
+        }
 
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+        let mut expander = Expander::new(lookup);
+        for entry in dotenv::parse_file(path)? {
+            if entry.quote.expands() {
+                expander.define(&entry.key, &entry.value);
+            } else {
+                expander.define_literal(&entry.key, &entry.value);
             }
+        }
+        let vars = expander.expand_all()?.into_iter().collect();
+        self.unresolved.extend_from_slice(expander.unresolved());
+        Ok(vars)
+    }
 
-            // Stop processing after document separator
-            if line == "---" {
-                skip_remaining = true;
+    /// Imports the `ENV` instructions and the `ARG` defaults of a Dockerfile.
+    /// `ARG`s without a default are skipped.
+    ///
+    /// As in a build, values are expanded in instruction order with the
+    /// `ARG`s and `ENV`s set before them in the same stage, and `lookup`
+    /// otherwise. `ARG`s before the first `FROM` are only in scope in a stage
+    /// that declares them again without a default.
+    fn parse_dockerfile(&mut self, content: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+        let mut global: HashMap<String, String> = HashMap::new();
+        // The variables of the current stage, `None` before the first FROM
+        let mut stage: Option<HashMap<String, String>> = None;
+
+        for (line, instruction) in dockerfile_instructions(content) {
+            let (keyword, args) = instruction
+                .split_once(char::is_whitespace)
+                .unwrap_or((instruction.as_str(), ""));
+            if keyword.eq_ignore_ascii_case("FROM") {
+                stage = Some(HashMap::new());
                 continue;
             }
-
-            // Skip all content after document separator
-            if skip_remaining {
+            let is_env = keyword.eq_ignore_ascii_case("ENV");
+            if !is_env && !keyword.eq_ignore_ascii_case("ARG") {
                 continue;
             }
 
-            // Parse key: value
-            if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim();
-                let value = line[colon_pos + 1..].trim();
-
-                // Remove quotes if present
-                let processed_value = if (value.starts_with('"') && value.ends_with('"'))
-                    || (value.starts_with('\'') && value.ends_with('\''))
-                {
-                    value[1..value.len() - 1].to_string()
-                } else {
-                    value.to_string()
-                };
+            // All values of one instruction see the variables from before it
+            let scope = stage.as_ref().unwrap_or(&global);
+            let lookup = |name: &str| scope.get(name).cloned().or_else(|| lookup(name));
+            let mut assigned = Vec::new();
+            let mut inherited = Vec::new();
+
+            let args = args.trim();
+            let words = shell_words(args);
+            if is_env && !words.first().is_some_and(|(word, _)| word.contains('=')) {
+                // The legacy `ENV KEY value` form sets a single variable to the rest of the line
+                if let Some((key, value)) = args.split_once(char::is_whitespace) {
+                    assigned.push((key.to_string(), self.expand_alone(key, value.trim(), &lookup)?));
+                }
+            } else {
+                for (word, literal) in words {
+                    match word.split_once('=') {
+                        Some((key, value)) => {
+                            let value = if literal {
+                                value.to_string()
+                            } else {
+                                self.expand_alone(key, value, &lookup)?
+                            };
+                            assigned.push((key.to_string(), value));
+                        }
+                        // `ARG NAME` in a stage brings in the default of a global ARG
+                        None if !is_env => inherited.extend(global.get(&word).map(|value| (word, value.clone()))),
+                        None => {}
+                    }
+                }
+            }
 
-                self.variables.insert(key.to_string(), processed_value);
+            let scope = stage.as_mut().unwrap_or(&mut global);
+            scope.extend(inherited);
+            for (key, value) in assigned {
+                scope.insert(key.clone(), value.clone());
+                self.lines.insert(key.clone(), line);
+                self.variables.insert(key, value);
             }
         }
+
+        Ok(())
     }
 
     fn parse_text(&mut self, content: &str) {
@@ -272,6 +628,134 @@ impl Importer {
     }
 }
 
+/// Returns true if the file at `path` is a TOML file by its extension.
+pub(crate) fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Joins the continuation lines of a Dockerfile and drops its comments,
+/// returning each instruction with the line it starts on.
+fn dockerfile_instructions(content: &str) -> Vec<(usize, String)> {
+    let mut instructions = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') || (trimmed.is_empty() && current.is_none()) {
+            continue;
+        }
+
+        let (start, mut instruction) = current.take().unwrap_or((index + 1, String::new()));
+        if let Some(continued) = trimmed.strip_suffix('\\') {
+            instruction.push_str(continued);
+            current = Some((start, instruction));
+        } else {
+            instruction.push_str(trimmed);
+            instructions.push((start, instruction));
+        }
+    }
+
+    instructions.extend(current);
+    instructions
+}
+
+/// Splits Dockerfile arguments into words like a shell. Each word is
+/// returned with whether it had single-quoted parts, which aren't expanded.
+fn shell_words(input: &str) -> Vec<(String, bool)> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut literal = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                literal = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            // The expander reads `$$` as a literal dollar sign
+                            Some('$') => word.push_str("$$"),
+                            Some(next @ ('"' | '\\')) => word.push(next),
+                            Some(next) => {
+                                word.push('\\');
+                                word.push(next);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('$') => word.push_str("$$"),
+                    Some(next) => word.push(next),
+                    None => {}
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push((std::mem::take(&mut word), literal));
+                    in_word = false;
+                    literal = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push((word, literal));
+    }
+    words
+}
+
+/// Converts a YAML or JSON scalar to a string, using an empty string for
+/// null. Lists and maps have no string value.
+fn scalar_string(value: &YamlValue) -> Option<String> {
+    match value {
+        YamlValue::Null => Some(String::new()),
+        YamlValue::Bool(b) => Some(b.to_string()),
+        YamlValue::Number(n) => Some(n.to_string()),
+        YamlValue::String(s) => Some(s.clone()),
+        YamlValue::Tagged(tagged) => scalar_string(&tagged.value),
+        YamlValue::Sequence(_) | YamlValue::Mapping(_) => None,
+    }
+}
+
+/// Returns the key of a YAML line that starts a block mapping entry, like
+/// `KEY: value`, `KEY:` or `"KEY": value`.
+fn yaml_key(line: &str) -> Option<&str> {
+    let (key, rest) = match line.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = line[1..].find(quote)? + 1;
+            (&line[1..end], &line[end + 1..])
+        }
+        '#' | '-' | '[' | '{' | '?' => return None,
+        _ => {
+            let end = line
+                .char_indices()
+                .find(|&(i, c)| c == ':' && line[i + 1..].chars().next().is_none_or(char::is_whitespace))?
+                .0;
+            (line[..end].trim_end(), &line[end..])
+        }
+    };
+    let rest = rest.trim_start().strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(key)
+}
+
 fn wildcard_to_regex(pattern: &str) -> String {
     let mut regex = String::new();
     regex.push('^');
@@ -335,6 +819,10 @@ mod tests {
             ImportFormat::from_extension("file.yml").unwrap(),
             ImportFormat::Yaml
         ));
+        assert!(matches!(
+            ImportFormat::from_extension("Cargo.toml").unwrap(),
+            ImportFormat::Toml
+        ));
         assert!(matches!(
             ImportFormat::from_extension("file.txt").unwrap(),
             ImportFormat::Text
@@ -369,6 +857,22 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_import_format_detects_docker_files() {
+        for path in ["docker-compose.yml", "app/compose.yaml", "docker-compose.override.yml"] {
+            assert!(matches!(
+                ImportFormat::from_extension(path).unwrap(),
+                ImportFormat::Compose
+            ));
+        }
+        for path in ["Dockerfile", "app/Dockerfile.dev", "web.dockerfile"] {
+            assert!(matches!(
+                ImportFormat::from_extension(path).unwrap(),
+                ImportFormat::Dockerfile
+            ));
+        }
+    }
+
     #[test]
     fn test_parse_dotenv_basic() {
         let mut importer = Importer::new();
@@ -472,6 +976,40 @@ SPECIAL=!@#$%^&*()
         assert_eq!(vars_map.get("SPECIAL").unwrap(), "!@#$%^&*()");
     }
 
+    #[test]
+    fn test_parse_dotenv_conformance_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("dotenv");
+        for entry in fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|s| s.to_str()) != Some("env") {
+                continue;
+            }
+
+            let mut importer = Importer::new();
+            importer.parse_dotenv(&fs::read_to_string(&path).unwrap());
+            let expected: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+
+            // Later assignments win, like they do when loading the file
+            let mut expected_vars = HashMap::new();
+            for pair in expected["entries"].as_array().unwrap() {
+                expected_vars.insert(
+                    pair[0].as_str().unwrap().to_string(),
+                    pair[1].as_str().unwrap().to_string(),
+                );
+            }
+            let vars: HashMap<_, _> = importer.get_variables().into_iter().collect();
+            assert_eq!(vars, expected_vars, "variables of {}", path.display());
+            let error_count = expected.get("errors").and_then(|e| e.as_array()).map_or(0, Vec::len);
+            assert_eq!(
+                importer.parse_errors().len(),
+                error_count,
+                "errors of {}",
+                path.display()
+            );
+        }
+    }
+
     #[test]
     fn test_parse_json_simple() {
         let mut importer = Importer::new();
@@ -527,19 +1065,31 @@ SPECIAL=!@#$%^&*()
         let content = r#"{
             "STRING": "value",
             "NUMBER": 42,
+            "FLOAT": 2.5,
             "BOOLEAN": true,
             "NULL": null,
             "ARRAY": [1, 2, 3],
-            "OBJECT": {"nested": "value"}
+            "OBJECT": {"nested": "value"},
+            "variable": "not structured"
         }"#;
 
         importer.parse_json(content).unwrap();
         let vars = importer.get_variables();
         let vars_map: HashMap<_, _> = vars.into_iter().collect();
 
-        // Only string values should be imported
-        assert_eq!(vars_map.len(), 1);
+        // Scalars are converted like YAML values, arrays and objects skipped
+        assert_eq!(vars_map.len(), 6);
         assert_eq!(vars_map.get("STRING").unwrap(), "value");
+        assert_eq!(vars_map.get("NUMBER").unwrap(), "42");
+        assert_eq!(vars_map.get("FLOAT").unwrap(), "2.5");
+        assert_eq!(vars_map.get("BOOLEAN").unwrap(), "true");
+        assert_eq!(vars_map.get("NULL").unwrap(), "");
+
+        let mut importer = Importer::new();
+        importer
+            .parse_json(r#"{"variables": [{"name": "PORT", "value": 8080}]}"#)
+            .unwrap();
+        assert_eq!(importer.get_variables(), [("PORT".to_string(), "8080".to_string())]);
     }
 
     #[test]
@@ -554,7 +1104,7 @@ KEY3: value with spaces
 KEY4: after document marker
 ";
 
-        importer.parse_yaml(content);
+        importer.parse_yaml(content).unwrap();
         let vars = importer.get_variables();
         let vars_map: HashMap<_, _> = vars.into_iter().collect();
 
@@ -571,17 +1121,18 @@ KEY4: after document marker
 KEY1: "quoted value"
 KEY2: 'single quoted'
 KEY3: "value: with colon"
-KEY4: unquoted: with colon
 "#;
 
-        importer.parse_yaml(content);
+        importer.parse_yaml(content).unwrap();
         let vars = importer.get_variables();
         let vars_map: HashMap<_, _> = vars.into_iter().collect();
 
         assert_eq!(vars_map.get("KEY1").unwrap(), "quoted value");
         assert_eq!(vars_map.get("KEY2").unwrap(), "single quoted");
         assert_eq!(vars_map.get("KEY3").unwrap(), "value: with colon");
-        assert_eq!(vars_map.get("KEY4").unwrap(), "unquoted: with colon");
+
+        // Not valid YAML, a plain value can't contain ": "
+        assert!(Importer::new().parse_yaml("KEY4: unquoted: with colon").is_err());
     }
 
     #[test]
@@ -591,25 +1142,282 @@ KEY4: unquoted: with colon
 # Empty value
 EMPTY:
 EMPTY2: 
-# No space after colon
-COMPACT:value
 # Multiple colons
 URL: http://example.com:8080
 # Special characters
-SPECIAL: !@#$%^&*()
+SPECIAL: '!@#$%^&*()'
 ";
 
-        importer.parse_yaml(content);
+        importer.parse_yaml(content).unwrap();
         let vars = importer.get_variables();
         let vars_map: HashMap<_, _> = vars.into_iter().collect();
 
         assert_eq!(vars_map.get("EMPTY").unwrap(), "");
         assert_eq!(vars_map.get("EMPTY2").unwrap(), "");
-        assert_eq!(vars_map.get("COMPACT").unwrap(), "value");
         assert_eq!(vars_map.get("URL").unwrap(), "http://example.com:8080");
         assert_eq!(vars_map.get("SPECIAL").unwrap(), "!@#$%^&*()");
     }
 
+    #[test]
+    fn test_parse_yaml_nested_values() {
+        let content = r"
+defaults: &defaults
+  host: localhost
+  port: 5432
+DB:
+  <<: *defaults
+  HOST: db.internal
+  POOL: [1, 2.5]
+DEBUG: true
+NOTES: |
+  line one
+  line two
+TAGGED: !secret hidden
+";
+
+        let mut importer = Importer::new();
+        importer.parse_yaml(content).unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+
+        assert_eq!(vars_map["DB__HOST"], "db.internal");
+        assert_eq!(vars_map["DB__host"], "localhost");
+        assert_eq!(vars_map["DB__port"], "5432");
+        assert_eq!(vars_map["DB__POOL__0"], "1");
+        assert_eq!(vars_map["DB__POOL__1"], "2.5");
+        assert_eq!(vars_map["DEBUG"], "true");
+        assert_eq!(vars_map["NOTES"], "line one\nline two\n");
+        assert_eq!(vars_map["TAGGED"], "hidden");
+        assert_eq!(vars_map["defaults__host"], "localhost");
+
+        let mut importer = Importer::new();
+        importer.set_separator("_");
+        importer.parse_yaml(content).unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars_map["DB_HOST"], "db.internal");
+
+        assert!(Importer::new().parse_yaml("- just\n- a list\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_toml_nested_tables() {
+        let content = r#"
+NAME = "app"
+PORT = 8080
+RATIO = 0.5
+DEBUG = false
+STARTED = 2024-01-02T03:04:05Z
+
+[DB]
+HOST = "db.internal"
+REPLICAS = ["a", "b"]
+
+[[JOBS]]
+CRON = "0 * * * *"
+"#;
+
+        let mut importer = Importer::new();
+        importer.parse_toml(content).unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+
+        assert_eq!(vars_map["NAME"], "app");
+        assert_eq!(vars_map["PORT"], "8080");
+        assert_eq!(vars_map["RATIO"], "0.5");
+        assert_eq!(vars_map["DEBUG"], "false");
+        assert_eq!(vars_map["STARTED"], "2024-01-02T03:04:05Z");
+        assert_eq!(vars_map["DB__HOST"], "db.internal");
+        assert_eq!(vars_map["DB__REPLICAS__1"], "b");
+        assert_eq!(vars_map["JOBS__0__CRON"], "0 * * * *");
+
+        assert!(Importer::new().parse_toml("NAME = unquoted").is_err());
+    }
+
+    #[test]
+    fn test_import_compose_environment_and_env_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("common.env"),
+            "SHARED=from_file\nPORT=3000\nRAW='$HOME'\nHOST_URL=http://${HOST}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join(".env"), "HOST=project.local\n").unwrap();
+        let compose = dir.path().join("docker-compose.yml");
+        fs::write(
+            &compose,
+            r"
+services:
+  web:
+    env_file:
+      - common.env
+      - path: missing.env
+        required: false
+    environment:
+      PORT: 8080
+      DEBUG: true
+      URL: http://${HOST}:${PORT:-80}
+      PASSTHROUGH:
+  worker:
+    env_file: common.env
+    environment:
+      - QUEUE=jobs
+      - PRICE=$$5
+",
+        )
+        .unwrap();
+        let path = compose.to_str().unwrap();
+
+        // Several services need one to be picked
+        let error = Importer::new()
+            .import_from_file_with(path, ImportFormat::Compose, |_| None)
+            .unwrap_err();
+        assert!(error.to_string().contains("web, worker"), "{error}");
+
+        let mut importer = Importer::new();
+        importer.set_service("web");
+        importer
+            .import_from_file_with(path, ImportFormat::Compose, |_| None)
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars_map["SHARED"], "from_file");
+        assert_eq!(vars_map["RAW"], "$HOME");
+        assert_eq!(vars_map["HOST_URL"], "http://project.local");
+        assert_eq!(vars_map["DEBUG"], "true");
+        assert_eq!(vars_map["PORT"], "8080");
+        // References come from the host and the project .env, not from the service
+        assert_eq!(vars_map["URL"], "http://project.local:80");
+        assert!(!vars_map.contains_key("PASSTHROUGH"));
+        assert!(!vars_map.contains_key("QUEUE"));
+
+        let mut importer = Importer::new();
+        importer.set_service("web");
+        importer
+            .import_from_file_with(path, ImportFormat::Compose, |name| {
+                ["HOST", "PORT"].contains(&name).then(|| format!("host-{name}"))
+            })
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars_map["URL"], "http://host-HOST:host-PORT");
+
+        let mut importer = Importer::new();
+        importer.set_service("worker");
+        importer
+            .import_from_file_with(path, ImportFormat::Compose, |_| None)
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars_map["QUEUE"], "jobs");
+        assert_eq!(vars_map["PRICE"], "$5");
+        assert_eq!(vars_map["PORT"], "3000");
+        assert!(!vars_map.contains_key("URL"));
+
+        let mut importer = Importer::new();
+        importer.set_service("db");
+        assert!(importer.import_from_file(path, ImportFormat::Compose).is_err());
+
+        let single = dir.path().join("compose.yaml");
+        fs::write(&single, "services:\n  api:\n    environment:\n      - MODE=prod\n").unwrap();
+        let mut importer = Importer::new();
+        importer
+            .import_from_file_with(single.to_str().unwrap(), ImportFormat::Compose, |_| None)
+            .unwrap();
+        assert_eq!(importer.get_variables(), [("MODE".to_string(), "prod".to_string())]);
+    }
+
+    #[test]
+    fn test_import_compose_merge_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let compose = dir.path().join("compose.yaml");
+        fs::write(
+            &compose,
+            r"
+x-common-env: &common-env
+  LOG_LEVEL: info
+  REGION: eu
+
+services:
+  api:
+    environment:
+      <<: *common-env
+      REGION: us
+      MODE: prod
+",
+        )
+        .unwrap();
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file_with(compose.to_str().unwrap(), ImportFormat::Compose, |_| None)
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars_map["LOG_LEVEL"], "info");
+        assert_eq!(vars_map["REGION"], "us");
+        assert_eq!(vars_map["MODE"], "prod");
+        assert!(!vars_map.contains_key("<<"));
+    }
+
+    #[test]
+    fn test_import_dockerfile_env_and_args() {
+        let content = r#"# syntax=docker/dockerfile:1
+ARG VERSION=1.2
+ARG TOKEN
+FROM rust:${VERSION}
+env APP_HOME=/app \
+    GREETING="hello world" RAW='$APP_HOME'
+# Legacy form
+ENV LOG_DIR ${APP_HOME}/logs
+RUN echo "ENV NOT_A_VAR=1"
+"#;
+        let file = create_temp_file(content, ".dockerfile");
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file_with(file.path().to_str().unwrap(), ImportFormat::Dockerfile, |_| None)
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+
+        assert_eq!(vars_map.len(), 5);
+        assert_eq!(vars_map["VERSION"], "1.2");
+        assert_eq!(vars_map["APP_HOME"], "/app");
+        assert_eq!(vars_map["GREETING"], "hello world");
+        assert_eq!(vars_map["RAW"], "$APP_HOME");
+        assert_eq!(vars_map["LOG_DIR"], "/app/logs");
+        assert_eq!(
+            importer.origin("GREETING"),
+            Some(VarOrigin::file(file.path().to_path_buf(), Some(5)))
+        );
+    }
+
+    #[test]
+    fn test_import_dockerfile_expands_in_order_per_stage() {
+        let content = r"ARG VERSION=1.2
+ARG BASE=rust:${VERSION}
+FROM ${BASE} AS build
+ENV APP_HOME=/app
+ENV APP_HOME=/srv LOG_DIR=${APP_HOME}/logs
+ENV BIN=${APP_HOME}/bin EARLY=${LATER}
+ENV LATER=set
+FROM debian
+ARG VERSION
+ENV TAG=v${VERSION} SRC=${APP_HOME}
+";
+        let file = create_temp_file(content, ".dockerfile");
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file_with(file.path().to_str().unwrap(), ImportFormat::Dockerfile, |_| None)
+            .unwrap();
+        let vars_map: HashMap<_, _> = importer.get_variables().into_iter().collect();
+
+        assert_eq!(vars_map["BASE"], "rust:1.2");
+        // Values of one instruction see the variables from before it
+        assert_eq!(vars_map["LOG_DIR"], "/app/logs");
+        assert_eq!(vars_map["BIN"], "/srv/bin");
+        // Later instructions and other stages aren't in scope
+        assert_eq!(vars_map["EARLY"], "");
+        assert_eq!(vars_map["SRC"], "");
+        assert_eq!(vars_map["TAG"], "v1.2");
+        let unresolved: Vec<&str> = importer.unresolved().iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(unresolved, ["LATER", "APP_HOME"]);
+    }
+
     #[test]
     fn test_import_from_file_dotenv() {
         let content = "KEY1=value1\nKEY2=value2";
@@ -624,6 +1432,76 @@ SPECIAL: !@#$%^&*()
         assert_eq!(vars.len(), 2);
     }
 
+    #[test]
+    fn test_import_dotenv_expands_references() {
+        let content =
+            "URL=http://${HOST}:${PORT:-8080}\nHOST=localhost\nRAW='${HOST}'\nGREETING=hi $ENVX_IMPORT_TEST_MISSING";
+        let file = create_temp_file(content, ".env");
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file(file.path().to_str().unwrap(), ImportFormat::DotEnv)
+            .unwrap();
+
+        let vars: HashMap<String, String> = importer.get_variables().into_iter().collect();
+        assert_eq!(vars["URL"], "http://localhost:8080");
+        assert_eq!(vars["RAW"], "${HOST}");
+        assert_eq!(vars["GREETING"], "hi ");
+        assert_eq!(importer.unresolved().len(), 1);
+        assert_eq!(importer.unresolved()[0].name, "ENVX_IMPORT_TEST_MISSING");
+
+        let file = create_temp_file("A=$B\nB=${A}", ".env");
+        assert!(
+            Importer::new()
+                .import_from_file(file.path().to_str().unwrap(), ImportFormat::DotEnv)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_import_records_origin() {
+        let content = "# header\nKEY1=value1\n\nKEY2=value2";
+        let file = create_temp_file(content, ".env");
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file(file.path().to_str().unwrap(), ImportFormat::DotEnv)
+            .unwrap();
+        importer.add_prefix("APP_");
+
+        assert_eq!(
+            importer.origin("APP_KEY1"),
+            Some(VarOrigin::file(file.path().to_path_buf(), Some(2)))
+        );
+        assert_eq!(
+            importer.origin("APP_KEY2"),
+            Some(VarOrigin::file(file.path().to_path_buf(), Some(4)))
+        );
+        assert_eq!(importer.origin("KEY1"), None);
+    }
+
+    #[test]
+    fn test_import_records_yaml_lines() {
+        let content = "# header\nPORT: 8080\nDB:\n  HOST: localhost  # comment\n  \"USER\": app\n  POOL: [1, 2]\nNOTES: |\n  KEY: not a key\nDEBUG: true\n---\nPORT: 1\n";
+        let file = create_temp_file(content, ".yaml");
+
+        let mut importer = Importer::new();
+        importer
+            .import_from_file(file.path().to_str().unwrap(), ImportFormat::Yaml)
+            .unwrap();
+
+        let line = |name: &str| match importer.origin(name) {
+            Some(VarOrigin::File { line, .. }) => line,
+            _ => None,
+        };
+        assert_eq!(line("PORT"), Some(2));
+        assert_eq!(line("DB__HOST"), Some(4));
+        assert_eq!(line("DB__USER"), Some(5));
+        assert_eq!(line("DB__POOL__0"), None);
+        assert_eq!(line("NOTES"), Some(7));
+        assert_eq!(line("DEBUG"), Some(9));
+    }
+
     #[test]
     fn test_import_from_file_auto_detect() {
         // Test .env file
@@ -848,7 +1726,7 @@ LOG_PATH=/var/log/app
         importer.parse_json("{}").unwrap();
         assert_eq!(importer.get_variables().len(), 0);
 
-        importer.parse_yaml("");
+        importer.parse_yaml("").unwrap();
         assert_eq!(importer.get_variables().len(), 0);
     }
 
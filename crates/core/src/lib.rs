@@ -1,30 +1,53 @@
 pub mod analysis;
+pub mod composer;
+pub mod dotenv;
 pub mod env;
 mod env_watcher;
 pub mod error;
+pub mod expand;
 pub mod exporter;
 pub mod history;
+pub mod hook;
 pub mod importer;
 pub mod path;
 pub mod profile_manager;
 pub mod project_config;
 pub mod project_manager;
+pub mod secret;
+pub mod shell_config;
+pub mod shell_export;
 pub mod snapshot;
+pub mod snapshot_archive;
 pub mod snapshot_manager;
+mod snapshot_store;
+pub mod startup_env;
 mod templates;
+pub mod vault;
 pub mod wizard;
 
 pub use analysis::{Analyzer, PathAnalyzer, ValidationResult};
-pub use env::{EnvVar, EnvVarManager, EnvVarSource};
+pub use composer::{ComposedVar, EnvComposer};
+pub use dotenv::{Dotenv, DotenvDocument, Entry as DotenvEntry, ParseError as DotenvError, Quote};
+pub use env::{EnvVar, EnvVarManager, EnvVarSource, VarOrigin};
 pub use env_watcher::{ChangeEvent, ChangeType, ConflictStrategy, EnvWatcher, SyncMode, WatchConfig};
 pub use error::EnvxError;
+pub use expand::{ExpandError, Expander, Unresolved};
 pub use exporter::{ExportFormat, Exporter};
-pub use history::{History, HistoryEntry};
+pub use history::{History, HistoryAction, HistoryEntry};
+pub use hook::{AllowList, HOOK_STATE_VAR, HookState};
 pub use importer::{ImportFormat, Importer};
 pub use path::PathManager;
-pub use profile_manager::ProfileManager;
-pub use project_config::{ProjectConfig, RequiredVar, ValidationRules};
+pub use profile_manager::{
+    LayerConflict, LayerReport, MergeSide, MergeSummary, ProfileDiff, ProfileManager, ProfileScope, ResolvedVar,
+};
+pub use project_config::{ProfileSelection, ProjectConfig, RequiredVar, ValidationRules};
 pub use project_manager::{ProjectManager, ValidationReport};
-pub use snapshot::{Profile, ProfileVar, Snapshot};
-pub use snapshot_manager::SnapshotManager;
+pub use secret::{SecretClassifier, SecretKind};
+pub use shell_config::{ShellConfig, ShellKind};
+pub use shell_export::{EnvChange, ShellDialect};
+pub use snapshot::{AUTO_TAG, MASKED_KEY, Profile, ProfileVar, Snapshot, SnapshotSummary, TAGS_KEY};
+pub use snapshot_archive::{ArchiveSecrets, SnapshotArchive};
+pub use snapshot_manager::{PrunePolicy, RestoreOptions, RestoreReport, SnapshotDiff, SnapshotManager};
+pub use snapshot_store::MigrationReport;
 pub use templates::{ProjectTemplate, get_builtin_templates};
+pub use vault::{KeySource, KeyStore, Vault};
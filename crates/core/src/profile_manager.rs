@@ -1,32 +1,154 @@
-use crate::EnvVarManager;
-use crate::snapshot::Profile;
+use crate::env::wildcard_to_regex;
+use crate::expand::{Expander, Unresolved};
+use crate::snapshot::{Profile, ProfileVar};
+use crate::vault::{Vault, is_encrypted};
+use crate::{EnvVarManager, EnvVarSource, KeyStore, ProjectManager, SecretClassifier, VarOrigin};
 use ahash::AHashMap as HashMap;
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
+use indexmap::IndexMap;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::cell::{OnceCell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Directory inside a project's `.envx` holding its profiles, one YAML file each
+pub const PROJECT_PROFILES_DIR: &str = "profiles";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ProfileConfig {
     pub active: Option<String>,
     pub profiles: HashMap<String, Profile>,
 }
 
+/// A variable value resolved from a profile and its parents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedVar {
+    pub value: String,
+    /// Name of the profile that defines the value
+    pub profile: String,
+    /// Whether the value replaces a system variable of the same name
+    pub override_system: bool,
+    /// Whether references in the value are left as they are, which is the
+    /// case for secrets and values stored encrypted
+    pub literal: bool,
+}
+
+/// A variable that several layers define differently, or that is also set
+/// by the system, see [`ProfileManager::resolve_layers`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerConflict {
+    pub name: String,
+    /// Every definition in layer order
+    pub layers: Vec<ResolvedVar>,
+    /// Value of the system variable, if one is set
+    pub system: Option<String>,
+    /// Profile whose value is used, `None` if the system value is kept
+    pub winner: Option<String>,
+}
+
+/// The outcome of applying profiles in layers
+#[derive(Debug, Default)]
+pub struct LayerReport {
+    /// References that could not be resolved
+    pub unresolved: Vec<Unresolved>,
+    pub conflicts: Vec<LayerConflict>,
+}
+
+/// Differences between the variables of two profiles, see [`ProfileManager::diff`]
+#[derive(Debug, Default)]
+pub struct ProfileDiff {
+    /// Variables only in the second profile
+    pub added: BTreeMap<String, ProfileVar>,
+    /// Variables only in the first profile
+    pub removed: BTreeMap<String, ProfileVar>,
+    /// Variables whose value or `override_system` flag differs (old, new)
+    pub changed: BTreeMap<String, (ProfileVar, ProfileVar)>,
+    /// Variables that only differ in being enabled or disabled (old, new)
+    pub toggled: BTreeMap<String, (ProfileVar, ProfileVar)>,
+}
+
+impl ProfileDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.toggled.is_empty()
+    }
+}
+
+/// Which profile wins a merge conflict, see [`ProfileManager::merge`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeSide {
+    /// Keep the variable of the profile merged into
+    Ours,
+    /// Take the variable of the profile merged from
+    Theirs,
+}
+
+/// What [`ProfileManager::merge`] did
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    /// Variables copied because the target didn't have them
+    pub added: Vec<String>,
+    /// Conflicting variables taken from the source
+    pub taken: Vec<String>,
+    /// Conflicting variables the target kept
+    pub kept: Vec<String>,
+}
+
+/// Where a profile is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileScope {
+    /// In `profiles.json` in the envx config directory, shared by all projects
+    Global,
+    /// In `.envx/profiles/<name>.yaml` of the current project
+    Project,
+}
+
+impl fmt::Display for ProfileScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Project => write!(f, "project"),
+        }
+    }
+}
+
 pub struct ProfileManager {
     config_path: PathBuf,
+    /// Global profiles plus the profiles of the current project
     config: ProfileConfig,
+    /// `.envx/profiles` of the current project, if there is one
+    project_dir: Option<PathBuf>,
+    /// Files of the profiles stored in the project
+    project_profiles: HashMap<String, PathBuf>,
+    /// Global profiles hidden by a project profile of the same name
+    shadowed: HashMap<String, Profile>,
+    /// The key secrets are encrypted with, once unlocked
+    vault: OnceCell<Vault>,
+    /// What each file held when it was last read or written, so saving only
+    /// rewrites the files whose profiles changed
+    written: RefCell<HashMap<PathBuf, serde_json::Value>>,
 }
 
 impl ProfileManager {
     /// Creates a new `ProfileManager` instance.
     ///
+    /// Global profiles are read from `profiles.json` in the envx config
+    /// directory. Inside a project (see [`ProjectManager::find_and_load`]) the
+    /// profiles in its `.envx/profiles/*.yaml` are loaded as well and take
+    /// precedence over global profiles of the same name.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - The data/config directory cannot be found
     /// - The config directory cannot be created
     /// - The existing profiles.json file cannot be read or parsed
+    /// - The project configuration cannot be read or parsed
+    /// - A project profile file cannot be read or parsed
     pub fn new() -> Result<Self> {
         let config_dir = if cfg!(windows) {
             dirs::data_dir()
@@ -41,7 +163,8 @@ impl ProfileManager {
         fs::create_dir_all(&config_dir)?;
         let config_path = config_dir.join("profiles.json");
 
-        let config = if config_path.exists() {
+        let exists = config_path.exists();
+        let config: ProfileConfig = if exists {
             let content = fs::read_to_string(&config_path)?;
             serde_json::from_str(&content)?
         } else {
@@ -51,7 +174,58 @@ impl ProfileManager {
             }
         };
 
-        Ok(Self { config_path, config })
+        let mut manager = Self::with_config(config_path, config);
+        if exists {
+            manager.remember(&manager.config_path.clone(), &manager.config)?;
+        }
+        let mut project = ProjectManager::new()?;
+        if let Some(root) = project.find_and_load()? {
+            manager.load_project(root.join(".envx").join(PROJECT_PROFILES_DIR))?;
+        }
+
+        Ok(manager)
+    }
+
+    fn with_config(config_path: PathBuf, config: ProfileConfig) -> Self {
+        Self {
+            config_path,
+            config,
+            project_dir: None,
+            project_profiles: HashMap::new(),
+            shadowed: HashMap::new(),
+            vault: OnceCell::new(),
+            written: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Loads the project profiles stored in `dir`, replacing global profiles
+    /// of the same name.
+    fn load_project(&mut self, dir: PathBuf) -> Result<()> {
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if !path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+                    continue;
+                };
+
+                let content = fs::read_to_string(&path)?;
+                let mut profile: Profile = serde_yaml::from_str(&content)
+                    .map_err(|e| eyre!("Invalid profile file {}: {}", path.display(), e))?;
+                profile.name.clone_from(&name);
+                self.remember(&path, &profile)?;
+
+                if let Some(global) = self.config.profiles.insert(name.clone(), profile) {
+                    self.shadowed.insert(name.clone(), global);
+                }
+                self.project_profiles.insert(name, path);
+            }
+        }
+
+        self.project_dir = Some(dir);
+        Ok(())
     }
 
     /// Creates a new profile with the specified name and optional description.
@@ -62,26 +236,132 @@ impl ProfileManager {
     /// - A profile with the given name already exists
     /// - The configuration cannot be saved to disk
     pub fn create(&mut self, name: String, description: Option<String>) -> Result<()> {
-        if self.config.profiles.contains_key(&name) {
+        self.create_in(name, description, None, ProfileScope::Global)
+    }
+
+    /// Creates a new profile that inherits from `parent`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A profile with the given name already exists
+    /// - The parent profile is not found
+    /// - The configuration cannot be saved to disk
+    pub fn create_from(&mut self, name: String, description: Option<String>, parent: &str) -> Result<()> {
+        self.create_in(name, description, Some(parent), ProfileScope::Global)
+    }
+
+    /// Creates a new profile stored in `scope`, optionally inheriting from `parent`.
+    ///
+    /// A project profile may have the name of a global profile, which it then
+    /// hides while working in the project.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A profile with the given name already exists in `scope`
+    /// - The parent profile is not found or is the profile itself
+    /// - `scope` is [`ProfileScope::Project`] but there is no current project,
+    ///   or the name cannot be used as a file name
+    /// - The configuration cannot be saved to disk
+    pub fn create_in(
+        &mut self,
+        name: String,
+        description: Option<String>,
+        parent: Option<&str>,
+        scope: ProfileScope,
+    ) -> Result<()> {
+        let exists = match scope {
+            ProfileScope::Global => self.config.profiles.contains_key(&name) || self.shadowed.contains_key(&name),
+            ProfileScope::Project => self.project_profiles.contains_key(&name),
+        };
+        if exists {
             return Err(eyre!("Profile '{}' already exists", name));
         }
+        if let Some(parent) = parent {
+            if parent == name {
+                return Err(eyre!("Profile '{}' cannot inherit from itself", name));
+            }
+            if !self.config.profiles.contains_key(parent) {
+                return Err(eyre!("Parent profile '{}' not found", parent));
+            }
+        }
+
+        let mut profile = Profile::new(name.clone(), description);
+        profile.parent = parent.map(str::to_string);
+
+        if scope == ProfileScope::Project {
+            let path = self.project_file(&name)?;
+            if let Some(global) = self.config.profiles.remove(&name) {
+                self.shadowed.insert(name.clone(), global);
+            }
+            self.project_profiles.insert(name.clone(), path);
+        }
 
-        let profile = Profile::new(name.clone(), description);
         self.config.profiles.insert(name, profile);
         self.save()?;
         Ok(())
     }
 
+    /// The file a new project profile is stored in.
+    fn project_file(&self, name: &str) -> Result<PathBuf> {
+        let dir = self
+            .project_dir
+            .as_ref()
+            .ok_or_else(|| eyre!("Not inside an envx project. Run 'envx project init' first."))?;
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(eyre!("Profile name '{}' cannot be used as a file name", name));
+        }
+        Ok(dir.join(format!("{name}.yaml")))
+    }
+
+    /// Returns where the profile is stored, or `None` if it doesn't exist.
+    #[must_use]
+    pub fn scope(&self, name: &str) -> Option<ProfileScope> {
+        if self.project_profiles.contains_key(name) {
+            Some(ProfileScope::Project)
+        } else if self.config.profiles.contains_key(name) {
+            Some(ProfileScope::Global)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the project profile `name` hides a global profile.
+    #[must_use]
+    pub fn shadows_global(&self, name: &str) -> bool {
+        self.shadowed.contains_key(name)
+    }
+
+    /// Returns the directory project profiles are stored in, if envx runs
+    /// inside a project.
+    #[must_use]
+    pub fn project_dir(&self) -> Option<&Path> {
+        self.project_dir.as_deref()
+    }
+
     /// Deletes the specified profile.
     ///
     /// If the deleted profile is currently active, the active profile will be set to None.
+    /// Deleting a project profile removes its file and makes a global profile of
+    /// the same name visible again.
     ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - The specified profile is not found
+    /// - Other profiles inherit from the profile
     /// - The configuration cannot be saved to disk
     pub fn delete(&mut self, name: &str) -> Result<()> {
+        let children: Vec<&str> = self.children(name).iter().map(|p| p.name.as_str()).collect();
+        if !children.is_empty() {
+            return Err(eyre!(
+                "Profile '{}' is inherited by {}. Change their parent first.",
+                name,
+                children.join(", ")
+            ));
+        }
+
         if self.config.active.as_ref() == Some(&name.to_string()) {
             self.config.active = None;
         }
@@ -91,6 +371,16 @@ impl ProfileManager {
             .remove(name)
             .ok_or_else(|| color_eyre::eyre::eyre!("Profile '{}' not found", name))?;
 
+        if let Some(path) = self.project_profiles.remove(name) {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            self.written.get_mut().remove(&path);
+            if let Some(global) = self.shadowed.remove(name) {
+                self.config.profiles.insert(name.to_string(), global);
+            }
+        }
+
         self.save()?;
         Ok(())
     }
@@ -134,51 +424,459 @@ impl ProfileManager {
         Ok(())
     }
 
+    /// Returns a profile followed by its parent, grandparent and so on.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The specified profile or one of its ancestors is not found
+    /// - The parent chain contains a cycle
+    pub fn chain(&self, name: &str) -> Result<Vec<&Profile>> {
+        let mut chain: Vec<&Profile> = Vec::new();
+        let mut current = Some(name);
+
+        while let Some(profile_name) = current {
+            if chain.iter().any(|p| p.name == profile_name) {
+                let path: Vec<&str> = chain.iter().map(|p| p.name.as_str()).chain([profile_name]).collect();
+                return Err(eyre!(
+                    "Profile '{}' inherits from itself: {}",
+                    profile_name,
+                    path.join(" → ")
+                ));
+            }
+
+            let profile = self
+                .get(profile_name)
+                .ok_or_else(|| eyre!("Profile '{}' not found", profile_name))?;
+            chain.push(profile);
+            current = profile.parent.as_deref();
+        }
+
+        Ok(chain)
+    }
+
+    /// Returns the profiles that directly inherit from `name`.
+    #[must_use]
+    pub fn children(&self, name: &str) -> Vec<&Profile> {
+        self.config
+            .profiles
+            .values()
+            .filter(|p| p.parent.as_deref() == Some(name))
+            .collect()
+    }
+
+    /// Sets the profile `name` inherits from, or removes inheritance if
+    /// `parent` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The specified profile or parent is not found
+    /// - Inheriting from `parent` would create a cycle
+    /// - The configuration cannot be saved to disk
+    pub fn set_parent(&mut self, name: &str, parent: Option<&str>) -> Result<()> {
+        if !self.config.profiles.contains_key(name) {
+            return Err(eyre!("Profile '{}' not found", name));
+        }
+
+        if let Some(parent) = parent {
+            let ancestors = self.chain(parent)?;
+            if ancestors.iter().any(|p| p.name == name) {
+                let path: Vec<&str> = [name]
+                    .into_iter()
+                    .chain(ancestors.iter().map(|p| p.name.as_str()))
+                    .collect();
+                return Err(eyre!(
+                    "Profile '{}' cannot inherit from '{}': it would inherit from itself ({})",
+                    name,
+                    parent,
+                    path.join(" → ")
+                ));
+            }
+        }
+
+        let profile = self
+            .get_mut(name)
+            .ok_or_else(|| eyre!("Profile '{}' not found", name))?;
+        profile.parent = parent.map(str::to_string);
+        profile.updated_at = chrono::Utc::now();
+        self.save()
+    }
+
+    /// Resolves the variables a profile provides, including inherited ones.
+    ///
+    /// Parent profiles are resolved first, so a child's value overrides its
+    /// parent's. Disabled variables are skipped. Each value is paired with the
+    /// name of the profile that defines it. Encrypted values are decrypted and,
+    /// like other secrets, marked [`ResolvedVar::literal`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The specified profile is not found
+    /// - A parent profile is not found
+    /// - The parent chain contains a cycle
+    /// - An encrypted value cannot be decrypted
+    pub fn resolve(&self, name: &str) -> Result<IndexMap<String, ResolvedVar>> {
+        let chain = self.chain(name)?;
+
+        let secrets = SecretClassifier::for_current_project();
+        let mut resolved = IndexMap::new();
+        for profile in chain.iter().rev() {
+            for (var_name, var) in &profile.variables {
+                if var.enabled {
+                    let value = self.decrypt(&var.value)?;
+                    resolved.insert(
+                        var_name.clone(),
+                        ResolvedVar {
+                            literal: is_encrypted(&var.value) || secrets.is_secret(var_name, &value),
+                            value,
+                            profile: profile.name.clone(),
+                            override_system: var.override_system,
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Resolves several profiles layered in order, later profiles overriding
+    /// earlier ones.
+    ///
+    /// `system` returns the value of a system variable. A system variable is
+    /// only replaced if the winning definition sets `override_system`,
+    /// otherwise the variable is left out of the result. Variables that
+    /// layers define differently, or that the system also sets, are reported
+    /// as conflicts.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a profile cannot be resolved
+    /// (see [`ProfileManager::resolve`]).
+    pub fn resolve_layers<S: AsRef<str>>(
+        &self,
+        names: &[S],
+        system: impl Fn(&str) -> Option<String>,
+    ) -> Result<(IndexMap<String, ResolvedVar>, Vec<LayerConflict>)> {
+        let mut definitions: IndexMap<String, Vec<ResolvedVar>> = IndexMap::new();
+        for name in names {
+            for (var_name, var) in self.resolve(name.as_ref())? {
+                let layers = definitions.entry(var_name).or_default();
+                // Layers sharing a parent provide the same definition
+                if layers.last() != Some(&var) {
+                    layers.push(var);
+                }
+            }
+        }
+
+        let mut resolved = IndexMap::new();
+        let mut conflicts = Vec::new();
+        for (var_name, layers) in definitions {
+            let Some(winner) = layers.last().cloned() else {
+                continue;
+            };
+
+            let system_value = system(&var_name).filter(|value| *value != winner.value);
+            let keep_system = system_value.is_some() && !winner.override_system;
+            if system_value.is_some() || layers.iter().any(|layer| layer.value != winner.value) {
+                conflicts.push(LayerConflict {
+                    name: var_name.clone(),
+                    layers,
+                    system: system_value,
+                    winner: (!keep_system).then(|| winner.profile.clone()),
+                });
+            }
+
+            if !keep_system {
+                resolved.insert(var_name, winner);
+            }
+        }
+
+        Ok((resolved, conflicts))
+    }
+
     /// Applies a profile's environment variables to the given `EnvVarManager`.
     ///
-    /// If the profile has a parent profile, it will be applied first recursively,
+    /// If the profile has a parent profile, its variables are applied first,
     /// then the current profile's variables will be applied, potentially overriding
     /// parent values.
     ///
+    /// References like `${VAR}` in the values are expanded, see
+    /// [`Expander`]. Returns the references that could not be resolved.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
-    /// - The specified profile is not found
-    /// - A parent profile is not found during recursive application
+    /// - The profile cannot be resolved (see [`ProfileManager::resolve`])
+    /// - A value has an invalid or circular reference, or a required one is missing
     /// - Setting environment variables in the manager fails
-    pub fn apply(&self, name: &str, manager: &mut EnvVarManager) -> Result<()> {
-        let profile = self
-            .get(name)
-            .ok_or_else(|| color_eyre::eyre::eyre!("Profile '{}' not found", name))?;
-
-        // Apply parent profile first if exists
-        if let Some(parent) = &profile.parent {
-            self.apply(parent, manager)?;
-        }
+    pub fn apply(&self, name: &str, manager: &mut EnvVarManager) -> Result<Vec<Unresolved>> {
+        Ok(self.apply_layers(&[name], manager)?.unresolved)
+    }
 
-        // Apply this profile's variables
-        for (var_name, var) in &profile.variables {
-            if var.enabled {
+    /// Applies several profiles in layers, see [`ProfileManager::resolve_layers`].
+    ///
+    /// System variables are those defined system-wide, e.g. in
+    /// `/etc/environment` or the machine part of the Windows registry.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A profile cannot be resolved (see [`ProfileManager::resolve`])
+    /// - A value has an invalid or circular reference, or a required one is missing
+    /// - Setting environment variables in the manager fails
+    pub fn apply_layers<S: AsRef<str>>(&self, names: &[S], manager: &mut EnvVarManager) -> Result<LayerReport> {
+        let (mut vars, conflicts) = self.resolve_layers(names, |name| {
+            manager
+                .get(name)
+                .filter(|var| var.source == EnvVarSource::System)
+                .map(|var| var.value.clone())
+        })?;
+        let unresolved = expand_profile_vars(&mut vars, |var| manager.get(var).map(|v| v.value.clone()))?;
+
+        let names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+        let description = format!("apply profile {}", names.join(" + "));
+        manager.batch(&description, |manager| {
+            for (var_name, var) in vars {
                 // Always set the variable, regardless of whether it exists
                 // This ensures profile switching actually updates values
-                manager.set(var_name, &var.value, true)?;
+                manager.set_with_origin(&var_name, &var.value, true, VarOrigin::Profile(var.profile))?;
+            }
+
+            Ok(LayerReport { unresolved, conflicts })
+        })
+    }
+
+    /// Compares the variables two profiles define themselves.
+    ///
+    /// Encrypted values are decrypted, so the diff holds plain values.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Either profile is not found
+    /// - An encrypted value cannot be decrypted
+    pub fn diff(&self, from: &str, to: &str) -> Result<ProfileDiff> {
+        let decrypted = |name: &str| -> Result<BTreeMap<String, ProfileVar>> {
+            let profile = self.get(name).ok_or_else(|| eyre!("Profile '{}' not found", name))?;
+            profile
+                .variables
+                .iter()
+                .map(|(var_name, var)| {
+                    let mut var = var.clone();
+                    var.value = self.decrypt(&var.value)?;
+                    Ok((var_name.clone(), var))
+                })
+                .collect()
+        };
+        let old = decrypted(from)?;
+        let mut new = decrypted(to)?;
+
+        let mut diff = ProfileDiff::default();
+        for (name, old_var) in old {
+            match new.remove(&name) {
+                None => {
+                    diff.removed.insert(name, old_var);
+                }
+                Some(new_var)
+                    if old_var.value != new_var.value || old_var.override_system != new_var.override_system =>
+                {
+                    diff.changed.insert(name, (old_var, new_var));
+                }
+                Some(new_var) if old_var.enabled != new_var.enabled => {
+                    diff.toggled.insert(name, (old_var, new_var));
+                }
+                Some(_) => {}
             }
         }
+        diff.added = new;
 
-        Ok(())
+        Ok(diff)
+    }
+
+    /// Merges the variables of profile `src` into profile `dst`.
+    ///
+    /// Variables `dst` doesn't have are copied. For variables both profiles
+    /// define differently, `resolve` is called with the name and the
+    /// variables of `dst` and `src` and decides which one is kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Either profile is not found
+    /// - An encrypted value cannot be decrypted
+    /// - `resolve` returns an error
+    /// - The configuration cannot be saved to disk
+    pub fn merge(
+        &mut self,
+        src: &str,
+        dst: &str,
+        mut resolve: impl FnMut(&str, &ProfileVar, &ProfileVar) -> Result<MergeSide>,
+    ) -> Result<MergeSummary> {
+        let diff = self.diff(dst, src)?;
+        let mut summary = MergeSummary::default();
+
+        summary.added.extend(diff.added.into_keys());
+        for (name, (ours, theirs)) in diff.changed.into_iter().chain(diff.toggled) {
+            match resolve(&name, &ours, &theirs)? {
+                MergeSide::Ours => summary.kept.push(name),
+                MergeSide::Theirs => summary.taken.push(name),
+            }
+        }
+
+        // The diff holds decrypted values, so copy the variables as stored in
+        // `src` to keep encrypted ones encrypted
+        let source = self.get(src).ok_or_else(|| eyre!("Profile '{}' not found", src))?;
+        let taken: Vec<(String, ProfileVar)> = summary
+            .added
+            .iter()
+            .chain(&summary.taken)
+            .filter_map(|name| source.variables.get(name).map(|var| (name.clone(), var.clone())))
+            .collect();
+
+        let profile = self.get_mut(dst).ok_or_else(|| eyre!("Profile '{}' not found", dst))?;
+        if !taken.is_empty() {
+            profile.variables.extend(taken);
+            profile.updated_at = chrono::Utc::now();
+            self.save()?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Enables or disables the variables of a profile matching `pattern`,
+    /// keeping their values.
+    ///
+    /// `pattern` is a variable name or a wildcard pattern (`*` and `?`).
+    /// Disabled variables are skipped when the profile is resolved, so an
+    /// inherited value of the same name shows through. Returns the sorted
+    /// names of the matching variables.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The specified profile is not found
+    /// - No variable of the profile matches `pattern`
+    /// - The configuration cannot be saved to disk
+    pub fn set_enabled(&mut self, name: &str, pattern: &str, enabled: bool) -> Result<Vec<String>> {
+        let profile = self
+            .get_mut(name)
+            .ok_or_else(|| eyre!("Profile '{}' not found", name))?;
+
+        let mut matched: Vec<String> = if pattern.contains(['*', '?']) {
+            let re = Regex::new(&wildcard_to_regex(pattern))?;
+            profile
+                .variables
+                .keys()
+                .filter(|var| re.is_match(var))
+                .cloned()
+                .collect()
+        } else if profile.variables.contains_key(pattern) {
+            vec![pattern.to_string()]
+        } else {
+            Vec::new()
+        };
+        if matched.is_empty() {
+            return Err(eyre!("No variable in profile '{}' matches '{}'", name, pattern));
+        }
+        matched.sort();
+
+        for var in &matched {
+            profile.set_enabled(var, enabled);
+        }
+        self.save()?;
+
+        Ok(matched)
     }
 
     /// Exports a profile to JSON format.
     ///
+    /// Encrypted values are exported decrypted, as the key they are encrypted
+    /// with only exists on this machine.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - The specified profile is not found
+    /// - An encrypted value cannot be decrypted
     /// - The profile cannot be serialized to JSON
     pub fn export(&self, name: &str) -> Result<String> {
-        let profile = self.get(name).ok_or_else(|| eyre!("Profile '{}' not found", name))?;
+        let mut profile = self
+            .get(name)
+            .ok_or_else(|| eyre!("Profile '{}' not found", name))?
+            .clone();
+
+        for var in profile.variables.values_mut() {
+            var.value = self.decrypt(&var.value)?;
+        }
+
+        Ok(serde_json::to_string_pretty(&profile)?)
+    }
 
-        Ok(serde_json::to_string_pretty(profile)?)
+    /// Decrypts a value of a profile variable. Values that aren't encrypted are
+    /// returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be unlocked or the value cannot be decrypted.
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        if is_encrypted(value) {
+            self.vault(false)?.decrypt(value)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    /// Re-encrypts all secret values with `new` in memory, decrypting existing
+    /// ones with `old`. Returns the number of values encrypted. Nothing is
+    /// written until [`ProfileManager::save`] is called, which should only
+    /// happen once `new` is stored (see [`KeyStore::stage`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an encrypted value exists but `old` is `None` or
+    /// cannot decrypt it.
+    pub fn reencrypt(&mut self, old: Option<&Vault>, new: &Vault) -> Result<usize> {
+        let secrets = SecretClassifier::for_current_project();
+        let mut count = 0;
+
+        let project_profiles = &self.project_profiles;
+        let profiles = self
+            .config
+            .profiles
+            .iter_mut()
+            .map(|(name, profile)| (project_profiles.contains_key(name), profile))
+            .chain(self.shadowed.values_mut().map(|profile| (false, profile)));
+
+        for (in_project, profile) in profiles {
+            for (name, var) in &mut profile.variables {
+                let was_encrypted = is_encrypted(&var.value);
+                if was_encrypted {
+                    let old =
+                        old.ok_or_else(|| eyre!("Profile '{}' has encrypted values but no key exists", profile.name))?;
+                    var.value = old.decrypt(&var.value)?;
+                }
+
+                // Project files are only kept readable, see `save`
+                let encrypted = if in_project {
+                    if was_encrypted {
+                        var.value = new.encrypt(&var.value)?;
+                    }
+                    was_encrypted
+                } else {
+                    new.encrypt_secret(&secrets, name, &mut var.value)?
+                };
+                if encrypted {
+                    count += 1;
+                }
+            }
+        }
+
+        // The cached key is the old one
+        self.vault.take();
+        Ok(count)
     }
 
     /// Imports a profile from JSON data.
@@ -204,16 +902,120 @@ impl ProfileManager {
 
     /// Saves the current profile configuration to disk.
     ///
+    /// Global profiles are written to `profiles.json`, project profiles to
+    /// their YAML files; files whose profiles haven't changed are left alone.
+    /// Values of secret variables (see [`SecretClassifier`]) in global
+    /// profiles are encrypted, creating a key on first use (see
+    /// [`KeyStore::unlock`]). Project files are shared with everyone working
+    /// on the project, so they aren't encrypted with this machine's key.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
-    /// - The configuration cannot be serialized to JSON
-    /// - The configuration file cannot be written to disk
+    /// - The key to encrypt secrets with cannot be unlocked
+    /// - The configuration cannot be serialized to JSON or YAML
+    /// - A configuration file cannot be written to disk
     pub fn save(&self) -> Result<()> {
-        let content = serde_json::to_string_pretty(&self.config)?;
-        fs::write(&self.config_path, content)?;
+        let secrets = SecretClassifier::for_current_project();
+        let mut config = self.config.clone();
+
+        let project: Vec<(&PathBuf, Profile)> = self
+            .project_profiles
+            .iter()
+            .filter_map(|(name, path)| config.profiles.remove(name).map(|profile| (path, profile)))
+            .collect();
+        config.profiles.extend(
+            self.shadowed
+                .iter()
+                .map(|(name, profile)| (name.clone(), profile.clone())),
+        );
+
+        for profile in config.profiles.values_mut() {
+            for (name, var) in &mut profile.variables {
+                if !is_encrypted(&var.value) && secrets.is_secret(name, &var.value) {
+                    self.vault(true)?.encrypt_secret(&secrets, name, &mut var.value)?;
+                }
+            }
+        }
+
+        if self.remember(&self.config_path, &config)? {
+            fs::write(&self.config_path, serde_json::to_string_pretty(&config)?)?;
+        }
+
+        for (path, profile) in project {
+            if self.remember(path, &profile)? {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(path, serde_yaml::to_string(&profile)?)?;
+            }
+        }
         Ok(())
     }
+
+    /// Records that `path` holds `content`. Returns false if it already did,
+    /// so the file doesn't need to be written.
+    fn remember(&self, path: &Path, content: &impl Serialize) -> Result<bool> {
+        let value = serde_json::to_value(content)?;
+        let mut written = self.written.borrow_mut();
+        if written.get(path) == Some(&value) {
+            return Ok(false);
+        }
+        written.insert(path.to_path_buf(), value);
+        Ok(true)
+    }
+
+    /// Returns the key store profile secrets are encrypted with, the one next
+    /// to `profiles.json`.
+    #[must_use]
+    pub fn key_store(&self) -> KeyStore {
+        KeyStore::at(self.config_path.parent().unwrap_or(&self.config_path))
+    }
+
+    /// Returns the key secrets are encrypted with, unlocking it on first use
+    /// so it is derived once per manager. A key is only created if `create`
+    /// is true.
+    fn vault(&self, create: bool) -> Result<&Vault> {
+        if let Some(vault) = self.vault.get() {
+            return Ok(vault);
+        }
+
+        let keys = self.key_store();
+        let vault = if create {
+            keys.unlock()?
+        } else {
+            keys.unlock_existing()?
+        };
+        Ok(self.vault.get_or_init(|| vault))
+    }
+}
+
+/// Expands references in resolved profile values.
+///
+/// Values may reference other variables of the profile, anything else is
+/// looked up with `lookup`; `$$` stands for a literal `$`. Literal values
+/// (see [`ResolvedVar::literal`]) are kept as they are. Returns the
+/// references that could not be resolved.
+pub(crate) fn expand_profile_vars(
+    vars: &mut IndexMap<String, ResolvedVar>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<Unresolved>> {
+    let mut expander = Expander::new(lookup);
+    for (name, var) in vars.iter() {
+        if var.literal {
+            expander.define_literal(name, &var.value);
+        } else {
+            expander.define(name, &var.value);
+        }
+    }
+
+    for (name, value) in expander.expand_all()? {
+        if let Some(var) = vars.get_mut(&name) {
+            var.value = value;
+        }
+    }
+
+    Ok(expander.unresolved().to_vec())
 }
 
 #[cfg(test)]
@@ -232,7 +1034,7 @@ mod tests {
             profiles: HashMap::new(),
         };
 
-        let manager = ProfileManager { config_path, config };
+        let manager = ProfileManager::with_config(config_path, config);
 
         (manager, temp_dir)
     }
@@ -270,9 +1072,9 @@ mod tests {
         fs::write(&config_path, content).unwrap();
 
         // Now create manager with existing config
-        let manager = ProfileManager {
-            config_path: config_path.clone(),
-            config: if config_path.exists() {
+        let manager = ProfileManager::with_config(
+            config_path.clone(),
+            if config_path.exists() {
                 let content = fs::read_to_string(&config_path).unwrap();
                 serde_json::from_str(&content).unwrap()
             } else {
@@ -281,7 +1083,7 @@ mod tests {
                     profiles: HashMap::new(),
                 }
             },
-        };
+        );
 
         assert_eq!(manager.config.profiles.len(), 1);
         assert_eq!(manager.config.active, Some("test".to_string()));
@@ -435,7 +1237,7 @@ mod tests {
     #[test]
     fn test_apply_profile() {
         let (mut manager, _temp) = create_test_profile_manager();
-        let mut env_manager = EnvVarManager::new();
+        let mut env_manager = EnvVarManager::for_tests();
 
         // Create profile with variables
         manager.create("dev".to_string(), None).unwrap();
@@ -451,10 +1253,59 @@ mod tests {
         assert_eq!(env_manager.get("DEBUG").unwrap().value, "true");
     }
 
+    #[test]
+    fn test_apply_profile_expands_references() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        let mut env_manager = EnvVarManager::for_tests();
+        env_manager.set("ENVX_PROFILE_HOST", "db.local", false).unwrap();
+
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.add_var("DB_NAME".to_string(), "app".to_string(), false);
+        profile.add_var(
+            "DB_URL".to_string(),
+            "postgres://${ENVX_PROFILE_HOST}/${DB_NAME}?${ENVX_PROFILE_OPTS}".to_string(),
+            false,
+        );
+
+        let unresolved = manager.apply("dev", &mut env_manager).unwrap();
+
+        assert_eq!(env_manager.get("DB_URL").unwrap().value, "postgres://db.local/app?");
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].name, "ENVX_PROFILE_OPTS");
+        assert_eq!(unresolved[0].referenced_by.as_deref(), Some("DB_URL"));
+    }
+
+    #[test]
+    fn test_apply_profile_keeps_escapes_and_secrets_literal() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        let mut env_manager = EnvVarManager::for_tests();
+
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.add_var("PRICE_FORMAT".to_string(), "$$%.2f".to_string(), false);
+        profile.add_var("DB_PASSWORD".to_string(), "pa$word${X}".to_string(), false);
+        profile.add_var(
+            "DB_URL".to_string(),
+            "postgres://app:${DB_PASSWORD}@db".to_string(),
+            false,
+        );
+
+        let unresolved = manager.apply("dev", &mut env_manager).unwrap();
+
+        assert!(unresolved.is_empty());
+        assert_eq!(env_manager.get("PRICE_FORMAT").unwrap().value, "$%.2f");
+        assert_eq!(env_manager.get("DB_PASSWORD").unwrap().value, "pa$word${X}");
+        assert_eq!(
+            env_manager.get("DB_URL").unwrap().value,
+            "postgres://app:pa$word${X}@db"
+        );
+    }
+
     #[test]
     fn test_apply_profile_with_disabled_var() {
         let (mut manager, _temp) = create_test_profile_manager();
-        let mut env_manager = EnvVarManager::new();
+        let mut env_manager = EnvVarManager::for_tests();
 
         manager.create("dev".to_string(), None).unwrap();
         let profile = manager.get_mut("dev").unwrap();
@@ -477,7 +1328,7 @@ mod tests {
     #[test]
     fn test_apply_profile_with_parent() {
         let (mut manager, _temp) = create_test_profile_manager();
-        let mut env_manager = EnvVarManager::new();
+        let mut env_manager = EnvVarManager::for_tests();
 
         // Create parent profile
         manager.create("base".to_string(), None).unwrap();
@@ -499,18 +1350,430 @@ mod tests {
         assert_eq!(env_manager.get("DEV_VAR").unwrap().value, "dev_value");
         // Child should override parent
         assert_eq!(env_manager.get("OVERRIDE_ME").unwrap().value, "dev_override");
+
+        // Each value remembers the profile it came from
+        assert_eq!(
+            env_manager.get("BASE_VAR").unwrap().origin,
+            Some(VarOrigin::Profile("base".to_string()))
+        );
+        assert_eq!(
+            env_manager.get("OVERRIDE_ME").unwrap().origin,
+            Some(VarOrigin::Profile("dev".to_string()))
+        );
     }
 
     #[test]
     fn test_apply_nonexistent_profile() {
         let (manager, _temp) = create_test_profile_manager();
-        let mut env_manager = EnvVarManager::new();
+        let mut env_manager = EnvVarManager::for_tests();
 
         let result = manager.apply("nonexistent", &mut env_manager);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
     }
 
+    #[test]
+    fn test_resolve_profile_chain() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("base".to_string(), None).unwrap();
+        let profile = manager.get_mut("base").unwrap();
+        profile.add_var("SHARED".to_string(), "base".to_string(), false);
+        profile.add_var("BASE_ONLY".to_string(), "1".to_string(), false);
+
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.parent = Some("base".to_string());
+        profile.add_var("SHARED".to_string(), "dev".to_string(), false);
+
+        let resolved = manager.resolve("dev").unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved["SHARED"].value, "dev");
+        assert_eq!(resolved["SHARED"].profile, "dev");
+        assert_eq!(resolved["BASE_ONLY"].profile, "base");
+    }
+
+    #[test]
+    fn test_resolve_profile_cycle() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("a".to_string(), None).unwrap();
+        manager.create("b".to_string(), None).unwrap();
+        manager.get_mut("a").unwrap().parent = Some("b".to_string());
+        manager.get_mut("b").unwrap().parent = Some("a".to_string());
+
+        let result = manager.resolve("a");
+        assert!(result.unwrap_err().to_string().contains("inherits from itself"));
+    }
+
+    #[test]
+    fn test_create_from_multi_level_chain() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("base".to_string(), None).unwrap();
+        manager.create_from("staging".to_string(), None, "base").unwrap();
+        manager.create_from("staging-eu".to_string(), None, "staging").unwrap();
+        manager
+            .get_mut("base")
+            .unwrap()
+            .add_var("A".to_string(), "base".to_string(), false);
+        manager
+            .get_mut("base")
+            .unwrap()
+            .add_var("B".to_string(), "base".to_string(), false);
+        manager
+            .get_mut("staging")
+            .unwrap()
+            .add_var("B".to_string(), "staging".to_string(), false);
+        manager
+            .get_mut("staging-eu")
+            .unwrap()
+            .add_var("C".to_string(), "eu".to_string(), false);
+
+        let chain: Vec<&str> = manager
+            .chain("staging-eu")
+            .unwrap()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(chain, ["staging-eu", "staging", "base"]);
+
+        let resolved = manager.resolve("staging-eu").unwrap();
+        assert_eq!(resolved["A"].profile, "base");
+        assert_eq!(resolved["B"].value, "staging");
+        assert_eq!(resolved["C"].profile, "staging-eu");
+
+        assert!(manager.create_from("x".to_string(), None, "missing").is_err());
+    }
+
+    #[test]
+    fn test_set_parent_rejects_cycles() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("a".to_string(), None).unwrap();
+        manager.create("b".to_string(), None).unwrap();
+        manager.create("c".to_string(), None).unwrap();
+        manager.set_parent("b", Some("a")).unwrap();
+        manager.set_parent("c", Some("b")).unwrap();
+
+        let err = manager.set_parent("a", Some("c")).unwrap_err().to_string();
+        assert!(err.contains("a → c → b → a"), "{err}");
+        assert!(manager.set_parent("a", Some("a")).is_err());
+        assert!(manager.get("a").unwrap().parent.is_none());
+
+        manager.set_parent("c", None).unwrap();
+        assert!(manager.get("c").unwrap().parent.is_none());
+    }
+
+    #[test]
+    fn test_resolve_layers_reports_conflicts() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("base".to_string(), None).unwrap();
+        manager.create_from("aws".to_string(), None, "base").unwrap();
+        manager.create("local".to_string(), None).unwrap();
+        let base = manager.get_mut("base").unwrap();
+        base.add_var("REGION".to_string(), "eu".to_string(), false);
+        base.add_var("SHARED".to_string(), "same".to_string(), false);
+        manager
+            .get_mut("aws")
+            .unwrap()
+            .add_var("REGION".to_string(), "us".to_string(), false);
+        manager
+            .get_mut("local")
+            .unwrap()
+            .add_var("SHARED".to_string(), "same".to_string(), false);
+
+        let (vars, conflicts) = manager.resolve_layers(&["base", "aws", "local"], |_| None).unwrap();
+        assert_eq!(vars["REGION"].value, "us");
+        assert_eq!(vars["REGION"].profile, "aws");
+
+        // Equal values and definitions shared through a parent are no conflict
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].name, "REGION");
+        assert_eq!(conflicts[0].layers.len(), 2);
+        assert_eq!(conflicts[0].winner.as_deref(), Some("aws"));
+    }
+
+    #[test]
+    fn test_apply_layers_respects_override_system() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        let mut env_manager = EnvVarManager::for_tests();
+        for name in ["LANG", "EDITOR"] {
+            env_manager.vars.insert(
+                name.to_string(),
+                crate::EnvVar {
+                    name: name.to_string(),
+                    value: "system".to_string(),
+                    source: EnvVarSource::System,
+                    modified: chrono::Utc::now(),
+                    original_value: None,
+                    origin: None,
+                },
+            );
+        }
+
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.add_var("LANG".to_string(), "C".to_string(), false);
+        profile.add_var("EDITOR".to_string(), "vim".to_string(), true);
+
+        let report = manager.apply_layers(&["dev"], &mut env_manager).unwrap();
+        assert_eq!(env_manager.get("LANG").unwrap().value, "system");
+        assert_eq!(env_manager.get("EDITOR").unwrap().value, "vim");
+
+        assert_eq!(report.conflicts.len(), 2);
+        let lang = report.conflicts.iter().find(|c| c.name == "LANG").unwrap();
+        assert_eq!(lang.system.as_deref(), Some("system"));
+        assert!(lang.winner.is_none());
+    }
+
+    fn create_diff_profiles(manager: &mut ProfileManager) {
+        manager.create("dev".to_string(), None).unwrap();
+        manager.create("prod".to_string(), None).unwrap();
+
+        let dev = manager.get_mut("dev").unwrap();
+        dev.add_var("SAME".to_string(), "1".to_string(), false);
+        dev.add_var("CHANGED".to_string(), "dev".to_string(), false);
+        dev.add_var("TOGGLED".to_string(), "on".to_string(), false);
+        dev.add_var("DEV_ONLY".to_string(), "x".to_string(), false);
+
+        let prod = manager.get_mut("prod").unwrap();
+        prod.add_var("SAME".to_string(), "1".to_string(), false);
+        prod.add_var("CHANGED".to_string(), "prod".to_string(), false);
+        prod.add_var("TOGGLED".to_string(), "on".to_string(), false);
+        prod.variables.get_mut("TOGGLED").unwrap().enabled = false;
+        prod.add_var("PROD_ONLY".to_string(), "y".to_string(), false);
+    }
+
+    #[test]
+    fn test_diff_profiles() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        create_diff_profiles(&mut manager);
+
+        let diff = manager.diff("dev", "prod").unwrap();
+        assert_eq!(diff.added.keys().collect::<Vec<_>>(), ["PROD_ONLY"]);
+        assert_eq!(diff.removed.keys().collect::<Vec<_>>(), ["DEV_ONLY"]);
+        assert_eq!(diff.changed["CHANGED"].0.value, "dev");
+        assert_eq!(diff.changed["CHANGED"].1.value, "prod");
+        assert!(!diff.toggled["TOGGLED"].1.enabled);
+        assert!(!diff.changed.contains_key("SAME"));
+
+        assert!(manager.diff("dev", "dev").unwrap().is_empty());
+        assert!(manager.diff("dev", "missing").is_err());
+    }
+
+    #[test]
+    fn test_merge_profiles_with_strategy() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        create_diff_profiles(&mut manager);
+
+        let summary = manager.merge("prod", "dev", |_, _, _| Ok(MergeSide::Ours)).unwrap();
+        assert_eq!(summary.added, ["PROD_ONLY"]);
+        assert_eq!(summary.kept, ["CHANGED", "TOGGLED"]);
+        let dev = manager.get("dev").unwrap();
+        assert_eq!(dev.variables["CHANGED"].value, "dev");
+        assert_eq!(dev.variables["PROD_ONLY"].value, "y");
+        assert!(dev.variables.contains_key("DEV_ONLY"));
+
+        let summary = manager.merge("prod", "dev", |_, _, _| Ok(MergeSide::Theirs)).unwrap();
+        assert!(summary.added.is_empty());
+        assert_eq!(summary.taken, ["CHANGED", "TOGGLED"]);
+        let dev = manager.get("dev").unwrap();
+        assert_eq!(dev.variables["CHANGED"].value, "prod");
+        assert!(!dev.variables["TOGGLED"].enabled);
+        assert!(dev.variables.contains_key("DEV_ONLY"));
+    }
+
+    #[test]
+    fn test_merge_keeps_encrypted_values_encrypted() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        manager.create("prod".to_string(), None).unwrap();
+        manager.create("dev".to_string(), None).unwrap();
+        let prod = manager.get_mut("prod").unwrap();
+        prod.add_var("API_TOKEN".to_string(), "tok-abc123".to_string(), false);
+        manager.save().unwrap();
+        let content = fs::read_to_string(&manager.config_path).unwrap();
+        let mut manager =
+            ProfileManager::with_config(manager.config_path.clone(), serde_json::from_str(&content).unwrap());
+        let stored = manager.get("prod").unwrap().variables["API_TOKEN"].value.clone();
+        assert!(is_encrypted(&stored));
+
+        let summary = manager.merge("prod", "dev", |_, _, _| Ok(MergeSide::Theirs)).unwrap();
+        assert_eq!(summary.added, ["API_TOKEN"]);
+        assert_eq!(manager.get("dev").unwrap().variables["API_TOKEN"].value, stored);
+    }
+
+    #[test]
+    fn test_set_enabled_by_name_and_pattern() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        manager.create("base".to_string(), None).unwrap();
+        manager.create_from("dev".to_string(), None, "base").unwrap();
+
+        let base = manager.get_mut("base").unwrap();
+        base.add_var("API_URL".to_string(), "https://base".to_string(), false);
+        let dev = manager.get_mut("dev").unwrap();
+        dev.add_var("API_URL".to_string(), "https://dev".to_string(), false);
+        dev.add_var("API_TIMEOUT".to_string(), "30".to_string(), false);
+        dev.add_var("DEBUG".to_string(), "1".to_string(), false);
+
+        let changed = manager.set_enabled("dev", "API_*", false).unwrap();
+        assert_eq!(changed, ["API_TIMEOUT", "API_URL"]);
+
+        let dev = manager.get("dev").unwrap();
+        assert_eq!(dev.variables["API_URL"].value, "https://dev");
+        assert!(!dev.variables["API_URL"].enabled);
+        assert!(dev.variables["DEBUG"].enabled);
+
+        // The inherited value shows through the disabled one
+        let resolved = manager.resolve("dev").unwrap();
+        assert_eq!(resolved["API_URL"].value, "https://base");
+        assert!(!resolved.contains_key("API_TIMEOUT"));
+
+        assert_eq!(manager.set_enabled("dev", "API_URL", true).unwrap(), ["API_URL"]);
+        assert_eq!(manager.resolve("dev").unwrap()["API_URL"].value, "https://dev");
+
+        assert!(manager.set_enabled("dev", "MISSING", false).is_err());
+        assert!(manager.set_enabled("dev", "NOPE_*", false).is_err());
+        assert!(manager.set_enabled("missing", "DEBUG", false).is_err());
+    }
+
+    fn load_test_project(temp: &TempDir) -> ProfileManager {
+        let config_path = temp.path().join("profiles.json");
+        let config = serde_json::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        let mut manager = ProfileManager::with_config(config_path, config);
+        manager
+            .load_project(temp.path().join("project").join(PROJECT_PROFILES_DIR))
+            .unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_project_profiles_shadow_global_ones() {
+        let (mut manager, temp) = create_test_profile_manager();
+        manager.create("dev".to_string(), Some("global".to_string())).unwrap();
+        manager.create("base".to_string(), None).unwrap();
+        manager
+            .get_mut("dev")
+            .unwrap()
+            .add_var("WHERE".to_string(), "global".to_string(), false);
+        manager.save().unwrap();
+
+        // Creating a project profile needs a project
+        let err = manager.create_in("dev".to_string(), None, None, ProfileScope::Project);
+        assert!(err.is_err());
+
+        let mut manager = load_test_project(&temp);
+        manager
+            .create_in(
+                "dev".to_string(),
+                Some("project".to_string()),
+                Some("base"),
+                ProfileScope::Project,
+            )
+            .unwrap();
+        manager
+            .get_mut("dev")
+            .unwrap()
+            .add_var("WHERE".to_string(), "project".to_string(), false);
+        manager.save().unwrap();
+        assert!(
+            manager
+                .create_in("dev".to_string(), None, None, ProfileScope::Project)
+                .is_err()
+        );
+
+        let file = temp.path().join("project").join(PROJECT_PROFILES_DIR).join("dev.yaml");
+        assert!(file.exists());
+        let global = fs::read_to_string(temp.path().join("profiles.json")).unwrap();
+        assert!(global.contains("\"global\""));
+        assert!(!global.contains("\"project\""));
+
+        let mut manager = load_test_project(&temp);
+        assert_eq!(manager.scope("dev"), Some(ProfileScope::Project));
+        assert_eq!(manager.scope("base"), Some(ProfileScope::Global));
+        assert!(manager.shadows_global("dev"));
+        assert_eq!(manager.resolve("dev").unwrap()["WHERE"].value, "project");
+        assert_eq!(manager.list().len(), 2);
+
+        manager.delete("dev").unwrap();
+        assert!(!file.exists());
+        assert_eq!(manager.scope("dev"), Some(ProfileScope::Global));
+        assert_eq!(manager.resolve("dev").unwrap()["WHERE"].value, "global");
+    }
+
+    #[test]
+    fn test_save_only_rewrites_changed_project_profiles() {
+        let (mut manager, temp) = create_test_profile_manager();
+        manager.create("base".to_string(), None).unwrap();
+
+        let dir = temp.path().join("project").join(PROJECT_PROFILES_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        let ci = "# Used by the CI pipeline\nvariables:\n  CI:\n    value: \"true\"\n";
+        fs::write(dir.join("ci.yaml"), ci).unwrap();
+        fs::write(dir.join("dev.yaml"), "variables: {}\n").unwrap();
+
+        let mut manager = load_test_project(&temp);
+        manager
+            .get_mut("dev")
+            .unwrap()
+            .add_var("API_TOKEN".to_string(), "tok-abc123".to_string(), false);
+        manager.save().unwrap();
+
+        // The untouched file keeps its comment, the changed one isn't encrypted
+        // with this machine's key
+        assert_eq!(fs::read_to_string(dir.join("ci.yaml")).unwrap(), ci);
+        let dev = fs::read_to_string(dir.join("dev.yaml")).unwrap();
+        assert!(dev.contains("tok-abc123"));
+    }
+
+    #[test]
+    fn test_load_hand_written_project_profile() {
+        let (manager, temp) = create_test_profile_manager();
+        manager.save().unwrap();
+
+        let dir = temp.path().join("project").join(PROJECT_PROFILES_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ci.yml"),
+            "description: CI runs\nvariables:\n  CI:\n    value: \"true\"\n  DEBUG:\n    value: \"1\"\n    enabled: false\n",
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "not a profile").unwrap();
+
+        let manager = load_test_project(&temp);
+        let profile = manager.get("ci").unwrap();
+        assert_eq!(profile.name, "ci");
+        assert_eq!(profile.description.as_deref(), Some("CI runs"));
+        assert!(profile.variables["CI"].enabled);
+        assert!(!profile.variables["DEBUG"].override_system);
+
+        let resolved = manager.resolve("ci").unwrap();
+        assert_eq!(resolved["CI"].value, "true");
+        assert!(!resolved.contains_key("DEBUG"));
+
+        fs::write(dir.join("broken.yaml"), "variables: [").unwrap();
+        let config_path = temp.path().join("profiles.json");
+        let config = serde_json::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        let mut manager = ProfileManager::with_config(config_path, config);
+        let err = manager.load_project(dir).unwrap_err().to_string();
+        assert!(err.contains("broken.yaml"));
+    }
+
+    #[test]
+    fn test_delete_inherited_profile() {
+        let (mut manager, _temp) = create_test_profile_manager();
+
+        manager.create("base".to_string(), None).unwrap();
+        manager.create_from("dev".to_string(), None, "base").unwrap();
+
+        let err = manager.delete("base").unwrap_err().to_string();
+        assert!(err.contains("inherited by dev"));
+
+        manager.delete("dev").unwrap();
+        manager.delete("base").unwrap();
+    }
+
     #[test]
     fn test_export_profile() {
         let (mut manager, _temp) = create_test_profile_manager();
@@ -615,13 +1878,13 @@ mod tests {
 
         // Create and save
         {
-            let mut manager = ProfileManager {
-                config_path: config_path.clone(),
-                config: ProfileConfig {
+            let mut manager = ProfileManager::with_config(
+                config_path.clone(),
+                ProfileConfig {
                     active: None,
                     profiles: HashMap::new(),
                 },
-            };
+            );
 
             manager.create("dev".to_string(), None).unwrap();
             manager.create("prod".to_string(), None).unwrap();
@@ -635,13 +1898,10 @@ mod tests {
         {
             assert!(config_path.exists());
 
-            let manager = ProfileManager {
-                config_path: config_path.clone(),
-                config: {
-                    let content = fs::read_to_string(&config_path).unwrap();
-                    serde_json::from_str(&content).unwrap()
-                },
-            };
+            let manager = ProfileManager::with_config(config_path.clone(), {
+                let content = fs::read_to_string(&config_path).unwrap();
+                serde_json::from_str(&content).unwrap()
+            });
 
             assert_eq!(manager.config.profiles.len(), 2);
             assert_eq!(manager.config.active, Some("dev".to_string()));
@@ -665,4 +1925,54 @@ mod tests {
         let profile_mut = manager.get_mut("test");
         assert!(profile_mut.is_some());
     }
+
+    #[test]
+    fn test_secrets_are_encrypted_at_rest() {
+        let (mut manager, _temp) = create_test_profile_manager();
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.add_var("API_TOKEN".to_string(), "tok-abc123".to_string(), false);
+        profile.add_var("PORT".to_string(), "3000".to_string(), false);
+        manager.save().unwrap();
+
+        let content = fs::read_to_string(&manager.config_path).unwrap();
+        assert!(!content.contains("tok-abc123"));
+        assert!(content.contains("3000"));
+
+        let reloaded =
+            ProfileManager::with_config(manager.config_path.clone(), serde_json::from_str(&content).unwrap());
+        let stored = &reloaded.get("dev").unwrap().variables["API_TOKEN"].value;
+        assert!(is_encrypted(stored));
+        assert_eq!(reloaded.decrypt(stored).unwrap(), "tok-abc123");
+
+        let resolved = reloaded.resolve("dev").unwrap();
+        assert_eq!(resolved["API_TOKEN"].value, "tok-abc123");
+        assert!(reloaded.export("dev").unwrap().contains("tok-abc123"));
+    }
+
+    #[test]
+    fn test_reencrypt_profiles() {
+        let (mut manager, temp) = create_test_profile_manager();
+        manager.create("dev".to_string(), None).unwrap();
+        let profile = manager.get_mut("dev").unwrap();
+        profile.add_var("API_TOKEN".to_string(), "tok-abc123".to_string(), false);
+        manager.save().unwrap();
+
+        let content = fs::read_to_string(&manager.config_path).unwrap();
+        manager.config = serde_json::from_str(&content).unwrap();
+
+        let keys = KeyStore::at(temp.path());
+        let old = keys.open().unwrap().unwrap();
+        let new = keys.generate(&crate::KeySource::Keyfile(None)).unwrap();
+        assert_eq!(manager.reencrypt(Some(&old), new.vault()).unwrap(), 1);
+        // Nothing is written before the new key is stored
+        assert_eq!(fs::read_to_string(&manager.config_path).unwrap(), content);
+        keys.stage(&new).unwrap();
+        manager.save().unwrap();
+        keys.commit(new).unwrap();
+
+        let stored = manager.get("dev").unwrap().variables["API_TOKEN"].value.clone();
+        assert!(old.decrypt(&stored).is_err());
+        assert_eq!(manager.resolve("dev").unwrap()["API_TOKEN"].value, "tok-abc123");
+    }
 }
@@ -20,8 +20,8 @@ pub struct ProjectConfig {
     /// Files to auto-load (in order)
     pub auto_load: Vec<String>,
 
-    /// Profile to activate
-    pub profile: Option<String>,
+    /// Profile to activate, or a list of profiles applied in layers
+    pub profile: Option<ProfileSelection>,
 
     /// Scripts to run
     pub scripts: HashMap<String, Script>,
@@ -31,6 +31,29 @@ pub struct ProjectConfig {
 
     /// Inherit from parent directories
     pub inherit: bool,
+
+    /// Variables holding secrets, masked in output
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sensitive: Vec<String>,
+}
+
+/// One profile, or several profiles layered in order with later ones
+/// overriding earlier ones
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileSelection {
+    One(String),
+    Layers(Vec<String>),
+}
+
+impl ProfileSelection {
+    #[must_use]
+    pub fn names(&self) -> &[String] {
+        match self {
+            Self::One(name) => std::slice::from_ref(name),
+            Self::Layers(names) => names,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +95,7 @@ impl Default for ProjectConfig {
             scripts: HashMap::new(),
             validation: ValidationRules::default(),
             inherit: true,
+            sensitive: Vec::new(),
         }
     }
 }
@@ -1,5 +1,11 @@
+use crate::dotenv;
+use crate::expand::Expander;
+use crate::importer::is_toml;
 use crate::project_config::ProjectConfig;
-use crate::{EnvVarManager, ProfileManager, ValidationRules};
+use crate::{
+    EnvComposer, EnvVarManager, ImportFormat, Importer, LayerConflict, LayerReport, ProfileManager, ValidationRules,
+    VarOrigin,
+};
 use ahash::AHashMap as HashMap;
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
@@ -10,6 +16,7 @@ use std::path::{Path, PathBuf};
 pub struct ProjectManager {
     config_dir: PathBuf,
     config: Option<ProjectConfig>,
+    config_path: Option<PathBuf>,
     current_dir: PathBuf,
 }
 
@@ -20,11 +27,25 @@ impl ProjectManager {
     ///
     /// This function will return an error if getting the current directory fails.
     pub fn new() -> Result<Self> {
-        Ok(Self {
+        Ok(Self::for_dir(std::env::current_dir()?))
+    }
+
+    /// Create a `ProjectManager` that searches for a project from `dir` instead
+    /// of the current directory
+    #[must_use]
+    pub fn for_dir(dir: PathBuf) -> Self {
+        Self {
             config_dir: PathBuf::from(".envx"),
             config: None,
-            current_dir: std::env::current_dir()?,
-        })
+            config_path: None,
+            current_dir: dir,
+        }
+    }
+
+    /// Returns the loaded project configuration, if any
+    #[must_use]
+    pub const fn config(&self) -> Option<&ProjectConfig> {
+        self.config.as_ref()
     }
 
     /// Initialize a new project configuration
@@ -85,6 +106,7 @@ impl ProjectManager {
             scripts: HashMap::new(),
             validation: ValidationRules::default(),
             inherit: true,
+            sensitive: Vec::new(),
         };
 
         config.save(file_path)?;
@@ -105,6 +127,7 @@ impl ProjectManager {
             let config_path = current.join(".envx").join("config.yaml");
             if config_path.exists() {
                 self.config = Some(ProjectConfig::load(&config_path)?);
+                self.config_path = Some(config_path);
                 return Ok(Some(current));
             }
 
@@ -118,40 +141,131 @@ impl ProjectManager {
 
     /// Apply project configuration
     ///
+    /// The project profiles are applied in layers, see
+    /// [`ProfileManager::apply_layers`]. References like `${VAR}` in the
+    /// defaults are expanded. Returns the references that could not be
+    /// resolved and the variables the profile layers disagree on.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - No project configuration is loaded
     /// - Profile application fails
     /// - Loading environment files fails
+    /// - A default has an invalid or circular reference, or a required one is missing
     /// - Setting environment variables fails
-    pub fn apply(&self, manager: &mut EnvVarManager, profile_manager: &mut ProfileManager) -> Result<()> {
+    pub fn apply(&self, manager: &mut EnvVarManager, profile_manager: &mut ProfileManager) -> Result<LayerReport> {
         let config = self
             .config
             .as_ref()
             .ok_or_else(|| color_eyre::eyre::eyre!("No project configuration loaded"))?;
 
-        // Apply profile if specified
-        if let Some(profile_name) = &config.profile {
-            profile_manager.apply(profile_name, manager)?;
-        }
+        let description = config
+            .name
+            .as_ref()
+            .map_or_else(|| "apply project".to_string(), |name| format!("apply project {name}"));
+
+        manager.batch(&description, |manager| {
+            // Apply profiles if specified
+            let mut report = match &config.profile {
+                Some(profiles) => profile_manager.apply_layers(profiles.names(), manager)?,
+                None => LayerReport::default(),
+            };
+
+            // Load auto-load files
+            for file in &config.auto_load {
+                let file_path = self.project_root().join(file);
+                if file_path.exists() {
+                    Self::load_auto_file(&file_path, manager)?;
+                }
+            }
+
+            // Apply defaults (only if variable not already set)
+            let (defaults, default_unresolved) = {
+                let mut expander = Expander::new(|var| manager.get(var).map(|v| v.value.clone()));
+                for (name, value) in &config.defaults {
+                    if manager.get(name).is_none() {
+                        expander.define(name, value);
+                    }
+                }
+                (expander.expand_all()?, expander.unresolved().to_vec())
+            };
+            report.unresolved.extend(default_unresolved);
+
+            for (name, value) in defaults {
+                manager.set_with_origin(&name, &value, true, VarOrigin::ProjectDefault(self.config_file()))?;
+            }
+
+            Ok(report)
+        })
+    }
+
+    /// Adds the project environment to `env` without applying it.
+    ///
+    /// Layers are added in the same order as [`ProjectManager::apply`]: the
+    /// project profiles, the auto-load files, and finally the defaults for
+    /// variables that are still unset. Returns the variables the profile
+    /// layers disagree on.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - No project configuration is loaded
+    /// - A project profile cannot be resolved
+    /// - An auto-load file cannot be read
+    pub fn compose(&self, env: &mut EnvComposer, profile_manager: &ProfileManager) -> Result<Vec<LayerConflict>> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| color_eyre::eyre::eyre!("No project configuration loaded"))?;
+
+        let conflicts = match &config.profile {
+            Some(profiles) => env.add_profiles(profile_manager, profiles.names())?,
+            None => Vec::new(),
+        };
 
-        // Load auto-load files
         for file in &config.auto_load {
-            let file_path = self.current_dir.join(file);
+            let file_path = self.project_root().join(file);
             if file_path.exists() {
-                Self::load_env_file(&file_path, manager)?;
+                env.add_env_file(&file_path)?;
             }
         }
 
-        // Apply defaults (only if variable not already set)
-        for (name, value) in &config.defaults {
-            if manager.get(name).is_none() {
-                manager.set(name, value, true)?;
+        let (defaults, unresolved) = {
+            let mut expander = Expander::new(|var| env.get(var).map(|v| v.value.clone()));
+            for (name, value) in &config.defaults {
+                if env.get(name).is_none() {
+                    expander.define(name, value);
+                }
             }
+            (expander.expand_all()?, expander.unresolved().to_vec())
+        };
+
+        for (name, value) in defaults {
+            env.set(&name, &value, Some(VarOrigin::ProjectDefault(self.config_file())));
         }
+        env.add_unresolved(unresolved);
 
-        Ok(())
+        Ok(conflicts)
+    }
+
+    fn config_file(&self) -> PathBuf {
+        self.config_path
+            .clone()
+            .unwrap_or_else(|| self.config_dir.join("config.yaml"))
+    }
+
+    /// Directory auto-load files are relative to.
+    ///
+    /// That's the directory holding `.envx/` when the configuration was found
+    /// there, even if envx runs from a subdirectory.
+    fn project_root(&self) -> PathBuf {
+        self.config_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .filter(|dir| dir.file_name().is_some_and(|name| name == ".envx"))
+            .and_then(Path::parent)
+            .map_or_else(|| self.current_dir.clone(), Path::to_path_buf)
     }
 
     /// Load configuration from a specific file
@@ -168,6 +282,7 @@ impl ProjectManager {
 
         self.config = Some(ProjectConfig::load(file_path)?);
         self.config_dir = file_path.to_path_buf();
+        self.config_path = Some(file_path.to_path_buf());
 
         Ok(())
     }
@@ -269,20 +384,28 @@ impl ProjectManager {
         Ok(())
     }
 
-    fn load_env_file(path: &Path, manager: &mut EnvVarManager) -> Result<()> {
-        let content = fs::read_to_string(path)?;
+    /// Loads an auto-load file: TOML files by their tables, anything else as
+    /// a `.env` file.
+    fn load_auto_file(path: &Path, manager: &mut EnvVarManager) -> Result<()> {
+        if !is_toml(path) {
+            return Self::load_env_file(path, manager);
+        }
 
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
+        let mut importer = Importer::new();
+        importer.import_from_file(&path.to_string_lossy(), ImportFormat::Toml)?;
+        let mut vars = importer.get_variables();
+        vars.sort();
+        for (name, value) in vars {
+            let origin = VarOrigin::file(path.to_path_buf(), None);
+            manager.set_with_origin(&name, &value, true, origin)?;
+        }
+        Ok(())
+    }
 
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value.trim().trim_matches('"').trim_matches('\'');
-                manager.set(key, value, true)?;
-            }
+    fn load_env_file(path: &Path, manager: &mut EnvVarManager) -> Result<()> {
+        for entry in dotenv::parse_file(path)? {
+            let origin = VarOrigin::file(path.to_path_buf(), Some(entry.line));
+            manager.set_with_origin(&entry.key, &entry.value, true, origin)?;
         }
 
         Ok(())
@@ -344,6 +467,7 @@ mod tests {
         let manager = ProjectManager {
             config_dir: current_dir.join(".envx"),
             config: None,
+            config_path: None,
             current_dir: current_dir.clone(),
         };
 
@@ -351,7 +475,7 @@ mod tests {
     }
 
     fn create_test_env_manager() -> EnvVarManager {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("EXISTING_VAR", "existing_value", false).unwrap();
         manager
     }
@@ -451,6 +575,26 @@ mod tests {
         assert_eq!(config.auto_load, vec![".env".to_string()]);
     }
 
+    #[test]
+    fn test_config_profile_one_or_layers() {
+        let (manager, temp_dir) = create_test_project_manager();
+        manager.init(None).unwrap();
+        let config_path = temp_dir.path().join(".envx").join("config.yaml");
+
+        let content = fs::read_to_string(&config_path).unwrap();
+        fs::write(&config_path, content.replace("profile: null", "profile: dev")).unwrap();
+        let config = ProjectConfig::load(&config_path).unwrap();
+        assert_eq!(config.profile.unwrap().names(), ["dev"]);
+
+        fs::write(
+            &config_path,
+            content.replace("profile: null", "profile: [base, aws, local]"),
+        )
+        .unwrap();
+        let config = ProjectConfig::load(&config_path).unwrap();
+        assert_eq!(config.profile.unwrap().names(), ["base", "aws", "local"]);
+    }
+
     #[test]
     fn test_find_and_load_in_current_dir() {
         let (mut manager, temp_dir) = create_test_project_manager();
@@ -478,6 +622,7 @@ mod tests {
         let parent_manager = ProjectManager {
             config_dir: parent_dir.join(".envx"),
             config: None,
+            config_path: None,
             current_dir: parent_dir.to_path_buf(),
         };
         parent_manager.init(Some("parent-project".to_string())).unwrap();
@@ -486,6 +631,7 @@ mod tests {
         let mut child_manager = ProjectManager {
             config_dir: PathBuf::from(".envx"),
             config: None,
+            config_path: None,
             current_dir: child_dir,
         };
 
@@ -528,6 +674,32 @@ mod tests {
 
         assert_eq!(env_manager.get("TEST_VAR").unwrap().value, "test_value");
         assert_eq!(env_manager.get("ANOTHER_VAR").unwrap().value, "another_value");
+        assert_eq!(
+            env_manager.get("ANOTHER_VAR").unwrap().origin,
+            Some(VarOrigin::file(temp_dir.path().join(".env"), Some(2)))
+        );
+    }
+
+    #[test]
+    fn test_apply_loads_toml_files() {
+        let (mut manager, temp_dir) = create_test_project_manager();
+        let mut env_manager = create_test_env_manager();
+        let mut profile_manager = create_test_profile_manager();
+
+        fs::write(
+            temp_dir.path().join("settings.toml"),
+            "TOML_NAME = \"app\"\n\n[TOML_DB]\nPORT = 5432\n",
+        )
+        .unwrap();
+
+        let mut config = create_test_config();
+        config.auto_load = vec!["settings.toml".to_string()];
+        manager.config = Some(config);
+
+        manager.apply(&mut env_manager, &mut profile_manager).unwrap();
+
+        assert_eq!(env_manager.get("TOML_NAME").unwrap().value, "app");
+        assert_eq!(env_manager.get("TOML_DB__PORT").unwrap().value, "5432");
     }
 
     #[test]
@@ -543,6 +715,10 @@ mod tests {
 
         assert_eq!(env_manager.get("NODE_ENV").unwrap().value, "development");
         assert_eq!(env_manager.get("PORT").unwrap().value, "3000");
+        assert!(matches!(
+            env_manager.get("PORT").unwrap().origin,
+            Some(VarOrigin::ProjectDefault(_))
+        ));
     }
 
     #[test]
@@ -562,6 +738,52 @@ mod tests {
         assert_eq!(env_manager.get("NODE_ENV").unwrap().value, "production");
     }
 
+    #[test]
+    fn test_defaults_expand_references() {
+        let (mut manager, _temp) = create_test_project_manager();
+        let mut env_manager = create_test_env_manager();
+        let mut profile_manager = create_test_profile_manager();
+        env_manager.set("PORT", "4000", false).unwrap();
+
+        let mut config = create_test_config();
+        config
+            .defaults
+            .insert("API_URL".to_string(), "http://localhost:${PORT}${API_PATH}".to_string());
+        config.defaults.insert("API_PATH".to_string(), "/v1".to_string());
+        config
+            .defaults
+            .insert("CACHE_DIR".to_string(), "${ENVX_TEST_CACHE_ROOT:-/tmp}/app".to_string());
+        manager.config = Some(config);
+
+        let report = manager.apply(&mut env_manager, &mut profile_manager).unwrap();
+
+        assert!(report.unresolved.is_empty());
+        assert_eq!(env_manager.get("API_URL").unwrap().value, "http://localhost:4000/v1");
+        assert_eq!(env_manager.get("CACHE_DIR").unwrap().value, "/tmp/app");
+    }
+
+    #[test]
+    fn test_compose_does_not_mutate() {
+        let (mut manager, temp_dir) = create_test_project_manager();
+        let profile_manager = create_test_profile_manager();
+
+        fs::write(temp_dir.path().join(".env"), "COMPOSE_ONLY_VAR=from_file\nPORT=4000").unwrap();
+
+        let mut config = create_test_config();
+        config.auto_load = vec![".env".to_string()];
+        manager.config = Some(config);
+
+        let mut env = EnvComposer::new();
+        env.set("NODE_ENV", "production", None);
+        manager.compose(&mut env, &profile_manager).unwrap();
+
+        assert_eq!(env.get("COMPOSE_ONLY_VAR").unwrap().value, "from_file");
+        // Auto-load files win over defaults, existing values win over defaults
+        assert_eq!(env.get("PORT").unwrap().value, "4000");
+        assert_eq!(env.get("NODE_ENV").unwrap().value, "production");
+        assert!(std::env::var("COMPOSE_ONLY_VAR").is_err());
+    }
+
     #[test]
     fn test_apply_no_config_error() {
         let (manager, _temp) = create_test_project_manager();
@@ -646,6 +868,7 @@ mod tests {
                 source: crate::EnvVarSource::User,
                 modified: chrono::Utc::now(),
                 original_value: None,
+                origin: None,
             },
         );
 
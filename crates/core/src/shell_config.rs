@@ -0,0 +1,637 @@
+use crate::{EnvxError, VarOrigin};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use indexmap::IndexMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BLOCK_START: &str = "# >>> envx managed block >>>";
+const BLOCK_END: &str = "# <<< envx managed block <<<";
+const BLOCK_NOTICE: &str = "# Managed by envx. Use `envx set` / `envx delete` instead of editing these lines.";
+/// Starts the comment above an entry that records where its value came from
+const ORIGIN_PREFIX: &str = "# envx origin: ";
+
+/// Syntax family of a shell startup file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    /// bash, zsh, sh and friends (`export NAME='value'`)
+    Posix,
+    /// fish (`set -gx NAME 'value'`)
+    Fish,
+}
+
+/// A shell startup file that envx keeps a managed block in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RcFile {
+    pub path: PathBuf,
+    pub kind: ShellKind,
+}
+
+/// A variable in the managed block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedVar {
+    pub value: String,
+    /// Where the value came from, kept in a comment above the entry
+    pub origin: Option<VarOrigin>,
+}
+
+/// Persists environment variables for Unix shells.
+///
+/// envx owns a clearly marked block in each shell startup file. Variables are
+/// added, updated and removed only inside that block, so everything the user
+/// wrote around it is left untouched.
+#[derive(Debug, Clone)]
+pub struct ShellConfig {
+    home: PathBuf,
+}
+
+impl ShellConfig {
+    #[must_use]
+    pub const fn new(home: PathBuf) -> Self {
+        Self { home }
+    }
+
+    /// Creates a `ShellConfig` for the current user's home directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn for_current_user() -> Result<Self> {
+        let home = dirs::home_dir().ok_or_else(|| eyre!("Could not find home directory"))?;
+        Ok(Self::new(home))
+    }
+
+    /// Returns the startup files envx writes to.
+    ///
+    /// `~/.profile` is always included so login shells pick up the variables.
+    /// `~/.bashrc`, `~/.zshrc` and fish's `conf.d` are only used when the user
+    /// already has them, envx never installs configuration for a shell that
+    /// isn't in use.
+    #[must_use]
+    pub fn rc_files(&self) -> Vec<RcFile> {
+        let mut files = vec![RcFile {
+            path: self.home.join(".profile"),
+            kind: ShellKind::Posix,
+        }];
+
+        for name in [".bashrc", ".zshrc"] {
+            let path = self.home.join(name);
+            if path.exists() {
+                files.push(RcFile {
+                    path,
+                    kind: ShellKind::Posix,
+                });
+            }
+        }
+
+        let fish_dir = self.home.join(".config").join("fish");
+        if fish_dir.is_dir() {
+            files.push(RcFile {
+                path: fish_dir.join("conf.d").join("envx.fish"),
+                kind: ShellKind::Fish,
+            });
+        }
+
+        files
+    }
+
+    /// Adds or updates a variable in every managed block.
+    ///
+    /// Returns the files that were actually changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The variable name is not a valid shell identifier
+    /// - A startup file cannot be read, backed up or written
+    pub fn persist(&self, name: &str, value: &str) -> Result<Vec<PathBuf>> {
+        self.persist_with_origin(name, value, None)
+    }
+
+    /// Adds or updates a variable in every managed block and records where
+    /// its value came from, so later envx runs can still show it.
+    ///
+    /// Returns the files that were actually changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`ShellConfig::persist`].
+    pub fn persist_with_origin(&self, name: &str, value: &str, origin: Option<&VarOrigin>) -> Result<Vec<PathBuf>> {
+        if !is_shell_identifier(name) {
+            return Err(
+                EnvxError::InvalidVarName(format!("'{name}' cannot be exported from a shell startup file")).into(),
+            );
+        }
+
+        let var = ManagedVar {
+            value: value.to_string(),
+            origin: origin.cloned(),
+        };
+        self.update_all(|entries| {
+            if entries.get(name) == Some(&var) {
+                false
+            } else {
+                entries.insert(name.to_string(), var.clone());
+                true
+            }
+        })
+    }
+
+    /// Removes a variable from every managed block.
+    ///
+    /// Returns the files that were actually changed, which is empty when
+    /// envx never persisted the variable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a startup file cannot be read, backed up or written.
+    pub fn remove(&self, name: &str) -> Result<Vec<PathBuf>> {
+        self.update_all(|entries| entries.shift_remove(name).is_some())
+    }
+
+    /// Returns the variables persisted in the managed block of `~/.profile`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn managed_vars(&self) -> Result<IndexMap<String, String>> {
+        let path = self.home.join(".profile");
+        if !path.exists() {
+            return Ok(IndexMap::new());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        Ok(parse_block(&content, ShellKind::Posix)
+            .into_iter()
+            .map(|(name, var)| (name, var.value))
+            .collect())
+    }
+
+    fn update_all<F>(&self, mut update: F) -> Result<Vec<PathBuf>>
+    where
+        F: FnMut(&mut IndexMap<String, ManagedVar>) -> bool,
+    {
+        let mut changed = Vec::new();
+
+        for rc in self.rc_files() {
+            let content = if rc.path.exists() {
+                fs::read_to_string(&rc.path)?
+            } else {
+                String::new()
+            };
+
+            let mut entries = parse_block(&content, rc.kind);
+            if !update(&mut entries) {
+                continue;
+            }
+
+            let new_content = replace_block(&content, &entries, rc.kind);
+            if new_content != content {
+                write_with_backup(&rc.path, &new_content)?;
+                changed.push(rc.path);
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Parses the entries inside the envx managed block of a startup file.
+#[must_use]
+pub fn parse_block(content: &str, kind: ShellKind) -> IndexMap<String, ManagedVar> {
+    let mut entries = IndexMap::new();
+    let Some((start, end)) = find_block(content) else {
+        return entries;
+    };
+
+    // A quoted value may span several lines, so keep reading until the quotes balance
+    let mut statement = String::new();
+    let mut origin = None;
+    for line in content.lines().skip(start + 1).take(end - start - 1) {
+        if statement.is_empty() {
+            if let Some(json) = line.trim_start().strip_prefix(ORIGIN_PREFIX) {
+                origin = serde_json::from_str(json).ok();
+                continue;
+            }
+            statement.push_str(line.trim_start());
+        } else {
+            statement.push('\n');
+            statement.push_str(line);
+        }
+
+        if has_open_quote(&statement, kind) {
+            continue;
+        }
+
+        if let Some((name, value)) = parse_entry(statement.trim_end(), kind) {
+            entries.insert(
+                name,
+                ManagedVar {
+                    value,
+                    origin: origin.take(),
+                },
+            );
+        }
+        statement.clear();
+    }
+
+    entries
+}
+
+/// Renders the managed block for the given entries, including the markers.
+#[must_use]
+pub fn render_block(entries: &IndexMap<String, ManagedVar>, kind: ShellKind) -> String {
+    let mut lines = vec![BLOCK_START.to_string(), BLOCK_NOTICE.to_string()];
+
+    for (name, ManagedVar { value, origin }) in entries {
+        if let Some(json) = origin.as_ref().and_then(|origin| serde_json::to_string(origin).ok()) {
+            lines.push(format!("{ORIGIN_PREFIX}{json}"));
+        }
+        lines.push(match kind {
+            ShellKind::Posix => format!("export {name}={}", quote_posix(value)),
+            ShellKind::Fish => format!("set -gx {name} {}", quote_fish(value)),
+        });
+    }
+
+    lines.push(BLOCK_END.to_string());
+    lines.join("\n")
+}
+
+/// Returns `content` with the managed block replaced by one rendered from `entries`.
+///
+/// An empty `entries` map removes the block entirely. A missing block is
+/// appended to the end of the file.
+#[must_use]
+pub fn replace_block(content: &str, entries: &IndexMap<String, ManagedVar>, kind: ShellKind) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let block = (!entries.is_empty()).then(|| render_block(entries, kind));
+
+    let mut result: Vec<String> = if let Some((start, end)) = find_block(content) {
+        let mut result: Vec<String> = lines[..start].iter().map(ToString::to_string).collect();
+        if let Some(block) = block {
+            result.push(block);
+        } else if result.last().is_some_and(|l| l.trim().is_empty()) {
+            // Drop the separator line we added together with the block
+            result.pop();
+        }
+        result.extend(lines[end + 1..].iter().map(ToString::to_string));
+        result
+    } else {
+        let mut result: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        if let Some(block) = block {
+            if result.last().is_some_and(|l| !l.trim().is_empty()) {
+                result.push(String::new());
+            }
+            result.push(block);
+        }
+        result
+    };
+
+    if result.is_empty() {
+        return String::new();
+    }
+
+    if result.last().is_some_and(|l| !l.is_empty()) || content.ends_with('\n') || content.is_empty() {
+        result.push(String::new());
+    }
+    result.join("\n")
+}
+
+/// Returns the line indices of the managed block's start and end markers.
+pub(crate) fn find_block(content: &str) -> Option<(usize, usize)> {
+    let mut start = None;
+    for (i, line) in content.lines().enumerate() {
+        match line.trim() {
+            BLOCK_START if start.is_none() => start = Some(i),
+            BLOCK_END => {
+                if let Some(start) = start {
+                    return Some((start, i));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_entry(line: &str, kind: ShellKind) -> Option<(String, String)> {
+    match kind {
+        ShellKind::Posix => {
+            let rest = line.strip_prefix("export ")?;
+            let (name, value) = rest.split_once('=')?;
+            Some((name.trim().to_string(), unquote_posix(value)))
+        }
+        ShellKind::Fish => {
+            let rest = line.strip_prefix("set -gx ")?;
+            let (name, value) = rest.split_once(' ')?;
+            Some((name.trim().to_string(), unquote_fish(value)))
+        }
+    }
+}
+
+fn has_open_quote(statement: &str, kind: ShellKind) -> bool {
+    let mut chars = statement.chars();
+    let mut in_single = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => in_single = !in_single,
+            // POSIX single quotes have no escapes, fish allows \\ and \' inside them
+            '\\' if !in_single || kind == ShellKind::Fish => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    in_single
+}
+
+/// Writes a startup file, keeping a copy of it as it was before envx first
+/// changed it.
+fn write_with_backup(path: &Path, content: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let backup = path.with_file_name(format!("{file_name}.envx.bak"));
+    if path.exists() && !backup.exists() {
+        fs::copy(path, &backup)?;
+    }
+
+    // Dotfile managers like stow or chezmoi link the startup files into a
+    // repository, so write to the file the link points to
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // The write goes through a sibling file so a crash never leaves a half-written rc file
+    crate::snapshot_store::write_atomic(&path, content.as_bytes())
+}
+
+/// Returns true if `name` is a valid shell variable name
+/// (`[A-Za-z_][A-Za-z0-9_]*`), which is safe to write into shell code.
+#[must_use]
+pub fn is_shell_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quotes a value for POSIX shells using single quotes.
+#[must_use]
+pub fn quote_posix(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quotes a value for fish using single quotes.
+#[must_use]
+pub fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn unquote_posix(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    let mut in_single = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => in_single = !in_single,
+            '\\' if !in_single => {
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                }
+            }
+            _ => result.push(ch),
+        }
+    }
+
+    result
+}
+
+fn unquote_fish(value: &str) -> String {
+    let inner = value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .unwrap_or(value);
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some(next @ ('\\' | '\'')) => result.push(next),
+                Some(next) => {
+                    result.push('\\');
+                    result.push(next);
+                }
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_shell_config() -> (ShellConfig, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ShellConfig::new(temp_dir.path().to_path_buf());
+        (config, temp_dir)
+    }
+
+    #[test]
+    fn test_rc_files_only_existing_shells() {
+        let (config, temp) = create_test_shell_config();
+
+        let files = config.rc_files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, temp.path().join(".profile"));
+
+        fs::write(temp.path().join(".zshrc"), "").unwrap();
+        fs::create_dir_all(temp.path().join(".config").join("fish")).unwrap();
+
+        let files = config.rc_files();
+        assert_eq!(files.len(), 3);
+        assert!(files.iter().any(|f| f.path.ends_with(".zshrc")));
+        assert!(
+            files
+                .iter()
+                .any(|f| f.kind == ShellKind::Fish && f.path.ends_with("conf.d/envx.fish"))
+        );
+    }
+
+    #[test]
+    fn test_persist_creates_block_and_keeps_user_content() {
+        let (config, temp) = create_test_shell_config();
+        let bashrc = temp.path().join(".bashrc");
+        fs::write(&bashrc, "# my aliases\nalias ll='ls -l'\n").unwrap();
+
+        let changed = config.persist("FOO", "bar").unwrap();
+        assert_eq!(changed.len(), 2);
+
+        let content = fs::read_to_string(&bashrc).unwrap();
+        assert!(content.starts_with("# my aliases\nalias ll='ls -l'\n"));
+        assert!(content.contains(BLOCK_START));
+        assert!(content.contains("export FOO='bar'"));
+        assert!(content.contains(BLOCK_END));
+    }
+
+    #[test]
+    fn test_persist_updates_without_duplicates() {
+        let (config, temp) = create_test_shell_config();
+
+        config.persist("FOO", "one").unwrap();
+        config.persist("BAR", "two").unwrap();
+        config.persist("FOO", "three").unwrap();
+
+        let content = fs::read_to_string(temp.path().join(".profile")).unwrap();
+        assert_eq!(content.matches("export FOO=").count(), 1);
+        assert_eq!(content.matches(BLOCK_START).count(), 1);
+        assert!(content.contains("export FOO='three'"));
+
+        let vars = config.managed_vars().unwrap();
+        assert_eq!(vars.get("FOO").unwrap(), "three");
+        assert_eq!(vars.get("BAR").unwrap(), "two");
+    }
+
+    #[test]
+    fn test_persist_same_value_is_noop() {
+        let (config, _temp) = create_test_shell_config();
+
+        assert_eq!(config.persist("FOO", "bar").unwrap().len(), 1);
+        assert!(config.persist("FOO", "bar").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_persist_makes_backup() {
+        let (config, temp) = create_test_shell_config();
+        let profile = temp.path().join(".profile");
+        fs::write(&profile, "original\n").unwrap();
+
+        config.persist("FOO", "bar").unwrap();
+
+        let backup = fs::read_to_string(temp.path().join(".profile.envx.bak")).unwrap();
+        assert_eq!(backup, "original\n");
+
+        // Later writes keep the backup of the original
+        config.persist("FOO", "baz").unwrap();
+        let backup = fs::read_to_string(temp.path().join(".profile.envx.bak")).unwrap();
+        assert_eq!(backup, "original\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_persist_keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (config, temp) = create_test_shell_config();
+        let dotfiles = temp.path().join("dotfiles");
+        fs::create_dir_all(&dotfiles).unwrap();
+        let target = dotfiles.join("profile");
+        fs::write(&target, "umask 022\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        let profile = temp.path().join(".profile");
+        std::os::unix::fs::symlink(&target, &profile).unwrap();
+
+        config.persist("FOO", "bar").unwrap();
+
+        assert!(fs::symlink_metadata(&profile).unwrap().file_type().is_symlink());
+        assert!(fs::read_to_string(&target).unwrap().contains("export FOO='bar'"));
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_dir(&dotfiles).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_persist_rejects_invalid_names() {
+        let (config, _temp) = create_test_shell_config();
+
+        assert!(config.persist("MY-VAR", "x").is_err());
+        assert!(config.persist("1VAR", "x").is_err());
+        assert!(config.persist("", "x").is_err());
+    }
+
+    #[test]
+    fn test_remove_entry_and_empty_block() {
+        let (config, temp) = create_test_shell_config();
+        let profile = temp.path().join(".profile");
+        fs::write(&profile, "umask 022\n").unwrap();
+
+        config.persist("FOO", "bar").unwrap();
+        config.persist("BAZ", "qux").unwrap();
+
+        config.remove("FOO").unwrap();
+        let content = fs::read_to_string(&profile).unwrap();
+        assert!(!content.contains("FOO"));
+        assert!(content.contains("export BAZ='qux'"));
+
+        config.remove("BAZ").unwrap();
+        let content = fs::read_to_string(&profile).unwrap();
+        assert_eq!(content, "umask 022\n");
+
+        // Removing something envx never wrote leaves files alone
+        assert!(config.remove("NEVER_SET").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_quoting_round_trip() {
+        let values = ["simple", "it's", "back\\slash", "$HOME `cmd` \"q\"", "line1\nline2", ""];
+
+        for value in values {
+            let mut entries = IndexMap::new();
+            entries.insert(
+                "VAR".to_string(),
+                ManagedVar {
+                    value: value.to_string(),
+                    origin: None,
+                },
+            );
+
+            for kind in [ShellKind::Posix, ShellKind::Fish] {
+                let content = replace_block("", &entries, kind);
+                let parsed = parse_block(&content, kind);
+                assert_eq!(parsed.get("VAR").map(|var| var.value.as_str()), Some(value), "{kind:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_persist_keeps_origin() {
+        let (config, temp) = create_test_shell_config();
+        let origin = VarOrigin::file(PathBuf::from("/work/app/.env"), Some(3));
+
+        config.persist_with_origin("FOO", "it's", Some(&origin)).unwrap();
+        config.persist("BAR", "two").unwrap();
+
+        let content = fs::read_to_string(temp.path().join(".profile")).unwrap();
+        let parsed = parse_block(&content, ShellKind::Posix);
+        assert_eq!(parsed["FOO"].value, "it's");
+        assert_eq!(parsed["FOO"].origin, Some(origin));
+        assert_eq!(parsed["BAR"].origin, None);
+
+        // Setting the value again without an origin drops the old one
+        config.persist("FOO", "it's").unwrap();
+        let content = fs::read_to_string(temp.path().join(".profile")).unwrap();
+        assert!(!content.contains(ORIGIN_PREFIX));
+    }
+
+    #[test]
+    fn test_fish_block_syntax() {
+        let (config, temp) = create_test_shell_config();
+        fs::create_dir_all(temp.path().join(".config").join("fish")).unwrap();
+
+        config.persist("EDITOR", "nvim").unwrap();
+
+        let content = fs::read_to_string(temp.path().join(".config/fish/conf.d/envx.fish")).unwrap();
+        assert!(content.contains("set -gx EDITOR 'nvim'"));
+    }
+}
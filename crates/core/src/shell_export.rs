@@ -0,0 +1,231 @@
+use crate::EnvxError;
+use crate::exporter::{escape_powershell, escape_shell};
+use crate::shell_config::{is_shell_identifier, quote_fish};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::fmt;
+use std::path::Path;
+
+/// Shell syntax to emit environment statements in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellDialect {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Cmd,
+}
+
+impl ShellDialect {
+    /// Parses a shell name such as `bash` or `fish`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shell is not supported.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "bash" | "sh" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "powershell" | "pwsh" => Ok(Self::PowerShell),
+            "cmd" => Ok(Self::Cmd),
+            _ => Err(eyre!(
+                "Unsupported shell: {} (expected bash, zsh, fish, powershell or cmd)",
+                name
+            )),
+        }
+    }
+
+    /// Guesses the user's shell.
+    ///
+    /// Uses `$SHELL` on Unix and PowerShell on Windows, falling back to bash
+    /// when the shell is unknown.
+    #[must_use]
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return Self::PowerShell;
+        }
+
+        std::env::var("SHELL")
+            .ok()
+            .as_deref()
+            .and_then(|shell| Path::new(shell).file_name())
+            .and_then(|name| Self::from_name(&name.to_string_lossy()).ok())
+            .unwrap_or(Self::Bash)
+    }
+
+    /// Returns a statement that sets and exports a variable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `name` is not a valid shell variable name. The statement is meant to
+    ///   be evaluated, so such a name could run code.
+    /// - The value can't be written for cmd, which has no way to quote newlines,
+    ///   double quotes or `%` in `set "NAME=value"`. `%%` only stands for `%`
+    ///   in batch files, not at the prompt, so escaping it isn't reliable.
+    pub fn export(self, name: &str, value: &str) -> Result<String> {
+        check_name(name)?;
+        if self == Self::Cmd && value.contains(['\n', '\r', '"', '%']) {
+            return Err(eyre!("cmd can't set values containing newlines, double quotes or '%'"));
+        }
+
+        Ok(match self {
+            Self::Bash | Self::Zsh => format!("export {name}=\"{}\"", escape_shell(value)),
+            Self::Fish => format!("set -gx {name} {}", quote_fish(value)),
+            // The output is evaluated, so `$` must not expand either
+            Self::PowerShell => format!("$env:{name} = \"{}\"", escape_powershell(value).replace('$', "`$")),
+            Self::Cmd => format!("set \"{name}={value}\""),
+        })
+    }
+
+    /// Returns a statement that removes a variable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is not a valid shell variable name.
+    pub fn unset(self, name: &str) -> Result<String> {
+        check_name(name)?;
+        Ok(match self {
+            Self::Bash | Self::Zsh => format!("unset {name}"),
+            Self::Fish => format!("set -e {name}"),
+            Self::PowerShell => format!("Remove-Item Env:{name} -ErrorAction SilentlyContinue"),
+            Self::Cmd => format!("set \"{name}=\""),
+        })
+    }
+}
+
+impl fmt::Display for ShellDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+            Self::PowerShell => "powershell",
+            Self::Cmd => "cmd",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A change to apply to a shell's environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvChange {
+    Set { name: String, value: String },
+    Unset { name: String },
+}
+
+impl EnvChange {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Set { name, .. } | Self::Unset { name } => name,
+        }
+    }
+
+    /// Renders the change as a statement for the given shell.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variable name is not a valid shell variable name.
+    pub fn render(&self, dialect: ShellDialect) -> Result<String> {
+        match self {
+            Self::Set { name, value } => dialect.export(name, value),
+            Self::Unset { name } => dialect.unset(name),
+        }
+    }
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if is_shell_identifier(name) {
+        Ok(())
+    } else {
+        Err(EnvxError::InvalidVarName(format!("'{name}' cannot be set from a shell")).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(ShellDialect::from_name("bash").unwrap(), ShellDialect::Bash);
+        assert_eq!(ShellDialect::from_name("ZSH").unwrap(), ShellDialect::Zsh);
+        assert_eq!(ShellDialect::from_name("fish").unwrap(), ShellDialect::Fish);
+        assert_eq!(ShellDialect::from_name("pwsh").unwrap(), ShellDialect::PowerShell);
+        assert_eq!(ShellDialect::from_name("cmd").unwrap(), ShellDialect::Cmd);
+        assert!(ShellDialect::from_name("tcsh").is_err());
+    }
+
+    #[test]
+    fn test_posix_statements() {
+        assert_eq!(
+            ShellDialect::Bash.export("MSG", "say \"hi\" to $USER").unwrap(),
+            r#"export MSG="say \"hi\" to \$USER""#
+        );
+        assert_eq!(ShellDialect::Zsh.unset("MSG").unwrap(), "unset MSG");
+    }
+
+    #[test]
+    fn test_fish_statements() {
+        assert_eq!(
+            ShellDialect::Fish.export("MSG", "it's").unwrap(),
+            r"set -gx MSG 'it\'s'"
+        );
+        assert_eq!(ShellDialect::Fish.unset("MSG").unwrap(), "set -e MSG");
+    }
+
+    #[test]
+    fn test_powershell_statements() {
+        assert_eq!(
+            ShellDialect::PowerShell
+                .export("MSG", "say \"hi\" to $env:USER`s")
+                .unwrap(),
+            r#"$env:MSG = "say `"hi`" to `$env:USER``s""#
+        );
+        assert_eq!(
+            ShellDialect::PowerShell.unset("MSG").unwrap(),
+            "Remove-Item Env:MSG -ErrorAction SilentlyContinue"
+        );
+    }
+
+    #[test]
+    fn test_cmd_statements() {
+        assert_eq!(ShellDialect::Cmd.export("MSG", "a & b").unwrap(), r#"set "MSG=a & b""#);
+        assert_eq!(ShellDialect::Cmd.unset("MSG").unwrap(), r#"set "MSG=""#);
+        assert!(ShellDialect::Cmd.export("MSG", "say \"hi\"").is_err());
+        assert!(ShellDialect::Cmd.export("MSG", "line1\r\nline2").is_err());
+        assert!(ShellDialect::Cmd.export("MSG", "100%").is_err());
+        assert!(ShellDialect::Cmd.export("MSG", "%PATH%").is_err());
+    }
+
+    #[test]
+    fn test_rejects_names_that_are_not_identifiers() {
+        for name in ["a;touch${IFS}/tmp/pwned", "a b", "1A", "-x", "A$B", ""] {
+            for dialect in [
+                ShellDialect::Bash,
+                ShellDialect::Fish,
+                ShellDialect::PowerShell,
+                ShellDialect::Cmd,
+            ] {
+                assert!(dialect.export(name, "1").is_err(), "{name} in {dialect}");
+                assert!(dialect.unset(name).is_err(), "{name} in {dialect}");
+            }
+        }
+        assert!(ShellDialect::Bash.export("_Valid_1", "1").is_ok());
+    }
+
+    #[test]
+    fn test_render_change() {
+        let change = EnvChange::Set {
+            name: "A".to_string(),
+            value: "1".to_string(),
+        };
+        assert_eq!(change.name(), "A");
+        assert_eq!(change.render(ShellDialect::Bash).unwrap(), "export A=\"1\"");
+
+        let change = EnvChange::Unset { name: "A".to_string() };
+        assert_eq!(change.render(ShellDialect::Fish).unwrap(), "set -e A");
+    }
+}
@@ -1,7 +1,18 @@
 use crate::EnvVar;
 use ahash::AHashMap as HashMap;
 use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Metadata key holding the comma-separated tags of a snapshot
+pub const TAGS_KEY: &str = "tags";
+/// Tag of the snapshots envx takes by itself, e.g. before a restore
+pub const AUTO_TAG: &str = "auto";
+/// Metadata key holding the comma-separated variables whose secret values
+/// were left out of the archive a snapshot was imported from
+pub const MASKED_KEY: &str = "masked";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
@@ -13,24 +24,52 @@ pub struct Snapshot {
     pub metadata: HashMap<String, String>,
 }
 
+/// A snapshot without its variables, as kept in the snapshot index so
+/// listing snapshots doesn't read every one of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub variable_count: usize,
+}
+
+/// A named set of variables. Everything but the variables' values may be
+/// left out when writing a profile by hand, e.g. in a project's
+/// `.envx/profiles/<name>.yaml`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub description: Option<String>,
+    #[serde(default = "Utc::now")]
     pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
     pub variables: HashMap<String, ProfileVar>,
+    #[serde(default)]
     pub parent: Option<String>,
+    #[serde(default)]
     pub metadata: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfileVar {
     pub value: String,
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
+    #[serde(default)]
     pub override_system: bool,
 }
 
+const fn default_enabled() -> bool {
+    true
+}
+
 impl Snapshot {
     #[must_use]
     pub fn new(name: String, description: Option<String>) -> Self {
@@ -52,6 +91,128 @@ impl Snapshot {
         }
         snapshot
     }
+
+    /// Returns the tags of the snapshot, stored under [`TAGS_KEY`] in the metadata.
+    #[must_use]
+    pub fn tags(&self) -> Vec<&str> {
+        tags(&self.metadata)
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+
+    /// Returns the variables whose values were masked out of the archive the
+    /// snapshot was imported from, stored under [`MASKED_KEY`] in the metadata.
+    #[must_use]
+    pub fn masked(&self) -> Vec<&str> {
+        list(&self.metadata, MASKED_KEY)
+    }
+
+    /// Adds a tag. Returns `false` if the snapshot already has it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is empty or contains a comma.
+    pub fn add_tag(&mut self, tag: &str) -> Result<bool> {
+        if tag.is_empty() || tag.contains(',') {
+            return Err(eyre!(
+                "Invalid tag '{}': tags must be non-empty and cannot contain commas",
+                tag
+            ));
+        }
+        if self.has_tag(tag) {
+            return Ok(false);
+        }
+
+        let mut tags = self.tags();
+        tags.push(tag);
+        let tags = tags.join(",");
+        self.metadata.insert(TAGS_KEY.to_string(), tags);
+        Ok(true)
+    }
+
+    /// Removes a tag. Returns `false` if the snapshot doesn't have it.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        if !self.has_tag(tag) {
+            return false;
+        }
+
+        let tags: Vec<&str> = self.tags().into_iter().filter(|t| *t != tag).collect();
+        let tags = tags.join(",");
+        if tags.is_empty() {
+            self.metadata.remove(TAGS_KEY);
+        } else {
+            self.metadata.insert(TAGS_KEY.to_string(), tags);
+        }
+        true
+    }
+
+    /// Returns the labels of the snapshot, all metadata except its tags.
+    #[must_use]
+    pub fn labels(&self) -> BTreeMap<&str, &str> {
+        self.metadata
+            .iter()
+            .filter(|(key, _)| *key != TAGS_KEY)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+
+    /// Sets a label, replacing any previous value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is empty or [`TAGS_KEY`].
+    pub fn set_label(&mut self, key: &str, value: &str) -> Result<()> {
+        if key.is_empty() || key == TAGS_KEY {
+            return Err(eyre!("Invalid label '{}': use tags to tag a snapshot", key));
+        }
+        self.metadata.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Removes a label. Returns `false` if the snapshot doesn't have it.
+    pub fn remove_label(&mut self, key: &str) -> bool {
+        key != TAGS_KEY && self.metadata.remove(key).is_some()
+    }
+}
+
+impl SnapshotSummary {
+    /// Returns the tags of the snapshot, see [`Snapshot::tags`].
+    #[must_use]
+    pub fn tags(&self) -> Vec<&str> {
+        tags(&self.metadata)
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+}
+
+impl From<&Snapshot> for SnapshotSummary {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            id: snapshot.id.clone(),
+            name: snapshot.name.clone(),
+            description: snapshot.description.clone(),
+            created_at: snapshot.created_at,
+            metadata: snapshot.metadata.clone(),
+            variable_count: snapshot.variables.len(),
+        }
+    }
+}
+
+fn tags(metadata: &HashMap<String, String>) -> Vec<&str> {
+    list(metadata, TAGS_KEY)
+}
+
+fn list<'a>(metadata: &'a HashMap<String, String>, key: &str) -> Vec<&'a str> {
+    metadata
+        .get(key)
+        .map(|items| items.split(',').filter(|item| !item.is_empty()).collect())
+        .unwrap_or_default()
 }
 
 impl Profile {
@@ -85,6 +246,24 @@ impl Profile {
         self.variables.remove(name)
     }
 
+    /// Enables or disables a variable without removing its value.
+    ///
+    /// Returns `false` if the profile doesn't define the variable.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let Some(var) = self.variables.get_mut(name) else {
+            return false;
+        };
+        if var.enabled != enabled {
+            var.enabled = enabled;
+            self.updated_at = Utc::now();
+        }
+        true
+    }
+
+    /// Returns the enabled variables the profile defines itself.
+    ///
+    /// Inherited variables are not included, use
+    /// [`ProfileManager::resolve`](crate::ProfileManager::resolve) for those.
     #[must_use]
     pub fn get_active_vars(&self) -> HashMap<String, String> {
         self.variables
@@ -107,6 +286,7 @@ mod tests {
             source: EnvVarSource::User,
             modified: Utc::now(),
             original_value: None,
+            origin: None,
         }
     }
 
@@ -133,6 +313,33 @@ mod tests {
         assert!(snapshot.description.is_none());
     }
 
+    #[test]
+    fn test_snapshot_tags_and_labels() {
+        let mut snapshot = Snapshot::new("tagged".to_string(), None);
+        assert!(snapshot.tags().is_empty());
+
+        assert!(snapshot.add_tag("release").unwrap());
+        assert!(snapshot.add_tag("v1.2").unwrap());
+        assert!(!snapshot.add_tag("release").unwrap());
+        assert!(snapshot.add_tag("a,b").is_err());
+        assert_eq!(snapshot.tags(), ["release", "v1.2"]);
+
+        snapshot.set_label("ticket", "OPS-12").unwrap();
+        assert!(snapshot.set_label(TAGS_KEY, "x").is_err());
+        assert_eq!(
+            snapshot.labels().into_iter().collect::<Vec<_>>(),
+            [("ticket", "OPS-12")]
+        );
+
+        assert!(snapshot.remove_tag("release"));
+        assert!(!snapshot.remove_tag("release"));
+        assert!(snapshot.remove_tag("v1.2"));
+        assert!(!snapshot.metadata.contains_key(TAGS_KEY));
+        assert!(!snapshot.remove_label(TAGS_KEY));
+        assert!(snapshot.remove_label("ticket"));
+        assert!(snapshot.metadata.is_empty());
+    }
+
     #[test]
     fn test_snapshot_unique_ids() {
         let snapshot1 = Snapshot::new("snap1".to_string(), None);
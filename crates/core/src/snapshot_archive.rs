@@ -0,0 +1,274 @@
+use crate::SecretClassifier;
+use crate::snapshot::{MASKED_KEY, Snapshot};
+use crate::snapshot_store::{compress, decompress};
+use crate::vault::Vault;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+/// Value of the `format` field identifying a snapshot archive
+pub const ARCHIVE_FORMAT: &str = "envx-snapshot";
+const ARCHIVE_VERSION: u32 = 1;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How secret values are written to a [`SnapshotArchive`]
+#[derive(Debug, Clone)]
+pub enum ArchiveSecrets {
+    /// Write secrets in plain text
+    Plain,
+    /// Leave secrets out, recording only that they were masked
+    Mask,
+    /// Encrypt secrets with a key derived from this passphrase
+    Encrypt(String),
+}
+
+/// A snapshot in a portable file, to be imported on another machine.
+///
+/// The snapshot keeps its ID, metadata and the source and modification time
+/// of every variable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotArchive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Base64 salt of the passphrase key secrets are encrypted with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    /// Variables whose secret values were left out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub masked: Vec<String>,
+    pub snapshot: Snapshot,
+}
+
+impl SnapshotArchive {
+    /// Archives a snapshot whose values are decrypted, handling the values
+    /// `classifier` considers secret as `secrets` says.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the passphrase is empty or a value cannot be encrypted.
+    pub fn new(mut snapshot: Snapshot, secrets: &ArchiveSecrets, classifier: &SecretClassifier) -> Result<Self> {
+        let mut salt = None;
+        let mut masked = Vec::new();
+
+        match secrets {
+            ArchiveSecrets::Plain => {}
+            ArchiveSecrets::Mask => {
+                masked = snapshot
+                    .variables
+                    .values()
+                    .filter(|var| classifier.is_secret(&var.name, &var.value))
+                    .map(|var| var.name.clone())
+                    .collect();
+                masked.sort();
+                for name in &masked {
+                    snapshot.variables.remove(name);
+                }
+            }
+            ArchiveSecrets::Encrypt(passphrase) => {
+                let (vault, new_salt) = Vault::with_passphrase(passphrase, None)?;
+                for var in snapshot.variables.values_mut() {
+                    for value in std::iter::once(&mut var.value).chain(var.original_value.as_mut()) {
+                        vault.encrypt_secret(classifier, &var.name, value)?;
+                    }
+                }
+                salt = Some(new_salt);
+            }
+        }
+
+        Ok(Self {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            exported_at: Utc::now(),
+            salt,
+            masked,
+            snapshot,
+        })
+    }
+
+    /// Serializes the archive as pretty JSON, gzip-compressed if `compressed`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or compression fails.
+    pub fn to_bytes(&self, compressed: bool) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec_pretty(self)?;
+        if compressed { compress(&json) } else { Ok(json) }
+    }
+
+    /// Reads an archive written by [`SnapshotArchive::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data isn't a snapshot archive, was written by a
+    /// newer version of envx or its snapshot ID isn't a UUID.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let json = if data.starts_with(&GZIP_MAGIC) {
+            decompress(data)?
+        } else {
+            data.to_vec()
+        };
+
+        let mut archive: Self = serde_json::from_slice(&json).map_err(|e| eyre!("Not a snapshot archive: {e}"))?;
+        if archive.format != ARCHIVE_FORMAT {
+            return Err(eyre!("Not a snapshot archive: unknown format '{}'", archive.format));
+        }
+        if archive.version > ARCHIVE_VERSION {
+            return Err(eyre!(
+                "Snapshot archive version {} is newer than this version of envx supports",
+                archive.version
+            ));
+        }
+        // The ID names the snapshot's files, so anything but a UUID could escape the snapshots directory
+        archive.snapshot.id = uuid::Uuid::try_parse(&archive.snapshot.id)
+            .map_err(|_| {
+                eyre!(
+                    "Invalid snapshot archive: '{}' is not a snapshot ID",
+                    archive.snapshot.id
+                )
+            })?
+            .to_string();
+        Ok(archive)
+    }
+
+    /// Returns true if secrets in the archive are encrypted with a passphrase.
+    #[must_use]
+    pub const fn is_encrypted(&self) -> bool {
+        self.salt.is_some()
+    }
+
+    /// Returns the snapshot with its secrets decrypted with `passphrase`.
+    ///
+    /// Masked variables are recorded in the snapshot's metadata (see
+    /// [`Snapshot::masked`]), so restoring it leaves them alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive is encrypted and the passphrase is
+    /// missing or wrong.
+    pub fn into_snapshot(self, passphrase: Option<&str>) -> Result<Snapshot> {
+        let mut snapshot = self.snapshot;
+        if !self.masked.is_empty() {
+            snapshot.metadata.insert(MASKED_KEY.to_string(), self.masked.join(","));
+        }
+        let Some(salt) = self.salt else {
+            return Ok(snapshot);
+        };
+
+        let passphrase =
+            passphrase.ok_or_else(|| eyre!("The archive's secrets are encrypted, a passphrase is needed"))?;
+        let (vault, _) = Vault::with_passphrase(passphrase, Some(&salt))?;
+        for var in snapshot.variables.values_mut() {
+            for value in std::iter::once(&mut var.value).chain(var.original_value.as_mut()) {
+                *value = vault
+                    .decrypt(value)
+                    .map_err(|_| eyre!("Wrong passphrase for the snapshot archive"))?;
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnvVar, EnvVarSource};
+
+    fn create_test_snapshot() -> Snapshot {
+        let var = |name: &str, value: &str| EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+            source: EnvVarSource::User,
+            modified: Utc::now(),
+            original_value: None,
+            origin: None,
+        };
+        let mut snapshot = Snapshot::from_vars(
+            "broken".to_string(),
+            Some("Reproduces the bug".to_string()),
+            vec![var("API_KEY", "sk-1234567890abcdef"), var("PORT", "3000")],
+        );
+        snapshot.add_tag("bug").unwrap();
+        snapshot
+    }
+
+    #[test]
+    fn test_archive_roundtrip_keeps_id_and_metadata() {
+        let snapshot = create_test_snapshot();
+        let archive = SnapshotArchive::new(snapshot.clone(), &ArchiveSecrets::Plain, &SecretClassifier::new()).unwrap();
+
+        for compressed in [false, true] {
+            let bytes = archive.to_bytes(compressed).unwrap();
+            let imported = SnapshotArchive::from_bytes(&bytes)
+                .unwrap()
+                .into_snapshot(None)
+                .unwrap();
+            assert_eq!(imported.id, snapshot.id);
+            assert_eq!(imported.description, snapshot.description);
+            assert!(imported.has_tag("bug"));
+            assert_eq!(imported.variables["API_KEY"].value, "sk-1234567890abcdef");
+            assert_eq!(imported.variables["PORT"].modified, snapshot.variables["PORT"].modified);
+            assert_eq!(imported.variables["PORT"].source, EnvVarSource::User);
+        }
+    }
+
+    #[test]
+    fn test_archive_masks_secrets() {
+        let archive =
+            SnapshotArchive::new(create_test_snapshot(), &ArchiveSecrets::Mask, &SecretClassifier::new()).unwrap();
+        let json = String::from_utf8(archive.to_bytes(false).unwrap()).unwrap();
+        assert!(!json.contains("sk-1234567890abcdef"));
+        assert_eq!(archive.masked, ["API_KEY"]);
+
+        let snapshot = archive.into_snapshot(None).unwrap();
+        assert!(!snapshot.variables.contains_key("API_KEY"));
+        assert_eq!(snapshot.masked(), ["API_KEY"]);
+        assert_eq!(snapshot.variables["PORT"].value, "3000");
+    }
+
+    #[test]
+    fn test_archive_encrypts_secrets_with_passphrase() {
+        let archive = SnapshotArchive::new(
+            create_test_snapshot(),
+            &ArchiveSecrets::Encrypt("team secret".to_string()),
+            &SecretClassifier::new(),
+        )
+        .unwrap();
+        let bytes = archive.to_bytes(false).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("sk-1234567890abcdef"));
+
+        let archive = SnapshotArchive::from_bytes(&bytes).unwrap();
+        assert!(archive.is_encrypted());
+        assert!(archive.clone().into_snapshot(None).is_err());
+        assert!(archive.clone().into_snapshot(Some("wrong")).is_err());
+        let snapshot = archive.into_snapshot(Some("team secret")).unwrap();
+        assert_eq!(snapshot.variables["API_KEY"].value, "sk-1234567890abcdef");
+        assert_eq!(snapshot.variables["PORT"].value, "3000");
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_other_files() {
+        assert!(SnapshotArchive::from_bytes(b"{ \"PORT\": \"3000\" }").is_err());
+
+        let mut archive =
+            SnapshotArchive::new(create_test_snapshot(), &ArchiveSecrets::Plain, &SecretClassifier::new()).unwrap();
+        archive.version = ARCHIVE_VERSION + 1;
+        assert!(SnapshotArchive::from_bytes(&archive.to_bytes(false).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_ids_that_are_not_uuids() {
+        let mut archive =
+            SnapshotArchive::new(create_test_snapshot(), &ArchiveSecrets::Plain, &SecretClassifier::new()).unwrap();
+        for id in ["../../profiles", "/tmp/evil", "", "not-a-uuid"] {
+            archive.snapshot.id = id.to_string();
+            assert!(SnapshotArchive::from_bytes(&archive.to_bytes(false).unwrap()).is_err());
+        }
+
+        let id = "6f1c2a4e-8b3d-4e5f-9a7b-0c1d2e3f4a5b";
+        archive.snapshot.id = id.to_uppercase();
+        let read = SnapshotArchive::from_bytes(&archive.to_bytes(false).unwrap()).unwrap();
+        assert_eq!(read.snapshot.id, id);
+    }
+}
@@ -1,11 +1,65 @@
-use crate::snapshot::Snapshot;
-use crate::{EnvVar, EnvVarManager};
+use crate::env::wildcard_to_regex;
+use crate::snapshot::{AUTO_TAG, Snapshot, SnapshotSummary};
+use crate::snapshot_archive::{ArchiveSecrets, SnapshotArchive};
+use crate::snapshot_store::{MigrationReport, SnapshotStore};
+use crate::vault::{LazyVault, Vault, is_encrypted};
+use crate::{EnvVar, EnvVarManager, EnvVarSource, KeyStore, SecretClassifier};
 use ahash::AHashMap as HashMap;
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
+use regex::Regex;
 use std::fs;
 use std::path::PathBuf;
 
+/// Which variables a restore touches and how, see [`SnapshotManager::restore_with`]
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    /// Only restore variables matching one of these names or wildcard patterns
+    pub patterns: Vec<String>,
+    /// Only restore variables from these sources
+    pub sources: Vec<EnvVarSource>,
+    /// Also remove variables that match `patterns` or `sources` but aren't in
+    /// the snapshot. Needs at least one pattern or source, and never removes
+    /// user or system variables.
+    pub prune: bool,
+}
+
+impl RestoreOptions {
+    fn matcher(&self) -> Result<impl Fn(&EnvVar) -> bool + '_> {
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|pattern| Regex::new(&wildcard_to_regex(pattern)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(move |var: &EnvVar| {
+            (patterns.is_empty() || patterns.iter().any(|re| re.is_match(&var.name)))
+                && (self.sources.is_empty() || self.sources.contains(&var.source))
+        })
+    }
+}
+
+/// Which snapshots [`SnapshotManager::prune`] deletes. A snapshot is deleted
+/// if it matches every rule that is set.
+#[derive(Debug, Clone, Default)]
+pub struct PrunePolicy {
+    /// Keep this many of the newest snapshots
+    pub keep_last: Option<usize>,
+    /// Only delete snapshots older than this
+    pub older_than: Option<chrono::Duration>,
+    /// Only consider snapshots with this tag
+    pub tag: Option<String>,
+}
+
+/// The outcome of [`SnapshotManager::restore_with`]
+#[derive(Debug)]
+pub struct RestoreReport {
+    /// Changes made, from the environment before the restore to the snapshot
+    pub diff: SnapshotDiff,
+    /// Snapshot of the environment taken before the restore
+    pub backup: Snapshot,
+}
+
 pub struct SnapshotManager {
     storage_dir: PathBuf,
 }
@@ -48,33 +102,181 @@ impl SnapshotManager {
         Ok(snapshot)
     }
 
+    /// Packs a snapshot into a portable archive, decrypting its secrets and
+    /// writing them as `secrets` says.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The snapshot cannot be found by ID or name
+    /// - The local key cannot be unlocked to decrypt a secret
+    /// - A secret cannot be encrypted with the archive passphrase
+    pub fn export(&self, id_or_name: &str, secrets: &ArchiveSecrets) -> Result<SnapshotArchive> {
+        let mut snapshot = self.get(id_or_name)?;
+        self.decrypt(&mut snapshot)?;
+        SnapshotArchive::new(snapshot, secrets, &SecretClassifier::for_current_project())
+    }
+
+    /// Saves the snapshot of an archive, keeping its ID, and encrypts its
+    /// secrets with the local key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A snapshot with the same ID exists and `replace` is false
+    /// - The archive is encrypted and `passphrase` is missing or wrong
+    /// - There are file system errors when writing the snapshot
+    pub fn import(&self, archive: SnapshotArchive, passphrase: Option<&str>, replace: bool) -> Result<Snapshot> {
+        let snapshot = archive.into_snapshot(passphrase)?;
+        if !replace {
+            if let Some(existing) = self.store().read(&snapshot.id)? {
+                return Err(eyre!("Snapshot {} already exists as '{}'", snapshot.id, existing.name));
+            }
+        }
+
+        self.save_snapshot(&snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Creates a snapshot tagged [`AUTO_TAG`] of the variables before an
+    /// operation described by `reason` changes them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - There are file system errors when writing the snapshot file to disk
+    /// - JSON serialization of the snapshot fails
+    pub fn create_auto(&self, reason: &str, vars: Vec<EnvVar>) -> Result<Snapshot> {
+        self.create_tagged_auto("auto", format!("Automatic snapshot before {reason}"), vars)
+    }
+
+    /// Names the snapshot `{prefix}-{timestamp}-{id}` with the start of its ID,
+    /// so snapshots taken within the same second get distinct names.
+    fn create_tagged_auto(&self, prefix: &str, description: String, vars: Vec<EnvVar>) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::from_vars(String::new(), Some(description), vars);
+        snapshot.name = format!(
+            "{}-{}-{}",
+            prefix,
+            snapshot.created_at.format("%Y%m%d-%H%M%S"),
+            &snapshot.id[..8]
+        );
+        snapshot.add_tag(AUTO_TAG)?;
+        self.save_snapshot(&snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Changes a stored snapshot, e.g. its tags or labels, and writes it back.
+    ///
+    /// Secret values stay encrypted while `f` runs.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The snapshot cannot be found by ID or name
+    /// - `f` returns an error
+    /// - There are file system errors when writing the snapshot file
+    pub fn modify(&self, id_or_name: &str, f: impl FnOnce(&mut Snapshot) -> Result<()>) -> Result<Snapshot> {
+        let mut snapshot = self.get(id_or_name)?;
+        f(&mut snapshot)?;
+        self.save_snapshot(&snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Returns the snapshots [`SnapshotManager::prune`] would delete, newest first.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Neither `keep_last` nor `older_than` is set
+    /// - `older_than` reaches back past the earliest representable date
+    /// - There are file system errors when reading the snapshots
+    pub fn prune_candidates(&self, policy: &PrunePolicy) -> Result<Vec<SnapshotSummary>> {
+        if policy.keep_last.is_none() && policy.older_than.is_none() {
+            return Err(eyre!(
+                "A prune policy needs a number of snapshots to keep or a maximum age"
+            ));
+        }
+
+        let cutoff = policy
+            .older_than
+            .map(|age| {
+                chrono::Utc::now()
+                    .checked_sub_signed(age)
+                    .ok_or_else(|| eyre!("The maximum age {} is out of range", age))
+            })
+            .transpose()?;
+        Ok(self
+            .summaries()?
+            .into_iter()
+            .filter(|snapshot| policy.tag.as_deref().is_none_or(|tag| snapshot.has_tag(tag)))
+            .skip(policy.keep_last.unwrap_or(0))
+            .filter(|snapshot| cutoff.is_none_or(|cutoff| snapshot.created_at < cutoff))
+            .collect())
+    }
+
+    /// Deletes the snapshots matching `policy` and returns them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The policy is invalid (see [`SnapshotManager::prune_candidates`])
+    /// - There are file system errors when reading or deleting snapshot files
+    pub fn prune(&self, policy: &PrunePolicy) -> Result<Vec<SnapshotSummary>> {
+        let pruned = self.prune_candidates(policy)?;
+        let store = self.store();
+        for snapshot in &pruned {
+            store.remove(&snapshot.id)?;
+        }
+        store.collect_garbage()?;
+        Ok(pruned)
+    }
+
+    /// Lists the summaries of all snapshots sorted by creation date (newest
+    /// first), without reading their variables.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - There are file system errors when reading the snapshot index
+    /// - The snapshot index is corrupt
+    pub fn summaries(&self) -> Result<Vec<SnapshotSummary>> {
+        self.store().summaries()
+    }
+
     /// Lists all snapshots sorted by creation date (newest first).
     ///
+    /// This reads every snapshot, prefer [`SnapshotManager::summaries`] to list them.
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - There are file system errors when reading the snapshots directory
     /// - There are file system errors when reading individual snapshot files
     pub fn list(&self) -> Result<Vec<Snapshot>> {
+        let store = self.store();
         let mut snapshots = Vec::new();
-
-        for entry in fs::read_dir(&self.storage_dir)? {
-            let entry = entry?;
-            if entry.path().extension().and_then(|s| s.to_str()) == Some("json") {
-                let content = fs::read_to_string(entry.path())?;
-                if let Ok(snapshot) = serde_json::from_str::<Snapshot>(&content) {
-                    snapshots.push(snapshot);
-                }
-            }
+        for summary in store.summaries()? {
+            snapshots.extend(store.read(&summary.id)?);
         }
-
-        // Sort by creation date (newest first)
-        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
         Ok(snapshots)
     }
 
+    /// Converts snapshots written by earlier versions of envx, one pretty
+    /// JSON file each, to the compressed, deduplicated format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there are file system errors
+    /// when reading or writing snapshot files. Files that aren't snapshots are
+    /// skipped and listed in the report.
+    pub fn migrate(&self) -> Result<MigrationReport> {
+        self.store().migrate()
+    }
+
     /// Gets a snapshot by ID or name.
     ///
+    /// Secret values stay encrypted, see [`SnapshotManager::decrypt`].
+    ///
     /// # Errors
     ///
     /// This function will return an error if:
@@ -82,16 +284,16 @@ impl SnapshotManager {
     /// - There are file system errors when reading the snapshot file
     /// - JSON deserialization fails for the snapshot file
     pub fn get(&self, id_or_name: &str) -> Result<Snapshot> {
+        let store = self.store();
+
         // Try by ID first
-        let id_path = self.storage_dir.join(format!("{id_or_name}.json"));
-        if id_path.exists() {
-            let content = fs::read_to_string(&id_path)?;
-            return Ok(serde_json::from_str(&content)?);
+        if let Some(snapshot) = store.read(id_or_name)? {
+            return Ok(snapshot);
         }
 
         // Try by name
-        for snapshot in self.list()? {
-            if snapshot.name == id_or_name {
+        if let Some(summary) = store.summaries()?.into_iter().find(|s| s.name == id_or_name) {
+            if let Some(snapshot) = store.read(&summary.id)? {
                 return Ok(snapshot);
             }
         }
@@ -108,12 +310,94 @@ impl SnapshotManager {
     /// - There are file system errors when deleting the snapshot file
     pub fn delete(&self, id_or_name: &str) -> Result<()> {
         let snapshot = self.get(id_or_name)?;
-        let path = self.storage_dir.join(format!("{}.json", snapshot.id));
-        fs::remove_file(path)?;
+        let store = self.store();
+        store.remove(&snapshot.id)?;
+        store.collect_garbage()?;
+        Ok(())
+    }
+
+    /// Decrypts the encrypted values of a snapshot in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be unlocked or a value cannot be decrypted.
+    pub fn decrypt(&self, snapshot: &mut Snapshot) -> Result<()> {
+        let mut vault = LazyVault::new(self.key_store());
+        for var in snapshot.variables.values_mut() {
+            var.value = vault.decrypt(&var.value)?;
+            if let Some(original) = &mut var.original_value {
+                *original = vault.decrypt(original)?;
+            }
+        }
         Ok(())
     }
 
-    /// Restores environment variables from a snapshot by clearing current variables and applying snapshot values.
+    /// Re-encrypts the secret values of all snapshots with `new` in memory,
+    /// decrypting existing ones with `old`. Returns the snapshots and the
+    /// number of values encrypted. Write them with [`SnapshotManager::save_all`]
+    /// once `new` is stored (see [`KeyStore::stage`]).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - An encrypted value exists but `old` is `None` or cannot decrypt it
+    /// - There are file system errors when reading snapshot files
+    pub fn reencrypt(&self, old: Option<&Vault>, new: &Vault) -> Result<(Vec<Snapshot>, usize)> {
+        let secrets = SecretClassifier::for_current_project();
+        let mut count = 0;
+
+        let mut snapshots = self.list()?;
+        for snapshot in &mut snapshots {
+            for var in snapshot.variables.values_mut() {
+                for value in std::iter::once(&mut var.value).chain(var.original_value.as_mut()) {
+                    if is_encrypted(value) {
+                        let old = old.ok_or_else(|| {
+                            eyre!("Snapshot '{}' has encrypted values but no key exists", snapshot.name)
+                        })?;
+                        *value = old.decrypt(value)?;
+                    }
+                    if new.encrypt_secret(&secrets, &var.name, value)? {
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        Ok((snapshots, count))
+    }
+
+    /// Saves snapshots returned by [`SnapshotManager::reencrypt`] and removes
+    /// the values encrypted with the old key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are file system errors when writing snapshot files.
+    pub fn save_all(&self, snapshots: &[Snapshot]) -> Result<()> {
+        for snapshot in snapshots {
+            self.save_snapshot(snapshot)?;
+        }
+        // Values encrypted with the old key are no longer referenced
+        self.store().collect_garbage()?;
+        Ok(())
+    }
+
+    /// Restores all environment variables from a snapshot, see
+    /// [`SnapshotManager::restore_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`SnapshotManager::restore_with`].
+    pub fn restore(&self, id_or_name: &str, manager: &mut EnvVarManager) -> Result<()> {
+        self.restore_with(id_or_name, manager, &RestoreOptions::default())?;
+        Ok(())
+    }
+
+    /// Computes what restoring a snapshot with `options` would change in the
+    /// environment held by `manager`, without changing anything.
+    ///
+    /// The diff goes from the current environment to the snapshot: variables
+    /// the restore sets are `added` or `modified`, variables it removes are
+    /// `removed` (only with `options.prune`).
     ///
     /// # Errors
     ///
@@ -121,19 +405,104 @@ impl SnapshotManager {
     /// - The snapshot cannot be found by ID or name
     /// - There are file system errors when reading the snapshot file
     /// - JSON deserialization fails for the snapshot file
-    /// - Setting environment variables in the manager fails
-    pub fn restore(&self, id_or_name: &str, manager: &mut EnvVarManager) -> Result<()> {
-        let snapshot = self.get(id_or_name)?;
+    /// - An encrypted value cannot be decrypted
+    /// - A pattern in `options` is invalid, or `options.prune` is set without
+    ///   a pattern or source
+    pub fn plan_restore(
+        &self,
+        id_or_name: &str,
+        manager: &EnvVarManager,
+        options: &RestoreOptions,
+    ) -> Result<SnapshotDiff> {
+        let mut snapshot = self.get(id_or_name)?;
+        self.decrypt(&mut snapshot)?;
+        Self::plan(&snapshot, manager, options)
+    }
+
+    fn plan(snapshot: &Snapshot, manager: &EnvVarManager, options: &RestoreOptions) -> Result<SnapshotDiff> {
+        if options.prune && options.patterns.is_empty() && options.sources.is_empty() {
+            return Err(eyre!(
+                "Pruning needs a pattern or source to limit which variables are removed"
+            ));
+        }
+        let in_scope = options.matcher()?;
 
-        // Clear current variables
-        manager.clear();
+        let mut diff = SnapshotDiff::default();
+        for var in snapshot.variables.values().filter(|var| in_scope(var)) {
+            match manager.get(&var.name) {
+                Some(current) if current.value != var.value => {
+                    diff.modified.insert(var.name.clone(), (current.clone(), var.clone()));
+                }
+                Some(_) => {}
+                None => {
+                    diff.added.insert(var.name.clone(), var.clone());
+                }
+            }
+        }
 
-        // Restore from snapshot
-        for (_, var) in snapshot.variables {
-            manager.set(&var.name, &var.value, true)?;
+        if options.prune {
+            // Masked secrets are missing from the snapshot but weren't unset when it was taken
+            let masked = snapshot.masked();
+            let removable = |var: &EnvVar| {
+                !matches!(var.source, EnvVarSource::User | EnvVarSource::System) && !masked.contains(&var.name.as_str())
+            };
+            for var in manager.list().into_iter().filter(|var| in_scope(var) && removable(var)) {
+                if !snapshot.variables.contains_key(&var.name) {
+                    diff.removed.insert(var.name.clone(), var.clone());
+                }
+            }
         }
 
-        Ok(())
+        Ok(diff)
+    }
+
+    /// Restores environment variables from a snapshot.
+    ///
+    /// Only variables matching `options` are touched. Variables the snapshot
+    /// recorded as user or system variables are persisted again, process and
+    /// shell variables are only set for the current process. With
+    /// `options.prune`, matching session variables that aren't in the
+    /// snapshot are removed, except secrets masked out of the archive the
+    /// snapshot was imported from.
+    ///
+    /// Before anything changes, the current environment is saved as a new
+    /// snapshot so the restore can be rolled back by restoring that one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The restore cannot be planned (see [`SnapshotManager::plan_restore`])
+    /// - The pre-restore snapshot cannot be written
+    /// - Setting or removing environment variables in the manager fails
+    pub fn restore_with(
+        &self,
+        id_or_name: &str,
+        manager: &mut EnvVarManager,
+        options: &RestoreOptions,
+    ) -> Result<RestoreReport> {
+        let mut snapshot = self.get(id_or_name)?;
+        self.decrypt(&mut snapshot)?;
+        let diff = Self::plan(&snapshot, manager, options)?;
+
+        let backup = self.create_tagged_auto(
+            &format!("pre-restore-{}", snapshot.name),
+            format!("Automatic snapshot before restoring '{}'", snapshot.name),
+            manager.list().into_iter().cloned().collect(),
+        )?;
+
+        manager.batch(&format!("restore snapshot {}", snapshot.name), |manager| {
+            for var in diff.added.values().chain(diff.modified.values().map(|(_, new)| new)) {
+                let permanent = matches!(var.source, EnvVarSource::User | EnvVarSource::System);
+                manager.set(&var.name, &var.value, permanent)?;
+            }
+            for name in diff.removed.keys() {
+                manager.delete(name)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(RestoreReport { diff, backup })
     }
 
     /// Compares two snapshots and returns the differences between them.
@@ -144,41 +513,67 @@ impl SnapshotManager {
     /// - Either snapshot cannot be found by ID or name
     /// - There are file system errors when reading snapshot files
     /// - JSON deserialization fails for the snapshot files
+    /// - An encrypted value cannot be decrypted
     pub fn diff(&self, snapshot1: &str, snapshot2: &str) -> Result<SnapshotDiff> {
-        let snap1 = self.get(snapshot1)?;
-        let snap2 = self.get(snapshot2)?;
+        let mut snap1 = self.get(snapshot1)?;
+        let mut snap2 = self.get(snapshot2)?;
+        self.decrypt(&mut snap1)?;
+        self.decrypt(&mut snap2)?;
+
+        Ok(SnapshotDiff::between(
+            snap1.variables.into_values(),
+            snap2.variables.into_values(),
+        ))
+    }
 
-        let mut diff = SnapshotDiff::default();
+    /// Compares a snapshot with the environment held by `manager`, the
+    /// snapshot being the old side.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The snapshot cannot be found by ID or name
+    /// - There are file system errors when reading the snapshot file
+    /// - JSON deserialization fails for the snapshot file
+    /// - An encrypted value cannot be decrypted
+    pub fn diff_current(&self, snapshot: &str, manager: &EnvVarManager) -> Result<SnapshotDiff> {
+        let mut snap = self.get(snapshot)?;
+        self.decrypt(&mut snap)?;
+
+        Ok(SnapshotDiff::between(
+            snap.variables.into_values(),
+            manager.list().into_iter().cloned(),
+        ))
+    }
 
-        // Find added and modified
-        for (name, var2) in &snap2.variables {
-            match snap1.variables.get(name) {
-                Some(var1) => {
-                    if var1.value != var2.value {
-                        diff.modified.insert(name.clone(), (var1.clone(), var2.clone()));
-                    }
-                }
-                None => {
-                    diff.added.insert(name.clone(), var2.clone());
-                }
+    /// Writes a snapshot, e.g. one built with [`Snapshot::from_vars`], encrypting its secret values.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The key cannot be unlocked to encrypt a secret value
+    /// - There are file system errors when writing the snapshot file
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> color_eyre::Result<()> {
+        let secrets = SecretClassifier::for_current_project();
+        let mut vault = LazyVault::new(self.key_store());
+        let mut snapshot = snapshot.clone();
+        for var in snapshot.variables.values_mut() {
+            vault.encrypt_secret(&secrets, &var.name, &mut var.value)?;
+            if let Some(original) = &mut var.original_value {
+                vault.encrypt_secret(&secrets, &var.name, original)?;
             }
         }
 
-        // Find removed
-        for (name, var1) in &snap1.variables {
-            if !snap2.variables.contains_key(name) {
-                diff.removed.insert(name.clone(), var1.clone());
-            }
-        }
+        self.store().write(&snapshot)
+    }
 
-        Ok(diff)
+    fn store(&self) -> SnapshotStore<'_> {
+        SnapshotStore::new(&self.storage_dir)
     }
 
-    fn save_snapshot(&self, snapshot: &Snapshot) -> color_eyre::Result<()> {
-        let path = self.storage_dir.join(format!("{}.json", snapshot.id));
-        let content = serde_json::to_string_pretty(snapshot)?;
-        fs::write(path, content)?;
-        Ok(())
+    /// The key store of the config directory holding the snapshots.
+    fn key_store(&self) -> KeyStore {
+        KeyStore::at(self.storage_dir.parent().unwrap_or(&self.storage_dir))
     }
 }
 
@@ -189,9 +584,38 @@ pub struct SnapshotDiff {
     pub modified: HashMap<String, (EnvVar, EnvVar)>, // (old, new)
 }
 
+impl SnapshotDiff {
+    /// Compares two sets of variables by name and value.
+    pub fn between(old: impl IntoIterator<Item = EnvVar>, new: impl IntoIterator<Item = EnvVar>) -> Self {
+        let mut old: HashMap<String, EnvVar> = old.into_iter().map(|var| (var.name.clone(), var)).collect();
+
+        let mut diff = Self::default();
+        for var in new {
+            match old.remove(&var.name) {
+                Some(old_var) if old_var.value != var.value => {
+                    diff.modified.insert(var.name.clone(), (old_var, var));
+                }
+                Some(_) => {}
+                None => {
+                    diff.added.insert(var.name.clone(), var);
+                }
+            }
+        }
+        diff.removed = old;
+
+        diff
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::snapshot_store::decompress;
     use crate::{EnvVar, EnvVarSource};
     use chrono::Utc;
     use tempfile::TempDir;
@@ -212,11 +636,22 @@ mod tests {
             source: EnvVarSource::User,
             modified: Utc::now(),
             original_value: None,
+            origin: None,
         }
     }
 
+    fn stored_files(dir: &std::path::Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .flat_map(|entry| {
+                let path = entry.unwrap().path();
+                if path.is_dir() { stored_files(&path) } else { vec![path] }
+            })
+            .collect()
+    }
+
     fn create_test_env_manager() -> EnvVarManager {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::for_tests();
         manager.set("VAR1", "value1", false).unwrap();
         manager.set("VAR2", "value2", false).unwrap();
         manager.set("VAR3", "value3", false).unwrap();
@@ -259,7 +694,7 @@ mod tests {
         assert!(snapshot.variables.contains_key("TEST_VAR2"));
 
         // Verify snapshot was saved to disk
-        let snapshot_path = manager.storage_dir.join(format!("{}.json", snapshot.id));
+        let snapshot_path = manager.storage_dir.join(format!("{}.json.gz", snapshot.id));
         assert!(snapshot_path.exists());
     }
 
@@ -394,7 +829,7 @@ mod tests {
         assert!(manager.get(&snapshot.id).is_err());
 
         // Verify file is deleted
-        let snapshot_path = manager.storage_dir.join(format!("{}.json", snapshot.id));
+        let snapshot_path = manager.storage_dir.join(format!("{}.json.gz", snapshot.id));
         assert!(!snapshot_path.exists());
     }
 
@@ -430,17 +865,244 @@ mod tests {
         ];
         let snapshot = manager.create("to-restore".to_string(), None, vars).unwrap();
 
-        // Restore it
+        // Restore it; variables the snapshot doesn't have are kept
         let result = manager.restore(&snapshot.id, &mut env_manager);
         assert!(result.is_ok());
 
-        // Verify old variables are cleared and new ones are set
-        assert!(env_manager.get("VAR1").is_none());
+        assert_eq!(env_manager.get("VAR1").unwrap().value, "value1");
+        assert_eq!(env_manager.get("NEW_VAR1").unwrap().value, "new_value1");
+        assert_eq!(env_manager.get("NEW_VAR2").unwrap().value, "new_value2");
+    }
+
+    #[test]
+    fn test_restore_prunes_only_matching_session_variables() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        let mut env_manager = create_test_env_manager();
+        env_manager.vars.insert(
+            "VAR_PERSISTED".to_string(),
+            create_test_env_var("VAR_PERSISTED", "kept"),
+        );
+        let snapshot = manager
+            .create("prune".to_string(), None, vec![create_test_env_var("VAR1", "value1")])
+            .unwrap();
+
+        // Pruning has to be limited to some variables
+        let options = RestoreOptions {
+            prune: true,
+            ..RestoreOptions::default()
+        };
+        assert!(manager.plan_restore(&snapshot.id, &env_manager, &options).is_err());
+
+        let options = RestoreOptions {
+            patterns: vec!["VAR*".to_string()],
+            prune: true,
+            ..RestoreOptions::default()
+        };
+        let report = manager.restore_with(&snapshot.id, &mut env_manager, &options).unwrap();
+        assert_eq!(report.diff.removed.len(), 2);
         assert!(env_manager.get("VAR2").is_none());
         assert!(env_manager.get("VAR3").is_none());
+        assert_eq!(env_manager.get("VAR1").unwrap().value, "value1");
+        assert_eq!(env_manager.get("VAR_PERSISTED").unwrap().value, "kept");
+    }
 
-        assert_eq!(env_manager.get("NEW_VAR1").unwrap().value, "new_value1");
-        assert_eq!(env_manager.get("NEW_VAR2").unwrap().value, "new_value2");
+    #[test]
+    fn test_restore_scoped() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        let mut env_manager = create_test_env_manager();
+
+        let mut process_var = create_test_env_var("APP_PWD", "/tmp");
+        process_var.source = EnvVarSource::Process;
+        let vars = vec![
+            create_test_env_var("VAR1", "restored1"),
+            create_test_env_var("APP_HOST", "localhost"),
+            process_var,
+        ];
+        let snapshot = manager.create("scoped".to_string(), None, vars).unwrap();
+
+        let options = RestoreOptions {
+            patterns: vec!["APP_*".to_string()],
+            ..RestoreOptions::default()
+        };
+        let plan = manager.plan_restore(&snapshot.id, &env_manager, &options).unwrap();
+        assert_eq!(plan.added.len(), 2);
+        assert!(plan.modified.is_empty());
+        assert!(plan.removed.is_empty());
+        // Planning changes nothing
+        assert!(env_manager.get("APP_HOST").is_none());
+
+        let report = manager.restore_with(&snapshot.id, &mut env_manager, &options).unwrap();
+        assert_eq!(env_manager.get("APP_HOST").unwrap().source, EnvVarSource::User);
+        assert_eq!(env_manager.get("APP_PWD").unwrap().source, EnvVarSource::Process);
+        assert_eq!(env_manager.get("VAR1").unwrap().value, "value1");
+        assert!(env_manager.get("VAR2").is_some());
+
+        // The pre-restore snapshot holds the environment before the restore
+        let backup = manager.get(&report.backup.id).unwrap();
+        assert_eq!(backup.variables.len(), 3);
+        assert!(!backup.variables.contains_key("APP_HOST"));
+
+        let options = RestoreOptions {
+            sources: vec![EnvVarSource::User],
+            ..RestoreOptions::default()
+        };
+        let report = manager.restore_with(&snapshot.id, &mut env_manager, &options).unwrap();
+        assert_eq!(report.diff.modified["VAR1"].1.value, "restored1");
+        assert!(report.diff.removed.is_empty());
+        assert_eq!(env_manager.get("VAR1").unwrap().value, "restored1");
+
+        // Rolling back restores the old values
+        manager.restore(&report.backup.id, &mut env_manager).unwrap();
+        assert_eq!(env_manager.get("VAR1").unwrap().value, "value1");
+    }
+
+    #[test]
+    fn test_diff_snapshot_against_current() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        let mut env_manager = create_test_env_manager();
+
+        let vars = env_manager.list().into_iter().cloned().collect();
+        manager.create("before".to_string(), None, vars).unwrap();
+        assert!(manager.diff_current("before", &env_manager).unwrap().is_empty());
+
+        env_manager.set("VAR1", "changed", false).unwrap();
+        env_manager.set("VAR4", "value4", false).unwrap();
+        env_manager.delete("VAR2").unwrap();
+
+        let diff = manager.diff_current("before", &env_manager).unwrap();
+        assert_eq!(diff.modified["VAR1"].0.value, "value1");
+        assert_eq!(diff.modified["VAR1"].1.value, "changed");
+        assert!(diff.added.contains_key("VAR4"));
+        assert!(diff.removed.contains_key("VAR2"));
+        assert!(!diff.modified.contains_key("VAR3"));
+    }
+
+    fn create_aged_snapshot(manager: &SnapshotManager, name: &str, days: i64, tag: Option<&str>) {
+        let mut snapshot = Snapshot::new(name.to_string(), None);
+        snapshot.created_at = Utc::now() - chrono::Duration::days(days);
+        if let Some(tag) = tag {
+            snapshot.add_tag(tag).unwrap();
+        }
+        manager.save_snapshot(&snapshot).unwrap();
+    }
+
+    #[test]
+    fn test_prune_snapshots() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        create_aged_snapshot(&manager, "new", 1, None);
+        create_aged_snapshot(&manager, "auto-new", 2, Some(AUTO_TAG));
+        create_aged_snapshot(&manager, "old", 40, None);
+        create_aged_snapshot(&manager, "auto-old", 50, Some(AUTO_TAG));
+        create_aged_snapshot(&manager, "oldest", 60, None);
+
+        assert!(manager.prune_candidates(&PrunePolicy::default()).is_err());
+
+        let names = |snapshots: Vec<SnapshotSummary>| snapshots.into_iter().map(|s| s.name).collect::<Vec<_>>();
+        let policy = PrunePolicy {
+            keep_last: Some(3),
+            ..PrunePolicy::default()
+        };
+        assert_eq!(
+            names(manager.prune_candidates(&policy).unwrap()),
+            ["auto-old", "oldest"]
+        );
+
+        let policy = PrunePolicy {
+            older_than: Some(chrono::Duration::days(30)),
+            tag: Some(AUTO_TAG.to_string()),
+            ..PrunePolicy::default()
+        };
+        assert_eq!(names(manager.prune_candidates(&policy).unwrap()), ["auto-old"]);
+
+        let policy = PrunePolicy {
+            keep_last: Some(4),
+            older_than: Some(chrono::Duration::days(30)),
+            tag: None,
+        };
+        assert_eq!(names(manager.prune(&policy).unwrap()), ["oldest"]);
+        assert_eq!(manager.list().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_auto_snapshots_are_tagged() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        let env_manager = create_test_env_manager();
+
+        let vars = env_manager.list().into_iter().cloned().collect();
+        let snapshot = manager.create_auto("delete VAR1", vars).unwrap();
+        assert!(snapshot.name.starts_with("auto-"));
+        let again = manager.create_auto("delete VAR1", Vec::new()).unwrap();
+        assert_ne!(snapshot.name, again.name);
+        assert_eq!(
+            snapshot.description.as_deref(),
+            Some("Automatic snapshot before delete VAR1")
+        );
+
+        manager
+            .modify(&snapshot.id, |snapshot| snapshot.set_label("ticket", "OPS-1"))
+            .unwrap();
+        let stored = manager.get(&snapshot.id).unwrap();
+        assert!(stored.has_tag(AUTO_TAG));
+        assert_eq!(stored.labels()["ticket"], "OPS-1");
+        assert_eq!(stored.variables.len(), 3);
+    }
+
+    #[test]
+    fn test_export_and_import_archive() {
+        let (source, _source_temp) = create_test_snapshot_manager();
+        let (target, _target_temp) = create_test_snapshot_manager();
+        let vars = vec![
+            create_test_env_var("DB_PASSWORD", "hunter2"),
+            create_test_env_var("PORT", "3000"),
+        ];
+        let snapshot = source.create("broken".to_string(), None, vars).unwrap();
+
+        let archive = source
+            .export("broken", &ArchiveSecrets::Encrypt("team secret".to_string()))
+            .unwrap();
+        let bytes = archive.to_bytes(true).unwrap();
+
+        let archive = SnapshotArchive::from_bytes(&bytes).unwrap();
+        let imported = target.import(archive.clone(), Some("team secret"), false).unwrap();
+        assert_eq!(imported.id, snapshot.id);
+        assert!(target.import(archive.clone(), Some("team secret"), false).is_err());
+        target.import(archive, Some("team secret"), true).unwrap();
+
+        // Secrets are encrypted with the local key again
+        let mut stored = target.get(&snapshot.id).unwrap();
+        assert!(is_encrypted(&stored.variables["DB_PASSWORD"].value));
+        target.decrypt(&mut stored).unwrap();
+        assert_eq!(stored.variables["DB_PASSWORD"].value, "hunter2");
+        assert_eq!(stored.variables["PORT"].modified, snapshot.variables["PORT"].modified);
+    }
+
+    #[test]
+    fn test_pruning_restore_keeps_masked_secrets() {
+        let (source, _source_temp) = create_test_snapshot_manager();
+        let (target, _target_temp) = create_test_snapshot_manager();
+        let vars = vec![
+            create_test_env_var("APP_PASSWORD", "hunter2"),
+            create_test_env_var("APP_PORT", "3000"),
+        ];
+        source.create("masked".to_string(), None, vars).unwrap();
+
+        let archive = source.export("masked", &ArchiveSecrets::Mask).unwrap();
+        let archive = SnapshotArchive::from_bytes(&archive.to_bytes(false).unwrap()).unwrap();
+        let imported = target.import(archive, None, false).unwrap();
+        assert_eq!(target.get(&imported.id).unwrap().masked(), ["APP_PASSWORD"]);
+
+        let mut env_manager = EnvVarManager::for_tests();
+        env_manager.set("APP_PASSWORD", "local", false).unwrap();
+        env_manager.set("APP_DEBUG", "1", false).unwrap();
+        let options = RestoreOptions {
+            patterns: vec!["APP_*".to_string()],
+            prune: true,
+            ..RestoreOptions::default()
+        };
+        target.restore_with(&imported.id, &mut env_manager, &options).unwrap();
+        assert_eq!(env_manager.get("APP_PASSWORD").unwrap().value, "local");
+        assert_eq!(env_manager.get("APP_PORT").unwrap().value, "3000");
+        assert!(env_manager.get("APP_DEBUG").is_none());
     }
 
     #[test]
@@ -518,22 +1180,28 @@ mod tests {
     }
 
     #[test]
-    fn test_save_snapshot_creates_pretty_json() {
+    fn test_save_snapshot_creates_compressed_manifest() {
         let (manager, _temp) = create_test_snapshot_manager();
 
         let vars = vec![create_test_env_var("TEST_VAR", "test_value")];
         let snapshot = manager
-            .create("pretty-test".to_string(), Some("Pretty JSON test".to_string()), vars)
+            .create("compressed-test".to_string(), Some("Compressed test".to_string()), vars)
             .unwrap();
 
-        // Read the saved file
-        let snapshot_path = manager.storage_dir.join(format!("{}.json", snapshot.id));
-        let content = fs::read_to_string(snapshot_path).unwrap();
+        // Read the saved manifest
+        let snapshot_path = manager.storage_dir.join(format!("{}.json.gz", snapshot.id));
+        let content = String::from_utf8(decompress(&fs::read(snapshot_path).unwrap()).unwrap()).unwrap();
 
-        // Verify it's pretty-printed (contains indentation)
-        assert!(content.contains("\n  "));
-        assert!(content.contains("\"name\": \"pretty-test\""));
-        assert!(content.contains("\"description\": \"Pretty JSON test\""));
+        // The manifest refers to values by hash instead of holding them
+        assert!(content.contains("\"name\":\"compressed-test\""));
+        assert!(content.contains("\"description\":\"Compressed test\""));
+        assert!(!content.contains("test_value"));
+        assert!(!manager.storage_dir.join(format!("{}.json", snapshot.id)).exists());
+
+        // The index lists the snapshot without reading it
+        let summaries = manager.summaries().unwrap();
+        assert_eq!(summaries[0].name, "compressed-test");
+        assert_eq!(summaries[0].variable_count, 1);
     }
 
     #[test]
@@ -557,4 +1225,37 @@ mod tests {
         let snapshots = manager.list().unwrap();
         assert_eq!(snapshots.len(), 5);
     }
+
+    #[test]
+    fn test_snapshot_secrets_are_encrypted_at_rest() {
+        let (manager, _temp) = create_test_snapshot_manager();
+        let mut secret = create_test_env_var("DB_PASSWORD", "hunter2");
+        secret.original_value = Some("hunter1".to_string());
+        let vars = vec![secret, create_test_env_var("PORT", "3000")];
+        manager.create("secrets".to_string(), None, vars).unwrap();
+
+        let content: String = stored_files(&manager.storage_dir)
+            .iter()
+            .map(|path| {
+                let data = fs::read(path).unwrap();
+                String::from_utf8_lossy(&decompress(&data).unwrap_or(data)).into_owned()
+            })
+            .collect();
+        assert!(!content.contains("hunter2"));
+        assert!(!content.contains("hunter1"));
+        assert!(content.contains("3000"));
+
+        let mut stored = manager.get("secrets").unwrap();
+        assert!(is_encrypted(&stored.variables["DB_PASSWORD"].value));
+        manager.decrypt(&mut stored).unwrap();
+        assert_eq!(stored.variables["DB_PASSWORD"].value, "hunter2");
+        assert_eq!(
+            stored.variables["DB_PASSWORD"].original_value.as_deref(),
+            Some("hunter1")
+        );
+
+        let mut env = EnvVarManager::for_tests();
+        manager.restore("secrets", &mut env).unwrap();
+        assert_eq!(env.get("DB_PASSWORD").unwrap().value, "hunter2");
+    }
 }
@@ -0,0 +1,570 @@
+//! On-disk format of snapshots.
+//!
+//! The snapshots directory holds:
+//! - `index.json`: a [`SnapshotSummary`] per snapshot, newest first, so
+//!   listing snapshots doesn't read every one of them
+//! - `<id>.json.gz`: a gzip-compressed manifest per snapshot, holding the
+//!   variables with the SHA-256 of their values instead of the values
+//! - `objects/<ab>/<cdef...>`: gzip-compressed values named by their SHA-256,
+//!   shared by every snapshot holding the same value
+//! - `<id>.json`: snapshots in the old uncompressed format, still read until
+//!   they are migrated
+
+use crate::snapshot::{Snapshot, SnapshotSummary};
+use crate::{EnvVar, EnvVarSource, VarOrigin};
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "index.json";
+const INDEX_VERSION: u32 = 1;
+const MANIFEST_EXTENSION: &str = ".json.gz";
+const OBJECTS_DIR: &str = "objects";
+
+/// The outcome of [`SnapshotManager::migrate`](crate::SnapshotManager::migrate)
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Number of snapshots converted to the compressed format
+    pub migrated: usize,
+    /// Old snapshot files that couldn't be read and were left in place
+    pub skipped: Vec<PathBuf>,
+    /// Size of the snapshots directory before the migration, in bytes
+    pub bytes_before: u64,
+    /// Size of the snapshots directory after the migration, in bytes
+    pub bytes_after: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    snapshots: Vec<SnapshotSummary>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    #[serde(flatten)]
+    summary: SnapshotSummary,
+    variables: HashMap<String, StoredVar>,
+}
+
+/// An [`EnvVar`] whose values are object hashes
+#[derive(Serialize, Deserialize)]
+struct StoredVar {
+    value: String,
+    source: EnvVarSource,
+    modified: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<VarOrigin>,
+}
+
+/// Reads and writes the snapshots of one directory.
+pub(crate) struct SnapshotStore<'a> {
+    dir: &'a Path,
+}
+
+impl<'a> SnapshotStore<'a> {
+    pub(crate) const fn new(dir: &'a Path) -> Self {
+        Self { dir }
+    }
+
+    /// Returns the summaries of all snapshots, newest first.
+    ///
+    /// Snapshots in the old format are read in full, unreadable ones skipped.
+    pub(crate) fn summaries(&self) -> Result<Vec<SnapshotSummary>> {
+        let mut summaries = self.read_index()?;
+        for path in self.legacy_files()? {
+            if let Ok(snapshot) = read_legacy(&path) {
+                summaries.push(SnapshotSummary::from(&snapshot));
+            }
+        }
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(summaries)
+    }
+
+    /// Reads a snapshot by ID, `None` if there is none.
+    pub(crate) fn read(&self, id: &str) -> Result<Option<Snapshot>> {
+        if !is_snapshot_id(id) {
+            return Ok(None);
+        }
+        let manifest_path = self.manifest_path(id);
+        if manifest_path.exists() {
+            let manifest: Manifest = serde_json::from_slice(&decompress(&fs::read(&manifest_path)?)?)?;
+            return self.load(manifest).map(Some);
+        }
+
+        let legacy_path = self.legacy_path(id);
+        if legacy_path.exists() {
+            return read_legacy(&legacy_path).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Writes a snapshot as is, replacing any snapshot with the same ID.
+    pub(crate) fn write(&self, snapshot: &Snapshot) -> Result<()> {
+        if !is_snapshot_id(&snapshot.id) {
+            return Err(eyre!("Invalid snapshot ID '{}'", snapshot.id));
+        }
+        let mut variables = HashMap::new();
+        for (name, var) in &snapshot.variables {
+            let stored = StoredVar {
+                value: self.write_object(&var.value)?,
+                source: var.source.clone(),
+                modified: var.modified,
+                original_value: var
+                    .original_value
+                    .as_deref()
+                    .map(|v| self.write_object(v))
+                    .transpose()?,
+                origin: var.origin.clone(),
+            };
+            variables.insert(name.clone(), stored);
+        }
+
+        let summary = SnapshotSummary::from(snapshot);
+        let manifest = Manifest {
+            summary: summary.clone(),
+            variables,
+        };
+        write_atomic(
+            &self.manifest_path(&snapshot.id),
+            &compress(&serde_json::to_vec(&manifest)?)?,
+        )?;
+
+        let mut index = self.read_index()?;
+        index.retain(|s| s.id != summary.id);
+        index.push(summary);
+        self.write_index(index)?;
+
+        let legacy_path = self.legacy_path(&snapshot.id);
+        if legacy_path.exists() {
+            fs::remove_file(legacy_path)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a snapshot by ID. Returns `false` if there is none.
+    ///
+    /// Its values stay in the object store until [`SnapshotStore::collect_garbage`].
+    pub(crate) fn remove(&self, id: &str) -> Result<bool> {
+        if !is_snapshot_id(id) {
+            return Ok(false);
+        }
+        let mut removed = false;
+        for path in [self.manifest_path(id), self.legacy_path(id)] {
+            if path.exists() {
+                fs::remove_file(path)?;
+                removed = true;
+            }
+        }
+
+        let mut index = self.read_index()?;
+        let len = index.len();
+        index.retain(|s| s.id != id);
+        if index.len() != len {
+            self.write_index(index)?;
+        }
+        Ok(removed)
+    }
+
+    /// Deletes the values no snapshot refers to. Returns the number deleted.
+    ///
+    /// The references are read from the manifests on disk rather than the
+    /// index, so a snapshot missing from a stale index keeps its values.
+    pub(crate) fn collect_garbage(&self) -> Result<usize> {
+        let objects_dir = self.dir.join(OBJECTS_DIR);
+        if !objects_dir.exists() {
+            return Ok(0);
+        }
+
+        let mut referenced = HashSet::new();
+        for path in self.manifest_files()? {
+            let manifest: Manifest = serde_json::from_slice(&decompress(&fs::read(&path)?)?)?;
+            for var in manifest.variables.into_values() {
+                referenced.insert(var.value);
+                referenced.extend(var.original_value);
+            }
+        }
+
+        let mut count = 0;
+        for prefix in fs::read_dir(&objects_dir)? {
+            let prefix = prefix?;
+            for object in fs::read_dir(prefix.path())? {
+                let object = object?;
+                let hash = format!(
+                    "{}{}",
+                    prefix.file_name().to_string_lossy(),
+                    object.file_name().to_string_lossy()
+                );
+                if !referenced.contains(&hash) {
+                    fs::remove_file(object.path())?;
+                    count += 1;
+                }
+            }
+            if fs::read_dir(prefix.path())?.next().is_none() {
+                fs::remove_dir(prefix.path())?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Converts the snapshots in the old format.
+    pub(crate) fn migrate(&self) -> Result<MigrationReport> {
+        let mut report = MigrationReport {
+            bytes_before: dir_size(self.dir)?,
+            ..MigrationReport::default()
+        };
+
+        for path in self.legacy_files()? {
+            match read_legacy(&path) {
+                Ok(snapshot) => {
+                    self.write(&snapshot)?;
+                    report.migrated += 1;
+                }
+                Err(_) => report.skipped.push(path),
+            }
+        }
+
+        report.bytes_after = dir_size(self.dir)?;
+        Ok(report)
+    }
+
+    /// Rebuilds the variables of a manifest from the object store.
+    fn load(&self, manifest: Manifest) -> Result<Snapshot> {
+        let mut variables = HashMap::new();
+        for (name, var) in manifest.variables {
+            let var = EnvVar {
+                name: name.clone(),
+                value: self.read_object(&var.value)?,
+                source: var.source,
+                modified: var.modified,
+                original_value: var.original_value.map(|hash| self.read_object(&hash)).transpose()?,
+                origin: var.origin,
+            };
+            variables.insert(name, var);
+        }
+
+        let summary = manifest.summary;
+        Ok(Snapshot {
+            id: summary.id,
+            name: summary.name,
+            description: summary.description,
+            created_at: summary.created_at,
+            variables,
+            metadata: summary.metadata,
+        })
+    }
+
+    /// Reads the index, rebuilding it from the manifests if it is missing.
+    fn read_index(&self) -> Result<Vec<SnapshotSummary>> {
+        let path = self.dir.join(INDEX_FILE);
+        if path.exists() {
+            let index: Index = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            if index.version > INDEX_VERSION {
+                return Err(eyre!(
+                    "Snapshot index version {} is newer than this version of envx supports",
+                    index.version
+                ));
+            }
+            return Ok(index.snapshots);
+        }
+
+        let mut summaries = Vec::new();
+        for path in self.manifest_files()? {
+            let manifest: Manifest = serde_json::from_slice(&decompress(&fs::read(&path)?)?)?;
+            summaries.push(manifest.summary);
+        }
+        if !summaries.is_empty() {
+            self.write_index(summaries.clone())?;
+        }
+        Ok(summaries)
+    }
+
+    fn write_index(&self, mut snapshots: Vec<SnapshotSummary>) -> Result<()> {
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        let index = Index {
+            version: INDEX_VERSION,
+            snapshots,
+        };
+        write_atomic(
+            &self.dir.join(INDEX_FILE),
+            serde_json::to_string_pretty(&index)?.as_bytes(),
+        )
+    }
+
+    /// Stores a value unless it is stored already and returns its hash.
+    fn write_object(&self, value: &str) -> Result<String> {
+        let hash = Sha256::digest(value.as_bytes())
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            });
+
+        let path = self.object_path(&hash);
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_atomic(&path, &compress(value.as_bytes())?)?;
+        }
+        Ok(hash)
+    }
+
+    fn read_object(&self, hash: &str) -> Result<String> {
+        let path = self.object_path(hash);
+        let content = fs::read(&path).map_err(|e| eyre!("Missing snapshot value {}: {}", hash, e))?;
+        Ok(String::from_utf8(decompress(&content)?)?)
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        let (prefix, rest) = hash.split_at(2.min(hash.len()));
+        self.dir.join(OBJECTS_DIR).join(prefix).join(rest)
+    }
+
+    fn manifest_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}{MANIFEST_EXTENSION}"))
+    }
+
+    fn legacy_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Returns the manifests of the snapshots in the compressed format.
+    fn manifest_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(self.dir)? {
+            let path = entry?.path();
+            if path.to_string_lossy().ends_with(MANIFEST_EXTENSION) {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Returns the snapshot files in the old format.
+    fn legacy_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json")
+                && path.file_name().and_then(|s| s.to_str()) != Some(INDEX_FILE)
+            {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Returns true if `id` is a UUID as envx writes them. Snapshot IDs name
+/// files, so any other ID could point outside the snapshots directory.
+fn is_snapshot_id(id: &str) -> bool {
+    uuid::Uuid::try_parse(id).is_ok_and(|uuid| uuid.to_string() == id)
+}
+
+fn read_legacy(path: &Path) -> Result<Snapshot> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub(crate) fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Writes a file through a temporary file in the same directory, so readers
+/// never see half of it. An existing file keeps its permissions.
+///
+/// Each write gets its own temporary file, so concurrent writers of the same
+/// file never interleave their content.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| eyre!("Invalid file path {}", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), uuid::Uuid::new_v4()));
+    fs::write(&tmp, content)?;
+    let result = fs::metadata(path)
+        .map_or(Ok(()), |metadata| fs::set_permissions(&tmp, metadata.permissions()))
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(result?)
+}
+
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_snapshot(name: &str, vars: &[(&str, &str)]) -> Snapshot {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| EnvVar {
+                name: (*name).to_string(),
+                value: (*value).to_string(),
+                source: EnvVarSource::User,
+                modified: Utc::now(),
+                original_value: None,
+                origin: None,
+            })
+            .collect();
+        Snapshot::from_vars(name.to_string(), None, vars)
+    }
+
+    fn object_count(dir: &Path) -> usize {
+        walk(&dir.join(OBJECTS_DIR)).len()
+    }
+
+    fn walk(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flat_map(|entry| {
+                let path = entry.unwrap().path();
+                if path.is_dir() { walk(&path) } else { vec![path] }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_and_read_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        let mut snapshot = create_test_snapshot("one", &[("A", "1"), ("B", "2")]);
+        snapshot.variables.get_mut("A").unwrap().original_value = Some("0".to_string());
+        snapshot.metadata.insert("ticket".to_string(), "OPS-1".to_string());
+        store.write(&snapshot).unwrap();
+
+        let read = store.read(&snapshot.id).unwrap().unwrap();
+        assert_eq!(read.name, "one");
+        assert_eq!(read.variables["A"].value, "1");
+        assert_eq!(read.variables["A"].original_value.as_deref(), Some("0"));
+        assert_eq!(read.variables["B"].name, "B");
+        assert_eq!(read.metadata["ticket"], "OPS-1");
+        assert!(store.read("missing").unwrap().is_none());
+        assert!(store.read("../one").unwrap().is_none());
+
+        let summaries = store.summaries().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].variable_count, 2);
+    }
+
+    #[test]
+    fn test_values_are_deduplicated() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        let first = create_test_snapshot("first", &[("A", "shared"), ("B", "shared"), ("C", "x")]);
+        let second = create_test_snapshot("second", &[("A", "shared"), ("C", "y")]);
+        store.write(&first).unwrap();
+        store.write(&second).unwrap();
+        assert_eq!(object_count(temp.path()), 3);
+
+        store.remove(&first.id).unwrap();
+        assert_eq!(store.collect_garbage().unwrap(), 1);
+        assert_eq!(object_count(temp.path()), 2);
+        assert_eq!(store.read(&second.id).unwrap().unwrap().variables["C"].value, "y");
+    }
+
+    #[test]
+    fn test_garbage_collection_reads_manifests_not_the_index() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        store.write(&create_test_snapshot("one", &[("A", "1")])).unwrap();
+        let index = fs::read(temp.path().join(INDEX_FILE)).unwrap();
+        let unindexed = create_test_snapshot("two", &[("B", "2")]);
+        store.write(&unindexed).unwrap();
+
+        // Another writer replaced the index with one that misses a snapshot
+        fs::write(temp.path().join(INDEX_FILE), index).unwrap();
+
+        assert_eq!(store.collect_garbage().unwrap(), 0);
+        assert_eq!(object_count(temp.path()), 2);
+        assert_eq!(store.read(&unindexed.id).unwrap().unwrap().variables["B"].value, "2");
+    }
+
+    #[test]
+    fn test_writes_leave_no_temporary_files() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        store.write(&create_test_snapshot("one", &[("A", "1")])).unwrap();
+
+        assert!(
+            walk(temp.path())
+                .iter()
+                .all(|path| path.extension().is_none_or(|ext| ext != "tmp"))
+        );
+    }
+
+    #[test]
+    fn test_index_is_rebuilt_when_missing() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        store.write(&create_test_snapshot("one", &[("A", "1")])).unwrap();
+        store.write(&create_test_snapshot("two", &[("A", "2")])).unwrap();
+
+        fs::remove_file(temp.path().join(INDEX_FILE)).unwrap();
+        assert_eq!(store.summaries().unwrap().len(), 2);
+        assert!(temp.path().join(INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn test_migrate_legacy_snapshots() {
+        let temp = TempDir::new().unwrap();
+        let store = SnapshotStore::new(temp.path());
+        let legacy = create_test_snapshot("legacy", &[("A", "1")]);
+        fs::write(
+            temp.path().join(format!("{}.json", legacy.id)),
+            serde_json::to_string_pretty(&legacy).unwrap(),
+        )
+        .unwrap();
+        fs::write(temp.path().join("broken.json"), "{ invalid json }").unwrap();
+
+        // Old snapshots are readable before the migration
+        assert_eq!(store.summaries().unwrap().len(), 1);
+        assert_eq!(store.read(&legacy.id).unwrap().unwrap().name, "legacy");
+
+        let report = store.migrate().unwrap();
+        assert_eq!(report.migrated, 1);
+        assert_eq!(report.skipped, [temp.path().join("broken.json")]);
+        assert!(!temp.path().join(format!("{}.json", legacy.id)).exists());
+        assert!(store.manifest_path(&legacy.id).exists());
+        assert_eq!(store.read(&legacy.id).unwrap().unwrap().variables["A"].value, "1");
+
+        let report = store.migrate().unwrap();
+        assert_eq!(report.migrated, 0);
+    }
+}
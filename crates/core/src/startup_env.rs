@@ -0,0 +1,494 @@
+use crate::shell_config::{self, ShellKind, is_shell_identifier};
+use crate::{EnvVarSource, VarOrigin};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A variable definition found in a startup file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupVar {
+    pub name: String,
+    pub value: String,
+    pub source: EnvVarSource,
+    pub path: PathBuf,
+    pub line: usize,
+    /// Where envx got the value from, for variables it persisted itself
+    pub origin: Option<VarOrigin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    /// `NAME=value` lines, as in `/etc/environment` and `environment.d/*.conf`
+    Environment,
+    /// `NAME DEFAULT=value OVERRIDE=value` lines of `~/.pam_environment`
+    Pam,
+    /// `export NAME=value` statements in shell startup files
+    Shell,
+}
+
+/// Reads user and system variables from the files Unix systems define them in.
+///
+/// System variables come from `/etc/environment` and the systemd
+/// `environment.d` directories, user variables from `~/.pam_environment`,
+/// `~/.config/environment.d`, `~/.profile`, `~/.zshenv` and `~/.bashrc`.
+///
+/// `$HOME`, `${HOME}` and a leading `~` in shell and `~/.pam_environment`
+/// values are expanded, the same way the shell would.
+#[derive(Debug, Clone)]
+pub struct StartupEnvLoader {
+    root: PathBuf,
+    home: Option<PathBuf>,
+}
+
+impl StartupEnvLoader {
+    /// Creates a loader that resolves system files below `root` and user files
+    /// below `home`.
+    #[must_use]
+    pub const fn new(root: PathBuf, home: Option<PathBuf>) -> Self {
+        Self { root, home }
+    }
+
+    #[must_use]
+    pub fn for_current_user() -> Self {
+        Self::new(PathBuf::from("/"), dirs::home_dir())
+    }
+
+    /// Loads all variable definitions.
+    ///
+    /// Files are read in the order the system applies them, so when a name is
+    /// defined more than once the last definition is the effective one.
+    /// Missing or unreadable files are skipped.
+    #[must_use]
+    pub fn load(&self) -> Vec<StartupVar> {
+        let mut vars = Vec::new();
+
+        for (path, source, format) in self.files() {
+            match fs::read_to_string(&path) {
+                Ok(content) => vars.extend(parse_file(&content, &path, &source, format, self.home.as_deref())),
+                Err(e) => tracing::debug!("Skipping {}: {}", path.display(), e),
+            }
+        }
+
+        vars
+    }
+
+    fn files(&self) -> Vec<(PathBuf, EnvVarSource, FileFormat)> {
+        let mut files = Vec::new();
+
+        for dir in ["usr/lib/environment.d", "etc/environment.d"] {
+            for path in conf_files(&self.root.join(dir)) {
+                files.push((path, EnvVarSource::System, FileFormat::Environment));
+            }
+        }
+        files.push((
+            self.root.join("etc").join("environment"),
+            EnvVarSource::System,
+            FileFormat::Environment,
+        ));
+
+        if let Some(home) = &self.home {
+            for path in conf_files(&home.join(".config").join("environment.d")) {
+                files.push((path, EnvVarSource::User, FileFormat::Environment));
+            }
+            files.push((home.join(".pam_environment"), EnvVarSource::User, FileFormat::Pam));
+            for name in [".profile", ".zshenv", ".bashrc"] {
+                files.push((home.join(name), EnvVarSource::User, FileFormat::Shell));
+            }
+        }
+
+        files.retain(|(path, _, _)| path.is_file());
+        files
+    }
+}
+
+fn conf_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("conf"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn parse_file(
+    content: &str,
+    path: &Path,
+    source: &EnvVarSource,
+    format: FileFormat,
+    home: Option<&Path>,
+) -> Vec<StartupVar> {
+    let mut vars = Vec::new();
+    let home = home.and_then(Path::to_str);
+
+    // envx's own block may hold multi-line values, so it's read with the
+    // parser that wrote it instead of line by line
+    let block = if format == FileFormat::Shell {
+        shell_config::find_block(content)
+    } else {
+        None
+    };
+    if let Some((start, end)) = block {
+        let lines: Vec<&str> = content.lines().collect();
+        for (name, var) in shell_config::parse_block(content, ShellKind::Posix) {
+            let prefix = format!("export {name}=");
+            let line = (start..end)
+                .find(|&i| lines[i].trim_start().starts_with(&prefix))
+                .unwrap_or(start);
+            vars.push(StartupVar {
+                name,
+                value: var.value,
+                source: source.clone(),
+                path: path.to_path_buf(),
+                line: line + 1,
+                origin: var.origin,
+            });
+        }
+    }
+
+    for (index, line) in content.lines().enumerate() {
+        if block.is_some_and(|(start, end)| (start..=end).contains(&index)) {
+            continue;
+        }
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parsed = match format {
+            FileFormat::Environment => parse_environment_line(line.strip_prefix("export ").unwrap_or(line)),
+            FileFormat::Pam => parse_pam_line(line, home),
+            FileFormat::Shell => line
+                .strip_prefix("export ")
+                .and_then(|statement| parse_assignment(statement, home)),
+        };
+
+        if let Some((name, value)) = parsed {
+            vars.push(StartupVar {
+                name,
+                value,
+                source: source.clone(),
+                path: path.to_path_buf(),
+                line: index + 1,
+                origin: None,
+            });
+        }
+    }
+
+    vars
+}
+
+fn parse_environment_line(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once('=')?;
+    if !is_shell_identifier(name) {
+        return None;
+    }
+
+    // Values are taken verbatim up to the end of the line, one pair of
+    // surrounding quotes is dropped
+    let value = value.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+        .unwrap_or(value);
+
+    Some((name.to_string(), unquoted.to_string()))
+}
+
+fn parse_assignment(statement: &str, home: Option<&str>) -> Option<(String, String)> {
+    let (name, value) = statement.trim_start().split_once('=')?;
+    if !is_shell_identifier(name) {
+        return None;
+    }
+
+    Some((name.to_string(), parse_word(value, home)?))
+}
+
+fn parse_pam_line(line: &str, home: Option<&str>) -> Option<(String, String)> {
+    let (name, rest) = match line.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name, rest.trim_start()),
+        None => (line, ""),
+    };
+
+    // pam_env also accepts plain NAME=value lines
+    if rest.is_empty() {
+        return parse_assignment(name, home);
+    }
+    if !is_shell_identifier(name) {
+        return None;
+    }
+
+    let mut default = None;
+    let mut override_value = None;
+    let mut remaining = rest;
+    while !remaining.is_empty() {
+        let (key, value) = remaining.split_once('=')?;
+        let (word, consumed) = parse_word_with_len(value, home)?;
+        match key.trim() {
+            "DEFAULT" => default = Some(word),
+            "OVERRIDE" => override_value = Some(word),
+            _ => return None,
+        }
+        remaining = value[consumed..].trim_start();
+    }
+
+    override_value.or(default).map(|value| (name.to_string(), value))
+}
+
+/// Parses a single shell word, handling quoting and a trailing comment.
+/// References to the home directory outside single quotes are expanded
+/// when `home` is known.
+///
+/// Returns `None` when a quote is left open.
+fn parse_word(input: &str, home: Option<&str>) -> Option<String> {
+    parse_word_with_len(input, home).map(|(word, _)| word)
+}
+
+fn parse_word_with_len(input: &str, home: Option<&str>) -> Option<(String, usize)> {
+    let mut word = String::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(index, ch)) = chars.peek() {
+        match ch {
+            c if c.is_whitespace() => return Some((word, index)),
+            // A tilde only means the home directory at the start of a word
+            '~' if index == 0
+                && home.is_some()
+                && input[1..].chars().next().is_none_or(|c| c == '/' || c.is_whitespace()) =>
+            {
+                chars.next();
+                word.push_str(home.unwrap_or("~"));
+            }
+            '$' if home.is_some() && home_reference_len(&input[index..]) > 0 => {
+                let len = home_reference_len(&input[index..]);
+                while chars.next_if(|&(i, _)| i < index + len).is_some() {}
+                word.push_str(home.unwrap_or_default());
+            }
+            '\'' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        (_, '\'') => break,
+                        (_, c) => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        (_, '"') => break,
+                        (i, '$') if home.is_some() && home_reference_len(&input[i..]) > 0 => {
+                            let len = home_reference_len(&input[i..]);
+                            while chars.next_if(|&(j, _)| j < i + len).is_some() {}
+                            word.push_str(home.unwrap_or_default());
+                        }
+                        (_, '\\') => match chars.next()? {
+                            (_, c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            (_, c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        (_, c) => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                chars.next();
+                if let Some((_, c)) = chars.next() {
+                    word.push(c);
+                }
+            }
+            _ => {
+                chars.next();
+                word.push(ch);
+            }
+        }
+    }
+
+    Some((word, input.len()))
+}
+
+/// Length of the `$HOME` or `${HOME}` reference `input` starts with, or 0.
+fn home_reference_len(input: &str) -> usize {
+    if input.starts_with("${HOME}") {
+        return "${HOME}".len();
+    }
+
+    let rest = input.strip_prefix("$HOME").unwrap_or_default();
+    if input.starts_with("$HOME") && !rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        "$HOME".len()
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_loader() -> (StartupEnvLoader, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let home = temp_dir.path().join("home");
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::create_dir_all(&home).unwrap();
+
+        (StartupEnvLoader::new(root, Some(home)), temp_dir)
+    }
+
+    fn find<'a>(vars: &'a [StartupVar], name: &str) -> Vec<&'a StartupVar> {
+        vars.iter().filter(|v| v.name == name).collect()
+    }
+
+    #[test]
+    fn test_load_etc_environment() {
+        let (loader, temp) = create_test_loader();
+        fs::write(
+            temp.path().join("root/etc/environment"),
+            "# system defaults\nLANG=en_US.UTF-8\nEDITOR=\"vim\"\n\nnot a var\nMOTD=hello world\n",
+        )
+        .unwrap();
+
+        let vars = loader.load();
+        assert_eq!(vars.len(), 3);
+
+        let lang = find(&vars, "LANG")[0];
+        assert_eq!(lang.value, "en_US.UTF-8");
+        assert_eq!(lang.source, EnvVarSource::System);
+        assert_eq!(lang.line, 2);
+        assert!(lang.path.ends_with("etc/environment"));
+
+        assert_eq!(find(&vars, "EDITOR")[0].value, "vim");
+        assert_eq!(find(&vars, "MOTD")[0].value, "hello world");
+    }
+
+    #[test]
+    fn test_load_environment_d_sorted() {
+        let (loader, temp) = create_test_loader();
+        let dir = temp.path().join("home/.config/environment.d");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("20-b.conf"), "FOO=second\n").unwrap();
+        fs::write(dir.join("10-a.conf"), "FOO=first\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "FOO=ignored\n").unwrap();
+
+        let vars = loader.load();
+        let foo = find(&vars, "FOO");
+        assert_eq!(foo.len(), 2);
+        assert_eq!(foo[0].value, "first");
+        assert_eq!(foo[1].value, "second");
+        assert_eq!(foo[1].source, EnvVarSource::User);
+    }
+
+    #[test]
+    fn test_load_pam_environment() {
+        let (loader, temp) = create_test_loader();
+        fs::write(
+            temp.path().join("home/.pam_environment"),
+            "PAGER DEFAULT=less\nVISUAL DEFAULT=vi OVERRIDE=\"code --wait\"\nPLAIN=value\n",
+        )
+        .unwrap();
+
+        let vars = loader.load();
+        assert_eq!(find(&vars, "PAGER")[0].value, "less");
+        assert_eq!(find(&vars, "VISUAL")[0].value, "code --wait");
+        assert_eq!(find(&vars, "PLAIN")[0].value, "value");
+        assert!(vars.iter().all(|v| v.source == EnvVarSource::User));
+    }
+
+    #[test]
+    fn test_load_shell_files_only_exports() {
+        let (loader, temp) = create_test_loader();
+        fs::write(
+            temp.path().join("home/.bashrc"),
+            "alias ll='ls -l'\nLOCAL_ONLY=1\nexport GOPATH=\"$HOME/go\"\nexport MSG='it'\\''s' # note\n",
+        )
+        .unwrap();
+
+        let vars = loader.load();
+        assert_eq!(vars.len(), 2);
+        let home = temp.path().join("home");
+        assert_eq!(find(&vars, "GOPATH")[0].value, format!("{}/go", home.display()));
+        assert_eq!(find(&vars, "GOPATH")[0].line, 3);
+        assert_eq!(find(&vars, "MSG")[0].value, "it's");
+    }
+
+    #[test]
+    fn test_reads_envx_managed_block() {
+        let (loader, temp) = create_test_loader();
+        let config = crate::shell_config::ShellConfig::new(temp.path().join("home"));
+        config.persist("MANAGED", "a 'quoted' value").unwrap();
+        let origin = VarOrigin::Profile("dev".to_string());
+        config.persist_with_origin("FROM_PROFILE", "1", Some(&origin)).unwrap();
+
+        let vars = loader.load();
+        let managed = find(&vars, "MANAGED")[0];
+        assert_eq!(managed.value, "a 'quoted' value");
+        assert!(managed.path.ends_with(".profile"));
+        assert_eq!(managed.origin, None);
+        assert_eq!(find(&vars, "FROM_PROFILE")[0].origin, Some(origin));
+    }
+
+    #[test]
+    fn test_expands_home_outside_single_quotes() {
+        let (loader, temp) = create_test_loader();
+        fs::write(
+            temp.path().join("home/.profile"),
+            "export A=~/bin\nexport B=\"${HOME}/x\"\nexport C='$HOME'\nexport D=$HOMEDIR\nexport E=a~b\n",
+        )
+        .unwrap();
+
+        let vars = loader.load();
+        let home = temp.path().join("home").display().to_string();
+        assert_eq!(find(&vars, "A")[0].value, format!("{home}/bin"));
+        assert_eq!(find(&vars, "B")[0].value, format!("{home}/x"));
+        assert_eq!(find(&vars, "C")[0].value, "$HOME");
+        assert_eq!(find(&vars, "D")[0].value, "$HOMEDIR");
+        assert_eq!(find(&vars, "E")[0].value, "a~b");
+    }
+
+    #[test]
+    fn test_reads_multiline_values_from_managed_block() {
+        let (loader, temp) = create_test_loader();
+        let config = crate::shell_config::ShellConfig::new(temp.path().join("home"));
+        config.persist("FIRST", "one").unwrap();
+        config.persist("CERT", "line 1\nline 2").unwrap();
+        config.persist("LAST", "$HOME").unwrap();
+
+        let vars = loader.load();
+        assert_eq!(vars.len(), 3);
+        assert_eq!(find(&vars, "CERT")[0].value, "line 1\nline 2");
+        // envx quotes its values, so they are taken literally
+        assert_eq!(find(&vars, "LAST")[0].value, "$HOME");
+        assert_eq!(find(&vars, "LAST")[0].line, find(&vars, "CERT")[0].line + 2);
+    }
+
+    #[test]
+    fn test_system_before_user() {
+        let (loader, temp) = create_test_loader();
+        fs::write(temp.path().join("root/etc/environment"), "EDITOR=nano\n").unwrap();
+        fs::write(temp.path().join("home/.profile"), "export EDITOR=nvim\n").unwrap();
+
+        let vars = loader.load();
+        let editor = find(&vars, "EDITOR");
+        assert_eq!(editor[0].source, EnvVarSource::System);
+        assert_eq!(editor.last().unwrap().value, "nvim");
+        assert_eq!(editor.last().unwrap().source, EnvVarSource::User);
+    }
+
+    #[test]
+    fn test_unbalanced_quotes_skipped() {
+        let (loader, temp) = create_test_loader();
+        fs::write(temp.path().join("home/.zshenv"), "export BROKEN=\"oops\nexport OK=1\n").unwrap();
+
+        let vars = loader.load();
+        assert!(find(&vars, "BROKEN").is_empty());
+        assert_eq!(find(&vars, "OK")[0].value, "1");
+    }
+}
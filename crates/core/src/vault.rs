@@ -0,0 +1,649 @@
+use crate::SecretClassifier;
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix of values that are encrypted at rest
+pub const ENCRYPTED_PREFIX: &str = "envx:enc:v1:";
+/// Environment variable holding the passphrase secrets are encrypted with
+pub const PASSPHRASE_VAR: &str = "ENVX_PASSPHRASE";
+/// Environment variable pointing to the key file secrets are encrypted with
+pub const KEYFILE_VAR: &str = "ENVX_KEYFILE";
+
+const VAULT_FILE: &str = "vault.json";
+/// A key saved by [`KeyStore::stage`] that isn't current yet
+const PENDING_VAULT_FILE: &str = "vault.pending.json";
+const DEFAULT_KEYFILE: &str = "secret.key";
+/// Known plaintext used to check that a key is the right one
+const CHECK_PLAINTEXT: &str = "envx";
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Returns true if `value` was encrypted by a [`Vault`].
+#[must_use]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Where the encryption key comes from
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// A key derived from a passphrase with Argon2id
+    Passphrase(String),
+    /// A key derived from the contents of a file; `None` generates a random
+    /// key file in the envx config directory
+    Keyfile(Option<PathBuf>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KeyKind {
+    Passphrase,
+    Keyfile,
+}
+
+/// Describes the key without containing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    kind: KeyKind,
+    /// Base64 salt for passphrase keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    /// Key file for key file keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keyfile: Option<PathBuf>,
+    /// [`CHECK_PLAINTEXT`] encrypted with the key
+    check: String,
+}
+
+/// An unlocked encryption key.
+pub struct Vault {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Vault {
+    fn from_key(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// Derives a key from a passphrase, independent of any key store, e.g. to
+    /// encrypt values for another machine. Returns the key and the Base64
+    /// salt to keep next to the values; `None` generates a new salt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the passphrase is empty, the salt is corrupt or
+    /// the key cannot be derived.
+    pub fn with_passphrase(passphrase: &str, salt: Option<&str>) -> Result<(Self, String)> {
+        if passphrase.is_empty() {
+            return Err(eyre!("The passphrase must not be empty"));
+        }
+        let salt = if let Some(salt) = salt {
+            BASE64.decode(salt).map_err(|_| eyre!("The salt is corrupt"))?
+        } else {
+            let mut salt = vec![0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        };
+
+        let key = derive_passphrase_key(passphrase, &salt)?;
+        Ok((Self::from_key(&key), BASE64.encode(salt)))
+    }
+
+    /// Encrypts `plaintext` into a value starting with [`ENCRYPTED_PREFIX`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if encryption fails.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| eyre!("Failed to encrypt value"))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("{ENCRYPTED_PREFIX}{}", BASE64.encode(payload)))
+    }
+
+    /// Decrypts a value produced by [`Vault::encrypt`]. Values that aren't
+    /// encrypted are returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is corrupt or was encrypted with another key.
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(value.to_string());
+        };
+
+        let payload = BASE64
+            .decode(encoded)
+            .map_err(|_| eyre!("Encrypted value is corrupt"))?;
+        if payload.len() < NONCE_LEN {
+            return Err(eyre!("Encrypted value is corrupt"));
+        }
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| eyre!("Failed to decrypt value: it was encrypted with a different key"))?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+
+    /// Encrypts `value` in place if it holds a secret and isn't encrypted yet.
+    ///
+    /// Returns true if the value was encrypted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if encryption fails.
+    pub fn encrypt_secret(&self, secrets: &SecretClassifier, name: &str, value: &mut String) -> Result<bool> {
+        if is_encrypted(value) || !secrets.is_secret(name, value) {
+            return Ok(false);
+        }
+
+        *value = self.encrypt(value)?;
+        Ok(true)
+    }
+}
+
+/// A new key that hasn't replaced the current one yet, see [`KeyStore::generate`].
+pub struct PendingKey {
+    vault: Vault,
+    file: VaultFile,
+    keyfile: Option<(PathBuf, Vec<u8>)>,
+}
+
+impl PendingKey {
+    #[must_use]
+    pub const fn vault(&self) -> &Vault {
+        &self.vault
+    }
+}
+
+/// Manages the key secrets are encrypted with.
+///
+/// The key itself is never stored: `vault.json` in the envx config directory
+/// only records whether it comes from a passphrase ([`PASSPHRASE_VAR`]) or a
+/// key file ([`KEYFILE_VAR`], by default a generated `secret.key` next to it).
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    /// Opens the key store of the current user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data/config directory cannot be found.
+    pub fn new() -> Result<Self> {
+        let config_dir = if cfg!(windows) {
+            dirs::data_dir()
+                .ok_or_else(|| eyre!("Could not find data directory"))?
+                .join("envx")
+        } else {
+            dirs::config_dir()
+                .ok_or_else(|| eyre!("Could not find config directory"))?
+                .join("envx")
+        };
+
+        Ok(Self::at(config_dir))
+    }
+
+    /// Opens a key store kept in `dir`.
+    #[must_use]
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns true if a key has been set up.
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.vault_path().exists()
+    }
+
+    /// Returns a description of the current key, e.g. `key file ~/.config/envx/secret.key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key description cannot be read.
+    pub fn describe(&self) -> Result<Option<String>> {
+        let Some(file) = self.load_file()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(match file.kind {
+            KeyKind::Passphrase => "passphrase".to_string(),
+            KeyKind::Keyfile => format!("key file {}", self.keyfile_path(&file).display()),
+        }))
+    }
+
+    /// Unlocks the key, creating one on first use.
+    ///
+    /// A new key is derived from [`PASSPHRASE_VAR`] if it is set, otherwise
+    /// from the key file named by [`KEYFILE_VAR`], otherwise from a newly
+    /// generated key file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The key needs a passphrase and [`PASSPHRASE_VAR`] is not set
+    /// - The passphrase or key file doesn't match the key secrets were encrypted with
+    /// - The key files cannot be read or written
+    pub fn unlock(&self) -> Result<Vault> {
+        if let Some(vault) = self.open()? {
+            return Ok(vault);
+        }
+
+        let source = match (std::env::var(PASSPHRASE_VAR), std::env::var_os(KEYFILE_VAR)) {
+            (Ok(passphrase), _) => KeySource::Passphrase(passphrase),
+            (Err(_), Some(path)) => KeySource::Keyfile(Some(PathBuf::from(path))),
+            (Err(_), None) => KeySource::Keyfile(None),
+        };
+
+        let key = self.generate(&source)?;
+        self.commit(key)
+    }
+
+    /// Unlocks the existing key for decrypting. Unlike [`KeyStore::unlock`]
+    /// it never creates a key, as a new key can't decrypt anything.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no key has been set up, or in
+    /// the same cases as [`KeyStore::open`].
+    pub fn unlock_existing(&self) -> Result<Vault> {
+        self.open()?
+            .ok_or_else(|| eyre!("Encrypted values found but no key is set up in {}", self.dir.display()))
+    }
+
+    /// Unlocks the existing key, or returns `None` if no key has been set up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The key needs a passphrase and [`PASSPHRASE_VAR`] is not set
+    /// - The passphrase or key file doesn't match the key secrets were encrypted with
+    /// - The key files cannot be read
+    pub fn open(&self) -> Result<Option<Vault>> {
+        let Some(file) = self.load_file()? else {
+            return Ok(None);
+        };
+
+        let key = match file.kind {
+            KeyKind::Passphrase => {
+                let passphrase = std::env::var(PASSPHRASE_VAR).map_err(|_| {
+                    eyre!("Secrets are encrypted with a passphrase. Set {PASSPHRASE_VAR} to unlock them.")
+                })?;
+                let salt = BASE64
+                    .decode(file.salt.as_deref().unwrap_or_default())
+                    .map_err(|_| eyre!("Key description {} is corrupt", self.vault_path().display()))?;
+                derive_passphrase_key(&passphrase, &salt)?
+            }
+            KeyKind::Keyfile => {
+                let path = self.keyfile_path(&file);
+                let contents = fs::read(&path).map_err(|e| eyre!("Could not read key file {}: {e}", path.display()))?;
+                derive_keyfile_key(&contents)
+            }
+        };
+
+        let vault = Vault::from_key(&key);
+        if vault.decrypt(&file.check).ok().as_deref() != Some(CHECK_PLAINTEXT) {
+            return Err(match file.kind {
+                KeyKind::Passphrase => eyre!("Wrong passphrase in {PASSPHRASE_VAR}"),
+                KeyKind::Keyfile => eyre!(
+                    "Key file {} doesn't match the key secrets were encrypted with",
+                    self.keyfile_path(&file).display()
+                ),
+            });
+        }
+
+        Ok(Some(vault))
+    }
+
+    /// Creates a new key without replacing the current one.
+    ///
+    /// Use the returned key to re-encrypt secrets, then make it current with
+    /// [`KeyStore::commit`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key file cannot be read or the key cannot be derived.
+    pub fn generate(&self, source: &KeySource) -> Result<PendingKey> {
+        let (key, mut file, keyfile) = match source {
+            KeySource::Passphrase(passphrase) => {
+                if passphrase.is_empty() {
+                    return Err(eyre!("The passphrase must not be empty"));
+                }
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let key = derive_passphrase_key(passphrase, &salt)?;
+                let file = VaultFile {
+                    kind: KeyKind::Passphrase,
+                    salt: Some(BASE64.encode(salt)),
+                    keyfile: None,
+                    check: String::new(),
+                };
+                (key, file, None)
+            }
+            KeySource::Keyfile(Some(path)) => {
+                let contents = fs::read(path).map_err(|e| eyre!("Could not read key file {}: {e}", path.display()))?;
+                let file = VaultFile {
+                    kind: KeyKind::Keyfile,
+                    salt: None,
+                    keyfile: Some(fs::canonicalize(path)?),
+                    check: String::new(),
+                };
+                (derive_keyfile_key(&contents), file, None)
+            }
+            KeySource::Keyfile(None) => {
+                let mut random = [0u8; 32];
+                OsRng.fill_bytes(&mut random);
+                let contents = format!("{}\n", BASE64.encode(random)).into_bytes();
+                let path = self.new_keyfile_path();
+                let file = VaultFile {
+                    kind: KeyKind::Keyfile,
+                    salt: None,
+                    keyfile: Some(path.clone()),
+                    check: String::new(),
+                };
+                (derive_keyfile_key(&contents), file, Some((path, contents)))
+            }
+        };
+
+        let vault = Vault::from_key(&key);
+        file.check = vault.encrypt(CHECK_PLAINTEXT)?;
+        Ok(PendingKey { vault, file, keyfile })
+    }
+
+    /// Saves `key` to `vault.pending.json` without making it current, so
+    /// secrets written with it can still be decrypted if they are written
+    /// before [`KeyStore::commit`] but the commit never happens.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key files cannot be written.
+    pub fn stage(&self, key: &PendingKey) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        if let Some((path, contents)) = &key.keyfile {
+            write_private(path, contents)?;
+        }
+        let path = self.dir.join(PENDING_VAULT_FILE);
+        write_private(&path, serde_json::to_string_pretty(&key.file)?.as_bytes())?;
+        Ok(path)
+    }
+
+    /// Makes `key` the current key and returns it unlocked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key files cannot be written.
+    pub fn commit(&self, key: PendingKey) -> Result<Vault> {
+        fs::create_dir_all(&self.dir)?;
+
+        if let Some((path, contents)) = &key.keyfile {
+            write_private(path, contents)?;
+        }
+        write_private(&self.vault_path(), serde_json::to_string_pretty(&key.file)?.as_bytes())?;
+
+        let pending = self.dir.join(PENDING_VAULT_FILE);
+        if pending.exists() {
+            fs::remove_file(pending)?;
+        }
+
+        Ok(key.vault)
+    }
+
+    fn vault_path(&self) -> PathBuf {
+        self.dir.join(VAULT_FILE)
+    }
+
+    /// Where a generated key file goes: `secret.key`, or a new name if that is
+    /// taken, so the current key file stays intact until the new key is committed.
+    fn new_keyfile_path(&self) -> PathBuf {
+        let path = self.dir.join(DEFAULT_KEYFILE);
+        if path.exists() {
+            self.dir.join(format!("secret-{}.key", uuid::Uuid::new_v4().simple()))
+        } else {
+            path
+        }
+    }
+
+    fn keyfile_path(&self, file: &VaultFile) -> PathBuf {
+        std::env::var_os(KEYFILE_VAR)
+            .map(PathBuf::from)
+            .or_else(|| file.keyfile.clone())
+            .unwrap_or_else(|| self.dir.join(DEFAULT_KEYFILE))
+    }
+
+    fn load_file(&self) -> Result<Option<VaultFile>> {
+        let path = self.vault_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+}
+
+/// Unlocks a key store on first use, so commands that never touch an
+/// encrypted value never need the key.
+pub(crate) struct LazyVault {
+    keys: KeyStore,
+    vault: Option<Vault>,
+}
+
+impl LazyVault {
+    pub(crate) const fn new(keys: KeyStore) -> Self {
+        Self { keys, vault: None }
+    }
+
+    pub(crate) fn get(&mut self) -> Result<&Vault> {
+        match &mut self.vault {
+            Some(vault) => Ok(vault),
+            vault @ None => Ok(vault.insert(self.keys.unlock()?)),
+        }
+    }
+
+    /// Like [`LazyVault::get`], but never creates a key.
+    pub(crate) fn existing(&mut self) -> Result<&Vault> {
+        match &mut self.vault {
+            Some(vault) => Ok(vault),
+            vault @ None => Ok(vault.insert(self.keys.unlock_existing()?)),
+        }
+    }
+
+    pub(crate) fn decrypt(&mut self, value: &str) -> Result<String> {
+        if is_encrypted(value) {
+            self.existing()?.decrypt(value)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    pub(crate) fn encrypt_secret(&mut self, secrets: &SecretClassifier, name: &str, value: &mut String) -> Result<()> {
+        if !is_encrypted(value) && secrets.is_secret(name, value) {
+            self.get()?.encrypt_secret(secrets, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn derive_passphrase_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| eyre!("Could not derive key from passphrase: {e}"))?;
+    Ok(key)
+}
+
+fn derive_keyfile_key(contents: &[u8]) -> [u8; 32] {
+    Sha256::digest(contents).into()
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let temp = TempDir::new().unwrap();
+        let keys = KeyStore::at(temp.path());
+        let vault = keys.commit(keys.generate(&KeySource::Keyfile(None)).unwrap()).unwrap();
+
+        let encrypted = vault.encrypt("sk-1234567890").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("sk-1234567890"));
+        assert_ne!(encrypted, vault.encrypt("sk-1234567890").unwrap());
+        assert_eq!(vault.decrypt(&encrypted).unwrap(), "sk-1234567890");
+        assert_eq!(vault.decrypt("plain").unwrap(), "plain");
+
+        // The key can be unlocked again from the generated key file
+        let reopened = keys.open().unwrap().unwrap();
+        assert_eq!(reopened.decrypt(&encrypted).unwrap(), "sk-1234567890");
+    }
+
+    #[test]
+    fn test_with_passphrase_derives_the_same_key_from_the_salt() {
+        let (vault, salt) = Vault::with_passphrase("correct horse", None).unwrap();
+        let encrypted = vault.encrypt("sk-1234567890").unwrap();
+
+        let (same, same_salt) = Vault::with_passphrase("correct horse", Some(&salt)).unwrap();
+        assert_eq!(same_salt, salt);
+        assert_eq!(same.decrypt(&encrypted).unwrap(), "sk-1234567890");
+
+        let (other, _) = Vault::with_passphrase("wrong", Some(&salt)).unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+        assert!(Vault::with_passphrase("", None).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_secret_only_encrypts_secrets() {
+        let temp = TempDir::new().unwrap();
+        let keys = KeyStore::at(temp.path());
+        let vault = keys.commit(keys.generate(&KeySource::Keyfile(None)).unwrap()).unwrap();
+        let secrets = SecretClassifier::new();
+
+        let mut port = "3000".to_string();
+        assert!(!vault.encrypt_secret(&secrets, "PORT", &mut port).unwrap());
+        assert_eq!(port, "3000");
+
+        let mut token = "abc123".to_string();
+        assert!(vault.encrypt_secret(&secrets, "API_TOKEN", &mut token).unwrap());
+        assert!(is_encrypted(&token));
+
+        // Already encrypted values are left alone
+        let encrypted = token.clone();
+        assert!(!vault.encrypt_secret(&secrets, "API_TOKEN", &mut token).unwrap());
+        assert_eq!(token, encrypted);
+    }
+
+    #[test]
+    fn test_wrong_key_is_rejected() {
+        let temp = TempDir::new().unwrap();
+        let first = KeyStore::at(temp.path().join("first"));
+        let second = KeyStore::at(temp.path().join("second"));
+        let vault1 = first
+            .commit(first.generate(&KeySource::Keyfile(None)).unwrap())
+            .unwrap();
+        let vault2 = second
+            .commit(second.generate(&KeySource::Keyfile(None)).unwrap())
+            .unwrap();
+
+        let encrypted = vault1.encrypt("secret").unwrap();
+        assert!(vault2.decrypt(&encrypted).is_err());
+
+        // A key file that doesn't match the recorded key is refused
+        fs::copy(
+            temp.path().join("second").join(DEFAULT_KEYFILE),
+            temp.path().join("first").join(DEFAULT_KEYFILE),
+        )
+        .unwrap();
+        assert!(first.open().is_err());
+    }
+
+    #[test]
+    fn test_decrypting_never_creates_a_key() {
+        let temp = TempDir::new().unwrap();
+        let keys = KeyStore::at(temp.path());
+        let encrypted = Vault::with_passphrase("other", None)
+            .unwrap()
+            .0
+            .encrypt("secret")
+            .unwrap();
+
+        assert!(keys.unlock_existing().is_err());
+        assert!(LazyVault::new(keys.clone()).decrypt(&encrypted).is_err());
+        assert!(!keys.exists());
+    }
+
+    #[test]
+    fn test_staged_key_leaves_the_current_key_intact() {
+        let temp = TempDir::new().unwrap();
+        let keys = KeyStore::at(temp.path());
+        let current = keys.commit(keys.generate(&KeySource::Keyfile(None)).unwrap()).unwrap();
+        let encrypted = current.encrypt("secret").unwrap();
+
+        let pending = keys.generate(&KeySource::Keyfile(None)).unwrap();
+        keys.stage(&pending).unwrap();
+        assert!(temp.path().join(PENDING_VAULT_FILE).exists());
+        assert_eq!(keys.open().unwrap().unwrap().decrypt(&encrypted).unwrap(), "secret");
+
+        let new = keys.commit(pending).unwrap();
+        assert!(!temp.path().join(PENDING_VAULT_FILE).exists());
+        let reencrypted = new.encrypt("secret").unwrap();
+        assert_eq!(keys.open().unwrap().unwrap().decrypt(&reencrypted).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_passphrase_key() {
+        let temp = TempDir::new().unwrap();
+        let keys = KeyStore::at(temp.path());
+        let pending = keys
+            .generate(&KeySource::Passphrase("correct horse".to_string()))
+            .unwrap();
+        let salt = BASE64.decode(pending.file.salt.clone().unwrap()).unwrap();
+        let vault = keys.commit(pending).unwrap();
+
+        let encrypted = vault.encrypt("secret").unwrap();
+        let same = Vault::from_key(&derive_passphrase_key("correct horse", &salt).unwrap());
+        assert_eq!(same.decrypt(&encrypted).unwrap(), "secret");
+
+        let wrong = Vault::from_key(&derive_passphrase_key("wrong horse", &salt).unwrap());
+        assert!(wrong.decrypt(&encrypted).is_err());
+        assert_eq!(keys.describe().unwrap().as_deref(), Some("passphrase"));
+    }
+}
@@ -12,7 +12,10 @@ use colored::Colorize;
 use glob::glob;
 use serde::{Deserialize, Serialize};
 
-use crate::{ProfileManager, ProjectConfig, RequiredVar, ValidationRules as ConfigValidationRules};
+use crate::{
+    EnvVar, EnvVarSource, ExportFormat, Exporter, ProfileManager, ProfileSelection, ProjectConfig, RequiredVar,
+    SecretClassifier, ValidationRules as ConfigValidationRules,
+};
 
 // Custom error type for ESC handling
 #[derive(Debug)]
@@ -41,6 +44,9 @@ pub struct SelectedVariable {
     pub value: String,
     pub description: String,
     pub required: bool,
+    /// Whether the value is a secret that should be masked in output
+    #[serde(default)]
+    pub sensitive: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,7 +98,7 @@ pub struct ValidationRules {
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Integrations {
     pub shell_aliases: bool,
     pub auto_completion: bool,
@@ -168,6 +174,7 @@ pub struct SetupResult {
     pub imported_files: Vec<PathBuf>,
     pub create_env_files: bool,
     pub selected_vars: Vec<SelectedVariable>,
+    pub integrations: Integrations,
 }
 
 impl SetupWizard {
@@ -227,6 +234,10 @@ impl SetupWizard {
 
         // Step 7: Ask if user wants to create .env files
         let create_env_files = self.ask_create_env_files()?;
+        let integrations = Integrations {
+            docker_integration: self.ask_docker_integration(&project_type)?,
+            ..Integrations::default()
+        };
 
         // Step 8: Team setup
         let team_config = if self.ask_team_setup()? {
@@ -248,6 +259,7 @@ impl SetupWizard {
             imported_files,
             create_env_files,
             selected_vars,
+            integrations,
         };
 
         self.review_and_apply(&result)?;
@@ -432,7 +444,7 @@ impl SetupWizard {
                         .bright_green()
                         .bold()
                 );
-                std::thread::sleep(std::time::Duration::from_millis(1000));
+                std::thread::sleep(std::time::Duration::from_secs(1));
                 Ok(())
             }
             Some(false) => {
@@ -483,6 +495,36 @@ impl SetupWizard {
         }
     }
 
+    /// Offers Docker env files for Docker projects and projects that have a
+    /// Dockerfile or Compose file.
+    fn ask_docker_integration(&self, project_type: &ProjectType) -> Result<bool> {
+        let uses_docker = matches!(
+            project_type.category,
+            ProjectCategory::Docker | ProjectCategory::Microservices
+        ) || [
+            "Dockerfile",
+            "docker-compose.yml",
+            "docker-compose.yaml",
+            "compose.yml",
+            "compose.yaml",
+        ]
+        .iter()
+        .any(|file| Path::new(file).exists());
+
+        if !uses_docker {
+            return Ok(false);
+        }
+
+        match Confirm::with_theme(&self.theme)
+            .with_prompt("Would you like to generate Docker env files for your profiles?")
+            .default(true)
+            .interact_opt()?
+        {
+            Some(value) => Ok(value),
+            None => Err(EscPressed.into()),
+        }
+    }
+
     /// Prompts the user to select a project type from predefined options.
     ///
     /// # Errors
@@ -568,6 +610,7 @@ impl SetupWizard {
                         value,
                         description: var.description.clone(),
                         required: var.required,
+                        sensitive: var.sensitive,
                     });
                 }
             }
@@ -614,11 +657,20 @@ impl SetupWizard {
                     return Err(EscPressed.into());
                 };
 
+                let Some(sensitive) = Confirm::with_theme(&self.theme)
+                    .with_prompt("Is this value a secret (masked in envx output)?")
+                    .default(SecretClassifier::new().is_secret(&var_name, &value))
+                    .interact_opt()?
+                else {
+                    return Err(EscPressed.into());
+                };
+
                 selected_vars.push(SelectedVariable {
                     name: var_name,
                     value,
                     description,
                     required,
+                    sensitive,
                 });
 
                 let Some(add_more) = Confirm::with_theme(&self.theme)
@@ -1019,6 +1071,14 @@ impl SetupWizard {
             "Create .env files: {}",
             if result.create_env_files { "Yes" } else { "No" }
         );
+        println!(
+            "Docker env files: {}",
+            if result.integrations.docker_integration {
+                "Yes"
+            } else {
+                "No"
+            }
+        );
         println!(
             "Team Setup:       {}",
             if result.team_config.is_some() {
@@ -1181,6 +1241,10 @@ impl SetupWizard {
             Self::create_env_files_with_mappings(result, &profile_mappings)?;
         }
 
+        if result.integrations.docker_integration {
+            Self::create_docker_files(result, &profile_mappings)?;
+        }
+
         // Set environment variables in the current session
         for var in &result.selected_vars {
             unsafe { std::env::set_var(&var.name, &var.value) };
@@ -1223,6 +1287,41 @@ impl SetupWizard {
         Ok(())
     }
 
+    /// Writes each profile to `.envx/docker` as a `docker run --env-file` file
+    /// and as an `environment:` section for Compose.
+    fn create_docker_files(result: &SetupResult, mappings: &HashMap<String, String>) -> Result<()> {
+        println!("\n🐳 Creating Docker env files...");
+
+        let dir = Path::new(".envx").join("docker");
+        fs::create_dir_all(&dir)?;
+
+        for (original_name, config) in &result.profile_configs {
+            if let Some(actual_name) = mappings.get(original_name) {
+                let mut vars: Vec<EnvVar> = config
+                    .iter()
+                    .map(|(name, value)| EnvVar {
+                        name: name.clone(),
+                        value: value.clone(),
+                        source: EnvVarSource::Application(actual_name.clone()),
+                        modified: chrono::Utc::now(),
+                        original_value: None,
+                        origin: None,
+                    })
+                    .collect();
+                vars.sort_by(|a, b| a.name.cmp(&b.name));
+
+                let exporter = Exporter::new(vars, false);
+                for (extension, format) in [("env", ExportFormat::DockerEnv), ("compose.yml", ExportFormat::Compose)] {
+                    let path = dir.join(format!("{actual_name}.{extension}"));
+                    exporter.export_to_file(&path.to_string_lossy(), format)?;
+                    println!("✓ Created {}", path.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn check_required_variables(result: &SetupResult) {
         println!("\n🔍 Checking environment variables...");
 
@@ -1333,7 +1432,7 @@ impl SetupWizard {
                 .map(|v| (v.name.clone(), v.value.clone()))
                 .collect(),
             auto_load: vec![".env".to_string(), ".env.local".to_string()],
-            profile: result.profiles.first().cloned(),
+            profile: result.profiles.first().cloned().map(ProfileSelection::One),
             scripts: HashMap::new(),
             validation: ConfigValidationRules {
                 warn_unused: result.validation_rules.warn_missing,
@@ -1341,6 +1440,12 @@ impl SetupWizard {
                 patterns: result.validation_rules.custom_patterns.clone(),
             },
             inherit: true,
+            sensitive: result
+                .selected_vars
+                .iter()
+                .filter(|v| v.sensitive)
+                .map(|v| v.name.clone())
+                .collect(),
         };
 
         let yaml = serde_yaml::to_string(&config)?;
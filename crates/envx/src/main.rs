@@ -24,14 +24,15 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    let exit_code = cli.error_exit_code();
     if let Err(error) = envx_cli::execute(cli) {
-        handle_error(&error);
+        handle_error(&error, exit_code);
     }
 
     Ok(())
 }
 
-fn handle_error(error: &Report) {
+fn handle_error(error: &Report, exit_code: i32) {
     use std::io::Write;
     let stderr = &mut std::io::stderr();
 
@@ -116,7 +117,7 @@ fn handle_error(error: &Report) {
     let _ = writeln!(stderr);
 
     // Exit with error code
-    process::exit(1);
+    process::exit(exit_code);
 }
 
 // Styling functions for pretty output
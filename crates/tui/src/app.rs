@@ -1,10 +1,13 @@
 use color_eyre::Result;
-use envx_core::{EnvVar, EnvVarManager};
+use envx_core::vault::is_encrypted;
+use envx_core::{EnvVar, EnvVarManager, Profile, ProfileManager, ProfileVar, SecretClassifier, Vault};
 use ratatui::crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use tui_input::Input;
 use tui_input::backend::crossterm::EventHandler;
 use tui_textarea::{CursorMove, TextArea};
 
+use std::cell::OnceCell;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Mode {
     Normal,
@@ -13,6 +16,7 @@ pub enum Mode {
     Add,
     Confirm(ConfirmAction),
     View(String), // View mode for viewing full variable value
+    Profiles,     // Profile editor for enabling and disabling profile variables
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,16 +43,26 @@ pub struct App {
     pub status_message: Option<(String, std::time::Instant)>,
     pub should_quit: bool,
     pub scroll_offset: usize,
+    pub secrets: SecretClassifier,
+    /// Show secret values instead of masking them
+    pub reveal: bool,
+    /// Profiles shown in the profile editor, loaded when it is opened
+    pub profiles: Option<ProfileManager>,
+    /// Key of the profile secrets, unlocked the first time a value is
+    /// revealed. A failure is kept as well, so it isn't retried every frame.
+    profile_vault: OnceCell<std::result::Result<Vault, String>>,
+    pub profile_index: usize,
+    pub profile_var_index: usize,
 }
 
 impl App {
-    /// Creates a new App instance.
+    /// Creates a new App instance. Secret values are masked until revealed.
     ///
     /// # Errors
     ///
-    /// Returns an error if the environment variable manager fails to load variables.
+    /// Returns an error if the history journal cannot be read or the environment variable manager fails to load variables.
     pub fn new() -> Result<Self> {
-        let mut manager = EnvVarManager::new();
+        let mut manager = EnvVarManager::with_journal()?;
         manager.load_all()?;
         let vars = manager.list().into_iter().cloned().collect();
 
@@ -64,9 +78,70 @@ impl App {
             status_message: None,
             should_quit: false,
             scroll_offset: 0,
+            secrets: SecretClassifier::for_current_project(),
+            reveal: false,
+            profiles: None,
+            profile_vault: OnceCell::new(),
+            profile_index: 0,
+            profile_var_index: 0,
         })
     }
 
+    /// Returns the value of `var` as it should be displayed.
+    #[must_use]
+    pub fn display_value(&self, var: &EnvVar) -> String {
+        if self.reveal {
+            var.value.clone()
+        } else {
+            self.secrets.mask(&var.name, &var.value)
+        }
+    }
+
+    /// Returns all profiles sorted by name.
+    #[must_use]
+    pub fn sorted_profiles(&self) -> Vec<&Profile> {
+        let mut profiles = self.profiles.as_ref().map(ProfileManager::list).unwrap_or_default();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    /// Returns the profile selected in the profile editor.
+    #[must_use]
+    pub fn selected_profile(&self) -> Option<&Profile> {
+        self.sorted_profiles().get(self.profile_index).copied()
+    }
+
+    /// Returns the variables of the selected profile sorted by name.
+    #[must_use]
+    pub fn selected_profile_vars(&self) -> Vec<(&String, &ProfileVar)> {
+        let mut vars: Vec<_> = self
+            .selected_profile()
+            .map(|profile| profile.variables.iter().collect())
+            .unwrap_or_default();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        vars
+    }
+
+    /// Returns the value of a profile variable as it should be displayed.
+    #[must_use]
+    pub fn display_profile_value(&self, name: &str, var: &ProfileVar) -> String {
+        match &self.profiles {
+            Some(profiles) if self.reveal => {
+                let vault = self
+                    .profile_vault
+                    .get_or_init(|| profiles.key_store().unlock_existing().map_err(|e| e.to_string()));
+                match vault {
+                    Ok(vault) => vault
+                        .decrypt(&var.value)
+                        .unwrap_or_else(|e| format!("<cannot decrypt: {e}>")),
+                    Err(e) if is_encrypted(&var.value) => format!("<cannot decrypt: {e}>"),
+                    Err(_) => var.value.clone(),
+                }
+            }
+            _ => self.secrets.mask(name, &var.value),
+        }
+    }
+
     /// Handles a key event based on the current mode.
     ///
     /// Returns `true` if the event was handled and requires a re-render.
@@ -82,6 +157,7 @@ impl App {
             Mode::Edit | Mode::Add => Ok(self.handle_edit_mode(key)),
             Mode::Confirm(ref action) => self.handle_confirm_mode(key, action.clone()),
             Mode::View(_) => Ok(self.handle_view_mode(key)),
+            Mode::Profiles => Ok(self.handle_profiles_mode(key)),
         }
     }
 
@@ -101,36 +177,45 @@ impl App {
                 self.edit_value_textarea = TextArea::default();
                 self.active_edit_field = EditField::Name;
             }
-            KeyCode::Char('e' | 'E') => {
-                if !self.filtered_vars.is_empty() {
-                    let var = &self.filtered_vars[self.selected_index];
-                    self.edit_name_input = Input::default().with_value(var.name.clone());
+            KeyCode::Char('e' | 'E') if !self.filtered_vars.is_empty() => {
+                let var = &self.filtered_vars[self.selected_index];
+                self.edit_name_input = Input::default().with_value(var.name.clone());
 
-                    // Initialize textarea with the current value
-                    let mut textarea = TextArea::from(var.value.lines().collect::<Vec<&str>>());
-                    textarea.move_cursor(CursorMove::End);
-                    self.edit_value_textarea = textarea;
+                // Initialize textarea with the current value
+                let mut textarea = TextArea::from(var.value.lines().collect::<Vec<&str>>());
+                textarea.move_cursor(CursorMove::End);
+                self.edit_value_textarea = textarea;
 
-                    self.active_edit_field = EditField::Name;
-                    self.mode = Mode::Edit;
-                }
+                self.active_edit_field = EditField::Name;
+                self.mode = Mode::Edit;
             }
-            KeyCode::Char('v' | 'V') | KeyCode::Enter => {
-                if !self.filtered_vars.is_empty() {
-                    let var = &self.filtered_vars[self.selected_index];
-                    self.mode = Mode::View(var.name.clone());
-                }
+            KeyCode::Char('v' | 'V') | KeyCode::Enter if !self.filtered_vars.is_empty() => {
+                let var = &self.filtered_vars[self.selected_index];
+                self.mode = Mode::View(var.name.clone());
             }
-            KeyCode::Char('d' | 'D') => {
-                if !self.filtered_vars.is_empty() {
-                    let var_name = self.filtered_vars[self.selected_index].name.clone();
-                    self.mode = Mode::Confirm(ConfirmAction::Delete(var_name));
-                }
+            KeyCode::Char('d' | 'D') if !self.filtered_vars.is_empty() => {
+                let var_name = self.filtered_vars[self.selected_index].name.clone();
+                self.mode = Mode::Confirm(ConfirmAction::Delete(var_name));
             }
             KeyCode::Char('r' | 'R') => {
                 self.refresh_vars()?;
                 self.set_status("Refreshed environment variables");
             }
+            KeyCode::Char('s' | 'S') => {
+                self.toggle_reveal();
+            }
+            KeyCode::Char('p' | 'P') => match ProfileManager::new() {
+                Ok(profiles) => {
+                    self.profiles = Some(profiles);
+                    self.profile_vault = OnceCell::new();
+                    self.profile_index = 0;
+                    self.profile_var_index = 0;
+                    self.mode = Mode::Profiles;
+                }
+                Err(e) => {
+                    self.set_status(&format!("Error loading profiles: {e}"));
+                }
+            },
             KeyCode::Up | KeyCode::Char('k') => {
                 self.move_selection_up();
             }
@@ -147,10 +232,8 @@ impl App {
                 self.selected_index = 0;
                 self.scroll_offset = 0;
             }
-            KeyCode::End => {
-                if !self.filtered_vars.is_empty() {
-                    self.selected_index = self.filtered_vars.len() - 1;
-                }
+            KeyCode::End if !self.filtered_vars.is_empty() => {
+                self.selected_index = self.filtered_vars.len() - 1;
             }
             _ => {}
         }
@@ -167,6 +250,70 @@ impl App {
         false
     }
 
+    fn handle_profiles_mode(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.profiles = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Left | KeyCode::Char('h') if self.profile_index > 0 => {
+                self.profile_index -= 1;
+                self.profile_var_index = 0;
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab
+                if self.profile_index + 1 < self.sorted_profiles().len() =>
+            {
+                self.profile_index += 1;
+                self.profile_var_index = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') if self.profile_var_index > 0 => {
+                self.profile_var_index -= 1;
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.profile_var_index + 1 < self.selected_profile_vars().len() => {
+                self.profile_var_index += 1;
+            }
+            KeyCode::Char(' ' | 't') | KeyCode::Enter => {
+                self.toggle_profile_var();
+            }
+            KeyCode::Char('s' | 'S') => {
+                self.toggle_reveal();
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn toggle_profile_var(&mut self) {
+        let Some(profile) = self.selected_profile().map(|profile| profile.name.clone()) else {
+            return;
+        };
+        let Some((name, enabled)) = self
+            .selected_profile_vars()
+            .get(self.profile_var_index)
+            .map(|(name, var)| ((*name).clone(), var.enabled))
+        else {
+            return;
+        };
+        let Some(profiles) = self.profiles.as_mut() else {
+            return;
+        };
+
+        match profiles.set_enabled(&profile, &name, !enabled) {
+            Ok(_) if enabled => self.set_status(&format!("Disabled {name} in profile {profile}")),
+            Ok(_) => self.set_status(&format!("Enabled {name} in profile {profile}")),
+            Err(e) => self.set_status(&format!("Error saving profile: {e}")),
+        }
+    }
+
+    fn toggle_reveal(&mut self) {
+        self.reveal = !self.reveal;
+        if self.reveal {
+            self.set_status("Showing secret values");
+        } else {
+            self.set_status("Masking secret values");
+        }
+    }
+
     const fn move_selection_up(&mut self) {
         if self.selected_index > 0 {
             self.selected_index -= 1;
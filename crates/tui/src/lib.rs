@@ -16,6 +16,9 @@ use std::{
 
 /// Run the TUI application
 ///
+/// Secret values are masked unless `reveal` is set; they can also be toggled
+/// from within the TUI.
+///
 /// # Errors
 ///
 /// Returns an error if:
@@ -23,13 +26,14 @@ use std::{
 /// - App initialization fails
 /// - Terminal operations fail during execution
 /// - Cleanup operations fail
-pub fn run() -> Result<()> {
+pub fn run(reveal: bool) -> Result<()> {
     // Setup terminal
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     // Create app
     let mut app = App::new()?;
+    app.reveal = reveal;
 
     // Setup panic hook
     let panic_hook = std::panic::take_hook();
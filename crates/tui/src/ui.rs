@@ -5,7 +5,8 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{
-        Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        Wrap,
     },
 };
 
@@ -24,7 +25,11 @@ pub fn draw(f: &mut Frame, app: &mut App) {
         .split(f.area());
 
     draw_header(f, chunks[0], app);
-    draw_main_content(f, chunks[1], app);
+    if app.mode == Mode::Profiles {
+        draw_profile_editor(f, chunks[1], app);
+    } else {
+        draw_main_content(f, chunks[1], app);
+    }
     draw_status_bar(f, chunks[2], app);
 
     // Draw overlays based on mode
@@ -119,9 +124,31 @@ fn draw_status_bar(f: &mut Frame, area: Rect, app: &App) {
             Span::styled(" Delete ", Style::default().fg(Color::DarkGray)),
             Span::styled("r", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
             Span::styled(" Refresh ", Style::default().fg(Color::DarkGray)),
+            Span::styled("p", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled(" Profiles ", Style::default().fg(Color::DarkGray)),
+            Span::styled("s", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled(
+                if app.reveal { " Hide Secrets " } else { " Show Secrets " },
+                Style::default().fg(Color::DarkGray),
+            ),
             Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
             Span::styled(" Quit", Style::default().fg(Color::DarkGray)),
         ],
+        Mode::Profiles => vec![
+            Span::styled("←→", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::styled(" Profile ", Style::default().fg(Color::DarkGray)),
+            Span::styled("↑↓", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::styled(" Variable ", Style::default().fg(Color::DarkGray)),
+            Span::styled("Space", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::styled(" Enable/Disable ", Style::default().fg(Color::DarkGray)),
+            Span::styled("s", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled(
+                if app.reveal { " Hide Secrets " } else { " Show Secrets " },
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::styled(" Back", Style::default().fg(Color::DarkGray)),
+        ],
         Mode::Search => vec![
             Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
             Span::styled(" Cancel ", Style::default().fg(Color::DarkGray)),
@@ -176,7 +203,22 @@ fn draw_status_bar(f: &mut Frame, area: Rect, app: &App) {
     f.render_widget(keybindings_widget, status_chunks[0]);
 
     // Right info section
-    let info_content = if app.filtered_vars.is_empty() {
+    let info_content = if app.mode == Mode::Profiles {
+        let vars = app.selected_profile_vars();
+        let enabled = vars.iter().filter(|(_, var)| var.enabled).count();
+        vec![
+            Span::styled(
+                format!("{enabled}"),
+                Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" of ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format!("{}", vars.len()),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" enabled", Style::default().fg(Color::DarkGray)),
+        ]
+    } else if app.filtered_vars.is_empty() {
         vec![Span::styled(
             "No items",
             Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
@@ -261,7 +303,7 @@ fn draw_main_content(f: &mut Frame, area: Rect, app: &mut App) {
                 ),
                 Span::raw(" │ "),
                 Span::styled(
-                    format!("{:<50}", truncate_string(&var.value, 50)),
+                    format!("{:<50}", truncate_string(&app.display_value(var), 50)),
                     style.fg(Color::Gray),
                 ),
                 Span::raw(" │ "),
@@ -311,6 +353,88 @@ fn draw_main_content(f: &mut Frame, area: Rect, app: &mut App) {
     }
 }
 
+fn draw_profile_editor(f: &mut Frame, area: Rect, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+        .split(area);
+
+    let profiles = app.sorted_profiles();
+    let active = app.profiles.as_ref().and_then(|p| p.active()).map(|p| p.name.as_str());
+
+    let profile_items: Vec<ListItem> = profiles
+        .iter()
+        .map(|profile| {
+            let marker = if active == Some(profile.name.as_str()) {
+                "● "
+            } else {
+                "  "
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(marker, Style::default().fg(Color::Green)),
+                Span::raw(profile.name.clone()),
+            ]))
+        })
+        .collect();
+    let profile_list = List::new(profile_items)
+        .block(Block::default().borders(Borders::ALL).title("Profiles"))
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut profile_state = ListState::default().with_selected((!profiles.is_empty()).then_some(app.profile_index));
+    f.render_stateful_widget(profile_list, chunks[0], &mut profile_state);
+
+    let Some(profile) = app.selected_profile() else {
+        let empty = Paragraph::new("No profiles found. Create one with `envx profile create`.")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL).title("Variables"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, chunks[1]);
+        return;
+    };
+
+    let title = match &profile.parent {
+        Some(parent) => format!("Variables of {} (inherits from {parent})", profile.name),
+        None => format!("Variables of {}", profile.name),
+    };
+
+    let vars = app.selected_profile_vars();
+    let var_items: Vec<ListItem> = vars
+        .iter()
+        .map(|(name, var)| {
+            let (status, style) = if var.enabled {
+                (
+                    Span::styled("✓ ", Style::default().fg(Color::Green)),
+                    Style::default().fg(Color::White),
+                )
+            } else {
+                (
+                    Span::styled("✗ ", Style::default().fg(Color::Red)),
+                    Style::default().fg(Color::DarkGray),
+                )
+            };
+            let override_flag = if var.override_system { " [override]" } else { "" };
+
+            ListItem::new(Line::from(vec![
+                status,
+                Span::styled(format!("{:<30}", truncate_string(name, 30)), style),
+                Span::raw(" │ "),
+                Span::styled(
+                    format!(
+                        "{}{override_flag}",
+                        truncate_string(&app.display_profile_value(name, var), 50)
+                    ),
+                    style,
+                ),
+            ]))
+        })
+        .collect();
+    let var_list = List::new(var_items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .highlight_symbol("► ");
+    let mut var_state = ListState::default().with_selected((!vars.is_empty()).then_some(app.profile_var_index));
+    f.render_stateful_widget(var_list, chunks[1], &mut var_state);
+}
+
 fn draw_edit_dialog(f: &mut Frame, app: &App) {
     let area = centered_rect(80, 80, f.area());
 
@@ -399,7 +523,7 @@ fn draw_view_dialog(f: &mut Frame, app: &App, var_name: &str) {
             .direction(Direction::Vertical)
             .margin(1)
             .constraints([
-                Constraint::Length(3), // Variable info
+                Constraint::Length(4), // Variable info
                 Constraint::Length(1), // Separator
                 Constraint::Min(5),    // Value display
                 Constraint::Length(2), // Help text
@@ -425,14 +549,22 @@ fn draw_view_dialog(f: &mut Frame, app: &App, var_name: &str) {
                 Span::styled("Modified: ", Style::default().add_modifier(Modifier::BOLD)),
                 Span::raw(var.modified.format("%Y-%m-%d %H:%M:%S").to_string()),
             ]),
+            Line::from(vec![
+                Span::styled("Origin: ", Style::default().add_modifier(Modifier::BOLD)),
+                match &var.origin {
+                    Some(origin) => Span::raw(origin.to_string()),
+                    None => Span::styled("unknown", Style::default().fg(Color::DarkGray)),
+                },
+            ]),
         ];
 
         let info_widget = Paragraph::new(info).block(Block::default().borders(Borders::NONE));
         f.render_widget(info_widget, chunks[0]);
 
         // Value display with line numbers
-        let value_lines: Vec<Line> = if var.value.lines().count() > 1 {
-            var.value
+        let value = app.display_value(var);
+        let value_lines: Vec<Line> = if value.lines().count() > 1 {
+            value
                 .lines()
                 .enumerate()
                 .map(|(i, line)| {
@@ -443,7 +575,7 @@ fn draw_view_dialog(f: &mut Frame, app: &App, var_name: &str) {
                 })
                 .collect()
         } else {
-            vec![Line::from(var.value.clone())]
+            vec![Line::from(value.clone())]
         };
 
         let value_widget = Paragraph::new(value_lines)